@@ -0,0 +1,96 @@
+//! `Runtime::eval` on nets of growing size. Throughput is reported in rewrites
+//! per second, the `build` group times constructing the same nets without
+//! evaluating them.
+
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use rinet::inet::{net::Net, rule::RuleSet, runtime::Runtime, symbol::SymbolBook, term::TermPtr};
+
+fn fib(symbols: &SymbolBook, n: usize) -> Net {
+    let mut net = Net::new(symbols);
+    net.equations(|b| {
+        let result = b.output();
+        let num = b.n(n);
+        b.fibonacci(num.into(), result.into());
+    });
+    net
+}
+
+// 1 + 2 + ... + 100 = 5050
+fn sum(symbols: &SymbolBook) -> Net {
+    let mut net = Net::new(symbols);
+    net.equations(|b| {
+        let mut total: TermPtr = b.n(1).into();
+        for n in 2..=100 {
+            let (sum_in, sum_out) = match n {
+                100 => (b.output(), None),
+                _ => {
+                    let (sum_in, sum_out) = b.var();
+                    (sum_in, Some(sum_out))
+                }
+            };
+            let adder = b.adder(sum_in.into(), total);
+            let num = b.n(n);
+            b.add(num, adder);
+            if let Some(sum_out) = sum_out {
+                total = sum_out.into();
+            }
+        }
+    });
+    net
+}
+
+fn bench_eval(c: &mut Criterion) {
+    let mut symbols = SymbolBook::new();
+    symbols.declare_nat_symbols();
+    symbols.declare_arith_symbols();
+    symbols.declare_combinator_symbols();
+    symbols.declare_fib_symbols();
+    let mut rules = RuleSet::new(&symbols);
+    rules.arith_rules();
+    rules.define_combinator_rules();
+    rules.fib_rules();
+
+    // fib(n), or the sum without n
+    let cases = [
+        ("fib(4)", Some(4)),
+        ("fib(8)", Some(8)),
+        ("fib(16)", Some(16)),
+        ("fib(20)", Some(20)),
+        ("sum(1..=100)", None),
+    ];
+    let build = |n: Option<usize>| match n {
+        Some(n) => fib(&symbols, n),
+        None => sum(&symbols),
+    };
+
+    let net = Runtime::new(&rules, false).eval(sum(&symbols)).unwrap();
+    assert_eq!(net.read_nat(net.single_output().unwrap()), Some(5050));
+
+    let mut group = c.benchmark_group("eval");
+    for (name, n) in cases {
+        let runtime = Runtime::new(&rules, false);
+        runtime.eval(build(n)).unwrap();
+        group.throughput(Throughput::Elements(runtime.get_rewrites() as u64));
+        group.bench_function(name, |bencher| {
+            bencher.iter_batched(
+                || build(n),
+                |net| {
+                    let net = runtime.eval(black_box(net)).unwrap();
+                    assert!(net.is_normal());
+                    net
+                },
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("build");
+    for (name, n) in cases {
+        group.bench_function(name, |bencher| bencher.iter(|| black_box(build(n))));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_eval);
+criterion_main!(benches);
@@ -0,0 +1,73 @@
+/* C API for rinet, built with `cargo build --features ffi` (see src/ffi.rs). */
+#ifndef RINET_H
+#define RINET_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#define INET_POS 0
+#define INET_NEG 1
+
+/* status codes, see src/ffi.rs */
+#define INET_OK 0
+#define INET_ERR_NAME (-1)
+#define INET_ERR_SYMBOL (-2)
+#define INET_ERR_PORT (-3)
+#define INET_ERR_FAILED (-4)
+
+/* returned instead of a term handle on failure */
+#define INET_INVALID_HANDLE UINT32_MAX
+
+typedef void (*inet_builder_fn)(void *builder, void *user_data);
+
+/* symbols */
+void *inet_symbol_book_new(void);
+void inet_symbol_book_free(void *book);
+int32_t inet_symbol_book_declare_ctr0(void *book, const char *name);
+int32_t inet_symbol_book_declare_ctr1(void *book, const char *name, uint8_t port_polarity);
+int32_t inet_symbol_book_declare_ctr2(void *book, const char *name, uint8_t left_polarity,
+                                      uint8_t right_polarity);
+int32_t inet_symbol_book_declare_fun0(void *book, const char *name);
+int32_t inet_symbol_book_declare_fun1(void *book, const char *name, uint8_t port_polarity);
+int32_t inet_symbol_book_declare_fun2(void *book, const char *name, uint8_t left_polarity,
+                                      uint8_t right_polarity);
+
+/* rules */
+void *inet_rule_set_new(void *book);
+void inet_rule_set_free(void *rules);
+int32_t inet_rule_set_rule(void *rules, const char *ctr_name, const char *fun_name,
+                           inet_builder_fn body, void *user_data);
+
+uint32_t inet_rule_ctr_port(void *builder, uint8_t port_num);
+uint32_t inet_rule_fun_port(void *builder, uint8_t port_num);
+int32_t inet_rule_var(void *builder, uint32_t *neg, uint32_t *pos);
+uint32_t inet_rule_cell0(void *builder, const char *name);
+uint32_t inet_rule_cell1(void *builder, const char *name, uint32_t port);
+uint32_t inet_rule_cell2(void *builder, const char *name, uint32_t left_port, uint32_t right_port);
+int32_t inet_rule_redex(void *builder, uint32_t ctr, uint32_t fun);
+int32_t inet_rule_bind(void *builder, uint32_t var, uint32_t cell);
+int32_t inet_rule_connect(void *builder, uint32_t left, uint32_t right);
+
+/* nets */
+void *inet_net_new(void *book);
+void inet_net_free(void *net);
+int32_t inet_net_equations(void *net, inet_builder_fn body, void *user_data);
+
+uint32_t inet_net_output(void *builder);
+uint32_t inet_net_input(void *builder);
+int32_t inet_net_var(void *builder, uint32_t *neg, uint32_t *pos);
+uint32_t inet_net_cell0(void *builder, const char *name);
+uint32_t inet_net_cell1(void *builder, const char *name, uint32_t port);
+uint32_t inet_net_cell2(void *builder, const char *name, uint32_t left_port, uint32_t right_port);
+int32_t inet_net_redex(void *builder, uint32_t ctr, uint32_t fun);
+int32_t inet_net_bind(void *builder, uint32_t var, uint32_t cell);
+int32_t inet_net_connect(void *builder, uint32_t left, uint32_t right);
+int64_t inet_net_read_nat(void *net);
+
+/* runtime */
+void *inet_runtime_new(void *rules);
+void inet_runtime_free(void *runtime);
+void *inet_runtime_eval(void *runtime, void *net);
+size_t inet_runtime_get_rewrites(void *runtime);
+
+#endif
@@ -0,0 +1,14 @@
+// Loads rinet built with:
+//
+//   cargo build --release --target wasm32-unknown-unknown --features wasm
+//   wasm-bindgen --target web --out-dir js/pkg target/wasm32-unknown-unknown/release/rinet.wasm
+//
+// and exposes its exported entry points.
+import init, { rinet_add } from "./pkg/rinet.js";
+
+export async function loadRinet(url) {
+  await init(url);
+  return {
+    add: (left, right) => rinet_add(left, right),
+  };
+}
@@ -1,20 +1,55 @@
+#[cfg(not(feature = "wasm"))]
 use std::cell::RefCell;
+#[cfg(not(feature = "wasm"))]
+use std::collections::BTreeSet;
 use std::collections::HashMap;
 use std::fmt::Debug;
 use std::marker::PhantomData;
 use std::sync::atomic::{AtomicUsize, Ordering};
-use std::{alloc::Layout, ptr::NonNull};
-
-use std::alloc;
+use std::sync::Mutex;
+use std::{
+    alloc::{Allocator, Global, Layout},
+    ptr::NonNull,
+};
 
 use crate::{ArenaValue, Ptr};
 
-const FREE_SIZE: usize = 1 << 24; // 16,777,216
+/// The capacity of `RawArena::new`, arenas do not grow past their capacity.
+pub const DEFAULT_CAPACITY: usize = 1 << 22; // 4,194,304
 
+#[cfg(not(feature = "wasm"))]
 thread_local! {
-    static FREE: RefCell<HashMap<usize, Vec<usize>>> = RefCell::new(Default::default());
+    static FREE: RefCell<HashMap<usize, FreeList>> = RefCell::new(Default::default());
+}
+
+/// The indices one thread freed in an arena, as of the arena's epoch.
+#[cfg(not(feature = "wasm"))]
+#[derive(Debug, Default)]
+struct FreeList {
+    epoch: usize,
+    indices: Vec<usize>,
+}
+
+#[cfg(not(feature = "wasm"))]
+impl FreeList {
+    // indices freed before the arena was last compacted are stale
+    fn current(&mut self, epoch: usize) -> &mut Vec<usize> {
+        if self.epoch != epoch {
+            self.epoch = epoch;
+            self.indices = Vec::new();
+        }
+        &mut self.indices
+    }
 }
 
+// keys the free lists, unlike addresses ids survive moves and are never reused
+static NEXT_ARENA_ID: AtomicUsize = AtomicUsize::new(0);
+
+// the ids of the arenas not dropped yet, a thread drops the free lists of the
+// others when it starts a new one
+#[cfg(not(feature = "wasm"))]
+static LIVE_ARENAS: Mutex<BTreeSet<usize>> = Mutex::new(BTreeSet::new());
+
 #[derive(Debug, Clone, Copy)]
 pub struct ArenaPtr {
     pub(crate) index: usize,
@@ -26,22 +61,95 @@ impl Ptr for ArenaPtr {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResizeError {
+    /// The new capacity cannot hold every index handed out so far, free
+    /// slots below the highest one included.
+    TooSmall,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AllocError {
+    /// The slot holds a value, use `alloc_with_ptr` to replace it.
+    SlotOccupied,
+    /// The slot was freed by another thread and is on that thread's free list.
+    FreedOnOtherThread,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UseAfterFreeError {
+    /// The generation the pointer was taken at.
+    pub expected: u32,
+    /// The generation of the slot now, bumped each time it is freed.
+    pub found: u32,
+}
+
+/// A pointer paired with the generation of its slot when it was taken, so
+/// `get_checked` can tell a live value from one allocated after a free (ABA).
+#[derive(Debug, Clone, Copy)]
+pub struct VersionedPtr<P: Ptr> {
+    pub ptr: P,
+    pub generation: u32,
+}
+
+/// Where the values moved by `RawArena::extend_from_arena` or
+/// `RawArena::compact` ended up, by the index they had before the move.
+#[derive(Debug, Clone)]
+pub struct IndexTranslation<P: Ptr> {
+    ptrs: HashMap<usize, P>,
+}
+
+impl<P: Ptr> IndexTranslation<P> {
+    pub fn get(&self, old_index: usize) -> Option<P> {
+        self.ptrs.get(&old_index).copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.ptrs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ptrs.is_empty()
+    }
+
+    /// Old indices with their new ptrs, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, P)> + '_ {
+        self.ptrs.iter().map(|(old_index, ptr)| (*old_index, *ptr))
+    }
+}
+
+/// Entries count how many times their slot was freed, wrapping at `u32::MAX`.
 #[derive(Debug)]
 pub enum ArenaEntry<T: Debug> {
-    Occupied(T),
-    Free(usize),
+    Occupied { value: T, generation: u32 },
+    Free { generation: u32 },
 }
 
+/// The allocators arenas can hold, `Debug` so arenas still are.
+pub trait ArenaAllocator: Allocator + Debug + Send + Sync {}
+
+impl<A: Allocator + Debug + Send + Sync> ArenaAllocator for A {}
+
 /// An implementation of Arena that does not use Vec as the underlying storage
 /// because we want to allow cross-thread references and mutable references
 /// (INets are linear after all so we dont need the compiler to save us from ourselves)
 #[derive(Debug)]
 pub struct RawArena<T: ArenaValue<P>, P: Ptr = ArenaPtr> {
+    id: usize,
+    // bumped by compact, which leaves the free lists of all threads stale
+    #[cfg(not(feature = "wasm"))]
+    epoch: usize,
+    // wasm32 has no thread locals to speak of, so the arena owns its free
+    // list. The lock is uncontended there, and keeps the arena `Sync` when
+    // the feature is enabled on a threaded target.
+    #[cfg(feature = "wasm")]
+    free: Mutex<Vec<usize>>,
     mem: NonNull<ArenaEntry<T>>, // raw mutable pointer, non-zero, and covariant (?)
     len: AtomicUsize,
     next: AtomicUsize,
     capacity: usize,
     layout: Layout,
+    allocator: Box<dyn ArenaAllocator>,
     _p: PhantomData<P>,
 }
 
@@ -51,27 +159,41 @@ unsafe impl<T: ArenaValue<P>, P: Ptr> Sync for RawArena<T, P> {}
 
 impl<T: ArenaValue<P>, P: Ptr> RawArena<T, P> {
     pub fn new() -> Self {
-        Self::with_capacity(FREE_SIZE)
+        Self::with_capacity(DEFAULT_CAPACITY)
     }
 
     pub fn with_capacity(capacity: usize) -> Self {
+        Self::with_allocator(capacity, Global)
+    }
+
+    /// An arena whose memory comes from `allocator` rather than the global
+    /// allocator, e.g. a bump allocator or one that tracks allocations.
+    pub fn with_allocator<A: ArenaAllocator + 'static>(capacity: usize, allocator: A) -> Self {
         let layout: Layout =
             Layout::array::<ArenaEntry<T>>(capacity).expect("Could not allocate arena");
-        let ptr = unsafe { alloc::alloc(layout) } as *mut ArenaEntry<T>;
-        let mem = NonNull::new(ptr).expect("Could not allocate Nonnull");
+        let mem = allocator.allocate(layout).expect("Could not allocate arena").cast();
+        let id = NEXT_ARENA_ID.fetch_add(1, Ordering::SeqCst);
+        #[cfg(not(feature = "wasm"))]
+        LIVE_ARENAS.lock().unwrap().insert(id);
 
         Self {
+            id,
+            #[cfg(not(feature = "wasm"))]
+            epoch: 0,
+            #[cfg(feature = "wasm")]
+            free: Mutex::new(Vec::new()),
             mem,
             len: AtomicUsize::new(0),
             next: AtomicUsize::new(0),
             capacity,
             layout,
+            allocator: Box::new(allocator),
             _p: PhantomData,
         }
     }
 
     fn get_key(&self) -> usize {
-        self as *const _ as usize
+        self.id
     }
 
     #[inline]
@@ -80,8 +202,22 @@ impl<T: ArenaValue<P>, P: Ptr> RawArena<T, P> {
     }
 
     #[inline]
-    fn inc_next(&self) -> usize {
-        self.next.fetch_add(1, Ordering::SeqCst)
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// `len` without ordering against other threads' allocs and frees, so it
+    /// may be stale while they run.
+    #[inline]
+    pub fn len_relaxed(&self) -> usize {
+        self.len.load(Ordering::Relaxed)
+    }
+
+    /// One past the highest index ever allocated. Every index below it holds
+    /// either a value or a free entry.
+    #[inline]
+    fn next(&self) -> usize {
+        self.next.load(Ordering::SeqCst)
     }
 
     #[inline]
@@ -89,40 +225,107 @@ impl<T: ArenaValue<P>, P: Ptr> RawArena<T, P> {
         self.capacity
     }
 
+    /// Grows or shrinks the underlying allocation, preserving existing entries.
+    /// Live values can sit above `len` in a fragmented arena, so shrinking
+    /// stops at `next`.
+    pub fn resize(&mut self, new_capacity: usize) -> Result<(), ResizeError> {
+        if new_capacity == 0 || new_capacity < self.next() {
+            return Err(ResizeError::TooSmall);
+        }
+        let layout: Layout =
+            Layout::array::<ArenaEntry<T>>(new_capacity).expect("Could not resize arena");
+        let ptr = unsafe {
+            if layout.size() >= self.layout.size() {
+                self.allocator.grow(self.mem.cast(), self.layout, layout)
+            } else {
+                self.allocator.shrink(self.mem.cast(), self.layout, layout)
+            }
+        };
+        self.mem = ptr.expect("Could not resize arena").cast();
+        self.capacity = new_capacity;
+        self.layout = layout;
+        Ok(())
+    }
+
+    #[cfg(feature = "wasm")]
+    fn push_free_index(&self, index: usize) {
+        tracing::trace!("New free index: {}", index);
+        self.free.lock().unwrap().push(index);
+    }
+
+    #[cfg(feature = "wasm")]
+    fn pop_free_index(&self) -> Option<usize> {
+        self.free.lock().unwrap().pop()
+    }
+
+    #[cfg(feature = "wasm")]
+    fn remove_free_index(&self, index: usize) -> bool {
+        let mut free = self.free.lock().unwrap();
+        match free.iter().position(|free_index| *free_index == index) {
+            Some(position) => {
+                free.swap_remove(position);
+                true
+            }
+            None => false,
+        }
+    }
+
+    #[cfg(not(feature = "wasm"))]
     fn push_free_index(&self, index: usize) {
         FREE.with(|f| {
             tracing::trace!("New free index: {}", index);
             let mut frees = f.borrow_mut();
-            match frees.get_mut(&self.get_key()) {
-                Some(free) => free.push(index),
-                None => {
-                    frees.insert(self.get_key(), vec![index]);
-                }
+            if !frees.contains_key(&self.get_key()) {
+                let live = LIVE_ARENAS.lock().unwrap();
+                frees.retain(|id, _| live.contains(id));
             }
+            let free = frees.entry(self.get_key()).or_default();
+            free.current(self.epoch).push(index);
         });
     }
 
+    #[cfg(not(feature = "wasm"))]
     fn pop_free_index(&self) -> Option<usize> {
         FREE.with(|f| match f.borrow_mut().get_mut(&self.get_key()) {
-            Some(free) => free.pop().map(|index| {
-                tracing::trace!(
-                    "Reusing arena({}) index: {} (len={})",
-                    self.get_key(),
-                    index,
-                    free.len()
-                );
-                index
-            }),
+            Some(free) => {
+                let free = free.current(self.epoch);
+                free.pop().map(|index| {
+                    tracing::trace!(
+                        "Reusing arena({}) index: {} (len={})",
+                        self.get_key(),
+                        index,
+                        free.len()
+                    );
+                    index
+                })
+            }
             None => None,
         })
     }
 
+    #[cfg(not(feature = "wasm"))]
+    fn remove_free_index(&self, index: usize) -> bool {
+        FREE.with(|f| match f.borrow_mut().get_mut(&self.get_key()) {
+            Some(free) => {
+                let free = free.current(self.epoch);
+                match free.iter().position(|free_index| *free_index == index) {
+                    Some(position) => {
+                        free.swap_remove(position);
+                        true
+                    }
+                    None => false,
+                }
+            }
+            None => false,
+        })
+    }
+
     pub fn alloc(&self, value: T) -> P {
         tracing::trace!("ALLOC: Arena {}, Len: {}", self.get_key(), self.len());
-        let index = match self.pop_free_index() {
+        let (index, generation) = match self.pop_free_index() {
             Some(index) => {
-                assert!(index < self.inc_next());
-                index
+                assert!(index < self.next());
+                (index, self.get_generation(index))
             }
             None => {
                 let index = self.next.fetch_add(1, Ordering::SeqCst);
@@ -137,17 +340,62 @@ impl<T: ArenaValue<P>, P: Ptr> RawArena<T, P> {
                     "Max capacity reached: {}",
                     self.capacity()
                 );
-                index
+                (index, 0)
             }
         };
-        self.alloc_with_index(value, index)
+        self.alloc_with_index(value, index, generation)
     }
 
     pub fn alloc_with_ptr(&self, value: T, ptr: impl Ptr) -> P {
-        self.alloc_with_index(value, ptr.get_index())
+        let index = ptr.get_index();
+        assert!(index < self.next());
+        // replacing a value in place does not change the len, and drops the
+        // value replaced once the new one is written
+        let replaced = match self.get_from_index(index) {
+            ArenaEntry::Occupied { .. } => {
+                self.len.fetch_sub(1, Ordering::SeqCst);
+                Some(unsafe { self.mem.as_ptr().add(index).read() })
+            }
+            ArenaEntry::Free { .. } => None,
+        };
+        let ptr = self.alloc_with_index(value, index, self.get_generation(index));
+        drop(replaced);
+        ptr
     }
 
-    fn alloc_with_index(&self, value: T, index: usize) -> P {
+    /// Allocates into a specific freed slot, taking it off the free list so
+    /// `alloc` does not hand it out again.
+    pub fn try_alloc_with_ptr(&self, value: T, ptr: impl Ptr) -> Result<P, AllocError> {
+        let index = ptr.get_index();
+        assert!(index < self.next());
+        if let ArenaEntry::Occupied { .. } = self.get_from_index(index) {
+            return Err(AllocError::SlotOccupied);
+        }
+        if !self.remove_free_index(index) {
+            return Err(AllocError::FreedOnOtherThread);
+        }
+        Ok(self.alloc_with_index(value, index, self.get_generation(index)))
+    }
+
+    /// Allocates at `index` whether or not it was reached yet, so tests can
+    /// build values at known indexes regardless of allocation history. Slots
+    /// skipped on the way become free and are handed out by later `alloc`s,
+    /// lowest first.
+    pub fn alloc_at(&mut self, index: usize, value: T) -> Result<P, AllocError> {
+        assert!(index < self.capacity(), "Max capacity reached: {}", self.capacity());
+        let next = self.next();
+        if index < next {
+            return self.try_alloc_with_ptr(value, ArenaPtr { index });
+        }
+        for gap in (next..index).rev() {
+            unsafe { self.mem.as_ptr().add(gap).write(ArenaEntry::Free { generation: 0 }) }
+            self.push_free_index(gap);
+        }
+        self.next.store(index + 1, Ordering::SeqCst);
+        Ok(self.alloc_with_index(value, index, 0))
+    }
+
+    fn alloc_with_index(&self, value: T, index: usize, generation: u32) -> P {
         let offset = index
             .checked_mul(std::mem::size_of::<ArenaEntry<T>>())
             .expect("Cannot reach memory location");
@@ -159,26 +407,82 @@ impl<T: ArenaValue<P>, P: Ptr> RawArena<T, P> {
 
         let ptr = value.to_ptr(index);
         tracing::trace!("Alloc[{:?}]: {:?}", &ptr, &value);
-        let entry = ArenaEntry::Occupied(value);
+        let entry = ArenaEntry::Occupied { value, generation };
         unsafe { self.mem.as_ptr().add(index).write(entry) }
         ptr
     }
 
-    pub fn get<'a>(&'a self, ptr: P) -> Option<&'a T> {
+    /// Whether `ptr` points to an allocated value, unlike `get` this does not
+    /// panic on free or out of bounds entries.
+    pub fn contains(&self, ptr: P) -> bool {
+        ptr.get_index() < self.next()
+            && matches!(self.get_from_index(ptr.get_index()), ArenaEntry::Occupied { .. })
+    }
+
+    /// Pairs `ptr` with the current generation of its slot.
+    pub fn versioned(&self, ptr: P) -> VersionedPtr<P> {
+        assert!(self.contains(ptr), "Ptr is not allocated: {:?}", ptr);
+        VersionedPtr {
+            ptr,
+            generation: self.get_generation(ptr.get_index()),
+        }
+    }
+
+    /// Like `get`, but fails instead of returning a value that was allocated
+    /// into the slot after `generation` was taken.
+    pub fn get_checked(&self, ptr: P, generation: u32) -> Result<&T, UseAfterFreeError> {
         assert!(
-            ptr.get_index() < self.inc_next(),
+            ptr.get_index() < self.next(),
             "Ptr index is out of bounds (next={}): {:?}",
-            self.inc_next(),
+            self.next(),
             ptr
         );
+        let found = self.get_generation(ptr.get_index());
+        match self.get_from_index(ptr.get_index()) {
+            ArenaEntry::Occupied { value, .. } if found == generation => Ok(value),
+            _ => Err(UseAfterFreeError {
+                expected: generation,
+                found,
+            }),
+        }
+    }
 
-        match unsafe { self.get_from_index(ptr.get_index()) } {
-            ArenaEntry::Occupied(value) => Some(value),
-            ArenaEntry::Free(_) => panic!("Trying to get a Free arena index: {:?}", ptr),
+    fn get_generation(&self, index: usize) -> u32 {
+        match self.get_from_index(index) {
+            ArenaEntry::Occupied { generation, .. } => *generation,
+            ArenaEntry::Free { generation } => *generation,
         }
     }
 
-    fn get_from_index<'a>(&'a self, index: usize) -> &'a ArenaEntry<T> {
+    pub fn get(&self, ptr: P) -> Option<&T> {
+        assert!(
+            ptr.get_index() < self.next(),
+            "Ptr index is out of bounds (next={}): {:?}",
+            self.next(),
+            ptr
+        );
+
+        match self.get_from_index(ptr.get_index()) {
+            ArenaEntry::Occupied { value, .. } => Some(value),
+            ArenaEntry::Free { .. } => panic!("Trying to get a Free arena index: {:?}", ptr),
+        }
+    }
+
+    pub fn get_mut(&mut self, ptr: P) -> Option<&mut T> {
+        assert!(
+            ptr.get_index() < self.next(),
+            "Ptr index is out of bounds (next={}): {:?}",
+            self.next(),
+            ptr
+        );
+
+        match unsafe { &mut *self.mem.as_ptr().add(ptr.get_index()) } {
+            ArenaEntry::Occupied { value, .. } => Some(value),
+            ArenaEntry::Free { .. } => panic!("Trying to get a Free arena index: {:?}", ptr),
+        }
+    }
+
+    fn get_from_index(&self, index: usize) -> &ArenaEntry<T> {
         unsafe { &*self.mem.as_ptr().add(index) }
     }
 
@@ -198,41 +502,122 @@ impl<T: ArenaValue<P>, P: Ptr> RawArena<T, P> {
 
     pub fn free(&self, ptr: P) -> T {
         tracing::trace!("FREE: Arena {}, Ptr: {}", self.get_key(), ptr.get_index());
-        assert!(ptr.get_index() < self.inc_next());
+        assert!(ptr.get_index() < self.next());
         self.push_free_index(ptr.get_index());
         unsafe {
             let mem_ptr = self.mem.as_ptr().add(ptr.get_index());
             match mem_ptr.read() {
-                ArenaEntry::Occupied(value) => {
-                    mem_ptr.write(ArenaEntry::Free(ptr.get_index()));
-                    assert!(self.len() > 0, "Arena len is 0");
+                ArenaEntry::Occupied { value, generation } => {
+                    mem_ptr.write(ArenaEntry::Free {
+                        generation: generation.wrapping_add(1),
+                    });
+                    assert!(!self.is_empty(), "Arena len is 0");
                     self.len.fetch_sub(1, Ordering::SeqCst);
                     value
                 }
-                ArenaEntry::Free(_) => unreachable!(),
+                ArenaEntry::Free { .. } => unreachable!(),
             }
         }
     }
 
     pub fn iter(&self) -> ArenaPtrIter<T, P> {
-        ArenaPtrIter::new(&self)
+        ArenaPtrIter::new(self)
     }
 
     pub fn values_iter(&self) -> ArenaValueIter<T, P> {
         ArenaValueIter::new(self.iter())
     }
+
+    /// Moves the live values of `other` into this arena, where they may land
+    /// at other indices. Ptrs held by the values themselves are left as they
+    /// are, translate them with the returned `IndexTranslation`.
+    pub fn extend_from_arena(&mut self, other: RawArena<T, P>) -> IndexTranslation<P> {
+        let entries = unsafe { std::slice::from_raw_parts_mut(other.mem.as_ptr(), other.next()) };
+        let ptrs = entries
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(index, entry)| {
+                let generation = match entry {
+                    ArenaEntry::Occupied { generation, .. } => *generation,
+                    ArenaEntry::Free { .. } => return None,
+                };
+                match std::mem::replace(entry, ArenaEntry::Free { generation }) {
+                    ArenaEntry::Occupied { value, .. } => Some((index, self.alloc(value))),
+                    ArenaEntry::Free { .. } => unreachable!(),
+                }
+            })
+            .collect();
+        // every value moved out, there is nothing left for drop, which also
+        // drops the free lists of other
+        other.len.store(0, Ordering::SeqCst);
+        IndexTranslation { ptrs }
+    }
+
+    /// Moves the live values to the front of the arena, keeping their order,
+    /// so `next` equals `len` afterwards. Ptrs held by the values themselves
+    /// are left as they are, translate them with the returned
+    /// `IndexTranslation`.
+    pub fn compact(&mut self) -> IndexTranslation<P> {
+        let entries = unsafe { std::slice::from_raw_parts_mut(self.mem.as_ptr(), self.next()) };
+        let mut ptrs = HashMap::new();
+        let mut next = 0;
+        for index in 0..entries.len() {
+            // the entries from next up to index are all free
+            if let ArenaEntry::Occupied { value, .. } = &entries[index] {
+                ptrs.insert(index, value.to_ptr(next));
+                entries.swap(next, index);
+                next += 1;
+            }
+        }
+        self.next.store(next, Ordering::SeqCst);
+        // the free lists hold indices from before the move, other threads
+        // drop theirs when they next see the new epoch
+        #[cfg(feature = "wasm")]
+        self.free.get_mut().unwrap().clear();
+        #[cfg(not(feature = "wasm"))]
+        {
+            self.epoch += 1;
+            FREE.with(|f| f.borrow_mut().remove(&self.get_key()));
+        }
+        IndexTranslation { ptrs }
+    }
+
+    /// The live values in index order. The arena stays mutably borrowed, so no
+    /// alloc or free can happen while the iterator is live.
+    pub fn values_iter_mut(&mut self) -> impl Iterator<Item = &mut T> + '_ {
+        // every index below next holds an occupied or free entry
+        let entries = unsafe { std::slice::from_raw_parts_mut(self.mem.as_ptr(), self.next()) };
+        entries.iter_mut().filter_map(|entry| match entry {
+            ArenaEntry::Occupied { value, .. } => Some(value),
+            ArenaEntry::Free { .. } => None,
+        })
+    }
+}
+
+impl<T: ArenaValue<P>, P: Ptr> Default for RawArena<T, P> {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl<T: ArenaValue<P>, P: Ptr> Drop for RawArena<T, P> {
     fn drop(&mut self) {
         unsafe {
-            // does not need to bring to the stack to
-            std::ptr::drop_in_place(std::slice::from_raw_parts_mut(
-                self.mem.as_ptr(),
-                self.len(),
-            ));
-            alloc::dealloc(self.mem.as_ptr() as _, self.layout);
+            // live values can sit anywhere below next, between free entries
+            for index in 0..self.next() {
+                if let ArenaEntry::Occupied { value, .. } = &mut *self.mem.as_ptr().add(index) {
+                    std::ptr::drop_in_place(value);
+                }
+            }
+            self.allocator.deallocate(self.mem.cast(), self.layout);
         };
+        // the lists other threads hold go when they start a new one
+        #[cfg(not(feature = "wasm"))]
+        {
+            LIVE_ARENAS.lock().unwrap().remove(&self.get_key());
+            // the thread locals may be gone already when dropped on thread exit
+            let _ = FREE.try_with(|f| f.borrow_mut().remove(&self.get_key()));
+        }
     }
 }
 
@@ -257,14 +642,14 @@ impl<'a, T: ArenaValue<P>, P: Ptr> Iterator for ArenaPtrIter<'a, T, P> {
     type Item = P;
 
     fn next(&mut self) -> Option<Self::Item> {
-        for i in self.index..self.arena.len() {
+        for i in self.index..self.arena.next() {
             match &self.arena.get_from_index(i) {
-                ArenaEntry::Occupied(value) => {
+                ArenaEntry::Occupied { value, .. } => {
                     let ptr = value.to_ptr(i);
                     self.index = i + 1;
                     return Some(ptr);
                 }
-                ArenaEntry::Free(_) => (),
+                ArenaEntry::Free { .. } => (),
             };
         }
         None
@@ -285,7 +670,7 @@ impl<'a, T: ArenaValue<P>, P: Ptr> Iterator for ArenaValueIter<'a, T, P> {
     type Item = &'a T;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let ptr = self.iter.next().unwrap();
+        let ptr = self.iter.next()?;
         self.iter.arena.get(ptr)
     }
 }
@@ -308,4 +693,273 @@ mod tests {
         assert_eq!(vec.free(ptr), 11);
         assert_eq!(vec.len(), 0);
     }
+
+    #[derive(Debug)]
+    struct DropCounter(std::rc::Rc<std::cell::Cell<usize>>);
+    impl ArenaValue<ArenaPtr> for DropCounter {
+        fn to_ptr(&self, index: usize) -> ArenaPtr {
+            ArenaPtr { index }
+        }
+    }
+    impl Drop for DropCounter {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    #[test]
+    fn test_drop_fragmented() {
+        let drops = std::rc::Rc::new(std::cell::Cell::new(0));
+        let vec = RawArena::<DropCounter>::with_capacity(4);
+        let ptrs: Vec<ArenaPtr> = (0..4).map(|_| vec.alloc(DropCounter(drops.clone()))).collect();
+        drop(vec.free(ptrs[0]));
+        drop(vec.free(ptrs[1]));
+        assert_eq!(drops.get(), 2);
+        // the two live values are at indices 2 and 3
+        drop(vec);
+        assert_eq!(drops.get(), 4);
+    }
+
+    #[test]
+    fn test_alloc_with_ptr_drops_replaced() {
+        let drops = std::rc::Rc::new(std::cell::Cell::new(0));
+        let vec = RawArena::<DropCounter>::with_capacity(2);
+        let ptr = vec.alloc(DropCounter(drops.clone()));
+        vec.alloc_with_ptr(DropCounter(drops.clone()), ptr);
+        assert_eq!(drops.get(), 1);
+        assert_eq!(vec.len(), 1);
+        drop(vec);
+        assert_eq!(drops.get(), 2);
+    }
+
+    #[test]
+    fn test_values_iter_mut() {
+        let mut vec = RawArena::<usize>::with_capacity(4);
+        let ptrs: Vec<ArenaPtr> = (0..4).map(|value| vec.alloc(value)).collect();
+        vec.free(ptrs[1]);
+        for value in vec.values_iter_mut() {
+            *value *= 10;
+        }
+        assert_eq!(vec.values_iter().copied().collect::<Vec<_>>(), [0, 20, 30]);
+    }
+
+    #[test]
+    fn test_try_alloc_with_ptr() {
+        let vec = RawArena::<usize>::with_capacity(4);
+        let ptr0 = vec.alloc(6);
+        let ptr1 = vec.alloc(7);
+        assert_eq!(vec.try_alloc_with_ptr(8, ptr0).err(), Some(AllocError::SlotOccupied));
+
+        vec.free(ptr0);
+        let ptr0 = vec.try_alloc_with_ptr(8, ptr0).unwrap();
+        assert_eq!(vec.get(ptr0), Some(&8));
+        assert_eq!(vec.len(), 2);
+        assert_eq!(vec.len_relaxed(), 2);
+
+        // the slot is no longer free, so alloc takes a new one
+        let ptr2 = vec.alloc(9);
+        assert_eq!(ptr2.get_index(), 2);
+        assert_eq!(vec.get(ptr1), Some(&7));
+    }
+
+    #[test]
+    fn test_extend_from_arena() {
+        let mut vec = RawArena::<usize>::with_capacity(8);
+        vec.alloc(1);
+        let other = RawArena::<usize>::with_capacity(8);
+        let ptrs: Vec<ArenaPtr> = (10..13).map(|value| other.alloc(value)).collect();
+        other.free(ptrs[1]);
+
+        let translation = vec.extend_from_arena(other);
+        assert_eq!(translation.len(), 2);
+        assert!(translation.get(1).is_none());
+        assert_eq!(vec.len(), 3);
+        let ptr0 = translation.get(0).unwrap();
+        let ptr2 = translation.get(2).unwrap();
+        assert_ne!(ptr0.get_index(), ptr2.get_index());
+        assert_eq!(vec.get(ptr0), Some(&10));
+        assert_eq!(vec.get(ptr2), Some(&12));
+    }
+
+    #[test]
+    #[cfg(not(feature = "wasm"))]
+    fn test_extend_from_arena_drops_free_list() {
+        let mut vec = RawArena::<usize>::with_capacity(8);
+        let other = RawArena::<usize>::with_capacity(8);
+        let other_id = other.get_key();
+        other.free(other.alloc(10));
+        other.alloc(11);
+        assert!(FREE.with(|f| f.borrow().contains_key(&other_id)));
+
+        vec.extend_from_arena(other);
+        assert!(FREE.with(|f| !f.borrow().contains_key(&other_id)));
+        assert!(!LIVE_ARENAS.lock().unwrap().contains(&other_id));
+
+        // a list left behind for a dropped arena, as on another thread, goes
+        // when a new list starts
+        FREE.with(|f| f.borrow_mut().insert(other_id, FreeList::default()));
+        vec.free(vec.alloc(12));
+        assert!(FREE.with(|f| !f.borrow().contains_key(&other_id)));
+        assert!(FREE.with(|f| f.borrow().contains_key(&vec.get_key())));
+    }
+
+    #[test]
+    fn test_compact() {
+        let mut vec = RawArena::<usize>::with_capacity(8);
+        let ptrs: Vec<ArenaPtr> = (10..15).map(|value| vec.alloc(value)).collect();
+        vec.free(ptrs[0]);
+        vec.free(ptrs[3]);
+
+        let translation = vec.compact();
+        assert_eq!(translation.len(), 3);
+        assert!(translation.get(0).is_none() && translation.get(3).is_none());
+        assert_eq!(translation.get(1).unwrap().get_index(), 0);
+        assert_eq!(translation.get(2).unwrap().get_index(), 1);
+        assert_eq!(translation.get(4).unwrap().get_index(), 2);
+        assert_eq!(vec.next(), vec.len());
+        assert_eq!(vec.values_iter().copied().collect::<Vec<_>>(), [11, 12, 14]);
+
+        // the freed indices are not handed out again
+        assert_eq!(vec.alloc(15).get_index(), 3);
+        assert_eq!(vec.alloc(16).get_index(), 4);
+    }
+
+    #[test]
+    #[cfg(not(feature = "wasm"))]
+    fn test_compact_keeps_id() {
+        let mut vec = RawArena::<usize>::with_capacity(8);
+        let id = vec.get_key();
+        let ptrs: Vec<ArenaPtr> = (10..13).map(|value| vec.alloc(value)).collect();
+        vec.free(ptrs[0]);
+        vec.compact();
+        assert_eq!(vec.get_key(), id);
+        assert!(FREE.with(|f| !f.borrow().contains_key(&id)));
+
+        // a list another thread kept from before the compaction is stale
+        vec.free(vec.alloc(13));
+        FREE.with(|f| f.borrow_mut().get_mut(&id).unwrap().epoch -= 1);
+        assert_eq!(vec.pop_free_index(), None);
+        assert_eq!(FREE.with(|f| f.borrow()[&id].epoch), 1);
+    }
+
+    #[test]
+    fn test_alloc_at() {
+        let mut vec = RawArena::<usize>::with_capacity(8);
+        let ptr = vec.alloc_at(3, 6).unwrap();
+        assert_eq!(ptr.get_index(), 3);
+        assert_eq!(vec.len(), 1);
+        assert_eq!(vec.alloc_at(3, 7).err(), Some(AllocError::SlotOccupied));
+
+        // the skipped slots are free, and handed out lowest first
+        assert!(!vec.contains(ArenaPtr { index: 0 }));
+        assert_eq!(vec.alloc(7).get_index(), 0);
+        assert_eq!(vec.alloc_at(2, 8).unwrap().get_index(), 2);
+        assert_eq!(vec.alloc(9).get_index(), 1);
+        assert_eq!(vec.alloc(10).get_index(), 4);
+        assert_eq!(vec.values_iter().copied().collect::<Vec<_>>(), [7, 9, 8, 6, 10]);
+    }
+
+    #[test]
+    fn test_contains() {
+        let vec = RawArena::<usize>::with_capacity(2);
+        let ptr = vec.alloc(6);
+        assert!(vec.contains(ptr));
+        vec.free(ptr);
+        assert!(!vec.contains(ptr));
+        assert!(!vec.contains(ArenaPtr { index: 1 }));
+    }
+
+    #[test]
+    fn test_get_mut() {
+        let mut vec = RawArena::<usize>::with_capacity(2);
+        let ptr = vec.alloc(6);
+        *vec.get_mut(ptr).unwrap() = 7;
+        assert_eq!(vec.get(ptr), Some(&7));
+        assert_eq!(vec.len(), 1);
+    }
+
+    #[test]
+    fn test_get_checked() {
+        let vec = RawArena::<usize>::with_capacity(2);
+        let ptr = vec.alloc(6);
+        let versioned = vec.versioned(ptr);
+        assert_eq!(vec.get_checked(ptr, versioned.generation), Ok(&6));
+
+        vec.free(ptr);
+        let err = UseAfterFreeError {
+            expected: 0,
+            found: 1,
+        };
+        assert_eq!(vec.get_checked(ptr, versioned.generation), Err(err));
+
+        // the slot is reused, but the old pointer still fails the check
+        let ptr = vec.alloc(7);
+        assert_eq!(ptr.get_index(), versioned.ptr.get_index());
+        assert_eq!(vec.get_checked(ptr, versioned.generation), Err(err));
+        assert_eq!(vec.get_checked(ptr, vec.versioned(ptr).generation), Ok(&7));
+    }
+
+    #[test]
+    fn test_resize() {
+        let mut vec = RawArena::<usize>::with_capacity(2);
+        let ptr0 = vec.alloc(6);
+        let ptr1 = vec.alloc(7);
+        assert_eq!(vec.resize(1), Err(ResizeError::TooSmall));
+        assert_eq!(vec.resize(4), Ok(()));
+        assert_eq!(vec.capacity(), 4);
+        let ptr2 = vec.alloc(8);
+        assert_eq!(vec.len(), 3);
+        assert_eq!(vec.get(ptr0), Some(&6));
+        assert_eq!(vec.get(ptr1), Some(&7));
+        assert_eq!(vec.get(ptr2), Some(&8));
+    }
+
+    #[test]
+    fn test_resize_fragmented() {
+        let mut vec = RawArena::<usize>::with_capacity(4);
+        let ptrs: Vec<ArenaPtr> = (6..10).map(|value| vec.alloc(value)).collect();
+        vec.free(ptrs[0]);
+        vec.free(ptrs[1]);
+        // two live values, but the last one is at index 3
+        assert_eq!(vec.len(), 2);
+        assert_eq!(vec.resize(2), Err(ResizeError::TooSmall));
+        assert_eq!(vec.resize(3), Err(ResizeError::TooSmall));
+        assert_eq!(vec.resize(4), Ok(()));
+        assert_eq!(vec.get(ptrs[3]), Some(&9));
+    }
+
+    // counts the bytes currently allocated through it
+    #[derive(Debug, Clone, Default)]
+    struct Tracking(std::sync::Arc<AtomicUsize>);
+
+    unsafe impl Allocator for Tracking {
+        fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, std::alloc::AllocError> {
+            self.0.fetch_add(layout.size(), Ordering::SeqCst);
+            Global.allocate(layout)
+        }
+
+        unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+            self.0.fetch_sub(layout.size(), Ordering::SeqCst);
+            Global.deallocate(ptr, layout)
+        }
+    }
+
+    #[test]
+    fn test_with_allocator() {
+        let tracking = Tracking::default();
+        let size = |capacity| Layout::array::<ArenaEntry<usize>>(capacity).unwrap().size();
+        let mut vec = RawArena::<usize>::with_allocator(4, tracking.clone());
+        assert_eq!(tracking.0.load(Ordering::SeqCst), size(4));
+
+        let ptr = vec.alloc(6);
+        // grow and shrink go through allocate and deallocate
+        vec.resize(8).unwrap();
+        assert_eq!(tracking.0.load(Ordering::SeqCst), size(8));
+        vec.resize(2).unwrap();
+        assert_eq!(tracking.0.load(Ordering::SeqCst), size(2));
+        assert_eq!(vec.get(ptr), Some(&6));
+
+        drop(vec);
+        assert_eq!(tracking.0.load(Ordering::SeqCst), 0);
+    }
 }
@@ -0,0 +1,91 @@
+//! Random sequences of `alloc` and `free` checked against a model of the arena.
+
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+use proptest::{prelude::*, test_runner::Config};
+use raw_arena::{
+    arenaraw::{ArenaPtr, RawArena},
+    Ptr,
+};
+
+#[derive(Debug, Clone)]
+enum Op {
+    Alloc(usize),
+    // frees the n-th live value, modulo the live count
+    Free(usize),
+}
+
+fn ops() -> impl Strategy<Value = Vec<Op>> {
+    prop::collection::vec(
+        prop_oneof![
+            any::<usize>().prop_map(Op::Alloc),
+            any::<usize>().prop_map(Op::Free),
+        ],
+        0..100,
+    )
+}
+
+/// Runs `ops` on `arena` and checks each step. Frees go to a per thread free
+/// list, so the most recent free on this thread is the next index allocated.
+/// Returns the values still live.
+fn run(arena: &RawArena<usize>, ops: &[Op], check_new_index: bool) -> Vec<(ArenaPtr, usize)> {
+    let mut live: Vec<(ArenaPtr, usize)> = Vec::new();
+    let mut free: Vec<usize> = Vec::new();
+    let mut highest = None;
+    let mut freed_any = false;
+    for op in ops {
+        match op {
+            Op::Alloc(value) => {
+                let ptr = arena.alloc(*value);
+                match free.pop() {
+                    Some(index) => assert_eq!(ptr.get_index(), index),
+                    None if check_new_index => {
+                        assert_eq!(ptr.get_index(), highest.map_or(0, |index| index + 1))
+                    }
+                    None => (),
+                }
+                highest = highest.max(Some(ptr.get_index()));
+                live.push((ptr, *value));
+            }
+            Op::Free(nth) if !live.is_empty() => {
+                let (ptr, value) = live.swap_remove(nth % live.len());
+                assert_eq!(arena.free(ptr), value);
+                // unwinding is slow, so only the first free is checked
+                if !freed_any {
+                    assert!(catch_unwind(AssertUnwindSafe(|| arena.get(ptr))).is_err());
+                    freed_any = true;
+                }
+                free.push(ptr.get_index());
+            }
+            Op::Free(_) => (),
+        }
+        for (ptr, value) in live.iter() {
+            assert_eq!(arena.get(*ptr), Some(value));
+        }
+    }
+    live
+}
+
+proptest! {
+    #![proptest_config(Config::with_cases(10000))]
+
+    #[test]
+    fn alloc_free_sequence(ops in ops()) {
+        let arena: RawArena<usize> = RawArena::with_capacity(128);
+        let live = run(&arena, &ops, true);
+        prop_assert_eq!(arena.len(), live.len());
+    }
+
+    #[test]
+    fn concurrent_alloc_free_sequences(threads in prop::collection::vec(ops(), 1..4)) {
+        let arena: RawArena<usize> = RawArena::with_capacity(512);
+        let live: usize = std::thread::scope(|scope| {
+            let handles: Vec<_> = threads
+                .iter()
+                .map(|ops| scope.spawn(|| run(&arena, ops, false).len()))
+                .collect();
+            handles.into_iter().map(|handle| handle.join().unwrap()).sum()
+        });
+        prop_assert_eq!(arena.len(), live);
+    }
+}
@@ -0,0 +1,260 @@
+//! An interactive prompt for declaring symbols and rules and evaluating nets,
+//! see `help` for the commands. History is kept in `~/.rinet_history`.
+
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+use rinet::inet::{
+    net::Net,
+    rule::RuleSet,
+    runtime::Runtime,
+    symbol::{SymbolBook, SymbolName},
+    Polarity,
+};
+use rustyline::{error::ReadlineError, DefaultEditor};
+
+const HELP: &str = "\
+symbol +(S -)                          declare a symbol, + for constructors
+rule (add x y) ⋈ Z  ⟶  x ↔ y           define a rule
+net < r | (S Z) ⋈ (add r (S Z)) >      set the current net
+eval                                   evaluate the current net
+step                                   rewrite one redex of the current net
+show                                   print the current net
+stats                                  print the rewrites so far
+quit";
+
+fn main() {
+    let history = std::env::var("HOME")
+        .map(|home| format!("{}/.rinet_history", home))
+        .unwrap_or_else(|_| ".rinet_history".to_string());
+    let mut editor = DefaultEditor::new().expect("Cannot open the terminal");
+    // there is no history the first time
+    let _ = editor.load_history(&history);
+
+    println!("Nat and arith symbols and rules are declared, type help for commands");
+    let mut repl = Repl::default();
+    // the rules and the net borrow the book, so a new symbol starts over with
+    // a new book
+    'book: loop {
+        let symbols = repl.book();
+        let mut session = Session::new(&symbols, &repl.rule_lines);
+        loop {
+            match editor.readline("inet> ") {
+                Ok(line) => {
+                    let line = line.trim();
+                    if line.is_empty() {
+                        continue;
+                    }
+                    let _ = editor.add_history_entry(line);
+                    match repl.command(&mut session, line) {
+                        Next::Prompt => {}
+                        Next::NewBook => continue 'book,
+                        Next::Quit => break 'book,
+                    }
+                }
+                Err(ReadlineError::Interrupted | ReadlineError::Eof) => break 'book,
+                Err(error) => {
+                    eprintln!("{}", error);
+                    break 'book;
+                }
+            }
+        }
+    }
+    if let Err(error) = editor.save_history(&history) {
+        eprintln!("Cannot save history to {}: {}", history, error);
+    }
+}
+
+#[derive(Default)]
+struct Stats {
+    rewrites: usize,
+    cell_reuses: usize,
+    cell_instantiations: usize,
+    var_instantiations: usize,
+}
+
+/// What `Repl::command` asks the prompt loop to do next.
+enum Next {
+    Prompt,
+    /// A symbol was declared, build a new book and session.
+    NewBook,
+    Quit,
+}
+
+/// Runs `f`, a panic is returned as `None`, e.g. for a missing rule.
+fn catch<R>(f: impl FnOnce() -> R) -> Option<R> {
+    catch_unwind(AssertUnwindSafe(f)).ok()
+}
+
+#[derive(Default)]
+struct Repl {
+    // declared at the prompt, replayed when the book is rebuilt
+    declared: Vec<(SymbolName, Polarity, Vec<Polarity>)>,
+    rule_lines: Vec<String>,
+    stats: Stats,
+}
+
+/// The rules and the net built against one book.
+struct Session<'s> {
+    symbols: &'s SymbolBook,
+    rules: RuleSet<'s>,
+    net: Option<Net<'s>>,
+}
+
+/// `net` borrowing `symbols` for as long as the session, the runtime hands
+/// it back borrowing them only for as long as the rules.
+fn rebind<'s>(net: Net<'_>, symbols: &'s SymbolBook) -> Net<'s> {
+    let Net { symbols: _, head, body, heap, var_names, eval_capacity } = net;
+    Net { symbols, head, body, heap, var_names, eval_capacity }
+}
+
+impl<'s> Session<'s> {
+    fn new(symbols: &'s SymbolBook, rule_lines: &[String]) -> Self {
+        Self {
+            symbols,
+            rules: Repl::rule_set(symbols, rule_lines),
+            net: None,
+        }
+    }
+}
+
+impl Repl {
+    fn book(&self) -> SymbolBook {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+        symbols.declare_arith_symbols();
+        for (name, polarity, ports) in &self.declared {
+            symbols.intern(name, *polarity, ports);
+        }
+        symbols
+    }
+
+    fn rule_set<'s>(symbols: &'s SymbolBook, rule_lines: &[String]) -> RuleSet<'s> {
+        let mut rules = RuleSet::new(symbols);
+        rules.arith_rules();
+        for line in rule_lines {
+            rules.parse_rule(line).expect("Rules parsed before parse again");
+        }
+        rules
+    }
+
+    /// Runs one command against `session`.
+    fn command(&mut self, session: &mut Session, line: &str) -> Next {
+        let (command, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let rest = rest.trim();
+        match command {
+            "symbol" => return self.symbol(session, rest),
+            "rule" => {
+                // parsed into a copy, the rules are kept as they were on error
+                let parsed = catch(|| {
+                    let mut rules = Self::rule_set(session.symbols, &self.rule_lines);
+                    rules.parse_rule(rest).map(|rule_ptr| {
+                        println!("{}", rules.display_rule(rule_ptr));
+                        rules
+                    })
+                });
+                match parsed {
+                    Some(Ok(rules)) => {
+                        self.rule_lines.push(rest.to_string());
+                        session.rules = rules;
+                    }
+                    Some(Err(error)) => println!("Invalid rule: {}", error),
+                    None => println!("Invalid rule"),
+                }
+            }
+            "net" => match catch(|| Net::from_str(rest, session.symbols)) {
+                Some(Ok(net)) => {
+                    println!("{}", net);
+                    session.net = Some(net);
+                }
+                Some(Err(error)) => println!("Invalid net: {}", error),
+                None => println!("Invalid net"),
+            },
+            "eval" => self.eval(session, false),
+            "step" => self.eval(session, true),
+            "show" => match &session.net {
+                Some(net) => println!("{}", net),
+                None => println!("No net, set one with net"),
+            },
+            "stats" => {
+                println!("REWRITES: {}", self.stats.rewrites);
+                println!("CELL REUSES: {}", self.stats.cell_reuses);
+                println!("CELL INSTANTIATIONS: {}", self.stats.cell_instantiations);
+                println!("VAR INSTANTIATIONS: {}", self.stats.var_instantiations);
+            }
+            "quit" | "exit" => return Next::Quit,
+            _ => println!("{}", HELP),
+        }
+        Next::Prompt
+    }
+
+    /// Declares a symbol written as displayed by `SymbolBook`, e.g. `-(add + -)`.
+    fn symbol(&mut self, session: &Session, text: &str) -> Next {
+        let polarity = |text: &str| text.parse::<Polarity>().ok();
+        let Some(symbol_polarity) = text.get(..1).and_then(polarity) else {
+            println!("Invalid symbol, expected + or - before {}", text);
+            return Next::Prompt;
+        };
+        let text = text[1..].trim();
+        let (name, ports) = match text.strip_prefix('(').and_then(|t| t.strip_suffix(')')) {
+            Some(cell) => {
+                let mut words = cell.split_whitespace();
+                let name = words.next().unwrap_or("");
+                let ports: Option<Vec<Polarity>> = words.map(polarity).collect();
+                match ports {
+                    Some(ports) => (name, ports),
+                    None => {
+                        println!("Invalid symbol, ports are + or -: {}", cell);
+                        return Next::Prompt;
+                    }
+                }
+            }
+            None => (text, Vec::new()),
+        };
+        if name.is_empty() || name.contains(|c: char| "()<>|=⋈←↔⟶,".contains(c)) {
+            println!("Invalid symbol name '{}'", name);
+            return Next::Prompt;
+        }
+        if session.symbols.to_sorted_vec().iter().any(|(other, _)| other.0 == name) {
+            println!("Symbol {} is already declared", name);
+            return Next::Prompt;
+        }
+
+        self.declared.push((SymbolName::intern(name), symbol_polarity, ports));
+        if session.net.is_some() {
+            println!("The net was cleared, it used the previous symbols");
+        }
+        Next::NewBook
+    }
+
+    fn eval(&mut self, session: &mut Session, single_step: bool) {
+        let Some(mut net) = session.net.take() else {
+            return println!("No net, set one with net");
+        };
+        let runtime = Runtime::new(&session.rules, false);
+        // a missing rule panics, the net is lost then
+        let result = catch(|| {
+            if single_step {
+                if !runtime.step(&mut net) {
+                    println!("No redex left");
+                }
+                net
+            } else {
+                runtime.eval(net).expect("The runtime has no rewrite limit")
+            }
+        });
+        self.stats.rewrites += runtime.get_rewrites();
+        self.stats.cell_reuses += runtime.get_cell_reuses();
+        self.stats.cell_instantiations += runtime.get_cell_instantiations();
+        self.stats.var_instantiations += runtime.get_var_instantiations();
+        match result.map(|net| rebind(net, session.symbols)) {
+            Some(net) => {
+                println!("{}", net);
+                if net.is_normal() {
+                    println!("{}", net.display_with_values(session.symbols));
+                }
+                session.net = Some(net);
+            }
+            None => println!("Evaluation failed, the net was cleared"),
+        }
+    }
+}
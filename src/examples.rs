@@ -1,4 +1,10 @@
 mod arith;
 mod combinators;
+mod eraser;
 mod fib;
+mod lambda;
+mod maybe;
+mod multiplication;
 mod nat;
+mod pair;
+mod ski;
@@ -21,6 +21,9 @@ impl SymbolBook {
         // sub
         self.fun2(&SUB_0, Polarity::Pos, Polarity::Neg);
         self.fun2(&SUB, Polarity::Pos, Polarity::Neg);
+
+        #[cfg(debug_assertions)]
+        self.assert_valid();
     }
 }
 
@@ -95,8 +98,8 @@ impl<'a> RuleSet<'a> {
         self.rule(&S, &ADD, |b| {
             let (neg_pvar, pos_pvar) = b.var();
             let fun_0 = b.fun_port_0();
-            let S_x = b.cell1(&S, pos_pvar.into());
-            b.bind(fun_0.into(), S_x);
+            let s_x = b.cell1(&S, pos_pvar.into());
+            b.bind(fun_0, s_x);
 
             let fun_1 = b.fun_port_1();
             let add = b.cell2(&ADD, neg_pvar.into(), fun_1.into());
@@ -144,3 +147,34 @@ impl<'a> RuleSet<'a> {
         });
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify_arith_rules() {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+        symbols.declare_arith_symbols();
+        let mut rules = RuleSet::new(&symbols);
+        rules.arith_rules();
+
+        // (ctr, fun, ctr ports, fun ports) for every rule above
+        let cases = [
+            (Z, ADD, vec![], vec!["r", "(S Z)"]),
+            (S, ADD, vec!["Z"], vec!["r", "(S Z)"]),
+            (Z, SUB, vec![], vec!["r", "(S Z)"]),
+            (S, SUB, vec!["(S Z)"], vec!["r", "(S Z)"]),
+            (Z, SUB_0, vec![], vec!["r", "Z"]),
+            (S, SUB_0, vec!["Z"], vec!["r", "Z"]),
+        ];
+        assert_eq!(rules.rules().count(), cases.len());
+        for (ctr, fun, ctr_ports, fun_ports) in cases {
+            let ctr_ptr = symbols.get_by_name(&ctr).unwrap();
+            let fun_ptr = symbols.get_by_name(&fun).unwrap();
+            let rule_ptr = rules.get_by_symbols(ctr_ptr, fun_ptr).unwrap();
+            assert_eq!(rules.verify_rule(rule_ptr, &[(ctr_ports, fun_ports)]), Ok(()));
+        }
+    }
+}
@@ -1,19 +1,43 @@
 use crate::inet::{
     cell::CellPtr,
     equation::{EquationBuilder, EquationPtr},
-    rule::RuleSet,
-    symbol::{SymbolBook, SymbolName},
+    rule::{PortNum, RuleBuilder, RuleSet},
+    symbol::{SymbolArity, SymbolBook, SymbolName},
     term::TermPtr,
     Polarity,
 };
 
-use super::nat::{S, Z};
+use super::{
+    eraser::ERA,
+    nat::{S, Z},
+};
+
+// Lazy sharing
+//
+// `share` hands out two `Extract` readers of one term, which may still be
+// under evaluation:
+//
+//   (Extract x⁻)     a reader; whatever consumes it is passed on to x
+//   (Share x⁻ o⁺)    the shared term x, parked until its first reader is
+//                    consumed; o leads to the other reader
+//
+// Nothing is copied until a function consumes the first reader: only then is
+// x split with `dup`, one copy for that function and one for the second
+// reader, which waits on the first until then. Erasing the first reader hands
+// x to the second one uncopied.
 
 pub const DUP: SymbolName = SymbolName("dup");
+pub const SHARE: SymbolName = SymbolName("Share");
+pub const EXTRACT: SymbolName = SymbolName("Extract");
 
 impl SymbolBook {
     pub fn declare_combinator_symbols(&mut self) {
         self.fun2(&DUP, Polarity::Pos, Polarity::Pos);
+        self.ctr2(&SHARE, Polarity::Neg, Polarity::Pos);
+        self.ctr1(&EXTRACT, Polarity::Neg);
+
+        #[cfg(debug_assertions)]
+        self.assert_valid();
     }
 }
 
@@ -28,20 +52,55 @@ impl<'a> EquationBuilder<'a> {
         let duplicator = self.duplicator(dup1.into(), dup2.into());
         self.redex(cell.into(), duplicator.into())
     }
+
+    /// Shares a term, which may still be under evaluation, between two
+    /// consumers. Returns the two `Extract` cells to consume it through, which
+    /// both read from the same `Share` cell. The second one is only fed once
+    /// the first is consumed.
+    pub fn share(&mut self, term: TermPtr) -> (CellPtr, CellPtr) {
+        let (s_input, s_output) = self.var();
+        let (o_input, o_output) = self.var();
+        let share = self.cell2(&SHARE, term, o_input.into());
+        self.bind(s_input, share);
+        let first = self.cell1(&EXTRACT, s_output.into());
+        let second = self.cell1(&EXTRACT, o_output.into());
+        (first, second)
+    }
+}
+
+impl<'a, 'b> RuleBuilder<'a, 'b> {
+    /// Like `EquationBuilder::share`.
+    pub fn share(&mut self, term: TermPtr) -> (CellPtr, CellPtr) {
+        let (s_input, s_output) = self.var();
+        let (o_input, o_output) = self.var();
+        let share = self.cell2(&SHARE, term, o_input.into());
+        self.bind(s_input, share);
+        let first = self.cell1(&EXTRACT, s_output.into());
+        let second = self.cell1(&EXTRACT, o_output.into());
+        (first, second)
+    }
+
+    // A copy of the rule's function cell, on the same ports
+    fn fun_copy(&mut self, name: &SymbolName, arity: SymbolArity) -> CellPtr {
+        let ports: Vec<TermPtr> = (0..arity.port_count())
+            .map(|port| self.fun_port(PortNum::from(port)).into())
+            .collect();
+        match arity {
+            SymbolArity::Zero => self.cell0(name),
+            SymbolArity::One => self.cell1(name, ports[0]),
+            SymbolArity::Two => self.cell2(name, ports[0], ports[1]),
+            SymbolArity::N(_) => self.cellN(name, &ports),
+        }
+    }
 }
 
 impl<'a> RuleSet<'a> {
+    /// Defines the `Share` and `Extract` rules for every function declared so
+    /// far, so declare all symbols first. The `era` rules are defined by
+    /// `define_eraser_rules`.
     pub fn define_combinator_rules(&mut self) {
         // Z ⋈ dup
-        self.rule(&Z, &DUP, |b| {
-            let r0 = b.fun_port_0();
-            let z0 = b.cell0(&Z);
-            b.bind(r0.into(), z0.into());
-
-            let r1 = b.fun_port_1();
-            let z1 = b.cell0(&Z);
-            b.bind(r1.into(), z1.into());
-        });
+        self.rule(&Z, &DUP, |b| b.erase_fun_ports());
 
         // S ⋈ dup
         self.rule(&S, &DUP, |b| {
@@ -60,5 +119,140 @@ impl<'a> RuleSet<'a> {
             let s1 = b.cell1(&S, x1_output.into());
             b.bind(r1.into(), s1.into());
         });
+
+        let symbols = self.symbols;
+        let funs = symbols
+            .to_sorted_by_index_vec()
+            .into_iter()
+            .filter(|(name, symbol_ptr)| {
+                symbol_ptr.get_polarity() == Polarity::Neg && **name != ERA
+            });
+        for (name, symbol_ptr) in funs {
+            let arity = symbol_ptr.get_arity();
+
+            // (Extract x) ⋈ f  ⟶  x ← f
+            self.rule(&EXTRACT, name, |b| {
+                let x = b.ctr_port_0();
+                let fun = b.fun_copy(name, arity);
+                b.bind(x, fun);
+            });
+
+            // (Share x o) ⋈ f  ⟶  x ← (dup f o)
+            self.rule(&SHARE, name, |b| {
+                let fun = b.fun_copy(name, arity);
+                let o = b.ctr_port_1();
+                let dup = b.cell2(&DUP, fun.into(), o.into());
+
+                let x = b.ctr_port_0();
+                b.bind(x, dup);
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::inet::{net::Net, runtime::Runtime};
+
+    use super::*;
+
+    fn share_symbols() -> SymbolBook {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+        symbols.declare_arith_symbols();
+        symbols.declare_combinator_symbols();
+        symbols.declare_eraser_symbols();
+        symbols
+    }
+
+    fn share_rules(symbols: &SymbolBook) -> RuleSet {
+        let mut rules = RuleSet::new(symbols);
+        rules.arith_rules();
+        rules.define_combinator_rules();
+        rules.define_eraser_rules();
+        rules
+    }
+
+    #[test]
+    fn test_share_pending_term() {
+        let symbols = share_symbols();
+        let rules = share_rules(&symbols);
+
+        // share 1 + 1 before it is computed, then add 1 to each copy
+        let mut net = Net::new(&symbols);
+        net.equations(|b| {
+            let (sum_input, sum_output) = b.var();
+            let one = b.one();
+            let adder = b.adder(sum_input.into(), one.into());
+            let one = b.one();
+            b.add(one, adder);
+
+            let (copy0, copy1) = b.share(sum_output.into());
+            for copy in [copy0, copy1] {
+                let out = b.output();
+                let one = b.one();
+                let adder = b.adder(out.into(), one.into());
+                b.add(copy, adder);
+            }
+        });
+
+        let runtime = Runtime::new(&rules, false);
+        let net = runtime.eval(net).unwrap();
+
+        let values = net.head_values();
+        assert_eq!(values.len(), 2);
+        for (_, value) in values {
+            assert_eq!(net.read_nat(value.unwrap()), Some(3));
+        }
+    }
+
+    #[test]
+    fn test_share_erased_reader_is_not_copied() {
+        let symbols = share_symbols();
+        let rules = share_rules(&symbols);
+
+        let mut net = Net::new(&symbols);
+        net.equations(|b| {
+            let out = b.output();
+            let three = b.n(3);
+            let (copy0, copy1) = b.share(three.into());
+            b.erase(copy0.into());
+            let zero = b.zero();
+            let adder = b.adder(out.into(), zero.into());
+            b.add(copy1, adder);
+        });
+
+        let runtime = Runtime::new(&rules, false);
+        let net = runtime.eval(net).unwrap();
+        assert_eq!(net.read_nat(net.single_output().unwrap()), Some(3));
+
+        // era and add each meet a reader, era meets the Share, and add walks
+        // the 3 uncopied layers of the term
+        assert_eq!(runtime.get_rewrites(), 3 + 4);
+    }
+
+    #[test]
+    fn test_verify_combinator_rules() {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+        symbols.declare_combinator_symbols();
+        let mut rules = RuleSet::new(&symbols);
+        rules.define_combinator_rules();
+
+        // (ctr, fun, ctr ports, fun ports) for every rule above, dup being
+        // the only function
+        let cases = [
+            (Z, DUP, vec![], vec!["a", "b"]),
+            (S, DUP, vec!["Z"], vec!["a", "b"]),
+            (EXTRACT, DUP, vec!["(S Z)"], vec!["a", "b"]),
+            (SHARE, DUP, vec!["(S Z)", "o"], vec!["a", "b"]),
+        ];
+        assert_eq!(rules.rules().count(), cases.len());
+        for (ctr, fun, ctr_ports, fun_ports) in cases {
+            let ctr_ptr = symbols.get_by_name(&ctr).unwrap();
+            let fun_ptr = symbols.get_by_name(&fun).unwrap();
+            let rule_ptr = rules.get_by_symbols(ctr_ptr, fun_ptr).unwrap();
+            assert_eq!(rules.verify_rule(rule_ptr, &[(ctr_ports, fun_ports)]), Ok(()));
+        }
     }
 }
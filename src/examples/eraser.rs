@@ -0,0 +1,158 @@
+use crate::inet::{
+    cell::CellPtr,
+    equation::EquationBuilder,
+    rule::RuleSet,
+    symbol::{SymbolBook, SymbolName},
+    term::{TermKind, TermPtr},
+};
+
+use super::{
+    combinators::{DUP, EXTRACT, SHARE},
+    nat::{S, Z},
+};
+
+// Erasure
+//
+// `era` consumes the constructors it meets and passes itself on to their
+// ports. Functions cannot meet `era`, so they are erased by `Erased`, which
+// passes itself on to their outputs and `era` to their inputs.
+
+pub const ERA: SymbolName = SymbolName("era");
+pub const ERASED: SymbolName = SymbolName("Erased");
+
+impl SymbolBook {
+    /// Requires the nat and combinator symbols.
+    pub fn declare_eraser_symbols(&mut self) {
+        self.fun0(&ERA);
+        self.ctr0(&ERASED);
+
+        #[cfg(debug_assertions)]
+        self.assert_valid();
+    }
+}
+
+impl<'a> EquationBuilder<'a> {
+    pub fn eraser(&mut self) -> CellPtr {
+        self.cell0(&ERA)
+    }
+
+    /// Erases a cell, or whatever value a var ends up holding.
+    pub fn erase(&mut self, term: TermPtr) {
+        let era = self.eraser();
+        match term.get_kind() {
+            TermKind::Cell => self.redex(term.get_cell_ptr(), era),
+            TermKind::Var => self.bind(term.get_var_ptr(), era),
+        };
+    }
+}
+
+impl<'a> RuleSet<'a> {
+    /// Requires the nat and combinator rules.
+    pub fn define_eraser_rules(&mut self) {
+        // Z ⋈ era  ⟶  ∅
+        self.rule(&Z, &ERA, |_| {});
+
+        // (S n) ⋈ era  ⟶  n ← era
+        self.rule(&S, &ERA, |b| {
+            let n = b.ctr_port_0();
+            let era = b.cell0(&ERA);
+            b.bind(n, era);
+        });
+
+        // (Extract x) ⋈ era  ⟶  x ← era
+        self.rule(&EXTRACT, &ERA, |b| {
+            let x = b.ctr_port_0();
+            let era = b.cell0(&ERA);
+            b.bind(x, era);
+        });
+
+        // (Share x o) ⋈ era  ⟶  x ↔ o
+        self.rule(&SHARE, &ERA, |b| {
+            let x = b.ctr_port_0();
+            let o = b.ctr_port_1();
+            b.connect(x, o);
+        });
+
+        // Erased ⋈ era  ⟶  ∅
+        self.rule(&ERASED, &ERA, |_| {});
+
+        // Erased ⋈ (dup d₀ d₁)  ⟶  d₀ ← Erased; d₁ ← Erased
+        self.rule(&ERASED, &DUP, |b| {
+            let d0 = b.fun_port_0();
+            let erased0 = b.cell0(&ERASED);
+            b.bind(d0, erased0);
+
+            let d1 = b.fun_port_1();
+            let erased1 = b.cell0(&ERASED);
+            b.bind(d1, erased1);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::inet::{net::Net, runtime::Runtime};
+
+    use super::*;
+
+    #[test]
+    fn test_erase_nat() {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+        symbols.declare_combinator_symbols();
+        symbols.declare_eraser_symbols();
+
+        let mut rules = RuleSet::new(&symbols);
+        rules.define_combinator_rules();
+        rules.define_eraser_rules();
+
+        let mut net = Net::new(&symbols);
+        net.equations(|b| {
+            let n = b.n(1000);
+            b.erase(n.into());
+
+            // a dup erases into Erased copies, which era erases in turn
+            let (d0_in, d0_out) = b.var();
+            let (d1_in, d1_out) = b.var();
+            let dup = b.duplicator(d0_in.into(), d1_in.into());
+            let erased = b.cell0(&ERASED);
+            b.redex(erased, dup);
+            b.erase(d0_out.into());
+            b.erase(d1_out.into());
+        });
+
+        let net = Runtime::new(&rules, false).eval(net).unwrap();
+        assert!(net.is_normal());
+        assert_eq!(net.body.len(), 0);
+        assert_eq!(net.heap.cells.len(), 0);
+    }
+
+    #[test]
+    fn test_verify_eraser_rules() {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+        symbols.declare_combinator_symbols();
+        symbols.declare_eraser_symbols();
+        let mut rules = RuleSet::new(&symbols);
+        rules.define_combinator_rules();
+        let combinator_rules = rules.rules().count();
+        rules.define_eraser_rules();
+
+        // (ctr, fun, ctr ports, fun ports) for every rule above
+        let cases = [
+            (Z, ERA, vec![], vec![]),
+            (S, ERA, vec!["(S Z)"], vec![]),
+            (EXTRACT, ERA, vec!["(S Z)"], vec![]),
+            (SHARE, ERA, vec!["(S Z)", "o"], vec![]),
+            (ERASED, ERA, vec![], vec![]),
+            (ERASED, DUP, vec![], vec!["a", "b"]),
+        ];
+        assert_eq!(rules.rules().count() - combinator_rules, cases.len());
+        for (ctr, fun, ctr_ports, fun_ports) in cases {
+            let ctr_ptr = symbols.get_by_name(&ctr).unwrap();
+            let fun_ptr = symbols.get_by_name(&fun).unwrap();
+            let rule_ptr = rules.get_by_symbols(ctr_ptr, fun_ptr).unwrap();
+            assert_eq!(rules.verify_rule(rule_ptr, &[(ctr_ports, fun_ports)]), Ok(()));
+        }
+    }
+}
@@ -10,16 +10,31 @@ use crate::inet::{
 
 use super::{
     combinators::DUP,
+    eraser::ERA,
     nat::{S, Z},
 };
 
 pub const FIB: SymbolName = SymbolName("fib");
 const FIB_0: SymbolName = SymbolName("fib₀");
+pub const FIB_SHARED: SymbolName = SymbolName("fibₛ");
+const FIB_PAIR: SymbolName = SymbolName("fibₚ");
 
 impl SymbolBook {
     pub fn declare_fib_symbols(&mut self) {
         self.fun1(&FIB, Polarity::Pos);
         self.fun1(&FIB_0, Polarity::Pos);
+
+        #[cfg(debug_assertions)]
+        self.assert_valid();
+    }
+
+    /// Requires the nat, arith, combinator and eraser symbols.
+    pub fn declare_fib_shared_symbols(&mut self) {
+        self.fun1(&FIB_SHARED, Polarity::Pos);
+        self.fun2(&FIB_PAIR, Polarity::Pos, Polarity::Pos);
+
+        #[cfg(debug_assertions)]
+        self.assert_valid();
     }
 }
 
@@ -36,6 +51,11 @@ impl<'a> EquationBuilder<'a> {
         let fib = self.cell1(&FIB, result);
         self.redex(num.into(), fib.into())
     }
+
+    pub fn fibonacci_shared(&mut self, num: TermPtr, result: TermPtr) {
+        let fib = self.cell1(&FIB_SHARED, result);
+        self.redex(num.into(), fib)
+    }
 }
 
 impl<'a, 'b> RuleBuilder<'a, 'b> {
@@ -90,6 +110,67 @@ impl<'a> RuleSet<'a> {
     }
 }
 
+impl<'a> RuleSet<'a> {
+    /// Fibonacci computing each number once: `fibₚ n` outputs fib(n + 1) and
+    /// fib(n), and shares fib(n + 1) between the next pair instead of
+    /// duplicating `n` and recomputing both halves like `fib₀` does. Requires
+    /// the nat, arith, combinator and eraser rules.
+    pub fn fib_shared_rules(&mut self) {
+        // Z ⋈ (fibₛ r₀)  ⟶  r₀ ← Z
+        self.rule(&Z, &FIB_SHARED, |b| {
+            let r0 = b.fun_port_0();
+            let zero = b.zero();
+            b.bind(r0, zero);
+        });
+
+        // (S l₀) ⋈ (fibₛ r₀)  ⟶  l₀ ← (fibₚ x₀ era); x₀ ⋈ (add r₀ Z)
+        //
+        // The add forces the readers left in the result.
+        self.rule(&S, &FIB_SHARED, |b| {
+            let (x0_in, x0_out) = b.var();
+            let r0 = b.fun_port_0();
+            let zero = b.zero();
+            let adder = b.adder(r0.into(), zero.into());
+            b.bind(x0_out, adder);
+
+            let era = b.cell0(&ERA);
+            let pair = b.cell2(&FIB_PAIR, x0_in.into(), era.into());
+            let l0 = b.ctr_port_0();
+            b.bind(l0, pair);
+        });
+
+        // Z ⋈ (fibₚ r₀ r₁)  ⟶  r₀ ← (S Z); r₁ ← Z
+        self.rule(&Z, &FIB_PAIR, |b| {
+            let r0 = b.fun_port_0();
+            let one = b.one();
+            b.bind(r0, one);
+
+            let r1 = b.fun_port_1();
+            let zero = b.zero();
+            b.bind(r1, zero);
+        });
+
+        // (S l₀) ⋈ (fibₚ r₀ r₁)  ⟶  l₀ ← (fibₚ x₀ x₁); (e₀, e₁) = share x₀; e₀ ⋈ (add r₀ x₁); r₁ ← e₁
+        //
+        // The add consumes the first reader, which the second one waits on.
+        self.rule(&S, &FIB_PAIR, |b| {
+            let (x0_in, x0_out) = b.var();
+            let (x1_in, x1_out) = b.var();
+            let pair = b.cell2(&FIB_PAIR, x0_in.into(), x1_in.into());
+            let l0 = b.ctr_port_0();
+            b.bind(l0, pair);
+
+            let (e0, e1) = b.share(x0_out.into());
+            let r0 = b.fun_port_0();
+            let adder = b.adder(r0.into(), x1_out.into());
+            b.redex(e0, adder);
+
+            let r1 = b.fun_port_1();
+            b.bind(r1, e1);
+        });
+    }
+}
+
 impl<'a> Net<'a> {
     pub fn fib(&mut self, n: usize) {
         self.equations(|b| {
@@ -99,3 +180,92 @@ impl<'a> Net<'a> {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::inet::runtime::Runtime;
+
+    use super::*;
+
+    #[test]
+    fn test_fib_shared_rewrites() {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+        symbols.declare_arith_symbols();
+        symbols.declare_fib_symbols();
+        symbols.declare_fib_shared_symbols();
+        symbols.declare_combinator_symbols();
+        symbols.declare_eraser_symbols();
+
+        let mut rules = RuleSet::new(&symbols);
+        rules.arith_rules();
+        rules.fib_rules();
+        rules.fib_shared_rules();
+        rules.define_combinator_rules();
+        rules.define_eraser_rules();
+
+        let runtime = Runtime::new(&rules, false);
+        let mut net = Net::new(&symbols);
+        net.fib(8);
+        let net = runtime.eval(net).unwrap();
+        assert_eq!(net.read_nat(net.single_output().unwrap()), Some(21));
+        let dup_rewrites = runtime.get_rewrites();
+
+        let runtime = Runtime::new(&rules, false);
+        let mut net = Net::new(&symbols);
+        net.equations(|b| {
+            let result = b.output();
+            let num = b.n(8);
+            b.fibonacci_shared(num.into(), result.into());
+        });
+        let net = runtime.eval(net).unwrap();
+        assert_eq!(net.read_nat(net.single_output().unwrap()), Some(21));
+        let shared_rewrites = runtime.get_rewrites();
+
+        // 146 against 271
+        assert!(
+            shared_rewrites < dup_rewrites,
+            "{} rewrites with sharing, {} with dup",
+            shared_rewrites,
+            dup_rewrites
+        );
+    }
+
+    #[test]
+    fn test_verify_fib_rules() {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+        symbols.declare_arith_symbols();
+        symbols.declare_fib_symbols();
+        symbols.declare_fib_shared_symbols();
+        symbols.declare_combinator_symbols();
+        symbols.declare_eraser_symbols();
+
+        let mut rules = RuleSet::new(&symbols);
+        rules.arith_rules();
+        rules.define_combinator_rules();
+        rules.define_eraser_rules();
+        let other_rules = rules.rules().count();
+        rules.fib_rules();
+        rules.fib_shared_rules();
+
+        // (ctr, fun, ctr ports, fun ports) for every rule above
+        let cases = [
+            (Z, FIB, vec![], vec!["r"]),
+            (S, FIB, vec!["(S Z)"], vec!["r"]),
+            (Z, FIB_0, vec![], vec!["r"]),
+            (S, FIB_0, vec!["(S Z)"], vec!["r"]),
+            (Z, FIB_SHARED, vec![], vec!["r"]),
+            (S, FIB_SHARED, vec!["(S Z)"], vec!["r"]),
+            (Z, FIB_PAIR, vec![], vec!["a", "b"]),
+            (S, FIB_PAIR, vec!["(S Z)"], vec!["a", "b"]),
+        ];
+        assert_eq!(rules.rules().count() - other_rules, cases.len());
+        for (ctr, fun, ctr_ports, fun_ports) in cases {
+            let ctr_ptr = symbols.get_by_name(&ctr).unwrap();
+            let fun_ptr = symbols.get_by_name(&fun).unwrap();
+            let rule_ptr = rules.get_by_symbols(ctr_ptr, fun_ptr).unwrap();
+            assert_eq!(rules.verify_rule(rule_ptr, &[(ctr_ports, fun_ports)]), Ok(()));
+        }
+    }
+}
@@ -0,0 +1,360 @@
+use crate::inet::{
+    cell::CellPtr,
+    equation::EquationBuilder,
+    rule::RuleSet,
+    symbol::{SymbolBook, SymbolName},
+    term::{TermKind, TermPtr},
+    var::PVarPtr,
+    Polarity,
+};
+
+use super::{
+    combinators::{DUP, EXTRACT},
+    eraser::{ERA, ERASED},
+    nat::S,
+};
+
+// Lambda calculus
+//
+// Lambdas are values (constructors) and applications consume them (functions):
+//
+//   (Lam body⁻ var⁺)   the body flows in, the argument flows out to the var occurrences
+//   (app arg⁻ result⁺) the argument flows in, the result flows out
+//
+// Sharing uses the `dup` combinator, which needs `Sup` to merge the vars of
+// duplicated lambdas back into a single value. Erasing uses `era` for values
+// and `Erased` for the vars of erased lambdas. Duplicators are not labeled so
+// `Sup ⋈ dup` always annihilates.
+//
+// An application is read through an `Extract` cell, so it can be passed on
+// like any other value before it is reduced.
+//
+// Church numerals apply `f` n times, λf.λx.f (f … (f x)), and are decoded by
+// applying them to λv.(S v) and Z.
+
+pub const LAM: SymbolName = SymbolName("Lam");
+pub const APP: SymbolName = SymbolName("app");
+pub const SUP: SymbolName = SymbolName("Sup");
+
+impl SymbolBook {
+    /// Requires the nat, combinator and eraser symbols.
+    pub fn declare_lambda_symbols(&mut self) {
+        self.ctr2(&LAM, Polarity::Neg, Polarity::Pos);
+        self.fun2(&APP, Polarity::Neg, Polarity::Pos);
+        self.ctr2(&SUP, Polarity::Neg, Polarity::Neg);
+
+        #[cfg(debug_assertions)]
+        self.assert_valid();
+    }
+}
+
+impl<'a> EquationBuilder<'a> {
+    pub fn lambda(&mut self, body: TermPtr, var: TermPtr) -> CellPtr {
+        self.cell2(&LAM, body, var)
+    }
+
+    pub fn applicator(&mut self, arg: TermPtr, result: TermPtr) -> CellPtr {
+        self.cell2(&APP, arg, result)
+    }
+
+    /// Applies `func` to `arg`. Returns an `Extract` reader of the result.
+    pub fn apply(&mut self, func: TermPtr, arg: TermPtr) -> CellPtr {
+        let (result_input, result_output) = self.var();
+        let app = self.applicator(arg, result_input.into());
+        match func.get_kind() {
+            TermKind::Cell => self.redex(func.get_cell_ptr(), app),
+            TermKind::Var => self.bind(func.get_var_ptr(), app),
+        }
+        self.cell1(&EXTRACT, result_output.into())
+    }
+
+    /// The Church numeral of `n`, λf.λx.f (f … (f x)).
+    pub fn church(&mut self, n: usize) -> CellPtr {
+        let (f_input, f_output) = self.var();
+        let (x_input, x_output) = self.var();
+        let mut body: TermPtr = x_output.into();
+        for f in self.copies(f_output, n) {
+            body = self.apply(f.into(), body).into();
+        }
+        let inner = self.lambda(body, x_input.into());
+        self.lambda(inner.into(), f_input.into())
+    }
+
+    /// The Church successor, λn.λf.λx.f (n f x).
+    pub fn church_succ(&mut self) -> CellPtr {
+        let (n_input, n_output) = self.var();
+        let (f_input, f_output) = self.var();
+        let (x_input, x_output) = self.var();
+        let f = self.copies(f_output, 2);
+        let nf = self.apply(n_output.into(), f[0].into());
+        let nfx = self.apply(nf.into(), x_output.into());
+        let body = self.apply(f[1].into(), nfx.into());
+        let lam_x = self.lambda(body.into(), x_input.into());
+        let lam_f = self.lambda(lam_x.into(), f_input.into());
+        self.lambda(lam_f.into(), n_input.into())
+    }
+
+    /// Decodes a Church numeral into a nat bound to `result`. The result is
+    /// forced by adding Z to it, so this requires the arith rules and the
+    /// arith symbols to be declared before the combinator rules are defined.
+    pub fn church_to_nat(&mut self, numeral: TermPtr, result: TermPtr) {
+        let (v_input, v_output) = self.var();
+        let s = self.cell1(&S, v_output.into());
+        let succ = self.lambda(s.into(), v_input.into());
+        let applied = self.apply(numeral, succ.into());
+        let zero = self.zero();
+        let nat = self.apply(applied.into(), zero.into());
+        let zero = self.zero();
+        let adder = self.adder(result, zero.into());
+        self.redex(nat, adder);
+    }
+
+    // `n` uses of `var`, duplicated as needed or erased if there are none
+    fn copies(&mut self, var: PVarPtr, n: usize) -> Vec<PVarPtr> {
+        if n == 0 {
+            let era = self.eraser();
+            self.bind(var, era);
+            return vec![];
+        }
+        let mut copies = Vec::with_capacity(n);
+        let mut rest = var;
+        for _ in 1..n {
+            let (copy_input, copy_output) = self.var();
+            let (rest_input, rest_output) = self.var();
+            let dup = self.duplicator(copy_input.into(), rest_input.into());
+            self.bind(rest, dup);
+            copies.push(copy_output);
+            rest = rest_output;
+        }
+        copies.push(rest);
+        copies
+    }
+}
+
+impl<'a> RuleSet<'a> {
+    /// Requires the nat, combinator and eraser rules.
+    pub fn define_lambda_rules(&mut self) {
+        // (Lam b v) ⋈ (app a r)  ⟶  v ↔ a; b ↔ r
+        self.rule(&LAM, &APP, |b| {
+            let var = b.ctr_port_1();
+            let arg = b.fun_port_0();
+            b.connect(var, arg);
+
+            let body = b.ctr_port_0();
+            let result = b.fun_port_1();
+            b.connect(body, result);
+        });
+
+        // (Lam b v) ⋈ (dup d₀ d₁)  ⟶  d₀ ← (Lam x₀ y₀); d₁ ← (Lam x₁ y₁); b ← (dup x₀ x₁); v ← (Sup y₀ y₁)
+        self.rule(&LAM, &DUP, |b| {
+            let (x0_in, x0_out) = b.var();
+            let (x1_in, x1_out) = b.var();
+            let (y0_in, y0_out) = b.var();
+            let (y1_in, y1_out) = b.var();
+
+            let d0 = b.fun_port_0();
+            let lam0 = b.cell2(&LAM, x0_out.into(), y0_in.into());
+            b.bind(d0, lam0);
+
+            let d1 = b.fun_port_1();
+            let lam1 = b.cell2(&LAM, x1_out.into(), y1_in.into());
+            b.bind(d1, lam1);
+
+            let body = b.ctr_port_0();
+            let dup = b.cell2(&DUP, x0_in.into(), x1_in.into());
+            b.bind(body, dup);
+
+            let var = b.ctr_port_1();
+            let sup = b.cell2(&SUP, y0_out.into(), y1_out.into());
+            b.bind(var, sup);
+        });
+
+        // (Lam b v) ⋈ era  ⟶  b ← era; v ← Erased
+        self.rule(&LAM, &ERA, |b| {
+            let body = b.ctr_port_0();
+            let era = b.cell0(&ERA);
+            b.bind(body, era);
+
+            let var = b.ctr_port_1();
+            let erased = b.cell0(&ERASED);
+            b.bind(var, erased);
+        });
+
+        // (Sup f₀ f₁) ⋈ (app a r)  ⟶  f₀ ← (app x₀ y₀); f₁ ← (app x₁ y₁); a ← (dup x₀ x₁); r ← (Sup y₀ y₁)
+        self.rule(&SUP, &APP, |b| {
+            let (x0_in, x0_out) = b.var();
+            let (x1_in, x1_out) = b.var();
+            let (y0_in, y0_out) = b.var();
+            let (y1_in, y1_out) = b.var();
+
+            let f0 = b.ctr_port_0();
+            let app0 = b.cell2(&APP, x0_out.into(), y0_in.into());
+            b.bind(f0, app0);
+
+            let f1 = b.ctr_port_1();
+            let app1 = b.cell2(&APP, x1_out.into(), y1_in.into());
+            b.bind(f1, app1);
+
+            let arg = b.fun_port_0();
+            let dup = b.cell2(&DUP, x0_in.into(), x1_in.into());
+            b.bind(arg, dup);
+
+            let result = b.fun_port_1();
+            let sup = b.cell2(&SUP, y0_out.into(), y1_out.into());
+            b.bind(result, sup);
+        });
+
+        // (Sup s₀ s₁) ⋈ (dup d₀ d₁)  ⟶  d₀ ↔ s₀; d₁ ↔ s₁
+        self.rule(&SUP, &DUP, |b| {
+            let s0 = b.ctr_port_0();
+            let d0 = b.fun_port_0();
+            b.connect(d0, s0);
+
+            let s1 = b.ctr_port_1();
+            let d1 = b.fun_port_1();
+            b.connect(d1, s1);
+        });
+
+        // (Sup s₀ s₁) ⋈ era  ⟶  s₀ ← era; s₁ ← era
+        self.rule(&SUP, &ERA, |b| {
+            let s0 = b.ctr_port_0();
+            let era0 = b.cell0(&ERA);
+            b.bind(s0, era0);
+
+            let s1 = b.ctr_port_1();
+            let era1 = b.cell0(&ERA);
+            b.bind(s1, era1);
+        });
+
+        // Erased ⋈ (app a r)  ⟶  a ← era; r ← Erased
+        self.rule(&ERASED, &APP, |b| {
+            let arg = b.fun_port_0();
+            let era = b.cell0(&ERA);
+            b.bind(arg, era);
+
+            let result = b.fun_port_1();
+            let erased = b.cell0(&ERASED);
+            b.bind(result, erased);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::inet::{net::Net, runtime::Runtime};
+
+    use super::*;
+
+    fn lambda_symbols() -> SymbolBook {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+        symbols.declare_arith_symbols();
+        symbols.declare_combinator_symbols();
+        symbols.declare_eraser_symbols();
+        symbols.declare_lambda_symbols();
+        symbols
+    }
+
+    fn lambda_rules(symbols: &SymbolBook) -> RuleSet {
+        let mut rules = RuleSet::new(symbols);
+        rules.arith_rules();
+        rules.define_combinator_rules();
+        rules.define_eraser_rules();
+        rules.define_lambda_rules();
+        rules
+    }
+
+    fn eval_church(build: impl FnOnce(&mut EquationBuilder) -> CellPtr) -> Option<usize> {
+        let symbols = lambda_symbols();
+        let rules = lambda_rules(&symbols);
+
+        let mut net = Net::new(&symbols);
+        net.equations(|b| {
+            let result = b.output();
+            let numeral = build(b);
+            b.church_to_nat(numeral.into(), result.into());
+        });
+
+        let runtime = Runtime::new(&rules, false);
+        let net = runtime.eval(net).unwrap();
+        assert!(net.is_normal());
+        net.read_nat(net.single_output()?)
+    }
+
+    #[test]
+    fn test_church_numerals() {
+        for n in 0..4 {
+            assert_eq!(eval_church(|b| b.church(n)), Some(n));
+        }
+    }
+
+    #[test]
+    fn test_identity() {
+        // (λx.x) 1
+        let one = eval_church(|b| {
+            let (x_in, x_out) = b.var();
+            let id = b.lambda(x_out.into(), x_in.into());
+            let one = b.church(1);
+            b.apply(id.into(), one.into())
+        });
+        assert_eq!(one, Some(1));
+    }
+
+    #[test]
+    fn test_k_combinator() {
+        // (λx.λy.x) 0 1
+        let zero = eval_church(|b| {
+            let (x_in, x_out) = b.var();
+            let (y_in, y_out) = b.var();
+            let era = b.eraser();
+            b.bind(y_out, era);
+            let inner = b.lambda(x_out.into(), y_in.into());
+            let k = b.lambda(inner.into(), x_in.into());
+
+            let zero = b.church(0);
+            let k_zero = b.apply(k.into(), zero.into());
+            let one = b.church(1);
+            b.apply(k_zero.into(), one.into())
+        });
+        assert_eq!(zero, Some(0));
+    }
+
+    #[test]
+    fn test_church_succ() {
+        // succ 2
+        let three = eval_church(|b| {
+            let succ = b.church_succ();
+            let two = b.church(2);
+            b.apply(succ.into(), two.into())
+        });
+        assert_eq!(three, Some(3));
+    }
+
+    #[test]
+    fn test_verify_lambda_rules() {
+        let symbols = lambda_symbols();
+        let mut rules = RuleSet::new(&symbols);
+        rules.define_combinator_rules();
+        rules.define_eraser_rules();
+        let other_rules = rules.rules().count();
+        rules.define_lambda_rules();
+
+        // (ctr, fun, ctr ports, fun ports) for every rule above
+        let cases = [
+            (LAM, APP, vec!["Z", "v"], vec!["(S Z)", "r"]),
+            (LAM, DUP, vec!["(S Z)", "v"], vec!["a", "b"]),
+            (LAM, ERA, vec!["(S Z)", "v"], vec![]),
+            (SUP, APP, vec!["f", "g"], vec!["(S Z)", "r"]),
+            (SUP, DUP, vec!["Z", "(S Z)"], vec!["a", "b"]),
+            (SUP, ERA, vec!["Z", "(S Z)"], vec![]),
+            (ERASED, APP, vec![], vec!["(S Z)", "r"]),
+        ];
+        assert_eq!(rules.rules().count() - other_rules, cases.len());
+        for (ctr, fun, ctr_ports, fun_ports) in cases {
+            let ctr_ptr = symbols.get_by_name(&ctr).unwrap();
+            let fun_ptr = symbols.get_by_name(&fun).unwrap();
+            let rule_ptr = rules.get_by_symbols(ctr_ptr, fun_ptr).unwrap();
+            assert_eq!(rules.verify_rule(rule_ptr, &[(ctr_ports, fun_ports)]), Ok(()));
+        }
+    }
+}
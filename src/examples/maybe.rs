@@ -0,0 +1,146 @@
+use crate::inet::{
+    cell::CellPtr,
+    equation::EquationBuilder,
+    rule::RuleSet,
+    symbol::{SymbolBook, SymbolName},
+    term::{TermKind, TermPtr},
+    Polarity,
+};
+
+use super::eraser::ERA;
+
+// Optional values
+//
+// `unwrap_or` passes on the value of a `Some` and erases its default, or
+// passes on the default for `None`.
+
+pub const NONE: SymbolName = SymbolName("None");
+pub const SOME: SymbolName = SymbolName("Some");
+pub const UNWRAP_OR: SymbolName = SymbolName("unwrap_or");
+
+impl SymbolBook {
+    /// Needs the eraser symbols too.
+    pub fn declare_maybe_symbols(&mut self) {
+        self.ctr0(&NONE);
+        self.ctr1(&SOME, Polarity::Neg);
+
+        // (unwrap_or result default)
+        self.fun2(&UNWRAP_OR, Polarity::Pos, Polarity::Neg);
+
+        #[cfg(debug_assertions)]
+        self.assert_valid();
+    }
+}
+
+impl<'a> EquationBuilder<'a> {
+    pub fn none(&mut self) -> CellPtr {
+        self.cell0(&NONE)
+    }
+
+    pub fn some(&mut self, value: TermPtr) -> CellPtr {
+        self.cell1(&SOME, value)
+    }
+
+    /// Unwraps a maybe cell, or the maybe a var ends up holding.
+    pub fn unwrap_or(&mut self, default: TermPtr, maybe: TermPtr, result: TermPtr) {
+        let unwrap_or = self.cell2(&UNWRAP_OR, result, default);
+        match maybe.get_kind() {
+            TermKind::Cell => self.redex(maybe.get_cell_ptr(), unwrap_or),
+            TermKind::Var => self.bind(maybe.get_var_ptr(), unwrap_or),
+        };
+    }
+}
+
+impl<'a> RuleSet<'a> {
+    /// Needs the eraser rules too.
+    pub fn define_maybe_rules(&mut self) {
+        // None ⋈ (unwrap_or r d)  ⟶  r ↔ d
+        self.rule(&NONE, &UNWRAP_OR, |b| {
+            let r = b.fun_port_0();
+            let d = b.fun_port_1();
+            b.connect(r, d);
+        });
+
+        // (Some v) ⋈ (unwrap_or r d)  ⟶  r ↔ v, d ← era
+        self.rule(&SOME, &UNWRAP_OR, |b| {
+            let r = b.fun_port_0();
+            let v = b.ctr_port_0();
+            b.connect(r, v);
+            let d = b.fun_port_1();
+            let era = b.cell0(&ERA);
+            b.bind(d, era);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::inet::{net::Net, runtime::Runtime};
+
+    use super::*;
+
+    fn unwrap_or_zero(some: Option<usize>) -> Option<usize> {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+        symbols.declare_combinator_symbols();
+        symbols.declare_eraser_symbols();
+        symbols.declare_maybe_symbols();
+
+        let mut rules = RuleSet::new(&symbols);
+        rules.define_combinator_rules();
+        rules.define_eraser_rules();
+        rules.define_maybe_rules();
+
+        let mut net = Net::new(&symbols);
+        net.equations(|b| {
+            let result = b.output();
+            let maybe = match some {
+                Some(value) => {
+                    let value = b.n(value);
+                    b.some(value.into())
+                }
+                None => b.none(),
+            };
+            let default = b.zero();
+            b.unwrap_or(default.into(), maybe.into(), result.into());
+        });
+
+        let net = Runtime::new(&rules, false).eval(net).unwrap();
+        assert!(net.is_normal());
+        net.read_nat(net.single_output()?)
+    }
+
+    #[test]
+    fn test_unwrap_or() {
+        assert_eq!(unwrap_or_zero(None), Some(0));
+        assert_eq!(unwrap_or_zero(Some(1)), Some(1));
+    }
+
+    #[test]
+    fn test_verify_maybe_rules() {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+        symbols.declare_combinator_symbols();
+        symbols.declare_eraser_symbols();
+        symbols.declare_maybe_symbols();
+
+        let mut rules = RuleSet::new(&symbols);
+        rules.define_combinator_rules();
+        rules.define_eraser_rules();
+        let other_rules = rules.rules().count();
+        rules.define_maybe_rules();
+
+        // (ctr, fun, ctr ports, fun ports) for every rule above
+        let cases = [
+            (NONE, UNWRAP_OR, vec![], vec!["r", "Z"]),
+            (SOME, UNWRAP_OR, vec!["(S Z)"], vec!["r", "(S Z)"]),
+        ];
+        assert_eq!(rules.rules().count() - other_rules, cases.len());
+        for (ctr, fun, ctr_ports, fun_ports) in cases {
+            let ctr_ptr = symbols.get_by_name(&ctr).unwrap();
+            let fun_ptr = symbols.get_by_name(&fun).unwrap();
+            let rule_ptr = rules.get_by_symbols(ctr_ptr, fun_ptr).unwrap();
+            assert_eq!(rules.verify_rule(rule_ptr, &[(ctr_ports, fun_ports)]), Ok(()));
+        }
+    }
+}
@@ -0,0 +1,178 @@
+use crate::inet::{
+    cell::CellPtr,
+    equation::EquationBuilder,
+    rule::{RuleBuilder, RuleSet},
+    symbol::{SymbolBook, SymbolName},
+    term::TermPtr,
+    Polarity,
+};
+
+use super::{
+    arith::ADD,
+    combinators::DUP,
+    nat::{S, Z},
+};
+
+pub const MUL: SymbolName = SymbolName("mul");
+const MUL_0: SymbolName = SymbolName("mul₀");
+
+impl SymbolBook {
+    /// Needs the nat, arith and combinator symbols too.
+    pub fn declare_multiplication_symbols(&mut self) {
+        // mul
+        self.fun2(&MUL, Polarity::Pos, Polarity::Neg);
+
+        // mul₀ consumes a nat and passes its second port on to its first
+        self.fun2(&MUL_0, Polarity::Pos, Polarity::Neg);
+
+        #[cfg(debug_assertions)]
+        self.assert_valid();
+    }
+}
+
+impl<'a> EquationBuilder<'a> {
+    // multiplier function
+    pub fn multiplier(
+        &mut self,
+        result: TermPtr,   // port 0
+        operand2: TermPtr, // port 1
+    ) -> CellPtr {
+        self.cell2(&MUL, result, operand2)
+    }
+
+    // multiply redex
+    pub fn multiply(
+        &mut self,
+        operand1: CellPtr,   // constructor
+        multiplier: CellPtr, // function
+    ) {
+        self.redex(operand1, multiplier)
+    }
+}
+
+impl<'a, 'b> RuleBuilder<'a, 'b> {
+    pub fn multiplier(
+        &mut self,
+        result: TermPtr,   // port 0
+        operand2: TermPtr, // port 1
+    ) -> CellPtr {
+        self.cell2(&MUL, result, operand2)
+    }
+}
+
+impl<'a> RuleSet<'a> {
+    /// Needs the arith and combinator rules too, `mul` adds its second
+    /// operand once per `S` of the first.
+    pub fn multiplication_rules(&mut self) {
+        // (mul r m) = Z  ⟶  m = (mul₀ r Z)
+        self.rule(&Z, &MUL, |b| {
+            let r = b.fun_port_0();
+            let m = b.fun_port_1();
+            let zero = b.zero();
+            let mul0 = b.cell2(&MUL_0, r.into(), zero.into());
+            b.bind(m, mul0);
+        });
+
+        // (mul r m) = (S n)  ⟶  m = (dup m₀ m₁), n = (mul (add r m₁) m₀)
+        self.rule(&S, &MUL, |b| {
+            let (m0_input, m0_output) = b.var();
+            let (m1_input, m1_output) = b.var();
+
+            let m = b.fun_port_1();
+            let dup = b.cell2(&DUP, m0_input.into(), m1_input.into());
+            b.bind(m, dup);
+
+            let r = b.fun_port_0();
+            let add = b.cell2(&ADD, r.into(), m1_output.into());
+            let mul = b.multiplier(add.into(), m0_output.into());
+            let n = b.ctr_port_0();
+            b.bind(n, mul);
+        });
+
+        // (mul₀ r a) = Z  ⟶  r = a
+        self.rule(&Z, &MUL_0, |b| {
+            let r = b.fun_port_0();
+            let a = b.fun_port_1();
+            b.connect(r, a);
+        });
+
+        // (mul₀ r a) = (S n)  ⟶  n = (mul₀ r a)
+        self.rule(&S, &MUL_0, |b| {
+            let r = b.fun_port_0();
+            let a = b.fun_port_1();
+            let mul0 = b.cell2(&MUL_0, r.into(), a.into());
+            let n = b.ctr_port_0();
+            b.bind(n, mul0);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::inet::{net::Net, runtime::Runtime};
+
+    use super::*;
+
+    fn multiply(n: usize, m: usize) -> Option<usize> {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+        symbols.declare_arith_symbols();
+        symbols.declare_combinator_symbols();
+        symbols.declare_multiplication_symbols();
+
+        let mut rules = RuleSet::new(&symbols);
+        rules.arith_rules();
+        rules.define_combinator_rules();
+        rules.multiplication_rules();
+
+        let mut net = Net::new(&symbols);
+        net.equations(|b| {
+            let result = b.output();
+            let m = b.n(m);
+            let multiplier = b.multiplier(result.into(), m.into());
+            let n = b.n(n);
+            b.multiply(n, multiplier);
+        });
+
+        let net = Runtime::new(&rules, false).eval(net).unwrap();
+        assert!(net.is_normal());
+        net.read_nat(net.single_output()?)
+    }
+
+    #[test]
+    fn test_multiply() {
+        assert_eq!(multiply(2, 3), Some(6));
+        assert_eq!(multiply(0, 5), Some(0));
+        assert_eq!(multiply(4, 0), Some(0));
+    }
+
+    #[test]
+    fn test_verify_multiplication_rules() {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+        symbols.declare_arith_symbols();
+        symbols.declare_combinator_symbols();
+        symbols.declare_multiplication_symbols();
+
+        let mut rules = RuleSet::new(&symbols);
+        rules.arith_rules();
+        rules.define_combinator_rules();
+        let other_rules = rules.rules().count();
+        rules.multiplication_rules();
+
+        // (ctr, fun, ctr ports, fun ports) for every rule above
+        let cases = [
+            (Z, MUL, vec![], vec!["r", "(S Z)"]),
+            (S, MUL, vec!["(S Z)"], vec!["r", "(S Z)"]),
+            (Z, MUL_0, vec![], vec!["r", "Z"]),
+            (S, MUL_0, vec!["Z"], vec!["r", "(S Z)"]),
+        ];
+        assert_eq!(rules.rules().count() - other_rules, cases.len());
+        for (ctr, fun, ctr_ports, fun_ports) in cases {
+            let ctr_ptr = symbols.get_by_name(&ctr).unwrap();
+            let fun_ptr = symbols.get_by_name(&fun).unwrap();
+            let rule_ptr = rules.get_by_symbols(ctr_ptr, fun_ptr).unwrap();
+            assert_eq!(rules.verify_rule(rule_ptr, &[(ctr_ports, fun_ports)]), Ok(()));
+        }
+    }
+}
@@ -1,8 +1,10 @@
 use crate::inet::{
     cell::CellPtr,
     equation::EquationBuilder,
+    net::Net,
     rule::RuleBuilder,
     symbol::{SymbolBook, SymbolName},
+    term::TermKind,
     Polarity,
 };
 
@@ -18,6 +20,9 @@ impl SymbolBook {
 
         // S
         self.ctr1(&S, Polarity::Neg);
+
+        #[cfg(debug_assertions)]
+        self.assert_valid();
     }
 }
 
@@ -61,10 +66,33 @@ impl<'a> EquationBuilder<'a> {
     }
 
     pub fn n(&mut self, num: usize) -> CellPtr {
+        let s = self.get_symbol_ptr(&S);
         let mut n = self.zero();
         for _ in 0..num {
-            n = self.succ(n)
+            n = self.cell1_ptr(s, n.into())
         }
         n
     }
 }
+
+impl<'a> Net<'a> {
+    /// Counts the S cells down to Z, or `None` if the number is not fully evaluated.
+    pub fn read_nat(&self, cell_ptr: CellPtr) -> Option<usize> {
+        let mut cell_ptr = cell_ptr;
+        let mut n = 0;
+        loop {
+            let cell = self.heap.get_cell(cell_ptr);
+            let name = cell.symbol_name(self.symbols)?;
+            if *name == Z {
+                return Some(n);
+            }
+            assert!(*name == S, "Not a nat: {}", name);
+            let port = cell.get_left_port();
+            cell_ptr = match port.get_kind() {
+                TermKind::Cell => port.get_cell_ptr(),
+                TermKind::Var => self.get_var_cell(port.get_var_ptr())?,
+            };
+            n += 1;
+        }
+    }
+}
@@ -0,0 +1,154 @@
+use crate::inet::{
+    cell::CellPtr,
+    equation::EquationBuilder,
+    rule::RuleSet,
+    symbol::{SymbolBook, SymbolName},
+    term::{TermKind, TermPtr},
+    Polarity,
+};
+
+use super::eraser::ERA;
+
+// Pairs
+//
+// `fst` and `snd` take one component of a `Pair` and erase the other.
+
+pub const PAIR: SymbolName = SymbolName("Pair");
+pub const FST: SymbolName = SymbolName("fst");
+pub const SND: SymbolName = SymbolName("snd");
+
+impl SymbolBook {
+    /// Needs the eraser symbols too.
+    pub fn declare_pair_symbols(&mut self) {
+        // (Pair fst snd)
+        self.ctr2(&PAIR, Polarity::Neg, Polarity::Neg);
+
+        // (fst result) and (snd result)
+        self.fun1(&FST, Polarity::Pos);
+        self.fun1(&SND, Polarity::Pos);
+
+        #[cfg(debug_assertions)]
+        self.assert_valid();
+    }
+}
+
+impl<'a> EquationBuilder<'a> {
+    pub fn pair(&mut self, fst: TermPtr, snd: TermPtr) -> CellPtr {
+        self.cell2(&PAIR, fst, snd)
+    }
+
+    /// Takes the first component of a pair cell, or of the pair a var ends
+    /// up holding.
+    pub fn fst(&mut self, pair: TermPtr, result: TermPtr) {
+        let fst = self.cell1(&FST, result);
+        self.project(pair, fst);
+    }
+
+    /// Takes the second component, like `fst`.
+    pub fn snd(&mut self, pair: TermPtr, result: TermPtr) {
+        let snd = self.cell1(&SND, result);
+        self.project(pair, snd);
+    }
+
+    fn project(&mut self, pair: TermPtr, projection: CellPtr) {
+        match pair.get_kind() {
+            TermKind::Cell => self.redex(pair.get_cell_ptr(), projection),
+            TermKind::Var => self.bind(pair.get_var_ptr(), projection),
+        };
+    }
+}
+
+impl<'a> RuleSet<'a> {
+    /// Needs the eraser rules too.
+    pub fn define_pair_rules(&mut self) {
+        // (Pair a b) ⋈ (fst r)  ⟶  r ↔ a, b ← era
+        self.rule(&PAIR, &FST, |b| {
+            let r = b.fun_port_0();
+            let a = b.ctr_port_0();
+            b.connect(r, a);
+            let snd = b.ctr_port_1();
+            let era = b.cell0(&ERA);
+            b.bind(snd, era);
+        });
+
+        // (Pair a b) ⋈ (snd r)  ⟶  r ↔ b, a ← era
+        self.rule(&PAIR, &SND, |b| {
+            let r = b.fun_port_0();
+            let snd = b.ctr_port_1();
+            b.connect(r, snd);
+            let a = b.ctr_port_0();
+            let era = b.cell0(&ERA);
+            b.bind(a, era);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::inet::{net::Net, runtime::Runtime};
+
+    use super::*;
+
+    fn project(snd: bool) -> Option<usize> {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+        symbols.declare_combinator_symbols();
+        symbols.declare_eraser_symbols();
+        symbols.declare_pair_symbols();
+
+        let mut rules = RuleSet::new(&symbols);
+        rules.define_combinator_rules();
+        rules.define_eraser_rules();
+        rules.define_pair_rules();
+
+        let mut net = Net::new(&symbols);
+        net.equations(|b| {
+            let result = b.output();
+            let zero = b.zero();
+            let one = b.one();
+            let pair = b.pair(zero.into(), one.into());
+            match snd {
+                false => b.fst(pair.into(), result.into()),
+                true => b.snd(pair.into(), result.into()),
+            }
+        });
+
+        let net = Runtime::new(&rules, false).eval(net).unwrap();
+        assert!(net.is_normal());
+        net.read_nat(net.single_output()?)
+    }
+
+    #[test]
+    fn test_pair_projections() {
+        assert_eq!(project(false), Some(0));
+        assert_eq!(project(true), Some(1));
+    }
+
+    #[test]
+    fn test_verify_pair_rules() {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+        symbols.declare_combinator_symbols();
+        symbols.declare_eraser_symbols();
+        symbols.declare_pair_symbols();
+
+        let mut rules = RuleSet::new(&symbols);
+        rules.define_combinator_rules();
+        rules.define_eraser_rules();
+        let other_rules = rules.rules().count();
+        rules.define_pair_rules();
+
+        // (ctr, fun, ctr ports, fun ports) for every rule above
+        let cases = [
+            (PAIR, FST, vec!["Z", "(S Z)"], vec!["r"]),
+            (PAIR, SND, vec!["Z", "(S Z)"], vec!["r"]),
+        ];
+        assert_eq!(rules.rules().count() - other_rules, cases.len());
+        for (ctr, fun, ctr_ports, fun_ports) in cases {
+            let ctr_ptr = symbols.get_by_name(&ctr).unwrap();
+            let fun_ptr = symbols.get_by_name(&fun).unwrap();
+            let rule_ptr = rules.get_by_symbols(ctr_ptr, fun_ptr).unwrap();
+            assert_eq!(rules.verify_rule(rule_ptr, &[(ctr_ports, fun_ports)]), Ok(()));
+        }
+    }
+}
@@ -0,0 +1,249 @@
+use crate::inet::{
+    cell::CellPtr,
+    equation::EquationBuilder,
+    rule::RuleSet,
+    symbol::{SymbolBook, SymbolName},
+    Polarity,
+};
+
+use super::{
+    combinators::DUP,
+    eraser::ERA,
+    lambda::APP,
+};
+
+// SKI combinators
+//
+// Combinators are values applied with `app`. Each application either reduces
+// or returns the combinator partially applied to its argument:
+//
+//   I x     = x
+//   K x y   = x
+//   S x y z = x z (y z)
+
+pub const SKI_S: SymbolName = SymbolName("SKI_S");
+pub const SKI_K: SymbolName = SymbolName("SKI_K");
+pub const SKI_I: SymbolName = SymbolName("SKI_I");
+const SKI_K_1: SymbolName = SymbolName("SKI_K₁");
+const SKI_S_1: SymbolName = SymbolName("SKI_S₁");
+const SKI_S_2: SymbolName = SymbolName("SKI_S₂");
+
+impl SymbolBook {
+    /// Requires the combinator and lambda symbols.
+    pub fn declare_ski_symbols(&mut self) {
+        self.ctr0(&SKI_I);
+        self.ctr0(&SKI_K);
+        self.ctr1(&SKI_K_1, Polarity::Neg);
+        self.ctr0(&SKI_S);
+        self.ctr1(&SKI_S_1, Polarity::Neg);
+        self.ctr2(&SKI_S_2, Polarity::Neg, Polarity::Neg);
+
+        #[cfg(debug_assertions)]
+        self.assert_valid();
+    }
+}
+
+impl<'a> EquationBuilder<'a> {
+    pub fn ski_s(&mut self) -> CellPtr {
+        self.cell0(&SKI_S)
+    }
+
+    pub fn ski_k(&mut self) -> CellPtr {
+        self.cell0(&SKI_K)
+    }
+
+    pub fn ski_i(&mut self) -> CellPtr {
+        self.cell0(&SKI_I)
+    }
+}
+
+impl<'a> RuleSet<'a> {
+    /// Requires the combinator and lambda rules.
+    pub fn define_ski_rules(&mut self) {
+        // I ⋈ (app a r)  ⟶  r ↔ a
+        self.rule(&SKI_I, &APP, |b| {
+            let arg = b.fun_port_0();
+            let result = b.fun_port_1();
+            b.connect(result, arg);
+        });
+
+        // K ⋈ (app a r)  ⟶  r ← (K₁ a)
+        self.rule(&SKI_K, &APP, |b| {
+            let arg = b.fun_port_0();
+            let k1 = b.cell1(&SKI_K_1, arg.into());
+
+            let result = b.fun_port_1();
+            b.bind(result, k1);
+        });
+
+        // (K₁ x) ⋈ (app a r)  ⟶  r ↔ x; a ← era
+        self.rule(&SKI_K_1, &APP, |b| {
+            let x = b.ctr_port_0();
+            let result = b.fun_port_1();
+            b.connect(result, x);
+
+            let arg = b.fun_port_0();
+            let era = b.cell0(&ERA);
+            b.bind(arg, era);
+        });
+
+        // S ⋈ (app a r)  ⟶  r ← (S₁ a)
+        self.rule(&SKI_S, &APP, |b| {
+            let arg = b.fun_port_0();
+            let s1 = b.cell1(&SKI_S_1, arg.into());
+
+            let result = b.fun_port_1();
+            b.bind(result, s1);
+        });
+
+        // (S₁ x) ⋈ (app a r)  ⟶  r ← (S₂ x a)
+        self.rule(&SKI_S_1, &APP, |b| {
+            let x = b.ctr_port_0();
+            let arg = b.fun_port_0();
+            let s2 = b.cell2(&SKI_S_2, x.into(), arg.into());
+
+            let result = b.fun_port_1();
+            b.bind(result, s2);
+        });
+
+        // (S₂ x y) ⋈ (app a r)  ⟶  a ← (dup z₀ z₁); x ← (app z₀ t); y ← (app z₁ u); t ← (app u r)
+        self.rule(&SKI_S_2, &APP, |b| {
+            let (z0_in, z0_out) = b.var();
+            let (z1_in, z1_out) = b.var();
+            let (t_in, t_out) = b.var();
+            let (u_in, u_out) = b.var();
+
+            let arg = b.fun_port_0();
+            let dup = b.cell2(&DUP, z0_in.into(), z1_in.into());
+            b.bind(arg, dup);
+
+            let x = b.ctr_port_0();
+            let x_app = b.cell2(&APP, z0_out.into(), t_in.into());
+            b.bind(x, x_app);
+
+            let y = b.ctr_port_1();
+            let y_app = b.cell2(&APP, z1_out.into(), u_in.into());
+            b.bind(y, y_app);
+
+            let result = b.fun_port_1();
+            let t_app = b.cell2(&APP, u_out.into(), result.into());
+            b.bind(t_out, t_app);
+        });
+
+        // combinators dropped by K are erased
+        self.rule(&SKI_I, &ERA, |_| {});
+        self.rule(&SKI_K, &ERA, |_| {});
+        self.rule(&SKI_S, &ERA, |_| {});
+
+        // (K₁ x) ⋈ era  ⟶  x ← era
+        self.rule(&SKI_K_1, &ERA, |b| {
+            let x = b.ctr_port_0();
+            let era = b.cell0(&ERA);
+            b.bind(x, era);
+        });
+
+        // (S₁ x) ⋈ era  ⟶  x ← era
+        self.rule(&SKI_S_1, &ERA, |b| {
+            let x = b.ctr_port_0();
+            let era = b.cell0(&ERA);
+            b.bind(x, era);
+        });
+
+        // (S₂ x y) ⋈ era  ⟶  x ← era; y ← era
+        self.rule(&SKI_S_2, &ERA, |b| {
+            let x = b.ctr_port_0();
+            let era_x = b.cell0(&ERA);
+            b.bind(x, era_x);
+
+            let y = b.ctr_port_1();
+            let era_y = b.cell0(&ERA);
+            b.bind(y, era_y);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::inet::{net::Net, runtime::Runtime};
+
+    use super::*;
+
+    #[test]
+    fn test_skk_zero() {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+        symbols.declare_arith_symbols();
+        symbols.declare_combinator_symbols();
+        symbols.declare_eraser_symbols();
+        symbols.declare_lambda_symbols();
+        symbols.declare_ski_symbols();
+
+        let mut rules = RuleSet::new(&symbols);
+        rules.arith_rules();
+        rules.define_combinator_rules();
+        rules.define_eraser_rules();
+        rules.define_lambda_rules();
+        rules.define_ski_rules();
+
+        // S K K 0
+        let mut net = Net::new(&symbols);
+        net.equations(|b| {
+            let result = b.output();
+
+            let s = b.ski_s();
+            let k = b.ski_k();
+            let sk = b.apply(s.into(), k.into());
+            let k = b.ski_k();
+            let skk = b.apply(sk.into(), k.into());
+
+            let zero = b.church(0);
+            let numeral = b.apply(skk.into(), zero.into());
+            b.church_to_nat(numeral.into(), result.into());
+        });
+
+        let runtime = Runtime::new(&rules, false);
+        let net = runtime.eval(net).unwrap();
+        assert_eq!(net.read_nat(net.single_output().unwrap()), Some(0));
+    }
+
+    #[test]
+    fn test_verify_ski_rules() {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+        symbols.declare_combinator_symbols();
+        symbols.declare_eraser_symbols();
+        symbols.declare_lambda_symbols();
+        symbols.declare_ski_symbols();
+
+        let mut rules = RuleSet::new(&symbols);
+        rules.define_combinator_rules();
+        rules.define_eraser_rules();
+        rules.define_lambda_rules();
+        let other_rules = rules.rules().count();
+        rules.define_ski_rules();
+
+        // (ctr, fun, ctr ports, fun ports) for every rule above, S₂ applying
+        // S K K to Z
+        let cases = [
+            (SKI_I, APP, vec![], vec!["Z", "r"]),
+            (SKI_K, APP, vec![], vec!["Z", "r"]),
+            (SKI_K_1, APP, vec!["Z"], vec!["(S Z)", "r"]),
+            (SKI_S, APP, vec![], vec!["Z", "r"]),
+            (SKI_S_1, APP, vec!["SKI_K"], vec!["SKI_K", "r"]),
+            (SKI_S_2, APP, vec!["SKI_K", "SKI_K"], vec!["Z", "r"]),
+            (SKI_I, ERA, vec![], vec![]),
+            (SKI_K, ERA, vec![], vec![]),
+            (SKI_S, ERA, vec![], vec![]),
+            (SKI_K_1, ERA, vec!["Z"], vec![]),
+            (SKI_S_1, ERA, vec!["Z"], vec![]),
+            (SKI_S_2, ERA, vec!["Z", "(S Z)"], vec![]),
+        ];
+        assert_eq!(rules.rules().count() - other_rules, cases.len());
+        for (ctr, fun, ctr_ports, fun_ports) in cases {
+            let ctr_ptr = symbols.get_by_name(&ctr).unwrap();
+            let fun_ptr = symbols.get_by_name(&fun).unwrap();
+            let rule_ptr = rules.get_by_symbols(ctr_ptr, fun_ptr).unwrap();
+            assert_eq!(rules.verify_rule(rule_ptr, &[(ctr_ports, fun_ports)]), Ok(()));
+        }
+    }
+}
@@ -0,0 +1,535 @@
+//! # C API
+//!
+//! Every object is an opaque pointer owned by the caller and released with the
+//! matching `_free` function. Objects borrow the ones they were created from,
+//! so a symbol book must outlive its rule sets and nets, and a rule set must
+//! outlive its runtimes.
+//!
+//! Cells and vars are handed out as `uint32_t` term handles. Polarities are
+//! `0` for positive and `1` for negative. See `include/rinet.h`.
+//!
+//! ## Errors
+//!
+//! Nothing panics across the API. Functions that return a status return
+//! `INET_OK` or a negative `INET_ERR_*` code, functions that return a handle
+//! return `INET_INVALID_HANDLE` and functions that return an object return
+//! NULL when they fail. Names and port numbers are checked before use; any
+//! other failure, such as a rule body that does not use every port, is
+//! reported as `INET_ERR_FAILED` and may leave the object partly updated.
+//!
+//! ## Safety
+//!
+//! Every function expects pointers returned by this API that have not been
+//! freed, NUL-terminated names, and handles created for the same builder, net
+//! or rule set.
+
+// the safety requirements are shared by every function and documented above
+#![allow(clippy::missing_safety_doc)]
+
+use std::{
+    ffi::{c_char, c_void, CStr},
+    panic::{self, AssertUnwindSafe},
+    ptr,
+};
+
+use crate::inet::{
+    cell::CellPtr,
+    equation::EquationBuilder,
+    net::{Net, NetF},
+    rule::{PortNum, RuleBuilder, RuleSet},
+    runtime::Runtime,
+    symbol::{SymbolBook, SymbolName},
+    term::TermPtr,
+    var::PVarPtr,
+    Polarity,
+};
+
+// term handles are `uint32_t`, too narrow for the 64-bit terms of a large heap
+#[cfg(feature = "large-heap")]
+compile_error!("The C API does not support the `large-heap` feature");
+
+pub type InetBuilderFn = extern "C" fn(builder: *mut c_void, user_data: *mut c_void);
+
+pub const INET_OK: i32 = 0;
+/// A NULL or non UTF-8 name.
+pub const INET_ERR_NAME: i32 = -1;
+/// A name that is not declared, or is redeclared with other polarities.
+pub const INET_ERR_SYMBOL: i32 = -2;
+/// A port number the symbol does not have, or a polarity other than 0 or 1.
+pub const INET_ERR_PORT: i32 = -3;
+/// Anything else, caught before it unwinds into the caller.
+pub const INET_ERR_FAILED: i32 = -4;
+
+pub const INET_INVALID_HANDLE: u32 = u32::MAX;
+
+// Runs `f`, returning `failed` if it panics.
+fn catch<R>(failed: R, f: impl FnOnce() -> R) -> R {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or(failed)
+}
+
+fn try_status(f: impl FnOnce() -> Result<(), i32>) -> i32 {
+    catch(INET_ERR_FAILED, || f().err().unwrap_or(INET_OK))
+}
+
+fn try_handle(f: impl FnOnce() -> Result<u32, i32>) -> u32 {
+    catch(INET_INVALID_HANDLE, || f().unwrap_or(INET_INVALID_HANDLE))
+}
+
+fn try_object(f: impl FnOnce() -> *mut c_void) -> *mut c_void {
+    catch(ptr::null_mut(), f)
+}
+
+unsafe fn to_str<'a>(name: *const c_char) -> Result<&'a str, i32> {
+    if name.is_null() {
+        return Err(INET_ERR_NAME);
+    }
+    CStr::from_ptr(name).to_str().map_err(|_| INET_ERR_NAME)
+}
+
+// Lookups only hash the name, so it does not need to outlive the call.
+unsafe fn lookup_name(name: *const c_char) -> Result<SymbolName, i32> {
+    let name = to_str(name)?;
+    Ok(SymbolName(std::mem::transmute::<&str, &'static str>(name)))
+}
+
+// Looks `name` up in `symbols`, so builders are only handed declared names.
+unsafe fn declared(symbols: &SymbolBook, name: *const c_char) -> Result<SymbolName, i32> {
+    let name = lookup_name(name)?;
+    match symbols.get_by_name(&name) {
+        Some(_) => Ok(name),
+        None => Err(INET_ERR_SYMBOL),
+    }
+}
+
+fn to_polarity(polarity: u8) -> Result<Polarity, i32> {
+    match polarity {
+        0 | 1 => Ok(Polarity::from(polarity)),
+        _ => Err(INET_ERR_PORT),
+    }
+}
+
+fn to_port_num(port_num: u8) -> Result<PortNum, i32> {
+    PortNum::from_index(port_num as usize).ok_or(INET_ERR_PORT)
+}
+
+fn to_handle(term_ptr: impl Into<TermPtr>) -> Result<u32, i32> {
+    match term_ptr.into().get_ptr() {
+        INET_INVALID_HANDLE => Err(INET_ERR_FAILED),
+        handle => Ok(handle),
+    }
+}
+
+fn to_term(handle: u32) -> TermPtr {
+    TermPtr::from(handle)
+}
+
+fn to_cell(handle: u32) -> CellPtr {
+    to_term(handle).get_cell_ptr()
+}
+
+fn to_var(handle: u32) -> PVarPtr {
+    to_term(handle).get_var_ptr()
+}
+
+unsafe fn symbol_book<'a>(book: *mut c_void) -> &'a mut SymbolBook {
+    &mut *(book as *mut SymbolBook)
+}
+
+unsafe fn rule_builder<'a>(builder: *mut c_void) -> &'a mut RuleBuilder<'static, 'static> {
+    &mut *(builder as *mut RuleBuilder)
+}
+
+unsafe fn net_builder<'a>(builder: *mut c_void) -> &'a mut EquationBuilder<'static, NetF> {
+    &mut *(builder as *mut EquationBuilder<NetF>)
+}
+
+// Declares `name` unless it is already declared with the same polarities. The
+// name is interned, so declaring it again allocates nothing.
+unsafe fn declare(
+    book: *mut c_void,
+    name: *const c_char,
+    polarity: Polarity,
+    port_polarities: &[u8],
+) -> i32 {
+    try_status(|| {
+        let book = symbol_book(book);
+        let name = lookup_name(name)?;
+        let port_polarities = port_polarities
+            .iter()
+            .map(|polarity| to_polarity(*polarity))
+            .collect::<Result<Vec<_>, _>>()?;
+        match book.get_by_name(&name) {
+            Some(symbol_ptr) => {
+                let symbol = book.get(symbol_ptr);
+                let same = symbol.get_polarity() == polarity
+                    && symbol.get_arity().port_count() == port_polarities.len()
+                    && (port_polarities.iter().enumerate()).all(|(port_num, polarity)| {
+                        symbol.get_port_polarity(PortNum::from(port_num)) == *polarity
+                    });
+                if !same {
+                    return Err(INET_ERR_SYMBOL);
+                }
+            }
+            None => {
+                book.intern(&SymbolName::intern(name.0), polarity, &port_polarities);
+            }
+        }
+        Ok(())
+    })
+}
+
+// Symbols ---------------------------
+
+#[no_mangle]
+pub extern "C" fn inet_symbol_book_new() -> *mut c_void {
+    try_object(|| Box::into_raw(Box::new(SymbolBook::new())) as *mut c_void)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn inet_symbol_book_free(book: *mut c_void) {
+    if !book.is_null() {
+        drop(Box::from_raw(book as *mut SymbolBook));
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn inet_symbol_book_declare_ctr0(
+    book: *mut c_void,
+    name: *const c_char,
+) -> i32 {
+    declare(book, name, Polarity::Pos, &[])
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn inet_symbol_book_declare_ctr1(
+    book: *mut c_void,
+    name: *const c_char,
+    port_polarity: u8,
+) -> i32 {
+    declare(book, name, Polarity::Pos, &[port_polarity])
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn inet_symbol_book_declare_ctr2(
+    book: *mut c_void,
+    name: *const c_char,
+    left_polarity: u8,
+    right_polarity: u8,
+) -> i32 {
+    declare(book, name, Polarity::Pos, &[left_polarity, right_polarity])
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn inet_symbol_book_declare_fun0(
+    book: *mut c_void,
+    name: *const c_char,
+) -> i32 {
+    declare(book, name, Polarity::Neg, &[])
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn inet_symbol_book_declare_fun1(
+    book: *mut c_void,
+    name: *const c_char,
+    port_polarity: u8,
+) -> i32 {
+    declare(book, name, Polarity::Neg, &[port_polarity])
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn inet_symbol_book_declare_fun2(
+    book: *mut c_void,
+    name: *const c_char,
+    left_polarity: u8,
+    right_polarity: u8,
+) -> i32 {
+    declare(book, name, Polarity::Neg, &[left_polarity, right_polarity])
+}
+
+// Rules -----------------------------
+
+#[no_mangle]
+pub unsafe extern "C" fn inet_rule_set_new(book: *mut c_void) -> *mut c_void {
+    try_object(|| Box::into_raw(Box::new(RuleSet::new(symbol_book(book)))) as *mut c_void)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn inet_rule_set_free(rules: *mut c_void) {
+    if !rules.is_null() {
+        drop(Box::from_raw(rules as *mut RuleSet));
+    }
+}
+
+/// Defines the rule for `ctr_name ⋈ fun_name`. The body is written by `body`,
+/// which receives a rule builder for the `inet_rule_*` functions.
+#[no_mangle]
+pub unsafe extern "C" fn inet_rule_set_rule(
+    rules: *mut c_void,
+    ctr_name: *const c_char,
+    fun_name: *const c_char,
+    body: InetBuilderFn,
+    user_data: *mut c_void,
+) -> i32 {
+    try_status(|| {
+        let rules = &mut *(rules as *mut RuleSet);
+        let ctr_name = declared(rules.symbols, ctr_name)?;
+        let fun_name = declared(rules.symbols, fun_name)?;
+        rules.rule(&ctr_name, &fun_name, |b| {
+            body(b as *mut RuleBuilder as *mut c_void, user_data)
+        });
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn inet_rule_ctr_port(builder: *mut c_void, port_num: u8) -> u32 {
+    try_handle(|| {
+        let builder = rule_builder(builder);
+        let port_num = to_port_num(port_num)?;
+        if !builder.has_ctr_port(port_num) {
+            return Err(INET_ERR_PORT);
+        }
+        to_handle(builder.ctr_port(port_num))
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn inet_rule_fun_port(builder: *mut c_void, port_num: u8) -> u32 {
+    try_handle(|| {
+        let builder = rule_builder(builder);
+        let port_num = to_port_num(port_num)?;
+        if !builder.has_fun_port(port_num) {
+            return Err(INET_ERR_PORT);
+        }
+        to_handle(builder.fun_port(port_num))
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn inet_rule_var(builder: *mut c_void, neg: *mut u32, pos: *mut u32) -> i32 {
+    try_status(|| {
+        let (neg_pvar, pos_pvar) = rule_builder(builder).var();
+        *neg = to_handle(neg_pvar)?;
+        *pos = to_handle(pos_pvar)?;
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn inet_rule_cell0(builder: *mut c_void, name: *const c_char) -> u32 {
+    try_handle(|| {
+        let builder = rule_builder(builder);
+        let name = declared(builder.symbols(), name)?;
+        to_handle(builder.cell0(&name))
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn inet_rule_cell1(
+    builder: *mut c_void,
+    name: *const c_char,
+    port: u32,
+) -> u32 {
+    try_handle(|| {
+        let builder = rule_builder(builder);
+        let name = declared(builder.symbols(), name)?;
+        to_handle(builder.cell1(&name, to_term(port)))
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn inet_rule_cell2(
+    builder: *mut c_void,
+    name: *const c_char,
+    left_port: u32,
+    right_port: u32,
+) -> u32 {
+    try_handle(|| {
+        let builder = rule_builder(builder);
+        let name = declared(builder.symbols(), name)?;
+        to_handle(builder.cell2(&name, to_term(left_port), to_term(right_port)))
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn inet_rule_redex(builder: *mut c_void, ctr: u32, fun: u32) -> i32 {
+    try_status(|| {
+        rule_builder(builder).redex(to_cell(ctr), to_cell(fun));
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn inet_rule_bind(builder: *mut c_void, var: u32, cell: u32) -> i32 {
+    try_status(|| {
+        rule_builder(builder).bind(to_var(var), to_cell(cell));
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn inet_rule_connect(builder: *mut c_void, left: u32, right: u32) -> i32 {
+    try_status(|| {
+        rule_builder(builder).connect(to_var(left), to_var(right));
+        Ok(())
+    })
+}
+
+// Nets ------------------------------
+
+#[no_mangle]
+pub unsafe extern "C" fn inet_net_new(book: *mut c_void) -> *mut c_void {
+    try_object(|| Box::into_raw(Box::new(Net::new(symbol_book(book)))) as *mut c_void)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn inet_net_free(net: *mut c_void) {
+    if !net.is_null() {
+        drop(Box::from_raw(net as *mut Net));
+    }
+}
+
+/// Adds equations to the net. `body` receives an equation builder for the
+/// `inet_net_*` builder functions.
+#[no_mangle]
+pub unsafe extern "C" fn inet_net_equations(
+    net: *mut c_void,
+    body: InetBuilderFn,
+    user_data: *mut c_void,
+) -> i32 {
+    try_status(|| {
+        let net = &mut *(net as *mut Net);
+        net.equations(|b| body(b as *mut EquationBuilder<NetF> as *mut c_void, user_data));
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn inet_net_output(builder: *mut c_void) -> u32 {
+    try_handle(|| to_handle(net_builder(builder).output()))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn inet_net_input(builder: *mut c_void) -> u32 {
+    try_handle(|| to_handle(net_builder(builder).input()))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn inet_net_var(builder: *mut c_void, neg: *mut u32, pos: *mut u32) -> i32 {
+    try_status(|| {
+        let (neg_pvar, pos_pvar) = net_builder(builder).var();
+        *neg = to_handle(neg_pvar)?;
+        *pos = to_handle(pos_pvar)?;
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn inet_net_cell0(builder: *mut c_void, name: *const c_char) -> u32 {
+    try_handle(|| {
+        let builder = net_builder(builder);
+        let name = declared(builder.symbols(), name)?;
+        to_handle(builder.cell0(&name))
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn inet_net_cell1(
+    builder: *mut c_void,
+    name: *const c_char,
+    port: u32,
+) -> u32 {
+    try_handle(|| {
+        let builder = net_builder(builder);
+        let name = declared(builder.symbols(), name)?;
+        to_handle(builder.cell1(&name, to_term(port)))
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn inet_net_cell2(
+    builder: *mut c_void,
+    name: *const c_char,
+    left_port: u32,
+    right_port: u32,
+) -> u32 {
+    try_handle(|| {
+        let builder = net_builder(builder);
+        let name = declared(builder.symbols(), name)?;
+        to_handle(builder.cell2(&name, to_term(left_port), to_term(right_port)))
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn inet_net_redex(builder: *mut c_void, ctr: u32, fun: u32) -> i32 {
+    try_status(|| {
+        net_builder(builder).redex(to_cell(ctr), to_cell(fun));
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn inet_net_bind(builder: *mut c_void, var: u32, cell: u32) -> i32 {
+    try_status(|| {
+        net_builder(builder).bind(to_var(var), to_cell(cell));
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn inet_net_connect(builder: *mut c_void, left: u32, right: u32) -> i32 {
+    try_status(|| {
+        net_builder(builder).connect(to_var(left), to_var(right));
+        Ok(())
+    })
+}
+
+/// The nat held by the only output of the net, or -1 if there is none.
+#[no_mangle]
+pub unsafe extern "C" fn inet_net_read_nat(net: *mut c_void) -> i64 {
+    catch(-1, || {
+        let net = &*(net as *mut Net);
+        net.single_output()
+            .and_then(|cell_ptr| net.read_nat(cell_ptr))
+            .map_or(-1, |nat| nat as i64)
+    })
+}
+
+// Runtime ---------------------------
+
+#[no_mangle]
+pub unsafe extern "C" fn inet_runtime_new(rules: *mut c_void) -> *mut c_void {
+    try_object(|| {
+        let rules = &*(rules as *mut RuleSet);
+        Box::into_raw(Box::new(Runtime::new(rules, false))) as *mut c_void
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn inet_runtime_free(runtime: *mut c_void) {
+    if !runtime.is_null() {
+        drop(Box::from_raw(runtime as *mut Runtime));
+    }
+}
+
+/// Evaluates the net, which is consumed. Returns the evaluated net, or NULL if
+/// the evaluation fails.
+#[no_mangle]
+pub unsafe extern "C" fn inet_runtime_eval(runtime: *mut c_void, net: *mut c_void) -> *mut c_void {
+    if net.is_null() {
+        return ptr::null_mut();
+    }
+    try_object(|| {
+        let runtime = &*(runtime as *mut Runtime);
+        let net = Box::from_raw(net as *mut Net);
+        match runtime.eval(*net) {
+            Ok(net) => Box::into_raw(Box::new(net)) as *mut c_void,
+            Err(_) => ptr::null_mut(),
+        }
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn inet_runtime_get_rewrites(runtime: *mut c_void) -> usize {
+    (*(runtime as *mut Runtime)).get_rewrites()
+}
@@ -1,12 +1,15 @@
 use std::{
     fmt::Display,
     ops::{BitAnd, BitOr, Shl, Shr},
+    str::FromStr,
 };
 
+pub mod bytecode;
 pub mod cell;
 pub mod equation;
 pub mod heap;
 pub mod net;
+pub mod parse;
 pub mod rule;
 pub mod runtime;
 pub mod symbol;
@@ -85,6 +88,43 @@ impl Display for Polarity {
     }
 }
 
+/// Text that is not a polarity, see `Polarity::from_str`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParsePolarityError(pub String);
+
+impl Display for ParsePolarityError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid polarity '{}', expected + or -", self.0)
+    }
+}
+
+/// Accepts `+` and `-` as displayed, or `Pos` and `Neg` in any case.
+impl FromStr for Polarity {
+    type Err = ParsePolarityError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "+" => Ok(Polarity::Pos),
+            "-" => Ok(Polarity::Neg),
+            _ if text.eq_ignore_ascii_case("pos") => Ok(Polarity::Pos),
+            _ if text.eq_ignore_ascii_case("neg") => Ok(Polarity::Neg),
+            _ => Err(ParsePolarityError(text.to_string())),
+        }
+    }
+}
+
+impl TryFrom<char> for Polarity {
+    type Error = ParsePolarityError;
+
+    fn try_from(value: char) -> Result<Self, Self::Error> {
+        match value {
+            '+' => Ok(Polarity::Pos),
+            '-' => Ok(Polarity::Neg),
+            _ => Err(ParsePolarityError(value.to_string())),
+        }
+    }
+}
+
 pub trait Polarized {
     fn polarity(&self) -> Polarity;
 }
@@ -133,6 +173,11 @@ impl<
         N
     }
 
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        N == 0
+    }
+
     #[inline]
     pub fn set(&self, bits: T, value: T) -> T {
         self.check_value(&value);
@@ -145,6 +190,32 @@ impl<
     }
 }
 
+/// For fields that do not fit in 64 bits, such as symbol books with more
+/// symbols than `SymbolPtr` can index.
+pub struct BitSet128<const N: usize> {
+    mask: u128,
+    offset: u8,
+}
+
+impl<const N: usize> BitSet128<N> {
+    #[inline]
+    pub fn new(mask: u128, offset: u8) -> Self {
+        assert!(offset <= 128);
+        Self { mask, offset }
+    }
+
+    #[inline]
+    pub fn set(&self, bits: u128, value: u128) -> u128 {
+        assert!(value <= self.mask);
+        bits | (value & self.mask).wrapping_shl(self.offset as u32)
+    }
+
+    #[inline]
+    pub fn get(&self, bits: u128) -> u128 {
+        bits.wrapping_shr(self.offset as u32) & self.mask
+    }
+}
+
 pub struct BitSet64<const N: usize> {
     mask: u64,
     offset: u8,
@@ -159,7 +230,7 @@ impl<const N: usize> BitSet64<N> {
 
     #[inline]
     pub fn set(&self, bits: u64, value: u64) -> u64 {
-        assert!(value < (self.mask << self.offset));
+        assert!(value <= self.mask);
         bits | ((value & self.mask) << self.offset)
     }
 
@@ -167,6 +238,12 @@ impl<const N: usize> BitSet64<N> {
     pub fn get(&self, bits: u64) -> u64 {
         (bits >> self.offset) & self.mask
     }
+
+    /// Returns `bits` with this field zeroed.
+    #[inline]
+    pub fn clear(&self, bits: u64) -> u64 {
+        bits & !(self.mask << self.offset)
+    }
 }
 
 pub struct Bits64<const N: usize>(u64);
@@ -203,7 +280,7 @@ impl<const N: usize> BitSet32<N> {
 
     #[inline]
     pub fn set(&self, bits: u32, value: u32) -> u32 {
-        assert!(value < (self.mask << self.offset));
+        assert!(value <= self.mask);
         bits | ((value & self.mask) << self.offset)
     }
 
@@ -212,10 +289,21 @@ impl<const N: usize> BitSet32<N> {
         (bits >> self.offset) & self.mask
     }
 
+    /// Returns `bits` with this field zeroed.
+    #[inline]
+    pub fn clear(&self, bits: u32) -> u32 {
+        bits & !(self.mask << self.offset)
+    }
+
     #[inline]
     pub fn len(&self) -> usize {
         N
     }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        N == 0
+    }
 }
 
 pub struct BitSet16<const N: usize> {
@@ -232,7 +320,7 @@ impl<const N: usize> BitSet16<N> {
 
     #[inline]
     pub fn set(&self, bits: u16, value: u16) -> u16 {
-        assert!(value < (self.mask << self.offset));
+        assert!(value <= self.mask);
         bits | ((value & self.mask) << self.offset)
     }
 
@@ -240,6 +328,12 @@ impl<const N: usize> BitSet16<N> {
     pub fn get(&self, bits: u16) -> u16 {
         (bits >> self.offset) & self.mask
     }
+
+    /// Returns `bits` with this field zeroed.
+    #[inline]
+    pub fn clear(&self, bits: u16) -> u16 {
+        bits & !(self.mask << self.offset)
+    }
 }
 
 pub struct BitSet8<const N: usize> {
@@ -256,7 +350,7 @@ impl<const N: usize> BitSet8<N> {
 
     #[inline]
     pub fn set(&self, bits: u8, value: u8) -> u8 {
-        assert!(value < (self.mask << self.offset));
+        assert!(value <= self.mask);
         bits | ((value & self.mask) << self.offset)
     }
 
@@ -264,6 +358,12 @@ impl<const N: usize> BitSet8<N> {
     pub fn get(&self, bits: u8) -> u8 {
         (bits >> self.offset) & self.mask
     }
+
+    /// Returns `bits` with this field zeroed.
+    #[inline]
+    pub fn clear(&self, bits: u8) -> u8 {
+        bits & !(self.mask << self.offset)
+    }
 }
 
 // ---------------------
@@ -281,3 +381,65 @@ impl<const N: usize> BitSet8<N> {
 //     }
 
 // }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bitset128() {
+        let low = BitSet128::<64>::new(u64::MAX as u128, 0);
+        let high = BitSet128::<60>::new((1 << 60) - 1, 64);
+        let top = BitSet128::<4>::new(0b1111, 124);
+
+        let bits = low.set(0, u64::MAX as u128);
+        let bits = high.set(bits, 42);
+        let bits = top.set(bits, 0b1010);
+        assert_eq!(low.get(bits), u64::MAX as u128);
+        assert_eq!(high.get(bits), 42);
+        assert_eq!(top.get(bits), 0b1010);
+        assert_eq!(bits >> 124, 0b1010);
+    }
+
+    #[test]
+    fn test_polarity_from_str() {
+        assert_eq!("+".parse(), Ok(Polarity::Pos));
+        assert_eq!("-".parse(), Ok(Polarity::Neg));
+        assert_eq!("Pos".parse(), Ok(Polarity::Pos));
+        assert_eq!("NEG".parse(), Ok(Polarity::Neg));
+        assert_eq!("neg".parse(), Ok(Polarity::Neg));
+        assert_eq!(
+            "positive".parse::<Polarity>(),
+            Err(ParsePolarityError("positive".to_string()))
+        );
+        assert_eq!(Polarity::try_from('+'), Ok(Polarity::Pos));
+        assert_eq!(Polarity::try_from('-'), Ok(Polarity::Neg));
+        assert!(Polarity::try_from('p').is_err());
+        for polarity in [Polarity::Pos, Polarity::Neg] {
+            assert_eq!(polarity.to_string().parse(), Ok(polarity));
+        }
+    }
+
+    #[test]
+    fn test_bitset_clear() {
+        let low = BitSet32::<16>::new(0xffff, 0);
+        let high = BitSet32::<16>::new(0xffff, 16);
+        let bits = high.set(low.set(0, 0x1234), 0xabcd);
+        assert_eq!(high.clear(bits), 0x1234);
+        assert_eq!(low.clear(bits), 0xabcd_0000);
+        assert_eq!(high.get(high.set(high.clear(bits), 7)), 7);
+
+        let field = BitSet64::<4>::new(0b1111, 60);
+        assert_eq!(field.clear(u64::MAX), u64::MAX >> 4);
+        let field = BitSet16::<4>::new(0b1111, 4);
+        assert_eq!(field.clear(u16::MAX), 0xff0f);
+        let field = BitSet8::<2>::new(0b11, 6);
+        assert_eq!(field.clear(u8::MAX), 0b0011_1111);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_bitset128_value_too_large() {
+        BitSet128::<4>::new(0b1111, 124).set(0, 0b10000);
+    }
+}
@@ -0,0 +1,1246 @@
+//! ## Bytecode
+//!
+//! A compact binary format for pre-compiled rule sets and nets, so they can be
+//! loaded without rebuilding them through the builder APIs. All integers are
+//! little endian:
+//!
+//! ```text
+//! header   "INET" version:u16
+//! symbols  count:u16 { name_len:u16 name:[u8] polarity:u8 arity:u8 ports:u8 }
+//! rules    count:u16 { ctr:u16 fun:u16 bvars:u16 count:u16 { kind:u8 term term } }
+//! net      present:u8 [ head:u32 { polarity:u8 } bvars:u32
+//!                       cells:u32 { symbol:u16 port* } count:u32 { kind:u8 port port } ]
+//! ```
+//!
+//! Symbols are written in index order without the reserved symbol 0, and
+//! `ports` holds one polarity bit per port. Rule terms are written as trees:
+//!
+//! ```text
+//! 0 symbol:u16 term*   cell, followed by one term per port
+//! 1 bvar:u16 polarity:u8
+//! 2 port:u8            constructor port
+//! 3 port:u8            function port
+//! ```
+//!
+//! The net section holds a net that was not evaluated yet, e.g. the input of a
+//! program. Its cells are listed ports first, so a port only refers back to an
+//! earlier cell, and its vars are numbered with the head vars first:
+//!
+//! ```text
+//! 0 cell:u32
+//! 1 var:u32 polarity:u8
+//! ```
+//!
+//! Decoded rules and nets are checked for polarities and linearity before
+//! anything is built from them, so malformed input is an error, not a panic.
+//!
+//! A `RuleSet` borrows its `SymbolBook`, so `RuleSet::from_bytecode` returns
+//! both as a `RuleSetWithSymbols`. To decode against a book the caller already
+//! holds use `RuleSet::from_bytecode_with_symbols`, and `Net::from_bytecode` to
+//! read the net section.
+
+use std::collections::{HashMap, HashSet};
+
+use super::{
+    cell::CellPtr,
+    equation::EquationKind,
+    net::{Net, NetValue},
+    rule::{PortNum, RuleBuilder, RulePort, RuleSet, RuleSetWithSymbols},
+    symbol::{SymbolBook, SymbolName, SymbolPtr},
+    term::{TermKind, TermPtr},
+    var::{PVarPtr, Var},
+    Polarity,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BytecodeError {
+    BadMagic,
+    UnsupportedVersion(u16),
+    UnexpectedEnd,
+    InvalidName,
+    InvalidArity(u8),
+    InvalidSymbol(u16),
+    InvalidTag(u8),
+    InvalidBvar(u16),
+    InvalidPort(u8),
+    /// The rule at this index repeats a symbol pair, wires a port of the wrong
+    /// polarity or does not use every var and port exactly once.
+    InvalidRule(u16),
+    /// As `InvalidRule`, for the net section.
+    InvalidNet,
+    /// The symbol at this index of the image is not declared the same way in
+    /// the book decoded against.
+    SymbolMismatch(u16),
+    /// A count, length or index that does not fit its field.
+    TooLarge(usize),
+    /// Only nets that were not evaluated yet can be encoded: no var may hold a
+    /// value, all free vars must be in the head and cells must form trees.
+    UnsupportedNet,
+    TrailingBytes(usize),
+}
+
+fn to_u16(value: usize) -> Result<u16, BytecodeError> {
+    u16::try_from(value).map_err(|_| BytecodeError::TooLarge(value))
+}
+
+fn to_u32(value: usize) -> Result<u32, BytecodeError> {
+    u32::try_from(value).map_err(|_| BytecodeError::TooLarge(value))
+}
+
+/// Writes the sections of the format in order.
+pub struct INetBytecode {
+    bytes: Vec<u8>,
+}
+
+impl INetBytecode {
+    pub const MAGIC: &'static [u8; 4] = b"INET";
+    pub const VERSION: u16 = 1;
+
+    const TAG_CELL: u8 = 0;
+    const TAG_BVAR: u8 = 1;
+    const TAG_CTR_PORT: u8 = 2;
+    const TAG_FUN_PORT: u8 = 3;
+
+    const TAG_NET_CELL: u8 = 0;
+    const TAG_NET_VAR: u8 = 1;
+
+    const MAX_PORTS: usize = 8;
+
+    fn new() -> Self {
+        let mut bytecode = Self { bytes: Vec::new() };
+        bytecode.bytes.extend_from_slice(Self::MAGIC);
+        bytecode.put_u16(Self::VERSION);
+        bytecode
+    }
+
+    fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+
+    fn put_u8(&mut self, value: u8) {
+        self.bytes.push(value)
+    }
+
+    fn put_u16(&mut self, value: u16) {
+        self.bytes.extend_from_slice(&value.to_le_bytes())
+    }
+
+    fn put_u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes())
+    }
+
+    fn put_symbols(&mut self, symbols: &SymbolBook) -> Result<(), BytecodeError> {
+        let named_ptrs = symbols.to_sorted_by_index_vec();
+        self.put_u16(to_u16(named_ptrs.len())?);
+        for (name, symbol_ptr) in named_ptrs {
+            let symbol = symbols.get(symbol_ptr);
+            let port_count = symbol.get_arity().port_count();
+            if port_count > Self::MAX_PORTS {
+                return Err(BytecodeError::TooLarge(port_count));
+            }
+            self.put_u16(to_u16(name.0.len())?);
+            self.bytes.extend_from_slice(name.0.as_bytes());
+            self.put_u8(symbol.get_polarity() as u8);
+            self.put_u8(port_count as u8);
+            let ports = (0..port_count).fold(0, |ports, port_num| {
+                ports | (symbol.get_port_polarity(PortNum::from(port_num)) as u8) << port_num
+            });
+            self.put_u8(ports);
+        }
+        Ok(())
+    }
+
+    fn put_symbol_ptr(&mut self, symbol_ptr: SymbolPtr) -> Result<(), BytecodeError> {
+        self.put_u16(to_u16(symbol_ptr.get_index())?);
+        Ok(())
+    }
+
+    fn put_rules(&mut self, rules: &RuleSet) -> Result<(), BytecodeError> {
+        self.put_u16(to_u16(rules.rules().count())?);
+        for rule_ptr in rules.rules() {
+            let rule = rules.get_rule(rule_ptr);
+            self.put_symbol_ptr(rule.ctr_ptr)?;
+            self.put_symbol_ptr(rule.fun_ptr)?;
+            self.put_u16(rule.get_bvar_count());
+            self.put_u16(to_u16(rule.body.len())?);
+            for eqn in rules.iter_rule_bodies(rule_ptr) {
+                self.put_u8(eqn.get_kind() as u8);
+                let (left, right): (TermPtr, TermPtr) = match eqn.get_kind() {
+                    EquationKind::Redex => (eqn.get_redex_ctr().into(), eqn.get_redex_fun().into()),
+                    EquationKind::Bind => (eqn.get_bind_var().into(), eqn.get_bind_cell().into()),
+                    EquationKind::Connect => {
+                        (eqn.get_connect_left().into(), eqn.get_connect_right().into())
+                    }
+                };
+                self.put_term(rules, left)?;
+                self.put_term(rules, right)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn put_term(&mut self, rules: &RuleSet, term: TermPtr) -> Result<(), BytecodeError> {
+        match term.get_kind() {
+            TermKind::Cell => {
+                let cell = rules.heap.copy_cell(term.get_cell_ptr());
+                self.put_u8(Self::TAG_CELL);
+                self.put_symbol_ptr(cell.get_symbol_ptr())?;
+                for port_num in 0..cell.get_arity().port_count() {
+                    self.put_term(rules, cell.get_port(PortNum::from(port_num)))?;
+                }
+            }
+            TermKind::Var => {
+                let var_ptr = term.get_var_ptr();
+                match rules.heap.get_var(var_ptr) {
+                    Var::Bound(bvar) => {
+                        self.put_u8(Self::TAG_BVAR);
+                        self.put_u16(bvar.get_id());
+                        self.put_u8(var_ptr.get_polarity() as u8);
+                    }
+                    Var::Free(RulePort::Ctr(port_num)) => {
+                        self.put_u8(Self::TAG_CTR_PORT);
+                        self.put_u8(*port_num as u8);
+                    }
+                    Var::Free(RulePort::Fun(port_num)) => {
+                        self.put_u8(Self::TAG_FUN_PORT);
+                        self.put_u8(*port_num as u8);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn put_net(&mut self, net: Option<&NetCode>) -> Result<(), BytecodeError> {
+        let net = match net {
+            Some(net) => net,
+            None => {
+                self.put_u8(0);
+                return Ok(());
+            }
+        };
+        self.put_u8(1);
+        self.put_u32(to_u32(net.head.len())?);
+        for polarity in net.head.iter() {
+            self.put_u8(*polarity as u8);
+        }
+        self.put_u32(net.bvar_count);
+        self.put_u32(to_u32(net.cells.len())?);
+        for (symbol_ptr, ports) in net.cells.iter() {
+            self.put_symbol_ptr(*symbol_ptr)?;
+            for port in ports.iter() {
+                self.put_port(port);
+            }
+        }
+        self.put_u32(to_u32(net.body.len())?);
+        for (kind, left, right) in net.body.iter() {
+            self.put_u8(*kind as u8);
+            self.put_port(left);
+            self.put_port(right);
+        }
+        Ok(())
+    }
+
+    fn put_port(&mut self, port: &PortCode) {
+        match port {
+            PortCode::Cell(index) => {
+                self.put_u8(Self::TAG_NET_CELL);
+                self.put_u32(*index);
+            }
+            PortCode::Var(id, polarity) => {
+                self.put_u8(Self::TAG_NET_VAR);
+                self.put_u32(*id);
+                self.put_u8(*polarity as u8);
+            }
+        }
+    }
+}
+
+struct BytecodeReader<'b> {
+    bytes: &'b [u8],
+    pos: usize,
+    // symbols by image index, without the reserved symbol 0
+    symbol_ptrs: Vec<SymbolPtr>,
+}
+
+impl<'b> BytecodeReader<'b> {
+    fn new(bytes: &'b [u8]) -> Result<Self, BytecodeError> {
+        let mut reader = Self {
+            bytes,
+            pos: 0,
+            symbol_ptrs: Vec::new(),
+        };
+        if reader.take(INetBytecode::MAGIC.len())? != INetBytecode::MAGIC {
+            return Err(BytecodeError::BadMagic);
+        }
+        match reader.get_u16()? {
+            INetBytecode::VERSION => Ok(reader),
+            version => Err(BytecodeError::UnsupportedVersion(version)),
+        }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'b [u8], BytecodeError> {
+        let end = self.pos + len;
+        let slice = self.bytes.get(self.pos..end).ok_or(BytecodeError::UnexpectedEnd)?;
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn get_u8(&mut self) -> Result<u8, BytecodeError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn get_u16(&mut self) -> Result<u16, BytecodeError> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn get_u32(&mut self) -> Result<u32, BytecodeError> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn get_polarity(&mut self) -> Result<Polarity, BytecodeError> {
+        match self.get_u8()? {
+            tag @ 2.. => Err(BytecodeError::InvalidTag(tag)),
+            polarity => Ok(Polarity::from(polarity as u16)),
+        }
+    }
+
+    fn get_symbols(&mut self) -> Result<SymbolBook, BytecodeError> {
+        let mut symbols = SymbolBook::new();
+        let count = self.get_u16()?;
+        if count as usize > SymbolPtr::MAX_INDEX {
+            return Err(BytecodeError::TooLarge(count as usize));
+        }
+        for _ in 0..count {
+            let len = self.get_u16()? as usize;
+            let name = std::str::from_utf8(self.take(len)?)
+                .map_err(|_| BytecodeError::InvalidName)?;
+            let name = SymbolName::intern(name);
+            // the reserved symbol is in every book, so it is caught here too
+            if symbols.get_by_name(&name).is_some() {
+                return Err(BytecodeError::InvalidName);
+            }
+            let polarity = self.get_polarity()?;
+            let port_count = self.get_u8()?;
+            if port_count as usize > INetBytecode::MAX_PORTS {
+                return Err(BytecodeError::InvalidArity(port_count));
+            }
+            let ports = self.get_u8()?;
+            let port_polarities: Vec<Polarity> = (0..port_count)
+                .map(|port_num| Polarity::from(((ports >> port_num) & 0b1) as u16))
+                .collect();
+            match (polarity, &port_polarities[..]) {
+                (Polarity::Pos, []) => symbols.ctr0(&name),
+                (Polarity::Pos, [port]) => symbols.ctr1(&name, *port),
+                (Polarity::Pos, [left, right]) => symbols.ctr2(&name, *left, *right),
+                (Polarity::Neg, []) => symbols.fun0(&name),
+                (Polarity::Neg, [port]) => symbols.fun1(&name, *port),
+                (Polarity::Neg, [left, right]) => symbols.fun2(&name, *left, *right),
+                (_, ports) => symbols.declareN(&name, polarity, ports),
+            };
+        }
+        #[cfg(debug_assertions)]
+        symbols.assert_valid();
+        Ok(symbols)
+    }
+
+    /// Resolves the symbols of the image against `symbols`, which must declare
+    /// each of them the same way.
+    fn map_symbols(&mut self, image: &SymbolBook, symbols: &SymbolBook) -> Result<(), BytecodeError> {
+        self.symbol_ptrs = Vec::new();
+        for (name, image_ptr) in image.to_sorted_by_index_vec() {
+            match symbols.get_by_name(name) {
+                Some(symbol_ptr) if symbols.get(symbol_ptr) == image.get(image_ptr) => {
+                    self.symbol_ptrs.push(symbol_ptr)
+                }
+                _ => return Err(BytecodeError::SymbolMismatch(image_ptr.get_index() as u16)),
+            }
+        }
+        Ok(())
+    }
+
+    fn get_symbol_ptr(&mut self) -> Result<SymbolPtr, BytecodeError> {
+        let index = self.get_u16()?;
+        self.symbol_ptrs
+            .get((index as usize).wrapping_sub(1))
+            .copied()
+            .ok_or(BytecodeError::InvalidSymbol(index))
+    }
+
+    fn get_symbol(&mut self, symbols: &SymbolBook) -> Result<(SymbolPtr, SymbolName), BytecodeError> {
+        let index = self.get_u16()?;
+        let symbol_ptr = self
+            .symbol_ptrs
+            .get((index as usize).wrapping_sub(1))
+            .copied()
+            .ok_or(BytecodeError::InvalidSymbol(index))?;
+        let name = symbols
+            .get_name(symbol_ptr)
+            .ok_or(BytecodeError::InvalidSymbol(index))?;
+        Ok((symbol_ptr, name))
+    }
+
+    fn get_rules(&mut self, symbols: &SymbolBook) -> Result<Vec<RuleCode>, BytecodeError> {
+        let mut pairs = HashSet::new();
+        let mut rule_codes = Vec::new();
+        for index in 0..self.get_u16()? {
+            let rule_code = self.get_rule(symbols)?;
+            let pair = (rule_code.ctr.0.get_index(), rule_code.fun.0.get_index());
+            if !pairs.insert(pair) || !rule_code.is_valid(symbols) {
+                return Err(BytecodeError::InvalidRule(index));
+            }
+            rule_codes.push(rule_code);
+        }
+        Ok(rule_codes)
+    }
+
+    fn get_rule(&mut self, symbols: &SymbolBook) -> Result<RuleCode, BytecodeError> {
+        let ctr = self.get_symbol(symbols)?;
+        let fun = self.get_symbol(symbols)?;
+        let ctr_port_count = symbols.get(ctr.0).get_arity().port_count();
+        let fun_port_count = symbols.get(fun.0).get_arity().port_count();
+        let bvar_count = self.get_u16()?;
+        let mut body = Vec::new();
+        for _ in 0..self.get_u16()? {
+            let kind = self.get_u8()?;
+            let get_term = |reader: &mut Self| {
+                reader.get_term(symbols, bvar_count, ctr_port_count, fun_port_count)
+            };
+            let left = get_term(self)?;
+            let right = get_term(self)?;
+            body.push(match (kind, left, right) {
+                (0, left @ TermCode::Cell(..), right @ TermCode::Cell(..)) => {
+                    EquationCode::Redex(left, right)
+                }
+                (1, left @ TermCode::Var(..), right @ TermCode::Cell(..)) => {
+                    EquationCode::Bind(left, right)
+                }
+                (2, left @ TermCode::Var(..), right @ TermCode::Var(..)) => {
+                    EquationCode::Connect(left, right)
+                }
+                _ => return Err(BytecodeError::InvalidTag(kind)),
+            });
+        }
+        Ok(RuleCode {
+            ctr,
+            fun,
+            bvar_count,
+            body,
+        })
+    }
+
+    fn get_term(
+        &mut self,
+        symbols: &SymbolBook,
+        bvar_count: u16,
+        ctr_port_count: usize,
+        fun_port_count: usize,
+    ) -> Result<TermCode, BytecodeError> {
+        match self.get_u8()? {
+            INetBytecode::TAG_CELL => {
+                let (symbol_ptr, name) = self.get_symbol(symbols)?;
+                let port_count = symbols.get(symbol_ptr).get_arity().port_count();
+                let ports = (0..port_count)
+                    .map(|_| self.get_term(symbols, bvar_count, ctr_port_count, fun_port_count))
+                    .collect::<Result<_, _>>()?;
+                Ok(TermCode::Cell(symbol_ptr, name, ports))
+            }
+            INetBytecode::TAG_BVAR => match self.get_u16()? {
+                bvar if bvar < bvar_count => Ok(TermCode::Var(VarCode::Bound(
+                    bvar,
+                    self.get_polarity()?,
+                ))),
+                bvar => Err(BytecodeError::InvalidBvar(bvar)),
+            },
+            tag @ (INetBytecode::TAG_CTR_PORT | INetBytecode::TAG_FUN_PORT) => {
+                let (port_num, port_count) = match tag {
+                    INetBytecode::TAG_CTR_PORT => (self.get_u8()?, ctr_port_count),
+                    _ => (self.get_u8()?, fun_port_count),
+                };
+                if port_num as usize >= port_count {
+                    return Err(BytecodeError::InvalidPort(port_num));
+                }
+                let port_num = PortNum::from(port_num as usize);
+                Ok(TermCode::Var(VarCode::Free(match tag {
+                    INetBytecode::TAG_CTR_PORT => RulePort::Ctr(port_num),
+                    _ => RulePort::Fun(port_num),
+                })))
+            }
+            tag => Err(BytecodeError::InvalidTag(tag)),
+        }
+    }
+
+    fn get_net(&mut self, symbols: &SymbolBook) -> Result<Option<NetCode>, BytecodeError> {
+        match self.get_u8()? {
+            0 => return Ok(None),
+            1 => (),
+            tag => return Err(BytecodeError::InvalidTag(tag)),
+        }
+        let head = (0..self.get_u32()?)
+            .map(|_| self.get_polarity())
+            .collect::<Result<_, _>>()?;
+        let bvar_count = self.get_u32()?;
+        let mut cells = Vec::new();
+        for _ in 0..self.get_u32()? {
+            let symbol_ptr = self.get_symbol_ptr()?;
+            let port_count = symbols.get(symbol_ptr).get_arity().port_count();
+            let ports = (0..port_count)
+                .map(|_| self.get_port())
+                .collect::<Result<_, _>>()?;
+            cells.push((symbol_ptr, ports));
+        }
+        let mut body = Vec::new();
+        for _ in 0..self.get_u32()? {
+            let kind = match self.get_u8()? {
+                0 => EquationKind::Redex,
+                1 => EquationKind::Bind,
+                2 => EquationKind::Connect,
+                tag => return Err(BytecodeError::InvalidTag(tag)),
+            };
+            body.push((kind, self.get_port()?, self.get_port()?));
+        }
+        let net_code = NetCode {
+            head,
+            bvar_count,
+            cells,
+            body,
+        };
+        match net_code.is_valid(symbols) {
+            true => Ok(Some(net_code)),
+            false => Err(BytecodeError::InvalidNet),
+        }
+    }
+
+    fn get_port(&mut self) -> Result<PortCode, BytecodeError> {
+        match self.get_u8()? {
+            INetBytecode::TAG_NET_CELL => Ok(PortCode::Cell(self.get_u32()?)),
+            INetBytecode::TAG_NET_VAR => Ok(PortCode::Var(self.get_u32()?, self.get_polarity()?)),
+            tag => Err(BytecodeError::InvalidTag(tag)),
+        }
+    }
+
+    fn finish(self) -> Result<(), BytecodeError> {
+        match self.bytes.len() - self.pos {
+            0 => Ok(()),
+            trailing => Err(BytecodeError::TrailingBytes(trailing)),
+        }
+    }
+
+    /// Reads the rules and net sections against `image`, the symbols read
+    /// from this image, resolved in `symbols`.
+    fn get_sections(
+        mut self,
+        image: &SymbolBook,
+        symbols: &SymbolBook,
+    ) -> Result<(Vec<RuleCode>, Option<NetCode>), BytecodeError> {
+        self.map_symbols(image, symbols)?;
+        let rule_codes = self.get_rules(symbols)?;
+        let net_code = self.get_net(symbols)?;
+        self.finish()?;
+        Ok((rule_codes, net_code))
+    }
+}
+
+// Decoded rules are checked before any of them is handed to a RuleBuilder,
+// so malformed input is reported instead of panicking mid-rule.
+struct RuleCode {
+    ctr: (SymbolPtr, SymbolName),
+    fun: (SymbolPtr, SymbolName),
+    bvar_count: u16,
+    body: Vec<EquationCode>,
+}
+
+enum EquationCode {
+    Redex(TermCode, TermCode),
+    Bind(TermCode, TermCode),
+    Connect(TermCode, TermCode),
+}
+
+enum TermCode {
+    Cell(SymbolPtr, SymbolName, Vec<TermCode>),
+    Var(VarCode),
+}
+
+enum VarCode {
+    Bound(u16, Polarity),
+    Free(RulePort),
+}
+
+// uses of each bvar end and each port of a rule, see `RuleCode::is_valid`
+struct RuleUses {
+    bvars: Vec<[u32; 2]>,
+    ctr_ports: Vec<u32>,
+    fun_ports: Vec<u32>,
+}
+
+impl RuleCode {
+    /// The checks `RuleBuilder` asserts: the redex symbols have the right
+    /// polarities, every term has the polarity its position expects and
+    /// every bvar end and port is used exactly once.
+    fn is_valid(&self, symbols: &SymbolBook) -> bool {
+        if self.ctr.0.get_polarity() != Polarity::Pos
+            || self.fun.0.get_polarity() != Polarity::Neg
+            || self.bvar_count == u16::MAX
+        {
+            return false;
+        }
+        let mut uses = RuleUses {
+            bvars: vec![[0; 2]; self.bvar_count as usize],
+            ctr_ports: vec![0; symbols.get(self.ctr.0).get_arity().port_count()],
+            fun_ports: vec![0; symbols.get(self.fun.0).get_arity().port_count()],
+        };
+        let body_valid = self.body.iter().all(|eqn| match eqn {
+            EquationCode::Redex(ctr, fun) => {
+                self.check_term(symbols, ctr, Polarity::Pos, &mut uses)
+                    && self.check_term(symbols, fun, Polarity::Neg, &mut uses)
+            }
+            EquationCode::Bind(var, cell) => {
+                let polarity = self.term_polarity(symbols, cell);
+                self.check_term(symbols, cell, polarity, &mut uses)
+                    && self.check_term(symbols, var, polarity.flip(), &mut uses)
+            }
+            EquationCode::Connect(left, right) => {
+                let polarity = self.term_polarity(symbols, left);
+                self.check_term(symbols, left, polarity, &mut uses)
+                    && self.check_term(symbols, right, polarity.flip(), &mut uses)
+            }
+        });
+        body_valid
+            && uses.bvars.iter().all(|ends| *ends == [1, 1])
+            && uses.ctr_ports.iter().chain(uses.fun_ports.iter()).all(|uses| *uses == 1)
+    }
+
+    fn term_polarity(&self, symbols: &SymbolBook, term: &TermCode) -> Polarity {
+        match term {
+            TermCode::Cell(symbol_ptr, ..) => symbol_ptr.get_polarity(),
+            TermCode::Var(VarCode::Bound(_, polarity)) => *polarity,
+            TermCode::Var(VarCode::Free(RulePort::Ctr(port_num))) => {
+                symbols.get(self.ctr.0).get_port_polarity(*port_num).flip()
+            }
+            TermCode::Var(VarCode::Free(RulePort::Fun(port_num))) => {
+                symbols.get(self.fun.0).get_port_polarity(*port_num).flip()
+            }
+        }
+    }
+
+    fn check_term(
+        &self,
+        symbols: &SymbolBook,
+        term: &TermCode,
+        polarity: Polarity,
+        uses: &mut RuleUses,
+    ) -> bool {
+        if self.term_polarity(symbols, term) != polarity {
+            return false;
+        }
+        match term {
+            TermCode::Cell(symbol_ptr, _, ports) => {
+                let symbol = symbols.get(*symbol_ptr);
+                ports.iter().enumerate().all(|(port_num, port)| {
+                    let port_polarity = symbol.get_port_polarity(PortNum::from(port_num));
+                    self.check_term(symbols, port, port_polarity.flip(), uses)
+                })
+            }
+            TermCode::Var(VarCode::Bound(bvar, polarity)) => {
+                uses.bvars[*bvar as usize][*polarity as usize] += 1;
+                true
+            }
+            TermCode::Var(VarCode::Free(RulePort::Ctr(port_num))) => {
+                uses.ctr_ports[*port_num as usize] += 1;
+                true
+            }
+            TermCode::Var(VarCode::Free(RulePort::Fun(port_num))) => {
+                uses.fun_ports[*port_num as usize] += 1;
+                true
+            }
+        }
+    }
+
+    fn build(&self, b: &mut RuleBuilder) {
+        let bvars: Vec<(PVarPtr, PVarPtr)> = (0..self.bvar_count).map(|_| b.var()).collect();
+        for eqn in self.body.iter() {
+            match eqn {
+                EquationCode::Redex(ctr, fun) => {
+                    let ctr = Self::build_term(&bvars, b, ctr);
+                    let fun = Self::build_term(&bvars, b, fun);
+                    b.redex(ctr.get_cell_ptr(), fun.get_cell_ptr());
+                }
+                EquationCode::Bind(var, cell) => {
+                    let var = Self::build_term(&bvars, b, var);
+                    let cell = Self::build_term(&bvars, b, cell);
+                    b.bind(var.get_var_ptr(), cell.get_cell_ptr());
+                }
+                EquationCode::Connect(left, right) => {
+                    let left = Self::build_term(&bvars, b, left);
+                    let right = Self::build_term(&bvars, b, right);
+                    b.connect(left.get_var_ptr(), right.get_var_ptr());
+                }
+            }
+        }
+    }
+
+    fn build_term(bvars: &[(PVarPtr, PVarPtr)], b: &mut RuleBuilder, term: &TermCode) -> TermPtr {
+        match term {
+            TermCode::Cell(_, name, ports) => {
+                let ports: Vec<TermPtr> = ports
+                    .iter()
+                    .map(|port| Self::build_term(bvars, b, port))
+                    .collect();
+                match &ports[..] {
+                    [] => b.cell0(name),
+                    [port] => b.cell1(name, *port),
+                    [left, right] => b.cell2(name, *left, *right),
+                    ports => b.cellN(name, ports),
+                }
+                .into()
+            }
+            TermCode::Var(VarCode::Bound(bvar, Polarity::Neg)) => bvars[*bvar as usize].0.into(),
+            TermCode::Var(VarCode::Bound(bvar, Polarity::Pos)) => bvars[*bvar as usize].1.into(),
+            TermCode::Var(VarCode::Free(RulePort::Ctr(port_num))) => b.ctr_port(*port_num).into(),
+            TermCode::Var(VarCode::Free(RulePort::Fun(port_num))) => b.fun_port(*port_num).into(),
+        }
+    }
+}
+
+fn build_rules(symbols: &SymbolBook, rule_codes: Vec<RuleCode>) -> RuleSet<'_> {
+    let mut rules = RuleSet::new(symbols);
+    for rule_code in rule_codes {
+        rules.rule(&rule_code.ctr.1, &rule_code.fun.1, |b| rule_code.build(b));
+    }
+    rules
+}
+
+// A net in the order it is written: cells before the cells and equations
+// using them, and vars numbered with the head vars first.
+struct NetCode {
+    head: Vec<Polarity>,
+    bvar_count: u32,
+    cells: Vec<(SymbolPtr, Vec<PortCode>)>,
+    body: Vec<(EquationKind, PortCode, PortCode)>,
+}
+
+enum PortCode {
+    Cell(u32),
+    Var(u32, Polarity),
+}
+
+// uses of each var end and each cell of a net, see `NetCode::is_valid`
+struct NetUses {
+    vars: Vec<[u32; 2]>,
+    cells: Vec<u32>,
+}
+
+impl NetCode {
+    fn from_net(net: &Net) -> Result<Self, BytecodeError> {
+        let mut var_ids = HashMap::new();
+        for var_ptr in net.head.iter() {
+            let index = var_ptr.get_fvar_ptr().get_index();
+            let value = net.heap.get_var(*var_ptr).get_store().get_value();
+            if !matches!(value, NetValue::Empty)
+                || var_ids.insert(index, to_u32(var_ids.len())?).is_some()
+            {
+                return Err(BytecodeError::UnsupportedNet);
+            }
+        }
+        let head_len = var_ids.len();
+
+        // cells in post order, so each cell comes after its ports
+        let mut cell_ptrs = Vec::new();
+        let mut cell_ids = HashMap::new();
+        let mut seen = HashSet::new();
+        for eqn in net.body.iter() {
+            let roots = match eqn.get_kind() {
+                EquationKind::Redex => vec![eqn.get_redex_ctr(), eqn.get_redex_fun()],
+                EquationKind::Bind => vec![eqn.get_bind_cell()],
+                EquationKind::Connect => vec![],
+            };
+            for root in roots {
+                let mut pending = vec![(root, false)];
+                while let Some((cell_ptr, ports_done)) = pending.pop() {
+                    if ports_done {
+                        cell_ids.insert(cell_ptr.get_index(), to_u32(cell_ptrs.len())?);
+                        cell_ptrs.push(cell_ptr);
+                        continue;
+                    }
+                    // a cell reached twice is shared or on a cycle
+                    if !seen.insert(cell_ptr.get_index()) {
+                        return Err(BytecodeError::UnsupportedNet);
+                    }
+                    pending.push((cell_ptr, true));
+                    let cell = net.heap.copy_cell(cell_ptr);
+                    for port_num in (0..cell.get_arity().port_count()).rev() {
+                        let port = cell.get_port(PortNum::from(port_num));
+                        if port.get_kind() == TermKind::Cell {
+                            pending.push((port.get_cell_ptr(), false));
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut port_code = |term: TermPtr| -> Result<PortCode, BytecodeError> {
+            if term.get_kind() == TermKind::Cell {
+                return Ok(PortCode::Cell(cell_ids[&term.get_cell_ptr().get_index()]));
+            }
+            let var_ptr = term.get_var_ptr();
+            let var = net.heap.get_var(var_ptr);
+            if !matches!(var.get_store().get_value(), NetValue::Empty) {
+                return Err(BytecodeError::UnsupportedNet);
+            }
+            let index = var_ptr.get_fvar_ptr().get_index();
+            let id = match (var_ids.get(&index), var) {
+                (Some(id), _) => *id,
+                (None, Var::Bound(_)) => {
+                    let id = to_u32(var_ids.len())?;
+                    var_ids.insert(index, id);
+                    id
+                }
+                (None, Var::Free(_)) => return Err(BytecodeError::UnsupportedNet),
+            };
+            Ok(PortCode::Var(id, var_ptr.get_polarity()))
+        };
+
+        let mut cells = Vec::with_capacity(cell_ptrs.len());
+        for cell_ptr in cell_ptrs {
+            let cell = net.heap.copy_cell(cell_ptr);
+            let ports = (0..cell.get_arity().port_count())
+                .map(|port_num| port_code(cell.get_port(PortNum::from(port_num))))
+                .collect::<Result<_, _>>()?;
+            cells.push((cell.get_symbol_ptr(), ports));
+        }
+        let mut body = Vec::with_capacity(net.body.len());
+        for eqn in net.body.iter() {
+            let (left, right): (TermPtr, TermPtr) = match eqn.get_kind() {
+                EquationKind::Redex => (eqn.get_redex_ctr().into(), eqn.get_redex_fun().into()),
+                EquationKind::Bind => (eqn.get_bind_var().into(), eqn.get_bind_cell().into()),
+                EquationKind::Connect => {
+                    (eqn.get_connect_left().into(), eqn.get_connect_right().into())
+                }
+            };
+            body.push((eqn.get_kind(), port_code(left)?, port_code(right)?));
+        }
+
+        let head = net.head.iter().map(|var_ptr| var_ptr.get_polarity()).collect();
+        let bvar_count = to_u32(var_ids.len() - head_len)?;
+        Ok(Self {
+            head,
+            bvar_count,
+            cells,
+            body,
+        })
+    }
+
+    /// The checks `EquationBuilder` asserts, as in `RuleCode::is_valid`, and
+    /// that each cell is used exactly once by a later cell or an equation.
+    fn is_valid(&self, symbols: &SymbolBook) -> bool {
+        let var_count = self.head.len() + self.bvar_count as usize;
+        // each head var is used at least once and each bvar twice, which also
+        // bounds what is allocated below by the size of the input
+        let occurrences: usize = self.cells.iter().map(|(_, ports)| ports.len()).sum();
+        if self.head.len() + 2 * self.bvar_count as usize > occurrences + 2 * self.body.len() {
+            return false;
+        }
+        let mut uses = NetUses {
+            vars: vec![[0; 2]; var_count],
+            cells: vec![0; self.cells.len()],
+        };
+        let cells_valid = self.cells.iter().enumerate().all(|(index, (symbol_ptr, ports))| {
+            let symbol = symbols.get(*symbol_ptr);
+            ports.iter().enumerate().all(|(port_num, port)| {
+                let port_polarity = symbol.get_port_polarity(PortNum::from(port_num));
+                self.check_port(port, port_polarity.flip(), index, &mut uses)
+            })
+        });
+        let cell_count = self.cells.len();
+        let body_valid = self.body.iter().all(|(kind, left, right)| match (kind, left, right) {
+            (EquationKind::Redex, PortCode::Cell(_), PortCode::Cell(_)) => {
+                self.check_port(left, Polarity::Pos, cell_count, &mut uses)
+                    && self.check_port(right, Polarity::Neg, cell_count, &mut uses)
+            }
+            (EquationKind::Bind, PortCode::Var(..), PortCode::Cell(_)) => {
+                self.port_polarity(right).map_or(false, |polarity| {
+                    self.check_port(right, polarity, cell_count, &mut uses)
+                        && self.check_port(left, polarity.flip(), cell_count, &mut uses)
+                })
+            }
+            (EquationKind::Connect, PortCode::Var(_, polarity), PortCode::Var(..)) => {
+                self.check_port(left, *polarity, cell_count, &mut uses)
+                    && self.check_port(right, polarity.flip(), cell_count, &mut uses)
+            }
+            _ => false,
+        });
+        // a head var is only used from the inside, by the end opposite to
+        // the one in the head
+        let head_used = self.head.iter().enumerate().all(|(id, polarity)| {
+            uses.vars[id][polarity.flip() as usize] == 1 && uses.vars[id][*polarity as usize] == 0
+        });
+        cells_valid
+            && body_valid
+            && head_used
+            && uses.vars[self.head.len()..].iter().all(|ends| *ends == [1, 1])
+            && uses.cells.iter().all(|uses| *uses == 1)
+    }
+
+    fn port_polarity(&self, port: &PortCode) -> Option<Polarity> {
+        match port {
+            PortCode::Cell(index) => self
+                .cells
+                .get(*index as usize)
+                .map(|(symbol_ptr, _)| symbol_ptr.get_polarity()),
+            PortCode::Var(_, polarity) => Some(*polarity),
+        }
+    }
+
+    // `cell_count` is the number of cells `port` may refer to
+    fn check_port(
+        &self,
+        port: &PortCode,
+        polarity: Polarity,
+        cell_count: usize,
+        uses: &mut NetUses,
+    ) -> bool {
+        match port {
+            PortCode::Cell(index) if (*index as usize) < cell_count => {
+                uses.cells[*index as usize] += 1;
+                self.port_polarity(port) == Some(polarity)
+            }
+            PortCode::Var(id, var_polarity) if (*id as usize) < uses.vars.len() => {
+                uses.vars[*id as usize][*var_polarity as usize] += 1;
+                *var_polarity == polarity
+            }
+            _ => false,
+        }
+    }
+
+    fn build<'a>(&self, symbols: &'a SymbolBook) -> Net<'a> {
+        let mut net = Net::new(symbols);
+        net.equations(|b| {
+            let mut vars: Vec<(PVarPtr, PVarPtr)> = self
+                .head
+                .iter()
+                .map(|polarity| {
+                    let inside = match polarity {
+                        Polarity::Pos => b.output(),
+                        Polarity::Neg => b.input(),
+                    };
+                    PVarPtr::wire(inside.get_fvar_ptr())
+                })
+                .collect();
+            vars.extend((0..self.bvar_count).map(|_| b.var()));
+
+            let mut cell_ptrs = Vec::with_capacity(self.cells.len());
+            for (symbol_ptr, ports) in self.cells.iter() {
+                let ports: Vec<TermPtr> = ports
+                    .iter()
+                    .map(|port| Self::build_port(&vars, &cell_ptrs, port))
+                    .collect();
+                cell_ptrs.push(match &ports[..] {
+                    [] => b.cell0_ptr(*symbol_ptr),
+                    [port] => b.cell1_ptr(*symbol_ptr, *port),
+                    [left, right] => b.cell2_ptr(*symbol_ptr, *left, *right),
+                    ports => b.cellN_ptr(*symbol_ptr, ports),
+                });
+            }
+            for (kind, left, right) in self.body.iter() {
+                let left = Self::build_port(&vars, &cell_ptrs, left);
+                let right = Self::build_port(&vars, &cell_ptrs, right);
+                match kind {
+                    EquationKind::Redex => b.redex(left.get_cell_ptr(), right.get_cell_ptr()),
+                    EquationKind::Bind => b.bind(left.get_var_ptr(), right.get_cell_ptr()),
+                    EquationKind::Connect => b.connect(left.get_var_ptr(), right.get_var_ptr()),
+                }
+            }
+        });
+        net
+    }
+
+    fn build_port(
+        vars: &[(PVarPtr, PVarPtr)],
+        cell_ptrs: &[CellPtr],
+        port: &PortCode,
+    ) -> TermPtr {
+        match port {
+            PortCode::Cell(index) => cell_ptrs[*index as usize].into(),
+            PortCode::Var(id, Polarity::Neg) => vars[*id as usize].0.into(),
+            PortCode::Var(id, Polarity::Pos) => vars[*id as usize].1.into(),
+        }
+    }
+}
+
+impl SymbolBook {
+    /// Reads the symbol table of a bytecode image produced by
+    /// `RuleSet::to_bytecode`.
+    pub fn from_bytecode(bytes: &[u8]) -> Result<SymbolBook, BytecodeError> {
+        BytecodeReader::new(bytes)?.get_symbols()
+    }
+}
+
+impl<'a> RuleSet<'a> {
+    pub fn to_bytecode(&self, symbols: &SymbolBook) -> Result<Vec<u8>, BytecodeError> {
+        self.write_bytecode(symbols, None)
+    }
+
+    /// Like `to_bytecode`, adding `net` as the net section. The net must not
+    /// have been evaluated yet, see `BytecodeError::UnsupportedNet`.
+    pub fn to_bytecode_with_net(
+        &self,
+        symbols: &SymbolBook,
+        net: &Net,
+    ) -> Result<Vec<u8>, BytecodeError> {
+        self.write_bytecode(symbols, Some(&NetCode::from_net(net)?))
+    }
+
+    fn write_bytecode(
+        &self,
+        symbols: &SymbolBook,
+        net_code: Option<&NetCode>,
+    ) -> Result<Vec<u8>, BytecodeError> {
+        let mut bytecode = INetBytecode::new();
+        bytecode.put_symbols(symbols)?;
+        bytecode.put_rules(self)?;
+        bytecode.put_net(net_code)?;
+        Ok(bytecode.into_bytes())
+    }
+
+    /// Reads a bytecode image with its own symbol book. A rule set borrows
+    /// its book, so both are returned together as a `RuleSetWithSymbols`.
+    pub fn from_bytecode(bytes: &[u8]) -> Result<RuleSetWithSymbols, BytecodeError> {
+        let mut reader = BytecodeReader::new(bytes)?;
+        let image = reader.get_symbols()?;
+        RuleSetWithSymbols::try_new(image, |symbols| {
+            let (rule_codes, _) = reader.get_sections(symbols, symbols)?;
+            Ok(build_rules(symbols, rule_codes))
+        })
+    }
+
+    /// Reads the rules of a bytecode image against `symbols`, which must
+    /// declare every symbol of the image the same way, by name.
+    pub fn from_bytecode_with_symbols(
+        bytes: &[u8],
+        symbols: &'a SymbolBook,
+    ) -> Result<RuleSet<'a>, BytecodeError> {
+        let mut reader = BytecodeReader::new(bytes)?;
+        let image = reader.get_symbols()?;
+        let (rule_codes, _) = reader.get_sections(&image, symbols)?;
+        Ok(build_rules(symbols, rule_codes))
+    }
+}
+
+impl<'a> Net<'a> {
+    /// Reads the net section of a bytecode image against `symbols`, as in
+    /// `RuleSet::from_bytecode_with_symbols`, or `None` if it has no net.
+    pub fn from_bytecode(
+        bytes: &[u8],
+        symbols: &'a SymbolBook,
+    ) -> Result<Option<Net<'a>>, BytecodeError> {
+        let mut reader = BytecodeReader::new(bytes)?;
+        let image = reader.get_symbols()?;
+        let (_, net_code) = reader.get_sections(&image, symbols)?;
+        Ok(net_code.map(|net_code| net_code.build(symbols)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inet::runtime::Runtime;
+
+    fn fib_symbols() -> SymbolBook {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+        symbols.declare_arith_symbols();
+        symbols.declare_combinator_symbols();
+        symbols.declare_fib_symbols();
+        symbols
+    }
+
+    fn fib_rules(symbols: &SymbolBook) -> RuleSet {
+        let mut rules = RuleSet::new(symbols);
+        rules.arith_rules();
+        rules.define_combinator_rules();
+        rules.fib_rules();
+        rules
+    }
+
+    // 1 + 1 shared before it is computed, with 1 added to each copy
+    fn shared_sum_net(symbols: &SymbolBook) -> Net {
+        let mut net = Net::new(symbols);
+        net.equations(|b| {
+            let (sum_input, sum_output) = b.var();
+            let one = b.one();
+            let adder = b.adder(sum_input.into(), one.into());
+            let one = b.one();
+            b.add(one, adder);
+
+            let (copy0, copy1) = b.share(sum_output.into());
+            for copy in [copy0, copy1] {
+                let out = b.output();
+                let one = b.one();
+                let adder = b.adder(out.into(), one.into());
+                b.add(copy, adder);
+            }
+        });
+        net
+    }
+
+    fn fib_image_with_net() -> Vec<u8> {
+        let symbols = fib_symbols();
+        let rules = fib_rules(&symbols);
+        rules
+            .to_bytecode_with_net(&symbols, &shared_sum_net(&symbols))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_bytecode_fib_rules_round_trip() {
+        let symbols = fib_symbols();
+        let rules = fib_rules(&symbols);
+        let bytes = rules.to_bytecode(&symbols).unwrap();
+        assert_eq!(&bytes[..4], INetBytecode::MAGIC);
+
+        let decoded = RuleSet::from_bytecode(&bytes).unwrap();
+        assert_eq!(decoded.rules().to_bytecode(decoded.symbols()), Ok(bytes.clone()));
+        assert_eq!(decoded.rules().to_string(), rules.to_string());
+
+        assert!(matches!(Net::from_bytecode(&bytes, &symbols), Ok(None)));
+        let decoded_rules = RuleSet::from_bytecode_with_symbols(&bytes, &symbols).unwrap();
+        assert_eq!(decoded_rules.to_bytecode(&symbols), Ok(bytes));
+    }
+
+    #[test]
+    fn test_bytecode_example_rules_round_trip() {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+        symbols.declare_arith_symbols();
+        symbols.declare_combinator_symbols();
+        symbols.declare_eraser_symbols();
+        symbols.declare_fib_symbols();
+        symbols.declare_fib_shared_symbols();
+        symbols.declare_lambda_symbols();
+        symbols.declare_ski_symbols();
+        symbols.declare_pair_symbols();
+        symbols.declare_maybe_symbols();
+        symbols.declare_multiplication_symbols();
+
+        let define_rules: [fn(&mut RuleSet); 10] = [
+            |rules| rules.arith_rules(),
+            |rules| rules.define_combinator_rules(),
+            |rules| rules.define_eraser_rules(),
+            |rules| rules.fib_rules(),
+            |rules| rules.fib_shared_rules(),
+            |rules| rules.define_lambda_rules(),
+            |rules| rules.define_ski_rules(),
+            |rules| rules.define_pair_rules(),
+            |rules| rules.define_maybe_rules(),
+            |rules| rules.multiplication_rules(),
+        ];
+        for define in define_rules {
+            let mut rules = RuleSet::new(&symbols);
+            define(&mut rules);
+            let bytes = rules.to_bytecode(&symbols).unwrap();
+            let decoded = RuleSet::from_bytecode(&bytes).unwrap();
+            assert_eq!(decoded.rules().to_bytecode(decoded.symbols()), Ok(bytes));
+            assert_eq!(decoded.rules().to_string(), rules.to_string());
+        }
+    }
+
+    #[test]
+    fn test_bytecode_net_round_trip() {
+        let bytes = fib_image_with_net();
+        let decoded = RuleSet::from_bytecode(&bytes).unwrap();
+        let symbols = decoded.symbols();
+        let net = Net::from_bytecode(&bytes, symbols).unwrap().unwrap();
+        assert_eq!(decoded.rules().to_bytecode_with_net(symbols, &net), Ok(bytes));
+
+        let runtime = Runtime::new(decoded.rules(), false);
+        let net = runtime.eval(net).unwrap();
+        let values = net.head_values();
+        assert_eq!(values.len(), 2);
+        for (_, value) in values {
+            assert_eq!(net.read_nat(value.unwrap()), Some(3));
+        }
+    }
+
+    #[test]
+    fn test_bytecode_encode_errors() {
+        let symbols = fib_symbols();
+        let rules = fib_rules(&symbols);
+        let runtime = Runtime::new(&rules, false);
+        let net = runtime.eval(shared_sum_net(&symbols)).unwrap();
+        assert_eq!(
+            rules.to_bytecode_with_net(&symbols, &net).err(),
+            Some(BytecodeError::UnsupportedNet)
+        );
+
+        let mut symbols = SymbolBook::new();
+        let name = "x".repeat(u16::MAX as usize + 1);
+        symbols.ctr0(&SymbolName::intern(&name));
+        assert_eq!(
+            RuleSet::new(&symbols).to_bytecode(&symbols).err(),
+            Some(BytecodeError::TooLarge(name.len()))
+        );
+    }
+
+    #[test]
+    fn test_bytecode_errors() {
+        assert_eq!(SymbolBook::from_bytecode(b"NETI").err(), Some(BytecodeError::BadMagic));
+        assert_eq!(
+            SymbolBook::from_bytecode(b"INET\x02\x00").err(),
+            Some(BytecodeError::UnsupportedVersion(2))
+        );
+        assert_eq!(
+            SymbolBook::from_bytecode(b"INET\x01\x00\x01").err(),
+            Some(BytecodeError::UnexpectedEnd)
+        );
+
+        let symbols = fib_symbols();
+        let bytes = fib_rules(&symbols).to_bytecode(&symbols).unwrap();
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert_eq!(
+            RuleSet::from_bytecode(&trailing).err(),
+            Some(BytecodeError::TrailingBytes(1))
+        );
+        assert_eq!(
+            RuleSet::from_bytecode_with_symbols(&bytes, &SymbolBook::new()).err(),
+            Some(BytecodeError::SymbolMismatch(1))
+        );
+
+        // two copies of the first symbol
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+        let mut duplicate = RuleSet::new(&symbols).to_bytecode(&symbols).unwrap();
+        let (first_name, _) = &symbols.to_sorted_by_index_vec()[0];
+        let first = duplicate[8..8 + 2 + first_name.0.len() + 3].to_vec();
+        duplicate[6] += 1;
+        duplicate.splice(8..8, first);
+        assert_eq!(RuleSet::from_bytecode(&duplicate).err(), Some(BytecodeError::InvalidName));
+    }
+
+    #[test]
+    fn test_bytecode_truncated() {
+        let bytes = fib_image_with_net();
+        for len in 0..bytes.len() {
+            assert!(RuleSet::from_bytecode(&bytes[..len]).is_err(), "{} bytes", len);
+            let symbols = fib_symbols();
+            assert!(Net::from_bytecode(&bytes[..len], &symbols).is_err(), "{} bytes", len);
+        }
+    }
+
+    #[test]
+    fn test_bytecode_corrupted() {
+        // any single byte changed is decoded or rejected, never a panic
+        let bytes = fib_image_with_net();
+        let symbols = fib_symbols();
+        for pos in 0..bytes.len() {
+            for corrupt in [|byte: u8| byte ^ 0xff, |byte: u8| byte.wrapping_add(1)] {
+                let mut corrupted = bytes.clone();
+                corrupted[pos] = corrupt(corrupted[pos]);
+                let _ = RuleSet::from_bytecode(&corrupted);
+                let _ = Net::from_bytecode(&corrupted, &symbols);
+            }
+        }
+    }
+}
@@ -3,37 +3,54 @@ use std::{
     marker::PhantomData,
 };
 
-use raw_arena::{arenaraw::RawArena, ArenaValue, Ptr};
+use raw_arena::{
+    arenaraw::{RawArena, DEFAULT_CAPACITY},
+    ArenaValue, Ptr,
+};
 
 use super::{
     rule::PortNum,
-    symbol::{SymbolArity, SymbolPtr},
-    term::{TermFamily, TermPtr},
+    symbol::{SymbolArity, SymbolBook, SymbolName, SymbolPtr},
+    term::{TermFamily, TermPtr, TermPtr32, TermPtr64},
     BitSet32, BitSet64, Polarity,
 };
 
+/// The cell pointer used throughout the heap, `CellPtr64` with the
+/// `large-heap` feature.
+#[cfg(not(feature = "large-heap"))]
+pub type CellPtr = CellPtr32;
+#[cfg(feature = "large-heap")]
+pub type CellPtr = CellPtr64;
+
+// a default arena must be addressable to its last cell, checked at compile time
+#[allow(clippy::assertions_on_constants)]
+const _: () = assert!(CellPtr::MAX_INDEX >= DEFAULT_CAPACITY - 1);
+
 #[derive(PartialEq, Clone, Copy)]
-pub struct CellPtr(u32);
-impl CellPtr {
-    const INDEX: BitSet32<23> = BitSet32 {
-        mask: 0b00000000_01111111_11111111_11111111,
+pub struct CellPtr32(u32);
+impl CellPtr32 {
+    const INDEX: BitSet32<22> = BitSet32 {
+        mask: 0b00000000_00111111_11111111_11111111,
         offset: 0,
     };
     const POLARITY: BitSet32<1> = BitSet32 {
-        mask: 0b00000000_1,
-        offset: 23,
+        mask: 0b1,
+        offset: 22,
     };
-    const _UNUSED: BitSet32<8> = BitSet32 {
-        mask: 0b11111111,
-        offset: 24,
+    const _UNUSED: BitSet32<9> = BitSet32 {
+        mask: 0b111111111,
+        offset: 23,
     };
 
-    const PTR: BitSet32<24> = BitSet32 {
-        mask: 0b00000000_11111111_11111111_11111111,
+    const PTR: BitSet32<23> = BitSet32 {
+        mask: 0b00000000_01111111_11111111_11111111,
         offset: 0,
     };
 
-    fn new(index: usize, polarity: Polarity) -> Self {
+    /// Cell indexes are 22 bits wide, enough for a `DEFAULT_CAPACITY` arena.
+    pub const MAX_INDEX: usize = (1 << 22) - 1;
+
+    pub(crate) fn new(index: usize, polarity: Polarity) -> Self {
         let mut new = Self(0);
         // new.set_kind(TermKind::Cell);
         new.set_polarity(polarity);
@@ -67,18 +84,18 @@ impl CellPtr {
     }
 }
 
-impl Ptr for CellPtr {
+impl Ptr for CellPtr32 {
     #[inline]
     fn get_index(&self) -> usize {
         self.get_index()
     }
 }
 
-impl Binary for CellPtr {
+impl Binary for CellPtr32 {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "{:09b}_{:01b}_{:023b}",
+            "{:09b}_{:01b}_{:022b}",
             Self::_UNUSED.get(self.0),
             self.get_polarity() as u8,
             self.get_index()
@@ -86,25 +103,25 @@ impl Binary for CellPtr {
     }
 }
 
-impl Into<TermPtr> for CellPtr {
-    fn into(self) -> TermPtr {
-        TermPtr::new_cell(self)
+impl Into<TermPtr32> for CellPtr32 {
+    fn into(self) -> TermPtr32 {
+        TermPtr32::new_cell(self)
     }
 }
 
-impl From<u32> for CellPtr {
+impl From<u32> for CellPtr32 {
     fn from(raw: u32) -> Self {
         Self(raw)
     }
 }
 
-impl From<TermPtr> for CellPtr {
-    fn from(value: TermPtr) -> Self {
-        CellPtr(value.get_term())
+impl From<TermPtr32> for CellPtr32 {
+    fn from(value: TermPtr32) -> Self {
+        CellPtr32(value.get_term())
     }
 }
 
-impl Debug for CellPtr {
+impl Debug for CellPtr32 {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         let name = format!("CellPtr({:032b})", self.0);
         let mut b = f.debug_struct(&name);
@@ -114,64 +131,311 @@ impl Debug for CellPtr {
     }
 }
 
-#[derive(Clone, Copy)]
+/// A `CellPtr` with a 31-bit index, the cell half of a `TermPtr64`.
+#[derive(PartialEq, Clone, Copy)]
+pub struct CellPtr64(u64);
+impl CellPtr64 {
+    const INDEX: BitSet64<31> = BitSet64 {
+        mask: 0b01111111_11111111_11111111_11111111,
+        offset: 0,
+    };
+    const POLARITY: BitSet64<1> = BitSet64 {
+        mask: 0b1,
+        offset: 31,
+    };
+
+    const PTR: BitSet64<32> = BitSet64 {
+        mask: 0b11111111_11111111_11111111_11111111,
+        offset: 0,
+    };
+
+    /// Cell indexes are 31 bits wide.
+    pub const MAX_INDEX: usize = (1 << 31) - 1;
+
+    pub fn new(index: usize, polarity: Polarity) -> Self {
+        let mut new = Self(0);
+        new.0 = Self::POLARITY.set(new.0, polarity as u64);
+        new.0 = Self::INDEX.set(new.0, index as u64);
+        new
+    }
+
+    #[inline]
+    pub fn get_ptr(&self) -> u32 {
+        Self::PTR.get(self.0) as u32
+    }
+
+    #[inline]
+    pub fn get_polarity(&self) -> Polarity {
+        Polarity::from(Self::POLARITY.get(self.0))
+    }
+
+    #[inline]
+    pub fn get_index(&self) -> usize {
+        Self::INDEX.get(self.0) as usize
+    }
+}
+
+impl Ptr for CellPtr64 {
+    #[inline]
+    fn get_index(&self) -> usize {
+        self.get_index()
+    }
+}
+
+impl Binary for CellPtr64 {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:01b}_{:031b}",
+            self.get_polarity() as u8,
+            self.get_index()
+        )
+    }
+}
+
+impl From<CellPtr32> for CellPtr64 {
+    fn from(cell_ptr: CellPtr32) -> Self {
+        CellPtr64::new(cell_ptr.get_index(), cell_ptr.get_polarity())
+    }
+}
+
+impl From<CellPtr64> for TermPtr64 {
+    fn from(cell_ptr: CellPtr64) -> Self {
+        TermPtr64::new_cell(cell_ptr)
+    }
+}
+
+impl From<u32> for CellPtr64 {
+    fn from(raw: u32) -> Self {
+        Self(raw as u64)
+    }
+}
+
+impl From<TermPtr64> for CellPtr64 {
+    fn from(value: TermPtr64) -> Self {
+        value.get_cell_ptr()
+    }
+}
+
+impl Debug for CellPtr64 {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = format!("CellPtr64({:064b})", self.0);
+        let mut b = f.debug_struct(&name);
+        b.field("polarity", &self.get_polarity());
+        b.field("index", &self.get_index());
+        b.finish()
+    }
+}
+
+/// Cells pack their symbol and up to two ports in one 64-bit word with the
+/// default `TermPtr32`, `large-heap` cells take 24 bytes. N-ary cells hold a
+/// `NaryPortsPtr` in place of their ports, the heap keeps all of them in its
+/// `NaryPortsArena`, so cells stay `Copy`.
+#[cfg(not(feature = "large-heap"))]
 pub struct Cell<T: TermFamily>(u64, PhantomData<T>);
+#[cfg(feature = "large-heap")]
+pub struct Cell<T: TermFamily>([TermPtr; 2], SymbolPtr, NaryPortsPtr, PhantomData<T>);
+
+// not derived, which would require T: Copy
+impl<T: TermFamily> Clone for Cell<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<T: TermFamily> Copy for Cell<T> {}
+
+#[cfg(not(feature = "large-heap"))]
 impl<T: TermFamily> Cell<T> {
-    const RIGHT_PORT: BitSet64<25> = BitSet64 {
-        mask: 0b00000000_00000000_00000000_00000000_00000001_11111111_11111111_11111111,
+    const RIGHT_PORT: BitSet64<24> = BitSet64 {
+        mask: 0b11111111_11111111_11111111,
         offset: 0,
     };
-    const LEFT_PORT: BitSet64<25> = BitSet64 {
-        mask: 0b00000000_00000011_11111111_11111111_1111111,
-        offset: 25,
+    const LEFT_PORT: BitSet64<24> = BitSet64 {
+        mask: 0b11111111_11111111_11111111,
+        offset: 24,
+    };
+    const ARITY: BitSet64<2> = BitSet64 {
+        mask: 0b11,
+        offset: 48,
     };
     const SYMBOL: BitSet64<14> = BitSet64 {
-        mask: 0b11111111_111111,
+        mask: 0b111111_11111111,
         offset: 50,
     };
+    // n-ary cells hold these in place of the ports
+    const NARY_PORTS: BitSet64<32> = BitSet64 {
+        mask: 0b11111111_11111111_11111111_11111111,
+        offset: 0,
+    };
+    const NARY_ARITY: BitSet64<4> = BitSet64 {
+        mask: 0b1111,
+        offset: 32,
+    };
+    // the ARITY of n-ary cells, whose port count is in NARY_ARITY
+    const NARY: u64 = 0b11;
+
+    #[inline]
+    fn empty(symbol_ptr: SymbolPtr) -> Self {
+        let bits = Self::SYMBOL.set(0, symbol_ptr.get_index_and_polarity() as u64);
+        let bits = match symbol_ptr.get_arity() {
+            SymbolArity::N(port_count) => {
+                let bits = Self::ARITY.set(bits, Self::NARY);
+                let bits = Self::NARY_ARITY.set(bits, port_count as u64);
+                Self::NARY_PORTS.set(bits, NaryPortsPtr::NONE.0 as u64)
+            }
+            arity => Self::ARITY.set(bits, arity.port_count() as u64),
+        };
+        Self(bits, PhantomData)
+    }
+
+    #[inline]
+    pub fn get_symbol_ptr(&self) -> SymbolPtr {
+        let arity = match Self::ARITY.get(self.0) {
+            Self::NARY => SymbolArity::N(Self::NARY_ARITY.get(self.0) as u8),
+            port_count => SymbolArity::from(port_count as usize),
+        };
+        SymbolPtr::from_index_and_polarity(Self::SYMBOL.get(self.0) as u32, arity)
+    }
+
+    #[inline]
+    fn port_bits(port_num: PortNum) -> BitSet64<24> {
+        match port_num {
+            PortNum::Zero => Self::LEFT_PORT,
+            _ => Self::RIGHT_PORT,
+        }
+    }
+
+    #[inline]
+    fn get_packed_port(&self, port_num: PortNum) -> TermPtr {
+        TermPtr::from(Self::port_bits(port_num).get(self.0) as u32)
+    }
+
+    #[inline]
+    fn set_packed_port(&mut self, port_num: PortNum, port: TermPtr) {
+        let port_bits = Self::port_bits(port_num);
+        self.0 = port_bits.set(port_bits.clear(self.0), port.get_ptr() as u64)
+    }
+
+    /// Where the heap keeps the ports of an n-ary cell.
+    #[inline]
+    pub(crate) fn get_nary_ports_ptr(&self) -> NaryPortsPtr {
+        debug_assert!(self.get_symbol_ptr().is_nary());
+        NaryPortsPtr(Self::NARY_PORTS.get(self.0) as u32)
+    }
+
+    #[inline]
+    pub(crate) fn set_nary_ports_ptr(&mut self, nary_ports_ptr: NaryPortsPtr) {
+        debug_assert!(self.get_symbol_ptr().is_nary());
+        self.0 = Self::NARY_PORTS.set(Self::NARY_PORTS.clear(self.0), nary_ports_ptr.0 as u64)
+    }
+}
+
+#[cfg(feature = "large-heap")]
+impl<T: TermFamily> Cell<T> {
+    #[inline]
+    fn empty(symbol_ptr: SymbolPtr) -> Self {
+        Self(Self::NO_PORTS, symbol_ptr, NaryPortsPtr::NONE, PhantomData)
+    }
+
+    #[inline]
+    pub fn get_symbol_ptr(&self) -> SymbolPtr {
+        self.1
+    }
+
+    #[inline]
+    fn get_packed_port(&self, port_num: PortNum) -> TermPtr {
+        self.0[port_num as usize]
+    }
+
+    #[inline]
+    fn set_packed_port(&mut self, port_num: PortNum, port: TermPtr) {
+        self.0[port_num as usize] = port;
+    }
+
+    /// Where the heap keeps the ports of an n-ary cell.
+    #[inline]
+    pub(crate) fn get_nary_ports_ptr(&self) -> NaryPortsPtr {
+        debug_assert!(self.get_symbol_ptr().is_nary());
+        self.2
+    }
+
+    #[inline]
+    pub(crate) fn set_nary_ports_ptr(&mut self, nary_ports_ptr: NaryPortsPtr) {
+        debug_assert!(self.get_symbol_ptr().is_nary());
+        self.2 = nary_ports_ptr
+    }
+}
+
+impl<T: TermFamily> Cell<T> {
+    // what ports past the arity hold
+    const NO_PORTS: [TermPtr; 2] = [TermPtr::NULL; 2];
 
     #[inline]
     pub fn new0(symbol_ptr: SymbolPtr) -> Self {
-        let mut cell = Self(0, PhantomData);
-        cell.set_symbol_ptr(symbol_ptr);
-        cell
+        Self::empty(symbol_ptr)
     }
 
     #[inline]
     pub fn new1(symbol_ptr: SymbolPtr, port: TermPtr) -> Self {
-        let mut cell = Self(0, PhantomData);
-        cell.set_symbol_ptr(symbol_ptr);
+        let mut cell = Self::empty(symbol_ptr);
         cell.set_port(PortNum::Zero, port);
         cell
     }
 
     #[inline]
     pub fn new2(symbol_ptr: SymbolPtr, left_port: TermPtr, right_port: TermPtr) -> Self {
-        let mut cell = Self(0, PhantomData);
-        cell.set_symbol_ptr(symbol_ptr);
+        let mut cell = Self::empty(symbol_ptr);
         cell.set_port(PortNum::Zero, left_port);
         cell.set_port(PortNum::One, right_port);
         cell
     }
 
-    #[inline]
-    pub fn get_symbol_ptr(&self) -> SymbolPtr {
-        SymbolPtr::from(Self::SYMBOL.get(self.0))
+    /// An n-ary cell whose ports the heap keeps at `nary_ports_ptr`, see
+    /// `Heap::cellN`.
+    #[allow(non_snake_case)]
+    pub(crate) fn newN(symbol_ptr: SymbolPtr, nary_ports_ptr: NaryPortsPtr) -> Self {
+        assert!(symbol_ptr.is_nary(), "Symbol is not n-ary: {:?}", symbol_ptr);
+        let mut cell = Self::empty(symbol_ptr);
+        cell.set_nary_ports_ptr(nary_ports_ptr);
+        cell
+    }
+
+    /// A cell with the ports in order, which must be exactly as many as the
+    /// symbol's arity. N-ary cells keep their ports in the heap, `Heap::cellN`
+    /// allocates them.
+    pub fn from_ports(symbol_ptr: SymbolPtr, ports: impl IntoIterator<Item = TermPtr>) -> Self {
+        let mut ports = ports.into_iter();
+        let mut next_port =
+            || ports.next().unwrap_or_else(|| panic!("Missing ports for {:?}", symbol_ptr));
+        let cell = match symbol_ptr.get_arity() {
+            SymbolArity::Zero => Self::new0(symbol_ptr),
+            SymbolArity::One => Self::new1(symbol_ptr, next_port()),
+            SymbolArity::Two => {
+                let left_port = next_port();
+                Self::new2(symbol_ptr, left_port, next_port())
+            }
+            SymbolArity::N(_) => panic!("N-ary cells need Heap::cellN: {:?}", symbol_ptr),
+        };
+        assert!(ports.next().is_none(), "Too many ports for {:?}", symbol_ptr);
+        cell
+    }
+
+    /// The name of this cell's symbol in `symbols`.
+    pub fn symbol_name<'s>(&self, symbols: &'s SymbolBook) -> Option<&'s SymbolName> {
+        symbols.get_name_ref(self.get_symbol_ptr())
     }
 
     #[inline]
-    fn set_symbol_ptr(&mut self, symbol_ptr: SymbolPtr) {
-        self.0 = Self::SYMBOL.set(self.0, symbol_ptr.into())
+    pub fn get_arity(&self) -> SymbolArity {
+        self.get_symbol_ptr().get_arity()
     }
 
+    /// One of the packed ports, n-ary cells have none, see
+    /// `Heap::get_cell_port`.
     #[inline]
     pub fn get_port(&self, port_num: PortNum) -> TermPtr {
-        assert!(port_num.is_valid_port(self.get_symbol_ptr().get_arity()));
-        match port_num {
-            PortNum::Zero => self.get_left_port_bits(),
-            PortNum::One => self.get_right_port_bits(),
-        }
-        .into()
+        assert!(self.is_packed_port(port_num));
+        self.get_packed_port(port_num)
     }
 
     #[inline]
@@ -184,33 +448,42 @@ impl<T: TermFamily> Cell<T> {
         self.get_port(PortNum::One)
     }
 
+    /// The two packed ports, read in one go whatever the arity, and how many
+    /// of them are valid (0, 1 or 2). N-ary cells return 0, the heap's
+    /// `NaryPortsArena` holds all their ports.
     #[inline]
-    pub fn set_port(&mut self, port_num: PortNum, port: TermPtr) {
-        assert!(port_num.is_valid_port(self.get_symbol_ptr().get_arity()));
-        match port_num {
-            PortNum::Zero => self.set_left_port_bits(port.get_ptr()),
-            PortNum::One => self.set_right_port_bits(port.get_ptr()),
-        }
-    }
-
-    #[inline]
-    fn get_left_port_bits(&self) -> u32 {
-        Self::LEFT_PORT.get(self.0) as u32
+    pub fn ports_as_array(&self) -> ([TermPtr; 2], usize) {
+        let mut ports = Self::NO_PORTS;
+        let count = self.write_ports_to_slice(&mut ports);
+        (ports, count)
     }
 
+    /// Like `ports_as_array`, writing into `out` so batches of cells can reuse
+    /// one buffer. Ports past the returned count are left unspecified.
     #[inline]
-    fn set_left_port_bits(&mut self, port_bits: u32) {
-        self.0 = Self::LEFT_PORT.set(self.0, port_bits as u64)
+    pub fn write_ports_to_slice(&self, out: &mut [TermPtr; 2]) -> usize {
+        match self.get_arity() {
+            SymbolArity::N(_) => 0,
+            arity => {
+                out[0] = self.get_packed_port(PortNum::Zero);
+                out[1] = self.get_packed_port(PortNum::One);
+                arity.port_count()
+            }
+        }
     }
 
     #[inline]
-    pub fn get_right_port_bits(&self) -> u32 {
-        Self::RIGHT_PORT.get(self.0) as u32
+    pub fn set_port(&mut self, port_num: PortNum, port: TermPtr) {
+        assert!(self.is_packed_port(port_num));
+        self.set_packed_port(port_num, port)
     }
 
     #[inline]
-    fn set_right_port_bits(&mut self, port_bits: u32) {
-        self.0 = Self::RIGHT_PORT.set(self.0, port_bits as u64)
+    fn is_packed_port(&self, port_num: PortNum) -> bool {
+        match self.get_arity() {
+            SymbolArity::N(_) => false,
+            arity => (port_num as usize) < arity.port_count(),
+        }
     }
 
     #[inline]
@@ -225,25 +498,40 @@ impl<T: TermFamily> ArenaValue<CellPtr> for Cell<T> {
     }
 }
 
+#[cfg(not(feature = "large-heap"))]
 impl<T: TermFamily> Binary for Cell<T> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "{:014b}_{:025b}_{:025b}",
+            "{:014b}_{:02b}_{:024b}_{:024b}",
+            Self::SYMBOL.get(self.0),
+            Self::ARITY.get(self.0),
+            Self::LEFT_PORT.get(self.0),
+            Self::RIGHT_PORT.get(self.0)
+        )
+    }
+}
+
+#[cfg(feature = "large-heap")]
+impl<T: TermFamily> Binary for Cell<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:018b}_{:b}_{:b}",
             self.get_symbol_ptr().get_raw(),
-            self.get_left_port_bits(),
-            self.get_right_port_bits()
+            self.0[0],
+            self.0[1]
         )
     }
 }
 
 impl<T: TermFamily> Debug for Cell<T> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        let arity = self.get_symbol_ptr().get_arity();
-        let name = format!("Cell{}({:064b})", arity as u8, self.0);
+        let arity = self.get_arity();
+        let name = format!("Cell{}({:b})", arity.port_count(), self);
         let mut b = f.debug_struct(&name);
         b.field("symbol", &self.get_symbol_ptr());
-        match self.get_symbol_ptr().get_arity() {
+        match arity {
             SymbolArity::Zero => (),
             SymbolArity::One => {
                 b.field("port", &self.get_port(PortNum::Zero));
@@ -252,6 +540,9 @@ impl<T: TermFamily> Debug for Cell<T> {
                 b.field("left", &self.get_port(PortNum::Zero));
                 b.field("right", &self.get_port(PortNum::One));
             }
+            SymbolArity::N(_) => {
+                b.field("nary_ports", &self.get_nary_ports_ptr());
+            }
         }
         b.finish()
     }
@@ -259,7 +550,298 @@ impl<T: TermFamily> Debug for Cell<T> {
 
 pub type Cells<T> = RawArena<Cell<T>, CellPtr>;
 
+#[derive(PartialEq, Clone, Copy)]
+pub struct NaryPortsPtr(u32);
+impl NaryPortsPtr {
+    // what n-ary cells hold until the heap allocates their ports
+    const NONE: NaryPortsPtr = NaryPortsPtr(u32::MAX);
+}
+
+impl Ptr for NaryPortsPtr {
+    #[inline]
+    fn get_index(&self) -> usize {
+        self.0 as usize
+    }
+}
+
+impl Debug for NaryPortsPtr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "NaryPortsPtr({})", self.0)
+    }
+}
+
+/// The ports of an n-ary cell, in order.
+#[derive(Debug)]
+pub struct NaryPorts(pub(crate) Box<[TermPtr]>);
+
+impl ArenaValue<NaryPortsPtr> for NaryPorts {
+    fn to_ptr(&self, index: usize) -> NaryPortsPtr {
+        NaryPortsPtr(index as u32)
+    }
+}
+
+/// One entry per live n-ary cell, the heap creates it on its first `cellN`
+/// so nets without n-ary symbols never allocate it.
+pub type NaryPortsArena = RawArena<NaryPorts, NaryPortsPtr>;
+
+/// A cell copied out of the heap with its n-ary ports, so they can still be
+/// read once the heap frees or reuses the cell.
+#[derive(Debug, Clone)]
+pub struct CellCopy<T: TermFamily> {
+    cell: Cell<T>,
+    nary_ports: Option<Box<[TermPtr]>>,
+}
+impl<T: TermFamily> CellCopy<T> {
+    pub(crate) fn new(cell: Cell<T>, nary_ports: Option<Box<[TermPtr]>>) -> Self {
+        Self { cell, nary_ports }
+    }
+
+    #[inline]
+    pub fn get_symbol_ptr(&self) -> SymbolPtr {
+        self.cell.get_symbol_ptr()
+    }
+
+    pub fn symbol_name<'s>(&self, symbols: &'s SymbolBook) -> Option<&'s SymbolName> {
+        self.cell.symbol_name(symbols)
+    }
+
+    #[inline]
+    pub fn get_arity(&self) -> SymbolArity {
+        self.cell.get_arity()
+    }
+
+    #[inline]
+    pub fn get_port(&self, port_num: PortNum) -> TermPtr {
+        assert!(port_num.is_valid_port(self.get_arity()));
+        match &self.nary_ports {
+            Some(ports) => ports[port_num as usize],
+            None => self.cell.get_port(port_num),
+        }
+    }
+
+    #[inline]
+    pub fn get_left_port(&self) -> TermPtr {
+        self.get_port(PortNum::Zero)
+    }
+
+    #[inline]
+    pub fn get_right_port(&self) -> TermPtr {
+        self.get_port(PortNum::One)
+    }
+
+    /// All the ports, in order.
+    pub fn ports(&self) -> impl Iterator<Item = TermPtr> + '_ {
+        (0..self.get_arity().port_count()).map(|port_num| self.get_port(PortNum::from(port_num)))
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use proptest::prelude::*;
+
     use super::*;
+    use crate::inet::{
+        heap::Heap,
+        net::NetF,
+        var::{PVarPtr, VarPtr},
+    };
+
+    #[test]
+    fn test_cell_from_ports() {
+        let ports: Vec<TermPtr> = (0..4)
+            .map(|index| CellPtr::new(index, Polarity::Neg).into())
+            .collect();
+        for (arity, port_count) in [(SymbolArity::Zero, 0), (SymbolArity::Two, 2)] {
+            let symbol_ptr = SymbolPtr::new(1, arity, Polarity::Pos);
+            let cell = Cell::<NetF>::from_ports(symbol_ptr, ports[..port_count].iter().copied());
+            assert_eq!(cell.get_arity(), arity);
+            for (port_num, port) in ports[..port_count].iter().enumerate() {
+                let cell_ptr = cell.get_port(PortNum::from(port_num)).get_cell_ptr();
+                assert_eq!(cell_ptr, port.get_cell_ptr());
+            }
+        }
+
+        let symbol_ptr = SymbolPtr::new(1, SymbolArity::N(4), Polarity::Pos);
+        let nary = std::panic::catch_unwind(|| {
+            Cell::<NetF>::from_ports(symbol_ptr, ports.iter().copied())
+        });
+        assert!(nary.is_err());
+
+        let symbol_ptr = SymbolPtr::new(1, SymbolArity::One, Polarity::Pos);
+        let too_many = std::panic::catch_unwind(|| {
+            Cell::<NetF>::from_ports(symbol_ptr, ports[..2].iter().copied())
+        });
+        assert!(too_many.is_err());
+        let missing = std::panic::catch_unwind(|| Cell::<NetF>::from_ports(symbol_ptr, []));
+        assert!(missing.is_err());
+    }
+
+    #[test]
+    fn test_cell_symbol_name() {
+        let mut symbols = SymbolBook::new();
+        let symbol_ptr = symbols.ctr0(&"Z".into());
+        let cell = Cell::<NetF>::new0(symbol_ptr);
+        assert_eq!(cell.symbol_name(&symbols), Some(&SymbolName("Z")));
+        assert_eq!(cell.symbol_name(&SymbolBook::new()), None);
+    }
+
+    #[test]
+    fn test_cell_is_copy_and_packed() {
+        fn assert_copy<T: Copy>() {}
+        assert_copy::<Cell<NetF>>();
+        #[cfg(not(feature = "large-heap"))]
+        assert_eq!(std::mem::size_of::<Cell<NetF>>(), 8);
+        #[cfg(feature = "large-heap")]
+        assert_eq!(std::mem::size_of::<Cell<NetF>>(), 24);
+    }
+
+    #[test]
+    fn test_cell_new_n() {
+        let heap = Heap::<NetF>::new();
+        // created on the first n-ary cell
+        assert!(heap.nary_ports.get().is_none());
+        let symbol_ptr = SymbolPtr::new(1, SymbolArity::N(4), Polarity::Pos);
+        let ports: Vec<TermPtr> = (0..4)
+            .map(|index| CellPtr::new(index, Polarity::Neg).into())
+            .collect();
+        let cell_ptr = heap.cellN(symbol_ptr, &ports);
+        assert_eq!(heap.get_cell(cell_ptr).get_arity(), SymbolArity::N(4));
+        assert_eq!(heap.get_cell_arity(cell_ptr), SymbolArity::N(4));
+        for (port_num, port) in ports.iter().enumerate() {
+            let port_ptr = heap.get_cell_port(cell_ptr, PortNum::from(port_num));
+            assert_eq!(port_ptr.get_cell_ptr(), port.get_cell_ptr());
+        }
+
+        let mut heap = heap;
+        let (_, pos_pvar) = PVarPtr::wire(VarPtr::new(7));
+        heap.set_cell_port(cell_ptr, PortNum::Three, pos_pvar.into());
+        let port = heap.get_cell_port(cell_ptr, PortNum::Three);
+        assert_eq!(port.get_var_ptr().get_fvar_ptr().get_index(), 7);
+
+        heap.set_cell_port(cell_ptr, PortNum::Zero, pos_pvar.into());
+        let port = heap.get_cell_port(cell_ptr, PortNum::Zero);
+        assert_eq!(port.get_ptr(), TermPtr::from(pos_pvar).get_ptr());
+        let port = heap.get_cell_port(cell_ptr, PortNum::One);
+        assert_eq!(port.get_cell_ptr(), ports[1].get_cell_ptr());
+
+        // a copy keeps the ports once the cell is freed
+        let copy = heap.copy_cell(cell_ptr);
+        heap.free_cell(cell_ptr);
+        assert!(heap.nary_ports.get().unwrap().is_empty());
+        assert_eq!(copy.get_arity(), SymbolArity::N(4));
+        assert_eq!(copy.ports().count(), 4);
+        assert_eq!(copy.get_port(PortNum::Two).get_cell_ptr(), ports[2].get_cell_ptr());
+    }
+
+    #[test]
+    fn test_cell_reuse_n() {
+        let heap = Heap::<NetF>::new();
+        let nary_ptr = SymbolPtr::new(1, SymbolArity::N(3), Polarity::Pos);
+        let port: TermPtr = CellPtr::new(1, Polarity::Neg).into();
+        let cell_ptr = heap.cellN(nary_ptr, &[port, port, port]);
+        let zero_ptr = SymbolPtr::new(2, SymbolArity::Zero, Polarity::Pos);
+        let cell_ptr = heap.reuse_cell0(zero_ptr, cell_ptr);
+        assert!(heap.nary_ports.get().unwrap().is_empty());
+        assert_eq!(heap.get_cell_arity(cell_ptr), SymbolArity::Zero);
+    }
+
+    #[test]
+    fn test_cell_n_compact() {
+        let mut heap = Heap::<NetF>::new();
+        let zero_ptr = SymbolPtr::new(2, SymbolArity::Zero, Polarity::Pos);
+        let garbage = heap.cell0(zero_ptr);
+        let zero = heap.cell0(zero_ptr);
+        let nary_ptr = SymbolPtr::new(1, SymbolArity::N(3), Polarity::Pos);
+        let port: TermPtr = zero.into();
+        // leaves a hole in the n-ary ports too
+        let nary_garbage = heap.cellN(nary_ptr, &[port, port, port]);
+        let cell_ptr = heap.cellN(nary_ptr, &[port, port, port]);
+        heap.free_cell(garbage);
+        heap.free_cell(nary_garbage);
+
+        let translation = heap.compact();
+        let zero = translation.translate_cell(zero);
+        let cell_ptr = translation.translate_cell(cell_ptr);
+        assert_eq!(cell_ptr.get_index(), 1);
+        assert_eq!(heap.get_cell_port(cell_ptr, PortNum::Two).get_cell_ptr(), zero);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_cell_new_n_invalid_port() {
+        let heap = Heap::<NetF>::new();
+        let symbol_ptr = SymbolPtr::new(1, SymbolArity::N(3), Polarity::Pos);
+        let port: TermPtr = CellPtr::new(1, Polarity::Neg).into();
+        let cell_ptr = heap.cellN(symbol_ptr, &[port, port, port]);
+        heap.copy_cell(cell_ptr).get_port(PortNum::Three);
+    }
+
+    #[test]
+    fn test_cell_ports_as_array() {
+        let port0: TermPtr = CellPtr::new(1, Polarity::Neg).into();
+        let port1: TermPtr = CellPtr::new(2, Polarity::Neg).into();
+        let port2: TermPtr = CellPtr::new(3, Polarity::Neg).into();
+
+        let cell = Cell::<NetF>::new0(SymbolPtr::new(1, SymbolArity::Zero, Polarity::Pos));
+        assert_eq!(cell.ports_as_array().1, 0);
+
+        let cell = Cell::<NetF>::new1(SymbolPtr::new(1, SymbolArity::One, Polarity::Pos), port0);
+        let (ports, count) = cell.ports_as_array();
+        assert_eq!((ports[0].get_ptr(), count), (port0.get_ptr(), 1));
+
+        let symbol_ptr = SymbolPtr::new(1, SymbolArity::Two, Polarity::Pos);
+        let cell = Cell::<NetF>::new2(symbol_ptr, port0, port1);
+        let (ports, count) = cell.ports_as_array();
+        assert_eq!(ports.map(|port| port.get_ptr()), [port0.get_ptr(), port1.get_ptr()]);
+        assert_eq!(count, 2);
+
+        let symbol_ptr = SymbolPtr::new(1, SymbolArity::N(3), Polarity::Pos);
+        let heap = Heap::<NetF>::new();
+        let cell_ptr = heap.cellN(symbol_ptr, &[port0, port1, port2]);
+        let mut out = [TermPtr::NULL; 2];
+        assert_eq!(heap.get_cell(cell_ptr).write_ports_to_slice(&mut out), 0);
+    }
+
+    proptest! {
+        #[test]
+        fn test_cell_symbol_and_port_packing(
+            index in 1..=SymbolPtr::MAX_INDEX,
+            arity in 0..=SymbolArity::MAX as usize,
+            polarity in 0..2u32,
+            cell_index in 0..=CellPtr::MAX_INDEX,
+        ) {
+            let symbol_ptr = SymbolPtr::new(index, SymbolArity::from(arity), Polarity::from(polarity));
+            let port: TermPtr = CellPtr::new(cell_index, Polarity::Neg).into();
+            let cell = match symbol_ptr.get_arity() {
+                SymbolArity::N(_) => Cell::<NetF>::newN(symbol_ptr, NaryPortsPtr(cell_index as u32)),
+                _ => Cell::<NetF>::from_ports(symbol_ptr, vec![port; arity]),
+            };
+            prop_assert!(cell.get_symbol_ptr() == symbol_ptr);
+            let (packed, count) = cell.ports_as_array();
+            for packed_port in &packed[..count] {
+                prop_assert_eq!(packed_port.get_ptr(), port.get_ptr());
+            }
+            if symbol_ptr.is_nary() {
+                prop_assert!(cell.get_nary_ports_ptr() == NaryPortsPtr(cell_index as u32));
+            }
+        }
+    }
+
+    #[test]
+    fn test_cell_ptr_max_index() {
+        let max = CellPtr::MAX_INDEX;
+        let cell_ptr = CellPtr::new(max, Polarity::Neg);
+        assert_eq!(cell_ptr.get_index(), max);
+        assert_eq!(cell_ptr.get_polarity(), Polarity::Neg);
+    }
+
+    proptest! {
+        #[test]
+        fn test_cell_ptr_bit_packing(index in 0..1usize << 22, polarity in 0..2u32) {
+            let cell_ptr = CellPtr::new(index, Polarity::from(polarity));
+            prop_assert_eq!(cell_ptr.get_index(), index);
+            prop_assert_eq!(cell_ptr.get_polarity(), Polarity::from(polarity));
+            prop_assert!(CellPtr::from(cell_ptr.get_ptr()) == cell_ptr);
+        }
+    }
 }
@@ -1,23 +1,32 @@
 use std::{
+    collections::{HashMap, VecDeque},
     fmt::{Binary, Debug, Display, Formatter},
     marker::PhantomData,
 };
 
-use raw_arena::{arenaraw::RawArena, ArenaValue, Ptr};
+use raw_arena::{
+    arenaraw::{RawArena, VersionedPtr},
+    ArenaValue, Ptr,
+};
 
 use crate::inet::Polarity;
 
 use super::{
-    cell::CellPtr,
+    cell::{CellCopy, CellPtr},
     heap::Heap,
     net::NetF,
-    symbol::{SymbolBook, SymbolName},
-    term::{TermFamily, TermPtr},
-    var::PVarPtr,
-    BitSet32, BitSet64,
+    rule::{PortNum, RuleF, RulePort, RuleSet},
+    symbol::{SymbolArity, SymbolBook, SymbolName, SymbolPtr},
+    term::{TermFamily, TermKind, TermPtr},
+    var::{PVarPtr, PVarPtrBuffer, Var, VarPtr},
+    BitSet32,
 };
+#[cfg(not(feature = "large-heap"))]
+use super::BitSet64;
+#[cfg(feature = "large-heap")]
+use super::BitSet128;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum EquationKind {
     Redex = 0,
     Bind = 1,
@@ -89,6 +98,33 @@ impl From<u64> for EquationKind {
     }
 }
 
+/// The order equations of one wave are evaluated in: binds and connects come
+/// first so the vars read by the redexes are already set.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub enum EquationPriority {
+    Bind = 0,
+    Connect = 1,
+    Redex = 2,
+}
+
+impl EquationPriority {
+    pub const ALL: [EquationPriority; 3] = [
+        EquationPriority::Bind,
+        EquationPriority::Connect,
+        EquationPriority::Redex,
+    ];
+}
+
+impl From<EquationKind> for EquationPriority {
+    fn from(kind: EquationKind) -> Self {
+        match kind {
+            EquationKind::Redex => EquationPriority::Redex,
+            EquationKind::Bind => EquationPriority::Bind,
+            EquationKind::Connect => EquationPriority::Connect,
+        }
+    }
+}
+
 #[derive(PartialEq, Clone, Copy)]
 pub struct EquationPtr(u32);
 impl EquationPtr {
@@ -151,8 +187,16 @@ impl Debug for EquationPtr {
     }
 }
 
+/// The bits of an `Equation`: two cell or var ptrs and the kind. Ptrs of a
+/// `large-heap` are 32 bits wide, which takes more than 64 bits.
+#[cfg(not(feature = "large-heap"))]
+pub type EquationBits = u64;
+#[cfg(feature = "large-heap")]
+pub type EquationBits = u128;
+
 #[derive(Clone, Copy)]
-pub struct Equation<T: TermFamily>(pub u64, pub PhantomData<T>);
+pub struct Equation<T: TermFamily>(pub EquationBits, pub PhantomData<T>);
+#[cfg(not(feature = "large-heap"))]
 impl<T: TermFamily> Equation<T> {
     const RIGHT: BitSet64<31> = BitSet64 {
         mask: 0b00000000_00000000_00000000_00000000_01111111_11111111_11111111_11111111,
@@ -166,7 +210,23 @@ impl<T: TermFamily> Equation<T> {
         mask: 0b11,
         offset: 62,
     };
-
+}
+#[cfg(feature = "large-heap")]
+impl<T: TermFamily> Equation<T> {
+    const RIGHT: BitSet128<32> = BitSet128 {
+        mask: 0b11111111_11111111_11111111_11111111,
+        offset: 0,
+    };
+    const LEFT: BitSet128<32> = BitSet128 {
+        mask: 0b11111111_11111111_11111111_11111111,
+        offset: 32,
+    };
+    const KIND: BitSet128<2> = BitSet128 {
+        mask: 0b11,
+        offset: 64,
+    };
+}
+impl<T: TermFamily> Equation<T> {
     pub fn redex(left: CellPtr, right: CellPtr) -> Self {
         assert!(left.get_polarity() == Polarity::Pos && right.get_polarity() == Polarity::Neg);
         let mut eqn = Equation(0, PhantomData);
@@ -194,12 +254,17 @@ impl<T: TermFamily> Equation<T> {
 
     #[inline]
     pub fn get_kind(&self) -> EquationKind {
-        EquationKind::from(Self::KIND.get(self.0))
+        EquationKind::from(Self::KIND.get(self.0) as u8)
+    }
+
+    #[inline]
+    pub fn get_priority(&self) -> EquationPriority {
+        EquationPriority::from(self.get_kind())
     }
 
     #[inline]
     fn set_kind(&mut self, kind: EquationKind) {
-        self.0 = Self::KIND.set(self.0, kind as u64)
+        self.0 = Self::KIND.set(self.0, kind as EquationBits)
     }
 
     #[inline]
@@ -209,7 +274,7 @@ impl<T: TermFamily> Equation<T> {
 
     #[inline]
     fn set_left(&mut self, value: u32) {
-        self.0 = Self::LEFT.set(self.0, value as u64)
+        self.0 = Self::LEFT.set(self.0, value as EquationBits)
     }
 
     #[inline]
@@ -219,7 +284,7 @@ impl<T: TermFamily> Equation<T> {
 
     #[inline]
     fn set_right(&mut self, value: u32) {
-        self.0 = Self::RIGHT.set(self.0, value as u64)
+        self.0 = Self::RIGHT.set(self.0, value as EquationBits)
     }
 
     #[inline]
@@ -274,6 +339,206 @@ impl<T: TermFamily> Equation<T> {
             heap,
         }
     }
+
+    /// Formats the equation right away, for callers that cannot hold on to
+    /// the borrows of `display_equation`.
+    pub fn to_display_string(&self, symbols: &SymbolBook, heap: &Heap<T>) -> String {
+        self.display_equation(symbols, heap).to_string()
+    }
+}
+
+/// Why a rule equation cannot be instantiated.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InstantiateError {
+    /// Both sides resolved to cells of the same polarity.
+    ShortCircuit(CellPtr, CellPtr),
+}
+
+/// A var that an `EquationBuilder` left dangling, by index.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UsageError {
+    /// A head var that is never connected.
+    UnusedHeadVar(usize),
+    /// A var from `var()` and the number of times it is used, other than 2.
+    VarUseCount(usize, usize),
+}
+
+impl Display for UsageError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UsageError::UnusedHeadVar(index) => write!(f, "Head var {} is never connected", index),
+            UsageError::VarUseCount(index, count) => {
+                write!(f, "Var {} is used {} times, expected 2", index, count)
+            }
+        }
+    }
+}
+
+/// The cells of a rewritten redex, reused by `Equation::instantiate_into`
+/// before allocating new ones, and counts of what it reused and allocated.
+pub struct Instantiation<I: Iterator<Item = CellPtr>> {
+    reuse: I,
+    pub cell_reuses: usize,
+    pub cell_instantiations: usize,
+    pub var_instantiations: usize,
+}
+
+impl<I: Iterator<Item = CellPtr>> Instantiation<I> {
+    pub fn new(reuse: I) -> Self {
+        Self {
+            reuse,
+            cell_reuses: 0,
+            cell_instantiations: 0,
+            var_instantiations: 0,
+        }
+    }
+
+    /// The cells that were not reused, to be freed.
+    pub fn into_unused(self) -> I {
+        self.reuse
+    }
+}
+
+impl Equation<RuleF> {
+    /// Instantiates this rule equation for the redex of `ctr` and `fun` into
+    /// `heap`: rule cells become net cells, bound vars are taken from `bvars`
+    /// and free vars resolve to the ports of `ctr` and `fun`. Binds and
+    /// connects that resolve to two cells come back as redexes, and connects
+    /// that resolve to a cell come back as binds.
+    pub fn instantiate_into<I: Iterator<Item = CellPtr>>(
+        &self,
+        rules: &RuleSet,
+        heap: &Heap<NetF>,
+        bvars: &PVarPtrBuffer,
+        ctr: &CellCopy<NetF>,
+        fun: &CellCopy<NetF>,
+        instantiation: &mut Instantiation<I>,
+    ) -> Result<Equation<NetF>, InstantiateError> {
+        let mut instantiator = Instantiator {
+            rules,
+            heap,
+            bvars,
+            ctr,
+            fun,
+            instantiation,
+        };
+        let (left, right) = match self.get_kind() {
+            EquationKind::Redex => {
+                let ctr_ptr = instantiator.cell(self.get_redex_ctr());
+                let fun_ptr = instantiator.cell(self.get_redex_fun());
+                (ctr_ptr.into(), fun_ptr.into())
+            }
+            EquationKind::Bind => {
+                let cell_ptr = instantiator.cell(self.get_bind_cell());
+                let term_ptr = instantiator.var(self.get_bind_var());
+                (cell_ptr.into(), term_ptr)
+            }
+            EquationKind::Connect => {
+                let left = instantiator.var(self.get_connect_left());
+                let right = instantiator.var(self.get_connect_right());
+                (left, right)
+            }
+        };
+        match (left.get_kind(), right.get_kind()) {
+            (TermKind::Cell, TermKind::Cell) => {
+                let (left, right) = (left.get_cell_ptr(), right.get_cell_ptr());
+                match (left.get_polarity(), right.get_polarity()) {
+                    (Polarity::Pos, Polarity::Neg) => Ok(Equation::redex(left, right)),
+                    (Polarity::Neg, Polarity::Pos) => Ok(Equation::redex(right, left)),
+                    _ => Err(InstantiateError::ShortCircuit(left, right)),
+                }
+            }
+            (TermKind::Cell, TermKind::Var) => {
+                Ok(Equation::bind(right.get_var_ptr(), left.get_cell_ptr()))
+            }
+            (TermKind::Var, TermKind::Cell) => {
+                Ok(Equation::bind(left.get_var_ptr(), right.get_cell_ptr()))
+            }
+            (TermKind::Var, TermKind::Var) => {
+                Ok(Equation::connect(left.get_var_ptr(), right.get_var_ptr()))
+            }
+        }
+    }
+}
+
+// the borrows of one instantiate_into call
+struct Instantiator<'a, 'r, I: Iterator<Item = CellPtr>> {
+    rules: &'a RuleSet<'r>,
+    heap: &'a Heap<NetF>,
+    bvars: &'a PVarPtrBuffer,
+    ctr: &'a CellCopy<NetF>,
+    fun: &'a CellCopy<NetF>,
+    instantiation: &'a mut Instantiation<I>,
+}
+
+impl<'a, 'r, I: Iterator<Item = CellPtr>> Instantiator<'a, 'r, I> {
+    // ports are instantiated before the cell, which may reuse a redex cell
+    fn cell(&mut self, rule_cell_ptr: CellPtr) -> CellPtr {
+        let rule_cell = self.rules.heap.copy_cell(rule_cell_ptr);
+        let symbol_ptr = rule_cell.get_symbol_ptr();
+        let heap = self.heap;
+        match rule_cell.get_arity() {
+            SymbolArity::Zero => match self.reuse() {
+                Some(cell_ptr) => heap.reuse_cell0(symbol_ptr, cell_ptr),
+                None => heap.cell0(symbol_ptr),
+            },
+            SymbolArity::One => {
+                let port_ptr = self.port(rule_cell.get_left_port());
+                match self.reuse() {
+                    Some(cell_ptr) => heap.reuse_cell1(symbol_ptr, port_ptr, cell_ptr),
+                    None => heap.cell1(symbol_ptr, port_ptr),
+                }
+            }
+            SymbolArity::Two => {
+                let left_port_ptr = self.port(rule_cell.get_left_port());
+                let right_port_ptr = self.port(rule_cell.get_right_port());
+                match self.reuse() {
+                    Some(cell_ptr) => {
+                        heap.reuse_cell2(symbol_ptr, left_port_ptr, right_port_ptr, cell_ptr)
+                    }
+                    None => heap.cell2(symbol_ptr, left_port_ptr, right_port_ptr),
+                }
+            }
+            SymbolArity::N(n) => {
+                let port_ptrs: Vec<TermPtr> = (0..n as usize)
+                    .map(|port_num| self.port(rule_cell.get_port(PortNum::from(port_num))))
+                    .collect();
+                match self.reuse() {
+                    Some(cell_ptr) => heap.reuse_cellN(symbol_ptr, &port_ptrs, cell_ptr),
+                    None => heap.cellN(symbol_ptr, &port_ptrs),
+                }
+            }
+        }
+    }
+
+    // the next redex cell to reuse, counting reuses and new cells
+    fn reuse(&mut self) -> Option<CellPtr> {
+        let reused = self.instantiation.reuse.next();
+        match reused {
+            Some(_) => self.instantiation.cell_reuses += 1,
+            None => self.instantiation.cell_instantiations += 1,
+        }
+        reused
+    }
+
+    fn port(&mut self, rule_port_ptr: TermPtr) -> TermPtr {
+        match rule_port_ptr.get_kind() {
+            TermKind::Cell => self.cell(rule_port_ptr.get_cell_ptr()).into(),
+            TermKind::Var => self.var(rule_port_ptr.get_var_ptr()),
+        }
+    }
+
+    fn var(&mut self, rule_var_ptr: PVarPtr) -> TermPtr {
+        self.instantiation.var_instantiations += 1;
+        match self.rules.heap.get_var(rule_var_ptr) {
+            Var::Bound(bvar_id) => match rule_var_ptr.get_polarity() {
+                Polarity::Pos => self.bvars.get_pos_var(bvar_id.get_id()).into(),
+                Polarity::Neg => self.bvars.get_neg_var(bvar_id.get_id()).into(),
+            },
+            Var::Free(RulePort::Ctr(port_num)) => self.ctr.get_port(*port_num),
+            Var::Free(RulePort::Fun(port_num)) => self.fun.get_port(*port_num),
+        }
+    }
 }
 
 impl<T: TermFamily> ArenaValue<EquationPtr> for Equation<T> {
@@ -282,8 +547,8 @@ impl<T: TermFamily> ArenaValue<EquationPtr> for Equation<T> {
     }
 }
 
-impl<T: TermFamily> From<u64> for Equation<T> {
-    fn from(value: u64) -> Self {
+impl<T: TermFamily> From<EquationBits> for Equation<T> {
+    fn from(value: EquationBits) -> Self {
         Equation(value, PhantomData)
     }
 }
@@ -373,7 +638,7 @@ pub type Equations<T> = RawArena<Equation<T>, EquationPtr>;
 
 pub struct EquationsDisplay<'a, T: TermFamily> {
     pub symbols: &'a SymbolBook,
-    pub body: &'a Vec<Equation<T>>,
+    pub body: &'a VecDeque<Equation<T>>,
     pub heap: &'a Heap<T>,
 }
 impl<'a, T: TermFamily> EquationsDisplay<'a, T> {
@@ -396,32 +661,44 @@ impl<'a, T: TermFamily> Display for EquationsDisplay<'a, T> {
 pub struct EquationBuilder<'a, F: TermFamily = NetF> {
     symbols: &'a SymbolBook,
     head: &'a mut Vec<PVarPtr>,
-    equations: &'a mut Vec<Equation<F>>,
+    equations: &'a mut VecDeque<Equation<F>>,
     heap: &'a mut Heap<F>,
+    var_names: &'a mut Vec<(&'static str, VersionedPtr<VarPtr>)>,
+    // what this builder added, checked by assert_fully_used
+    first_equation: usize,
+    cells: Vec<CellPtr>,
+    fvars: Vec<VarPtr>,
+    bvars: Vec<VarPtr>,
 }
 impl<'a, F: TermFamily> EquationBuilder<'a, F> {
     pub(crate) fn new(
         symbols: &'a SymbolBook,
         head: &'a mut Vec<PVarPtr>,
-        equations: &'a mut Vec<Equation<F>>,
+        equations: &'a mut VecDeque<Equation<F>>,
         heap: &'a mut Heap<F>,
+        var_names: &'a mut Vec<(&'static str, VersionedPtr<VarPtr>)>,
     ) -> Self {
         Self {
             symbols,
             head,
+            first_equation: equations.len(),
             equations,
             heap,
+            var_names,
+            cells: Vec::new(),
+            fvars: Vec::new(),
+            bvars: Vec::new(),
         }
     }
 
     pub fn redex(&mut self, ctr_ptr: CellPtr, fun_ptr: CellPtr) {
         assert!(ctr_ptr.get_polarity() == Polarity::Pos);
         assert!(fun_ptr.get_polarity() == Polarity::Neg);
-        self.equations.push(Equation::redex(ctr_ptr, fun_ptr))
+        self.equations.push_back(Equation::redex(ctr_ptr, fun_ptr))
     }
 
     pub fn bind(&mut self, var_ptr: PVarPtr, cell_ptr: CellPtr) {
-        self.equations.push(Equation::bind(var_ptr, cell_ptr))
+        self.equations.push_back(Equation::bind(var_ptr, cell_ptr))
     }
 
     pub fn connect(&mut self, left_ptr: PVarPtr, right_ptr: PVarPtr) {
@@ -429,28 +706,59 @@ impl<'a, F: TermFamily> EquationBuilder<'a, F> {
             left_ptr.get_polarity() != right_ptr.get_polarity(),
             "Cannot connect vars with same polarity"
         );
-        self.equations.push(Equation::connect(left_ptr, right_ptr))
+        self.equations.push_back(Equation::connect(left_ptr, right_ptr))
     }
 
     // ----------------
 
+    /// The symbols the cells are built from.
+    pub fn symbols(&self) -> &SymbolBook {
+        self.symbols
+    }
+
+    pub fn get_symbol_ptr(&self, name: &SymbolName) -> SymbolPtr {
+        self.symbols.get_by_name(name).unwrap() // TODO better error handling
+    }
+
     pub fn cell0(&mut self, name: &SymbolName) -> CellPtr {
-        let symbol_ptr = self.symbols.get_by_name(name).unwrap(); // TODO better error handling
-        self.heap.cell0(symbol_ptr)
+        self.cell0_ptr(self.get_symbol_ptr(name))
     }
 
     pub fn cell1(&mut self, name: &SymbolName, left_port: TermPtr) -> CellPtr {
-        let symbol_ptr = self.symbols.get_by_name(name).unwrap(); // TODO better error handling
+        self.cell1_ptr(self.get_symbol_ptr(name), left_port)
+    }
+
+    pub fn cell2(&mut self, name: &SymbolName, left_port: TermPtr, right_port: TermPtr) -> CellPtr {
+        self.cell2_ptr(self.get_symbol_ptr(name), left_port, right_port)
+    }
+
+    #[allow(non_snake_case)]
+    pub fn cellN(&mut self, name: &SymbolName, ports: &[TermPtr]) -> CellPtr {
+        self.cellN_ptr(self.get_symbol_ptr(name), ports)
+    }
+
+    // The `_ptr` variants take a symbol already looked up, for loops building
+    // many cells of the same symbol.
+
+    pub fn cell0_ptr(&mut self, symbol_ptr: SymbolPtr) -> CellPtr {
+        self.add_cell(self.heap.cell0(symbol_ptr))
+    }
+
+    pub fn cell1_ptr(&mut self, symbol_ptr: SymbolPtr, left_port: TermPtr) -> CellPtr {
         let symbol = self.symbols.get(symbol_ptr);
         // check left polarity
         assert!(left_port
             .get_polarity()
             .is_opposite(symbol.get_left_polarity()));
-        self.heap.cell1(symbol_ptr, left_port)
+        self.add_cell(self.heap.cell1(symbol_ptr, left_port))
     }
 
-    pub fn cell2(&mut self, name: &SymbolName, left_port: TermPtr, right_port: TermPtr) -> CellPtr {
-        let symbol_ptr = self.symbols.get_by_name(name).unwrap(); // TODO better error handling
+    pub fn cell2_ptr(
+        &mut self,
+        symbol_ptr: SymbolPtr,
+        left_port: TermPtr,
+        right_port: TermPtr,
+    ) -> CellPtr {
         let symbol = self.symbols.get(symbol_ptr);
         // check left polarity
         assert!(left_port
@@ -460,40 +768,313 @@ impl<'a, F: TermFamily> EquationBuilder<'a, F> {
         assert!(right_port
             .get_polarity()
             .is_opposite(symbol.get_right_polarity()));
-        self.heap.cell2(symbol_ptr, left_port, right_port)
+        self.add_cell(self.heap.cell2(symbol_ptr, left_port, right_port))
+    }
+
+    #[allow(non_snake_case)]
+    pub fn cellN_ptr(&mut self, symbol_ptr: SymbolPtr, ports: &[TermPtr]) -> CellPtr {
+        let symbol = self.symbols.get(symbol_ptr);
+        assert!(symbol.get_arity().port_count() == ports.len());
+        // check port polarities
+        for (port_num, port) in ports.iter().enumerate() {
+            assert!(port
+                .get_polarity()
+                .is_opposite(symbol.get_port_polarity(PortNum::from(port_num))));
+        }
+        self.add_cell(self.heap.cellN(symbol_ptr, ports))
+    }
+
+    fn add_cell(&mut self, cell_ptr: CellPtr) -> CellPtr {
+        self.cells.push(cell_ptr);
+        cell_ptr
     }
 
     // -------------------
 
     pub fn input(&mut self) -> PVarPtr {
-        let fvar_ptr = self.heap.fvar(F::FreeStore::default());
-        let (neg_pvar, pos_pvar) = PVarPtr::wire(fvar_ptr);
+        let (neg_pvar, pos_pvar) = self.heap.alloc_fvar_pair(F::FreeStore::default());
+        self.fvars.push(neg_pvar.get_fvar_ptr());
         self.head.push(neg_pvar);
         pos_pvar // input fvars need to be "consumed" by the net (input from an inside-pov)
     }
 
     pub fn output(&mut self) -> PVarPtr {
-        let fvar_ptr = self.heap.fvar(F::FreeStore::default());
-        let (neg_pvar, pos_pvar) = PVarPtr::wire(fvar_ptr);
+        let (neg_pvar, pos_pvar) = self.heap.alloc_fvar_pair(F::FreeStore::default());
+        self.fvars.push(neg_pvar.get_fvar_ptr());
         self.head.push(pos_pvar);
         neg_pvar // output fvars need to be "produced" by the net (output from an inside-pov)
     }
 
     pub fn var(&mut self) -> (PVarPtr, PVarPtr) {
-        let bvar_ptr = self.heap.bvar(F::BoundStore::default());
-        PVarPtr::wire(bvar_ptr)
+        let (neg_pvar, pos_pvar) = self.heap.alloc_bvar_pair(F::BoundStore::default());
+        self.bvars.push(neg_pvar.get_fvar_ptr());
+        (neg_pvar, pos_pvar)
+    }
+
+    /// Like `var`, naming the var for traces and `Net::var_name`.
+    pub fn var_named(&mut self, name: &'static str) -> (PVarPtr, PVarPtr) {
+        let (neg_pvar, pos_pvar) = self.var();
+        let var_ptr = neg_pvar.get_fvar_ptr();
+        tracing::trace!("Var {} = VARS[{}]", name, var_ptr.get_index());
+        self.var_names.push((name, self.heap.vars.versioned(var_ptr)));
+        (neg_pvar, pos_pvar)
     }
 
     // -------------------
 
-    pub(crate) fn build(self) -> Self {
-        self
+    /// Checks that every head var added by this builder is used and every
+    /// var from `var()` is used exactly twice, once per end. Uses are counted
+    /// in the binds, connects and cell ports added by this builder.
+    pub fn check_fully_used(&self) -> Result<(), UsageError> {
+        let mut uses: HashMap<usize, usize> = HashMap::new();
+        let mut count = |var_ptr: PVarPtr| {
+            *uses.entry(var_ptr.get_fvar_ptr().get_index()).or_default() += 1
+        };
+        for eqn in self.equations.range(self.first_equation..) {
+            match eqn.get_kind() {
+                EquationKind::Redex => (),
+                EquationKind::Bind => count(eqn.get_bind_var()),
+                EquationKind::Connect => {
+                    count(eqn.get_connect_left());
+                    count(eqn.get_connect_right());
+                }
+            }
+        }
+        for cell_ptr in self.cells.iter() {
+            let cell = self.heap.copy_cell(*cell_ptr);
+            for port_num in (0..cell.get_arity().port_count()).map(PortNum::from) {
+                let port = cell.get_port(port_num);
+                if port.is_var() {
+                    count(port.get_var_ptr());
+                }
+            }
+        }
+
+        for fvar_ptr in self.fvars.iter() {
+            if !uses.contains_key(&fvar_ptr.get_index()) {
+                return Err(UsageError::UnusedHeadVar(fvar_ptr.get_index()));
+            }
+        }
+        for bvar_ptr in self.bvars.iter() {
+            let count = uses.get(&bvar_ptr.get_index()).copied().unwrap_or(0);
+            if count != 2 {
+                return Err(UsageError::VarUseCount(bvar_ptr.get_index(), count));
+            }
+        }
+        Ok(())
+    }
+
+    /// Panics with the error of `check_fully_used`, if any.
+    pub fn assert_fully_used(&self) {
+        if let Err(error) = self.check_fully_used() {
+            panic!("{}", error);
+        }
+    }
+
+    /// Ends the builder, checking it with `check_fully_used` in debug builds.
+    pub(crate) fn build(self) -> Result<Self, UsageError> {
+        if cfg!(debug_assertions) {
+            self.check_fully_used()?;
+        }
+        Ok(self)
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use proptest::prelude::*;
+
     use super::*;
+    use crate::inet::net::NetF;
+
+    #[test]
+    fn test_equation_priority() {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+        symbols.declare_arith_symbols();
+
+        let mut net = crate::inet::net::Net::new(&symbols);
+        net.equations(|b| {
+            let result = b.output();
+            let one = b.one();
+            let adder = b.adder(result.into(), one.into());
+            let one = b.one();
+            b.add(one, adder);
+            let (x_in, x_out) = b.var();
+            let zero = b.zero();
+            b.bind(x_in, zero);
+            let (y_in, y_out) = b.var();
+            b.connect(x_out, y_in);
+            let zero = b.zero();
+            b.bind(y_out, zero);
+        });
+
+        let mut body = Vec::from(net.body.clone());
+        body.sort_by_key(|eqn| eqn.get_priority());
+        let kinds: Vec<_> = body.iter().map(|eqn| eqn.get_kind()).collect();
+        assert_eq!(
+            kinds,
+            [
+                EquationKind::Bind,
+                EquationKind::Bind,
+                EquationKind::Connect,
+                EquationKind::Redex
+            ]
+        );
+    }
+
+    #[test]
+    fn test_equation_builder_assert_fully_used() {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+
+        let mut net = crate::inet::net::Net::new(&symbols);
+        net.equations(|b| {
+            let result = b.output();
+            let (x_in, x_out) = b.var();
+            let zero = b.zero();
+            b.bind(x_in, zero);
+            let one = b.cell1(&"S".into(), x_out.into());
+            b.bind(result, one);
+            b.assert_fully_used();
+        });
+    }
+
+    #[test]
+    fn test_equation_builder_cell_ptr() {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+        let rules = RuleSet::new(&symbols);
+        let runtime = crate::inet::runtime::Runtime::new(&rules, false);
+
+        let mut net = crate::inet::net::Net::new(&symbols);
+        net.equations(|b| {
+            let result = b.output();
+            let s = b.get_symbol_ptr(&"S".into());
+            let zero = b.zero();
+            let one = b.cell1_ptr(s, zero.into());
+            b.bind(result, one);
+        });
+        let net = runtime.eval(net).unwrap();
+        assert_eq!(net.read_nat(net.single_output().unwrap()), Some(1));
+
+        let mut net = crate::inet::net::Net::new(&symbols);
+        net.equations(|b| {
+            let result = b.output();
+            let three = b.n(3);
+            b.bind(result, three);
+        });
+        let net = runtime.eval(net).unwrap();
+        assert_eq!(net.read_nat(net.single_output().unwrap()), Some(3));
+    }
+
+    #[test]
+    #[should_panic(expected = "is used 1 times, expected 2")]
+    fn test_equation_builder_dangling_var() {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+
+        let mut net = crate::inet::net::Net::new(&symbols);
+        net.equations(|b| {
+            let result = b.output();
+            let (_, x_out) = b.var();
+            let one = b.cell1(&"S".into(), x_out.into());
+            b.bind(result, one);
+            b.assert_fully_used();
+        });
+    }
+
+    #[test]
+    fn test_equation_builder_check_fully_used() {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+
+        let mut net = crate::inet::net::Net::new(&symbols);
+        net.equations(|b| {
+            let result = b.output();
+            let (x_in, x_out) = b.var();
+            let one = b.cell1(&"S".into(), x_out.into());
+            b.bind(result, one);
+            let index = x_in.get_fvar_ptr().get_index();
+            assert_eq!(b.check_fully_used(), Err(UsageError::VarUseCount(index, 1)));
+
+            let zero = b.zero();
+            b.bind(x_in, zero);
+            assert_eq!(b.check_fully_used(), Ok(()));
+        });
+    }
+
+    #[test]
+    fn test_equation_to_display_string() {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+
+        let mut net = crate::inet::net::Net::new(&symbols);
+        net.equations(|b| {
+            let result = b.output();
+            let zero = b.zero();
+            b.bind(result, zero);
+        });
+
+        let eqn = net.body[0];
+        let display = eqn.to_display_string(&symbols, &net.heap);
+        assert_eq!(display, eqn.display_equation(&symbols, &net.heap).to_string());
+        assert!(display.contains('Z'));
+    }
+
+    #[test]
+    fn test_equation_instantiate_into() {
+        use crate::inet::net::NetVar;
+
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+        symbols.declare_arith_symbols();
+        let mut rules = RuleSet::new(&symbols);
+        rules.arith_rules();
+
+        // (S Z) ⋈ (add r y)  ⟶  r ← (S x), Z ⋈ (add x y)
+        let s = symbols.get_by_name(&"S".into()).unwrap();
+        let z = symbols.get_by_name(&"Z".into()).unwrap();
+        let add = symbols.get_by_name(&"add".into()).unwrap();
+        let heap: Heap<NetF> = Heap::new();
+        let (r, _) = heap.alloc_fvar_pair(NetVar::default());
+        let (y, _) = heap.alloc_fvar_pair(NetVar::default());
+        let z_ptr = heap.cell0(z);
+        let ctr_ptr = heap.cell1(s, z_ptr.into());
+        let fun_ptr = heap.cell2(add, r.into(), y.into());
+        let ctr = heap.copy_cell(ctr_ptr);
+        let fun = heap.copy_cell(fun_ptr);
+
+        let rule = rules.get_rule(rules.get_by_symbols(s, add).unwrap());
+        let mut bvars = PVarPtrBuffer::new(rule.get_bvar_count());
+        for i in 0..rule.get_bvar_count() {
+            bvars.set(i, heap.bvar(NetVar::default()));
+        }
+        let reuse = [ctr_ptr, fun_ptr].into_iter();
+        let mut instantiation = Instantiation::new(reuse);
+        let eqns: Vec<Equation<NetF>> = rule
+            .body()
+            .map(|eqn_ptr| {
+                let eqn = rules.get_equation(*eqn_ptr);
+                eqn.instantiate_into(&rules, &heap, &bvars, &ctr, &fun, &mut instantiation)
+                    .unwrap()
+            })
+            .collect();
+
+        assert_eq!(eqns.len(), 2);
+        assert_eq!(eqns[0].get_kind(), EquationKind::Bind);
+        assert!(eqns[0].get_bind_var() == r);
+        assert_eq!(eqns[0].get_bind_cell(), ctr_ptr);
+        assert_eq!(heap.get_cell(ctr_ptr).get_symbol_ptr(), s);
+        assert_eq!(eqns[1].get_kind(), EquationKind::Redex);
+        assert_eq!(eqns[1].get_redex_ctr(), z_ptr);
+        assert_eq!(eqns[1].get_redex_fun(), fun_ptr);
+        assert_eq!(heap.get_cell(fun_ptr).get_right_port().get_ptr(), TermPtr::from(y).get_ptr());
+        assert_eq!(instantiation.cell_reuses, 2);
+        assert_eq!(instantiation.cell_instantiations, 0);
+        assert_eq!(instantiation.var_instantiations, 5);
+        assert_eq!(instantiation.into_unused().count(), 0);
+    }
 
     #[test]
     fn test_equation_kind_from_u8() {
@@ -558,4 +1139,37 @@ mod tests {
             "EquationPtr { kind: Redex, index: 100 }"
         );
     }
+
+    fn packed(kind: EquationKind, left: u32, right: u32) -> Equation<NetF> {
+        let mut eqn = Equation(0, PhantomData);
+        eqn.set_kind(kind);
+        eqn.set_left(left);
+        eqn.set_right(right);
+        eqn
+    }
+
+    #[test]
+    fn test_equation_max_left_and_right() {
+        let max = (1 << 31) - 1;
+        for kind in 0..3u64 {
+            let eqn = packed(EquationKind::from(kind), max, max);
+            assert_eq!(eqn.get_kind(), EquationKind::from(kind));
+            assert_eq!(eqn.get_left(), max);
+            assert_eq!(eqn.get_right(), max);
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn test_equation_bit_packing(
+            kind in 0..3u64,
+            left in 0..1u32 << 31,
+            right in 0..1u32 << 31,
+        ) {
+            let eqn = packed(EquationKind::from(kind), left, right);
+            prop_assert_eq!(eqn.get_kind(), EquationKind::from(kind));
+            prop_assert_eq!(eqn.get_left(), left);
+            prop_assert_eq!(eqn.get_right(), right);
+        }
+    }
 }
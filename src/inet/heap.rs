@@ -1,27 +1,41 @@
-use std::fmt::Display;
+use std::{fmt::Display, sync::OnceLock};
 
+use raw_arena::{
+    arenaraw::{ArenaAllocator, ArenaPtrIter, IndexTranslation, ResizeError},
+    Ptr,
+};
 use tracing::debug;
 
 use super::{
-    cell::{Cell, CellPtr, Cells},
+    cell::{Cell, CellCopy, CellPtr, Cells, NaryPorts, NaryPortsArena, NaryPortsPtr},
+    rule::PortNum,
     symbol::{SymbolArity, SymbolBook, SymbolPtr},
     term::{TermFamily, TermKind, TermPtr},
     var::{PVarPtr, Var, VarPtr, Vars},
+    Polarity,
 };
 
 #[derive(Debug)]
 pub struct Heap<T: TermFamily> {
     pub(crate) cells: Cells<T>,
+    pub(crate) nary_ports: OnceLock<NaryPortsArena>,
     pub(crate) vars: Vars<T>,
 }
 
 unsafe impl<F: TermFamily> Send for Heap<F> {}
 unsafe impl<F: TermFamily> Sync for Heap<F> {}
 
+impl<T: TermFamily> Default for Heap<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<T: TermFamily> Heap<T> {
     pub fn new() -> Self {
         Self {
             cells: Cells::new(),
+            nary_ports: OnceLock::new(),
             vars: Vars::new(),
         }
     }
@@ -29,13 +43,94 @@ impl<T: TermFamily> Heap<T> {
     pub fn with_capacity(cells_capacity: usize, vars_capacity: usize) -> Heap<T> {
         Self {
             cells: Cells::with_capacity(cells_capacity),
+            nary_ports: OnceLock::new(),
             vars: Vars::with_capacity(vars_capacity),
         }
     }
 
+    /// A heap whose cell and var arenas each take a clone of `allocator`, so
+    /// allocators with state should share it between clones. The n-ary ports
+    /// arena, created on the first `cellN`, uses the global allocator.
+    pub fn with_custom_allocator<A: ArenaAllocator + Clone + 'static>(
+        cells_capacity: usize,
+        vars_capacity: usize,
+        allocator: A,
+    ) -> Heap<T> {
+        Self {
+            cells: Cells::with_allocator(cells_capacity, allocator.clone()),
+            nary_ports: OnceLock::new(),
+            vars: Vars::with_allocator(vars_capacity, allocator),
+        }
+    }
+
+    pub fn resize_cells(&mut self, new_capacity: usize) -> Result<(), ResizeError> {
+        self.cells.resize(new_capacity)?;
+        match self.nary_ports.get_mut() {
+            Some(nary_ports) => nary_ports.resize(new_capacity),
+            None => Ok(()),
+        }
+    }
+
+    pub fn resize_vars(&mut self, new_capacity: usize) -> Result<(), ResizeError> {
+        self.vars.resize(new_capacity)
+    }
+
+    /// Moves the cells and vars of `other` into this heap. Ports and var
+    /// stores that point into `other` are translated to the new ptrs, ptrs
+    /// kept outside the heap, like equations, need `HeapTranslation`.
+    pub fn merge_heap(&mut self, other: Heap<T>) -> HeapTranslation {
+        let translation = HeapTranslation {
+            cells: self.cells.extend_from_arena(other.cells),
+            vars: self.vars.extend_from_arena(other.vars),
+        };
+        let nary_translation = other.nary_ports.into_inner().map(|other_nary_ports| {
+            self.nary_ports();
+            let nary_ports = self.nary_ports.get_mut().unwrap();
+            nary_ports.extend_from_arena(other_nary_ports)
+        });
+        self.translate_moved(&translation, nary_translation.as_ref());
+        translation
+    }
+
+    /// Moves the live cells and vars to the front of their arenas, keeping
+    /// their order. Ports and var stores are translated to the new ptrs,
+    /// ptrs kept outside the heap need `Net::apply_translations`.
+    pub fn compact(&mut self) -> HeapTranslation {
+        let translation = HeapTranslation {
+            cells: self.cells.compact(),
+            vars: self.vars.compact(),
+        };
+        let nary_translation = self.nary_ports.get_mut().map(|nary_ports| nary_ports.compact());
+        self.translate_moved(&translation, nary_translation.as_ref());
+        translation
+    }
+
+    fn translate_moved(
+        &mut self,
+        translation: &HeapTranslation,
+        nary_translation: Option<&IndexTranslation<NaryPortsPtr>>,
+    ) {
+        for (_, cell_ptr) in translation.cells.iter() {
+            let cell = self.cells.get_mut(cell_ptr).unwrap();
+            if cell.get_symbol_ptr().is_nary() {
+                let nary_ports_ptr = nary_translation
+                    .and_then(|nary_translation| nary_translation.get(cell.get_nary_ports_ptr().get_index()))
+                    .expect("N-ary ports were not moved");
+                cell.set_nary_ports_ptr(nary_ports_ptr);
+            }
+            for port_num in (0..self.get_cell_arity(cell_ptr).port_count()).map(PortNum::from) {
+                let port = translation.translate_term(self.get_cell_port(cell_ptr, port_num));
+                self.set_cell_port(cell_ptr, port_num, port);
+            }
+        }
+        for (_, var_ptr) in translation.vars.iter() {
+            T::translate_store(self.vars.get_mut(var_ptr).unwrap(), translation);
+        }
+    }
+
     pub fn cell0(&self, symbol_ptr: SymbolPtr) -> CellPtr {
         let cell0 = Cell::new0(symbol_ptr);
-        let ptr = self.cells.alloc(cell0.clone());
+        let ptr = self.cells.alloc(cell0);
         tracing::trace!(
             "[Len={}] Alloc CELLS[{}] = {:?}",
             self.cells.len(),
@@ -53,12 +148,13 @@ impl<T: TermFamily> Heap<T> {
             ptr.get_index(),
             cell0
         );
-        self.cells.alloc_with_ptr(cell0.clone(), ptr)
+        self.free_nary_ports(ptr);
+        self.cells.alloc_with_ptr(cell0, ptr)
     }
 
     pub fn cell1(&self, symbol_ptr: SymbolPtr, left_port: TermPtr) -> CellPtr {
         let cell1 = Cell::new1(symbol_ptr, left_port);
-        let ptr = self.cells.alloc(cell1.clone());
+        let ptr = self.cells.alloc(cell1);
         tracing::trace!(
             "[Len={}] Alloc CELLS[{}] = {:?}",
             self.cells.len(),
@@ -76,12 +172,13 @@ impl<T: TermFamily> Heap<T> {
             ptr.get_index(),
             cell1
         );
+        self.free_nary_ports(ptr);
         self.cells.alloc_with_ptr(cell1, ptr)
     }
 
     pub fn cell2(&self, symbol_ptr: SymbolPtr, left_port: TermPtr, right_port: TermPtr) -> CellPtr {
         let cell2 = Cell::new2(symbol_ptr, left_port, right_port);
-        let ptr = self.cells.alloc(cell2.clone());
+        let ptr = self.cells.alloc(cell2);
         tracing::trace!(
             "[Len={}] Alloc CELLS[{}] = {:?}",
             self.cells.len(),
@@ -105,23 +202,151 @@ impl<T: TermFamily> Heap<T> {
             ptr.get_index(),
             &cell2
         );
+        self.free_nary_ports(ptr);
         self.cells.alloc_with_ptr(cell2, ptr)
     }
 
+    /// Keeps the ports in the heap's `NaryPortsArena`, creating it on the
+    /// first n-ary cell.
+    #[allow(non_snake_case)]
+    pub fn cellN(&self, symbol_ptr: SymbolPtr, ports: &[TermPtr]) -> CellPtr {
+        let cell = Cell::newN(symbol_ptr, self.alloc_nary_ports(symbol_ptr, ports));
+        let ptr = self.cells.alloc(cell);
+        tracing::trace!(
+            "[Len={}] Alloc CELLS[{}] = {:?}",
+            self.cells.len(),
+            ptr.get_index(),
+            cell
+        );
+        ptr
+    }
+
+    #[allow(non_snake_case)]
+    pub fn reuse_cellN(&self, symbol_ptr: SymbolPtr, ports: &[TermPtr], ptr: CellPtr) -> CellPtr {
+        self.free_nary_ports(ptr);
+        let cell = Cell::newN(symbol_ptr, self.alloc_nary_ports(symbol_ptr, ports));
+        tracing::trace!(
+            "[Len={}] Reuse CELLS[{}] = {:?}",
+            self.cells.len(),
+            ptr.get_index(),
+            &cell
+        );
+        self.cells.alloc_with_ptr(cell, ptr)
+    }
+
+    fn alloc_nary_ports(&self, symbol_ptr: SymbolPtr, ports: &[TermPtr]) -> NaryPortsPtr {
+        assert_eq!(
+            ports.len(),
+            symbol_ptr.get_arity().port_count(),
+            "Wrong port count for {:?}",
+            symbol_ptr
+        );
+        self.nary_ports().alloc(NaryPorts(ports.into()))
+    }
+
+    // one entry per live n-ary cell, so as large as the cells arena
+    fn nary_ports(&self) -> &NaryPortsArena {
+        self.nary_ports
+            .get_or_init(|| NaryPortsArena::with_capacity(self.cells.capacity()))
+    }
+
+    // a reused or freed n-ary cell leaves its ports behind
+    #[inline]
+    fn free_nary_ports(&self, ptr: CellPtr) {
+        let cell = self.get_cell(ptr);
+        if cell.get_symbol_ptr().is_nary() {
+            self.nary_ports().free(cell.get_nary_ports_ptr());
+        }
+    }
+
+    /// Live cells, possibly stale while other threads rewrite. Cheap enough
+    /// to poll for metrics and logging.
+    pub fn cell_count_approx(&self) -> usize {
+        self.cells.len_relaxed()
+    }
+
+    /// Live vars, possibly stale while other threads rewrite.
+    pub fn var_count_approx(&self) -> usize {
+        self.vars.len_relaxed()
+    }
+
+    /// Live cells, ordered against every alloc and free.
+    pub fn cell_count_exact(&self) -> usize {
+        self.cells.len()
+    }
+
     pub fn get_cell<'a>(&'a self, cell_ptr: CellPtr) -> &'a Cell<T> {
         self.cells.get(cell_ptr).unwrap()
     }
 
-    // pub fn cells(&self) -> ArenaPtrIter<Cell<T>, CellPtr> {
-    //     self.cells.iter()
-    // }
+    /// The arity of a cell, n-ary ones included.
+    pub fn get_cell_arity(&self, cell_ptr: CellPtr) -> SymbolArity {
+        self.get_cell(cell_ptr).get_arity()
+    }
+
+    /// A port of a cell, n-ary ones included.
+    pub fn get_cell_port(&self, cell_ptr: CellPtr, port_num: PortNum) -> TermPtr {
+        let cell = self.get_cell(cell_ptr);
+        match cell.get_arity() {
+            SymbolArity::N(_) => {
+                assert!(port_num.is_valid_port(cell.get_arity()));
+                self.get_nary_ports(cell)[port_num as usize]
+            }
+            _ => cell.get_port(port_num),
+        }
+    }
+
+    pub fn set_cell_port(&mut self, cell_ptr: CellPtr, port_num: PortNum, port: TermPtr) {
+        let cell = self.cells.get_mut(cell_ptr).unwrap();
+        match cell.get_arity() {
+            SymbolArity::N(_) => {
+                assert!(port_num.is_valid_port(cell.get_arity()));
+                let nary_ports = self.nary_ports.get_mut().unwrap();
+                nary_ports.get_mut(cell.get_nary_ports_ptr()).unwrap().0[port_num as usize] = port;
+            }
+            _ => cell.set_port(port_num, port),
+        }
+    }
+
+    fn get_nary_ports(&self, cell: &Cell<T>) -> &[TermPtr] {
+        let nary_ports = self.nary_ports.get().and_then(|nary_ports| {
+            nary_ports.get(cell.get_nary_ports_ptr())
+        });
+        &nary_ports.expect("N-ary cell has no ports").0
+    }
+
+    /// The cell with the ports of an n-ary one, which stay readable after the cell is
+    /// freed or reused.
+    pub fn copy_cell(&self, cell_ptr: CellPtr) -> CellCopy<T> {
+        let cell = *self.get_cell(cell_ptr);
+        let nary_ports = match cell.get_symbol_ptr().is_nary() {
+            true => Some(self.get_nary_ports(&cell).into()),
+            false => None,
+        };
+        CellCopy::new(cell, nary_ports)
+    }
+
+    pub fn contains_cell(&self, cell_ptr: CellPtr) -> bool {
+        self.cells.contains(cell_ptr)
+    }
+
+    pub fn cells(&self) -> ArenaPtrIter<Cell<T>, CellPtr> {
+        self.cells.iter()
+    }
 
     pub fn get_var<'a>(&'a self, var_ptr: PVarPtr) -> &'a Var<T> {
         self.vars.get(var_ptr.into()).unwrap()
     }
 
+    /// For stores that are not atomic, such as the rule family's, which can
+    /// only be updated through a mutable heap.
+    pub fn get_var_mut(&mut self, var_ptr: PVarPtr) -> &mut Var<T> {
+        self.vars.get_mut(var_ptr.into()).unwrap()
+    }
+
     pub fn free_cell(&self, cell_ptr: CellPtr) -> Cell<T> {
         let index = cell_ptr.get_index();
+        self.free_nary_ports(cell_ptr);
         let cell = self.cells.free(cell_ptr);
         tracing::trace!(
             "[Len={}] Free CELLS[{}] = {:?}",
@@ -152,9 +377,23 @@ impl<T: TermFamily> Heap<T> {
         var_ptr
     }
 
-    // pub fn vars(&self) -> ArenaPtrIter<Var<T>, VarPtr> {
-    //     self.vars.iter()
-    // }
+    /// Allocates a bound var and returns its (neg, pos) ends.
+    pub fn alloc_bvar_pair(&self, store: T::BoundStore) -> (PVarPtr, PVarPtr) {
+        PVarPtr::wire(self.bvar(store))
+    }
+
+    /// Allocates a free var and returns its (neg, pos) ends.
+    pub fn alloc_fvar_pair(&self, store: T::FreeStore) -> (PVarPtr, PVarPtr) {
+        PVarPtr::wire(self.fvar(store))
+    }
+
+    pub fn contains_var(&self, var_ptr: PVarPtr) -> bool {
+        self.vars.contains(var_ptr.into())
+    }
+
+    pub fn vars(&self) -> ArenaPtrIter<Var<T>, VarPtr> {
+        self.vars.iter()
+    }
 
     pub fn free_var(&self, var_ptr: PVarPtr) -> Var<T> {
         let index = var_ptr.get_fvar_ptr().get_index();
@@ -207,6 +446,21 @@ impl<T: TermFamily> Heap<T> {
         }
     }
 
+    /// Emits a trace event for every live cell and var, when tracing is on.
+    pub fn debug_print(&self, symbols: &SymbolBook) {
+        if !tracing::enabled!(tracing::Level::TRACE) {
+            return;
+        }
+        for cell_ptr in self.cells() {
+            let cell = self.display_cell(symbols, cell_ptr);
+            tracing::trace!("CELLS[{}] = {}", cell_ptr.get_index(), cell);
+        }
+        for var_ptr in self.vars() {
+            let var = self.display_var(symbols, var_ptr);
+            tracing::trace!("VARS[{}] = {}", var_ptr.get_index(), var);
+        }
+    }
+
     // pub fn display_vars<'a>(&'a self, symbols: &'a SymbolBook) -> VarsDisplay<T> {
     //     VarsDisplay {
     //         symbols: symbols,
@@ -215,13 +469,56 @@ impl<T: TermFamily> Heap<T> {
     // }
 }
 
+/// Where `Heap::merge_heap` or `Heap::compact` moved cells and vars.
+#[derive(Debug, Clone)]
+pub struct HeapTranslation {
+    pub cells: IndexTranslation<CellPtr>,
+    pub vars: IndexTranslation<VarPtr>,
+}
+
+impl HeapTranslation {
+    pub fn translate_cell(&self, cell_ptr: CellPtr) -> CellPtr {
+        self.cells
+            .get(cell_ptr.get_index())
+            .unwrap_or_else(|| panic!("Cell {:?} was not moved", cell_ptr))
+    }
+
+    /// Keeps the polarity of `var_ptr`.
+    pub fn translate_var(&self, var_ptr: PVarPtr) -> PVarPtr {
+        let new_var_ptr = self
+            .vars
+            .get(var_ptr.get_fvar_ptr().get_index())
+            .unwrap_or_else(|| panic!("Var {:?} was not moved", var_ptr));
+        let (neg_pvar, pos_pvar) = PVarPtr::wire(new_var_ptr);
+        match var_ptr.get_polarity() {
+            Polarity::Neg => neg_pvar,
+            Polarity::Pos => pos_pvar,
+        }
+    }
+
+    pub fn translate_term(&self, term: TermPtr) -> TermPtr {
+        match term.get_kind() {
+            TermKind::Cell => self.translate_cell(term.get_cell_ptr()).into(),
+            TermKind::Var => self.translate_var(term.get_var_ptr()).into(),
+        }
+    }
+}
+
 pub struct HeapDisplay<'a, T: TermFamily> {
     symbols: &'a SymbolBook,
     heap: &'a Heap<T>,
 }
 impl<'a, T: TermFamily> Display for HeapDisplay<'a, T> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        Result::Ok(())
+        for cell_ptr in self.heap.cells() {
+            let cell = self.heap.display_cell(self.symbols, cell_ptr);
+            writeln!(f, "CELLS[{}] = {}", cell_ptr.get_index(), cell)?;
+        }
+        for var_ptr in self.heap.vars() {
+            let var = self.heap.display_var(self.symbols, var_ptr);
+            writeln!(f, "VARS[{}] = {}", var_ptr.get_index(), var)?;
+        }
+        Ok(())
     }
 }
 
@@ -252,13 +549,10 @@ pub struct CellDisplay<'a, T: TermFamily> {
 }
 impl<'a, T: TermFamily> Display for CellDisplay<'a, T> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let cell = match self.heap.cells.get(self.cell_ptr) {
-            Some(cell) => cell,
-            None => panic!("Cell {:?} not found", self.cell_ptr),
-        };
+        let cell = self.heap.copy_cell(self.cell_ptr);
 
-        let name = self.symbols.get_name(cell.get_symbol_ptr()).unwrap();
-        match cell.get_symbol_ptr().get_arity() {
+        let name = cell.symbol_name(self.symbols).unwrap();
+        match cell.get_arity() {
             SymbolArity::Zero => {
                 write!(f, "{}", name)
             }
@@ -267,7 +561,7 @@ impl<'a, T: TermFamily> Display for CellDisplay<'a, T> {
                     f,
                     "({} {})",
                     name,
-                    self.heap.display_term(self.symbols, cell.get_left_port())
+                    self.heap.display_term(self.symbols, cell.get_port(PortNum::Zero))
                 )
             }
             SymbolArity::Two => {
@@ -275,10 +569,18 @@ impl<'a, T: TermFamily> Display for CellDisplay<'a, T> {
                     f,
                     "({} {} {})",
                     name,
-                    self.heap.display_term(self.symbols, cell.get_left_port()),
-                    self.heap.display_term(self.symbols, cell.get_right_port())
+                    self.heap.display_term(self.symbols, cell.get_port(PortNum::Zero)),
+                    self.heap.display_term(self.symbols, cell.get_port(PortNum::One))
                 )
             }
+            SymbolArity::N(n) => (0..n as usize)
+                .fold(write!(f, "({}", name), |result, port_num| {
+                    result.and_then(|_| {
+                        let port = cell.get_port(PortNum::from(port_num));
+                        write!(f, " {}", self.heap.display_term(self.symbols, port))
+                    })
+                })
+                .and_then(|_| write!(f, ")")),
         }
     }
 }
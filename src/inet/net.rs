@@ -1,20 +1,27 @@
 use std::{
+    collections::{HashMap, HashSet, VecDeque},
     fmt::Display,
     marker::PhantomData,
-    sync::atomic::{AtomicU32, Ordering},
+    sync::atomic::Ordering,
 };
 
-use tracing::{debug, warn};
+use raw_arena::arenaraw::{VersionedPtr, DEFAULT_CAPACITY};
+use tracing::{debug, error, warn};
 
 use super::{
-    cell::CellPtr,
-    equation::{Equation, EquationBuilder, EquationsDisplay},
-    heap::Heap,
-    symbol::SymbolBook,
-    term::TermFamily,
-    var::{PVarPtr, Var},
+    cell::{Cell, CellPtr, NaryPorts},
+    equation::{Equation, EquationBits, EquationBuilder, EquationKind, EquationsDisplay, UsageError},
+    heap::{Heap, HeapTranslation},
+    rule::{PortNum, Rule, RuleSet},
+    symbol::{SymbolArity, SymbolBook, SymbolName, SymbolPtr},
+    term::{TermFamily, TermKind, TermPtr},
+    var::{PVarPtr, Var, VarPtr},
+    Polarity,
 };
 
+/// The smallest arena `Net::with_capacity_hint` allocates.
+pub const MIN_CAPACITY_HINT: usize = 1 << 10;
+
 #[derive(Debug, Copy, Clone)]
 pub struct NetF {}
 impl TermFamily for NetF {
@@ -41,50 +48,137 @@ impl TermFamily for NetF {
             Var::Free(_) => write!(f, "_.{}", index),
         }
     }
+
+    fn translate_store(var: &mut Var<NetF>, translation: &HeapTranslation) {
+        let store = match var {
+            Var::Bound(store) | Var::Free(store) => store,
+        };
+        let value = match store.get_value() {
+            NetValue::Empty => return,
+            NetValue::Cell(cell_ptr) => translation.translate_cell(cell_ptr).get_ptr() as NetVarBits,
+            NetValue::Link(var_ptr) => {
+                NetVar::LINK | translation.translate_var(var_ptr).get_ptr() as NetVarBits
+            }
+        };
+        store.0.store(value, Ordering::SeqCst);
+    }
+}
+
+/// What a `NetVar` holds: nothing yet, the cell bound to it, or the var it was
+/// connected to before either of them had a cell.
+#[derive(Debug, Clone, Copy)]
+pub enum NetValue {
+    Empty,
+    Cell(CellPtr),
+    Link(PVarPtr),
+}
+
+/// A structural problem found by `Net::validate`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NetError {
+    /// A freed or never allocated cell is referenced.
+    DanglingCell(CellPtr),
+    /// A freed or never allocated var is referenced.
+    DanglingVar(PVarPtr),
+    /// Head vars must be free vars.
+    BoundHeadVar(PVarPtr),
+    /// A redex needs a positive constructor and a negative function.
+    RedexPolarity(CellPtr, CellPtr),
+    /// Both ends of a connect have the same polarity.
+    ShortCircuit(PVarPtr, PVarPtr),
+    /// A port holds a term with the same polarity as the port.
+    PortPolarity(CellPtr, PortNum),
 }
 
+/// The bits of a `NetVar`: a cell or var ptr and the link flag. Ptrs of a
+/// `large-heap` are 32 bits wide, which leaves no room for the flag in 32 bits.
+#[cfg(not(feature = "large-heap"))]
+pub type NetVarBits = u32;
+#[cfg(not(feature = "large-heap"))]
+type AtomicNetVarBits = std::sync::atomic::AtomicU32;
+#[cfg(feature = "large-heap")]
+pub type NetVarBits = u64;
+#[cfg(feature = "large-heap")]
+type AtomicNetVarBits = std::sync::atomic::AtomicU64;
+
 #[derive(Debug)]
-pub struct NetVar(AtomicU32);
+pub struct NetVar(AtomicNetVarBits);
 
 impl NetVar {
-    const NULL: u32 = u32::MAX;
+    /// The value of an empty var, see `compare_and_bind`.
+    pub const NULL: NetVarBits = NetVarBits::MAX;
+    const LINK: NetVarBits = 1 << (NetVarBits::BITS - 1);
 
-    pub fn get_cell_ptr(&self) -> Option<CellPtr> {
-        let value = self.0.load(Ordering::SeqCst);
-        if value != Self::NULL {
-            Some(CellPtr::from(value))
+    // the casts to u32 only narrow with `large-heap`
+    #[allow(clippy::unnecessary_cast)]
+    fn to_value(value: NetVarBits) -> NetValue {
+        if value == Self::NULL {
+            NetValue::Empty
+        } else if value & Self::LINK != 0 {
+            NetValue::Link(PVarPtr::from((value & !Self::LINK) as u32))
         } else {
-            return None;
-        }
-    }
-
-    pub fn set_or_get(&self, cell_ptr: CellPtr) -> (CellPtr, Option<CellPtr>) {
-        let old_value = self.0.swap(cell_ptr.get_ptr(), Ordering::SeqCst);
-        if old_value != Self::NULL {
-            if old_value != cell_ptr.get_ptr() {
-                // debug!(
-                //     "Swapping var value {:?} with {:?}",
-                //     cell_ptr,
-                //     self.get_cell_ptr()
-                // );
-                (cell_ptr, Some(CellPtr::from(old_value)))
-            } else {
-                warn!(
-                    "WARN: Setting var with value {:?} twice?",
-                    self.get_cell_ptr()
-                );
-                return (cell_ptr, None);
-            }
-        } else {
-            // debug!("Setting var with value {:?}", self.get_cell_ptr());
-            return (cell_ptr, None);
+            NetValue::Cell(CellPtr::from(value as u32))
+        }
+    }
+
+    pub fn get_value(&self) -> NetValue {
+        Self::to_value(self.0.load(Ordering::SeqCst))
+    }
+
+    pub fn get_cell_ptr(&self) -> Option<CellPtr> {
+        match self.get_value() {
+            NetValue::Cell(cell_ptr) => Some(cell_ptr),
+            _ => None,
+        }
+    }
+
+    /// Sets the cell and returns what the var held before. A previous cell
+    /// must interact with the new one and a link must receive the new cell.
+    pub fn set_or_get(&self, cell_ptr: CellPtr) -> NetValue {
+        let old_value = self.0.swap(cell_ptr.get_ptr() as NetVarBits, Ordering::SeqCst);
+        if old_value == cell_ptr.get_ptr() as NetVarBits {
+            warn!(
+                "WARN: Setting var with value {:?} twice?",
+                self.get_cell_ptr()
+            );
+            return NetValue::Empty;
+        }
+        Self::to_value(old_value)
+    }
+
+    /// Binds the cell only if the var still holds `expected`, `NetVar::NULL`
+    /// for an empty var, so a bound value is never overwritten. Otherwise
+    /// returns the cell or link the var holds.
+    pub fn compare_and_bind(
+        &self,
+        expected: NetVarBits,
+        cell_ptr: CellPtr,
+    ) -> Result<(), NetValue> {
+        let value = cell_ptr.get_ptr() as NetVarBits;
+        self.0
+            .compare_exchange(expected, value, Ordering::AcqRel, Ordering::Acquire)
+            .map(|_| ())
+            .map_err(Self::to_value)
+    }
+
+    /// Links this var to `var_ptr` if it is still empty, otherwise returns the
+    /// cell or link it already holds.
+    pub fn link(&self, var_ptr: PVarPtr) -> NetValue {
+        match self.0.compare_exchange(
+            Self::NULL,
+            Self::LINK | var_ptr.get_ptr() as NetVarBits,
+            Ordering::SeqCst,
+            Ordering::SeqCst,
+        ) {
+            Ok(_) => NetValue::Empty,
+            Err(value) => Self::to_value(value),
         }
     }
 }
 
 impl Default for NetVar {
     fn default() -> Self {
-        Self(AtomicU32::new(Self::NULL))
+        Self(AtomicNetVarBits::new(Self::NULL))
     }
 }
 
@@ -103,20 +197,65 @@ impl Default for Equation<NetF> {
     }
 }
 
+/// What a `Net::gc` run reclaimed.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct GcStats {
+    pub cells_freed: usize,
+    pub vars_freed: usize,
+    pub bytes_freed: usize,
+}
+
+/// The structural changes between two nets sharing a heap layout, typically a
+/// net before and after a rewrite step. Cells count as changed when their
+/// symbol or ports differ, vars when the cell they hold does.
+#[derive(Debug, Default)]
+pub struct NetDiff {
+    pub added_cells: Vec<CellPtr>,
+    pub removed_cells: Vec<CellPtr>,
+    pub added_equations: Vec<Equation<NetF>>,
+    pub removed_equations: Vec<Equation<NetF>>,
+    pub changed_vars: Vec<(VarPtr, Option<CellPtr>, Option<CellPtr>)>,
+    // rendered when diffing, while both heaps are at hand
+    lines: Vec<String>,
+}
+
+impl NetDiff {
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+}
+
+impl Display for NetDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.lines
+            .iter()
+            .fold(Ok(()), |result, line| result.and_then(|_| writeln!(f, "{}", line)))
+    }
+}
+
 #[derive(Debug)]
 pub struct Net<'a> {
     pub symbols: &'a SymbolBook,
     pub head: Vec<PVarPtr>,
-    pub body: Vec<Equation<NetF>>,
+    pub body: VecDeque<Equation<NetF>>,
     pub heap: Heap<NetF>,
+    /// Names given by `EquationBuilder::var_named`, with the generation of
+    /// the var they were given to.
+    pub var_names: Vec<(&'static str, VersionedPtr<VarPtr>)>,
+    /// The cells and vars `Runtime` grows the heap to before evaluating, set
+    /// by `with_capacity_hint`. Arenas do not grow during evaluation, so a
+    /// heap sized by the hint could not hold rewrites past the estimate.
+    pub eval_capacity: Option<usize>,
 }
 impl<'a> Net<'a> {
     pub fn new(symbols: &'a SymbolBook) -> Self {
         Self {
             symbols,
             head: Vec::new(),
-            body: Vec::new(),
+            body: VecDeque::new(),
             heap: Heap::new(),
+            var_names: Vec::new(),
+            eval_capacity: None,
         }
     }
 
@@ -124,8 +263,38 @@ impl<'a> Net<'a> {
         Self {
             symbols,
             head: Vec::new(),
-            body: Vec::with_capacity(capacity[0]),
+            body: VecDeque::with_capacity(capacity[0]),
             heap: Heap::with_capacity(capacity[1], capacity[2]),
+            var_names: Vec::new(),
+            eval_capacity: None,
+        }
+    }
+
+    /// Like `with_capacity`, estimating the heap from `initial_equations` and
+    /// the average rule body size: each initial equation is expected to fire
+    /// a rule adding that many equations, each with up to two cells and two
+    /// vars. The estimates never go below `MIN_CAPACITY_HINT`. They only size
+    /// the heap while the net is built: evaluation first grows it to at least
+    /// `DEFAULT_CAPACITY`, see `eval_capacity`.
+    pub fn with_capacity_hint(
+        symbols: &'a SymbolBook,
+        rules: &RuleSet,
+        initial_equations: usize,
+    ) -> Self {
+        let (rule_count, body_len) = rules.rules().fold((0, 0), |(count, len), rule_ptr| {
+            (count + 1, len + rules.get_rule(rule_ptr).body().len())
+        });
+        let average = match rule_count {
+            0 => 1,
+            _ => ((body_len + rule_count - 1) / rule_count).max(1),
+        };
+        let terms = initial_equations
+            .saturating_mul(average + 1)
+            .saturating_mul(2)
+            .max(MIN_CAPACITY_HINT);
+        Self {
+            eval_capacity: Some(terms.max(DEFAULT_CAPACITY)),
+            ..Self::with_capacity(symbols, [initial_equations, terms, terms])
         }
     }
 
@@ -140,9 +309,930 @@ impl<'a> Net<'a> {
             &mut self.head,
             &mut self.body,
             &mut self.heap,
+            &mut self.var_names,
         );
         builder_fn(&mut builder);
-        builder.build();
+        if let Err(error) = builder.build() {
+            error!("Net equations left a dangling var: {}", error);
+        }
+    }
+
+    pub fn builder(&mut self) -> NetBuilder {
+        NetBuilder {
+            builder: EquationBuilder::new(
+                self.symbols,
+                &mut self.head,
+                &mut self.body,
+                &mut self.heap,
+                &mut self.var_names,
+            ),
+        }
+    }
+
+    /// The name `var_ptr` was created with by `EquationBuilder::var_named`.
+    /// Once evaluation frees the var its name is gone, also for a var that
+    /// later reuses the slot.
+    pub fn var_name(&self, var_ptr: VarPtr) -> Option<&'static str> {
+        self.var_names
+            .iter()
+            .rev()
+            .find(|(_, named)| named.ptr.get_index() == var_ptr.get_index())
+            .filter(|(_, named)| self.heap.vars.get_checked(named.ptr, named.generation).is_ok())
+            .map(|(name, _)| *name)
+    }
+
+    /// The pending equations of one kind, in body order.
+    pub fn equations_of_kind(
+        &self,
+        kind: EquationKind,
+    ) -> impl Iterator<Item = Equation<NetF>> + '_ {
+        self.body
+            .iter()
+            .copied()
+            .filter(move |eqn| eqn.get_kind() == kind)
+    }
+
+    /// Removes the pending equations of one kind and yields them in body
+    /// order, the others stay in the body. They are removed even if the
+    /// iterator is not consumed.
+    pub fn drain_equations_of_kind(
+        &mut self,
+        kind: EquationKind,
+    ) -> impl Iterator<Item = Equation<NetF>> {
+        let (drained, kept): (VecDeque<_>, VecDeque<_>) = std::mem::take(&mut self.body)
+            .into_iter()
+            .partition(|eqn| eqn.get_kind() == kind);
+        self.body = kept;
+        drained.into_iter()
+    }
+
+    pub fn redexes(&self) -> impl Iterator<Item = Equation<NetF>> + '_ {
+        self.equations_of_kind(EquationKind::Redex)
+    }
+
+    pub fn binds(&self) -> impl Iterator<Item = Equation<NetF>> + '_ {
+        self.equations_of_kind(EquationKind::Bind)
+    }
+
+    pub fn connects(&self) -> impl Iterator<Item = Equation<NetF>> + '_ {
+        self.equations_of_kind(EquationKind::Connect)
+    }
+
+    /// The pending equations in body order, each redex with the rule that
+    /// would rewrite it, without evaluating anything.
+    pub fn equations_with_rule<'s>(
+        &'s self,
+        rules: &'s RuleSet,
+        symbols: &'s SymbolBook,
+    ) -> Vec<EquationWithRule<'s>> {
+        self.body
+            .iter()
+            .map(|eqn| {
+                let rule = match eqn.get_kind() {
+                    EquationKind::Redex => {
+                        let ctr = self.heap.get_cell(eqn.get_redex_ctr()).get_symbol_ptr();
+                        let fun = self.heap.get_cell(eqn.get_redex_fun()).get_symbol_ptr();
+                        rules.get_by_symbols(ctr, fun).map(|rule_ptr| rules.get_rule(rule_ptr))
+                    }
+                    EquationKind::Bind | EquationKind::Connect => None,
+                };
+                EquationWithRule {
+                    equation: *eqn,
+                    rule,
+                    symbols,
+                    heap: &self.heap,
+                }
+            })
+            .collect()
+    }
+
+    /// Turns the binds whose var already holds a cell into redexes, and
+    /// moves binds on linked vars to the var at the end of the link, freeing
+    /// the bound vars passed through as `Runtime` does. Returns the number of
+    /// redexes created, so it can run before `is_normal`.
+    pub fn reduce_all_binds(&mut self) -> usize {
+        let mut reduced = 0;
+        for eqn in self.body.iter_mut() {
+            if eqn.get_kind() != EquationKind::Bind {
+                continue;
+            }
+            let cell_ptr = eqn.get_bind_cell();
+            let mut var_ptr = eqn.get_bind_var();
+            loop {
+                let var = self.heap.get_var(var_ptr);
+                let value = var.get_store().get_value();
+                if !matches!(value, NetValue::Empty) && var.is_bound() {
+                    self.heap.free_var(var_ptr);
+                }
+                match value {
+                    NetValue::Empty => {
+                        *eqn = Equation::bind(var_ptr, cell_ptr);
+                        break;
+                    }
+                    NetValue::Link(linked_ptr) => var_ptr = linked_ptr,
+                    NetValue::Cell(other_ptr) => {
+                        *eqn = match (cell_ptr.get_polarity(), other_ptr.get_polarity()) {
+                            (Polarity::Pos, Polarity::Neg) => Equation::redex(cell_ptr, other_ptr),
+                            (Polarity::Neg, Polarity::Pos) => Equation::redex(other_ptr, cell_ptr),
+                            _ => panic!("Short-circuit: {:?} x {:?}", cell_ptr, other_ptr),
+                        };
+                        reduced += 1;
+                        break;
+                    }
+                }
+            }
+        }
+        reduced
+    }
+
+    /// True when no redex is left to rewrite.
+    pub fn is_normal(&self) -> bool {
+        self.redexes().next().is_none()
+    }
+
+    /// Sorts the pending equations by kind (redexes, binds, then connects) and
+    /// then by the indices of their left and right terms, so the body no longer
+    /// depends on the order the equations were created in.
+    pub fn sort_equations(&mut self) {
+        self.body.make_contiguous().sort_by_key(|eqn| {
+            let (left, right) = match eqn.get_kind() {
+                EquationKind::Redex => (
+                    eqn.get_redex_ctr().get_index(),
+                    eqn.get_redex_fun().get_index(),
+                ),
+                EquationKind::Bind => (
+                    eqn.get_bind_var().get_fvar_ptr().get_index(),
+                    eqn.get_bind_cell().get_index(),
+                ),
+                EquationKind::Connect => (
+                    eqn.get_connect_left().get_fvar_ptr().get_index(),
+                    eqn.get_connect_right().get_fvar_ptr().get_index(),
+                ),
+            };
+            (eqn.get_kind() as u8, left, right)
+        });
+    }
+
+    /// Pending connects may still form redexes, so each adds to the metric.
+    pub const CONNECT_PENALTY: u64 = 2;
+
+    /// Over-approximates the work left to normalise the net: every redex
+    /// weighs one plus the length of its rule body, and every connect adds
+    /// `CONNECT_PENALTY`. Nets growing through recursion are underestimated.
+    pub fn complexity_metric(&self, rules: &RuleSet) -> u64 {
+        self.body
+            .iter()
+            .map(|eqn| match eqn.get_kind() {
+                EquationKind::Redex => {
+                    let ctr = self.heap.get_cell(eqn.get_redex_ctr()).get_symbol_ptr();
+                    let fun = self.heap.get_cell(eqn.get_redex_fun()).get_symbol_ptr();
+                    match rules.get_by_symbols(ctr, fun) {
+                        Some(rule_ptr) => 1 + rules.get_rule(rule_ptr).body.len() as u64,
+                        None => 1,
+                    }
+                }
+                EquationKind::Bind => 0,
+                EquationKind::Connect => Self::CONNECT_PENALTY,
+            })
+            .sum()
+    }
+
+    // Outputs ----------------------------
+
+    /// Pairs each head var with the cell it was bound to, if any.
+    pub fn head_values(&self) -> Vec<(PVarPtr, Option<CellPtr>)> {
+        self.head
+            .iter()
+            .copied()
+            .map(|fvar_ptr| {
+                assert!(self.heap.get_var(fvar_ptr).is_free());
+                (fvar_ptr, self.get_var_cell(fvar_ptr))
+            })
+            .collect()
+    }
+
+    /// Like `head_values`, with the symbol name of each bound cell, to read
+    /// outputs by name rather than by position.
+    pub fn head_named_iter<'s>(
+        &self,
+        symbols: &'s SymbolBook,
+    ) -> impl Iterator<Item = (Option<&'s SymbolName>, PVarPtr, Option<CellPtr>)> {
+        let named: Vec<_> = self
+            .head_values()
+            .into_iter()
+            .map(|(fvar_ptr, value)| {
+                let name =
+                    value.and_then(|cell_ptr| self.heap.get_cell(cell_ptr).symbol_name(symbols));
+                (name, fvar_ptr, value)
+            })
+            .collect();
+        named.into_iter()
+    }
+
+    /// The cell bound to a var, following links to connected vars.
+    pub fn get_var_cell(&self, var_ptr: PVarPtr) -> Option<CellPtr> {
+        match self.heap.get_var(var_ptr).get_store().get_value() {
+            NetValue::Empty => None,
+            NetValue::Cell(cell_ptr) => Some(cell_ptr),
+            NetValue::Link(linked_ptr) => self.get_var_cell(linked_ptr),
+        }
+    }
+
+    /// The value of the only head var, for nets with a single output.
+    pub fn single_output(&self) -> Option<CellPtr> {
+        assert!(
+            self.head.len() == 1,
+            "Expected a single output but found {}",
+            self.head.len()
+        );
+        self.head_values()[0].1
+    }
+
+    // Copying ----------------------------
+
+    /// Copies every var and cell of this net into `target_heap` under fresh
+    /// indices. Returns the head and body of the copy, ready to be appended
+    /// to a net that owns `target_heap`.
+    pub fn copy_with_fresh_vars(
+        &self,
+        target_heap: &Heap<NetF>,
+    ) -> (Vec<PVarPtr>, Vec<Equation<NetF>>) {
+        // snapshot the source first, the target heap may be this net's heap
+        let source_vars: Vec<VarPtr> = self.heap.vars().collect();
+        let source_cells: Vec<CellPtr> = self.heap.cells().collect();
+
+        let mut vars = HashMap::new();
+        for var_ptr in source_vars {
+            let new_var_ptr = match self.heap.vars.get(var_ptr).unwrap() {
+                Var::Bound(_) => target_heap.bvar(NetVar::default()),
+                Var::Free(_) => target_heap.fvar(NetVar::default()),
+            };
+            vars.insert(var_ptr.get_index(), new_var_ptr);
+        }
+
+        let mut cells = HashMap::new();
+        for cell_ptr in source_cells {
+            self.copy_cell(target_heap, &vars, &mut cells, cell_ptr);
+        }
+
+        // vars are copied empty, restore what they held
+        for (index, new_var_ptr) in vars.iter() {
+            let var_ptr = PVarPtr::wire(VarPtr::new(*index)).0;
+            let new_store = target_heap.get_var(PVarPtr::wire(*new_var_ptr).0).get_store();
+            match self.heap.get_var(var_ptr).get_store().get_value() {
+                NetValue::Empty => (),
+                NetValue::Cell(cell_ptr) => {
+                    new_store.set_or_get(cells[&cell_ptr.get_index()]);
+                }
+                NetValue::Link(linked_ptr) => {
+                    new_store.link(Self::fresh_var(&vars, linked_ptr));
+                }
+            }
+        }
+
+        let head = self
+            .head
+            .iter()
+            .map(|var_ptr| Self::fresh_var(&vars, *var_ptr))
+            .collect();
+        let body = self
+            .body
+            .iter()
+            .map(|eqn| match eqn.get_kind() {
+                EquationKind::Redex => Equation::redex(
+                    cells[&eqn.get_redex_ctr().get_index()],
+                    cells[&eqn.get_redex_fun().get_index()],
+                ),
+                EquationKind::Bind => Equation::bind(
+                    Self::fresh_var(&vars, eqn.get_bind_var()),
+                    cells[&eqn.get_bind_cell().get_index()],
+                ),
+                EquationKind::Connect => Equation::connect(
+                    Self::fresh_var(&vars, eqn.get_connect_left()),
+                    Self::fresh_var(&vars, eqn.get_connect_right()),
+                ),
+            })
+            .collect();
+
+        (head, body)
+    }
+
+    fn fresh_var(vars: &HashMap<usize, VarPtr>, var_ptr: PVarPtr) -> PVarPtr {
+        let (neg_pvar, pos_pvar) = PVarPtr::wire(vars[&var_ptr.get_fvar_ptr().get_index()]);
+        match var_ptr.get_polarity() {
+            Polarity::Neg => neg_pvar,
+            Polarity::Pos => pos_pvar,
+        }
+    }
+
+    // copies ports before the cell itself since a cell is created with its
+    // ports. Cells are revisited once their port cells are copied, through an
+    // explicit stack so long chains of cells do not overflow the call stack.
+    fn copy_cell(
+        &self,
+        target_heap: &Heap<NetF>,
+        vars: &HashMap<usize, VarPtr>,
+        cells: &mut HashMap<usize, CellPtr>,
+        cell_ptr: CellPtr,
+    ) -> CellPtr {
+        let mut pending = vec![(cell_ptr, false)];
+        while let Some((cell_ptr, ports_copied)) = pending.pop() {
+            if cells.contains_key(&cell_ptr.get_index()) {
+                continue;
+            }
+            let cell = self.heap.copy_cell(cell_ptr);
+            if !ports_copied {
+                pending.push((cell_ptr, true));
+                pending.extend(
+                    cell.ports()
+                        .filter(|port| port.is_cell())
+                        .map(|port| (port.get_cell_ptr(), false)),
+                );
+                continue;
+            }
+
+            let ports: Vec<TermPtr> = cell
+                .ports()
+                .map(|port| match port.get_kind() {
+                    TermKind::Cell => cells[&port.get_cell_ptr().get_index()].into(),
+                    TermKind::Var => Self::fresh_var(vars, port.get_var_ptr()).into(),
+                })
+                .collect();
+
+            let symbol_ptr = cell.get_symbol_ptr();
+            let new_cell_ptr = match cell.get_arity() {
+                SymbolArity::Zero => target_heap.cell0(symbol_ptr),
+                SymbolArity::One => target_heap.cell1(symbol_ptr, ports[0]),
+                SymbolArity::Two => target_heap.cell2(symbol_ptr, ports[0], ports[1]),
+                SymbolArity::N(_) => target_heap.cellN(symbol_ptr, &ports),
+            };
+            cells.insert(cell_ptr.get_index(), new_cell_ptr);
+        }
+        cells[&cell_ptr.get_index()]
+    }
+
+    /// Translates the head, body and var names after `Heap::compact` moved
+    /// the cells and vars of this net's heap.
+    pub fn apply_translations(&mut self, translation: &HeapTranslation) {
+        for var_ptr in self.head.iter_mut() {
+            *var_ptr = translation.translate_var(*var_ptr);
+        }
+        for eqn in self.body.iter_mut() {
+            *eqn = match eqn.get_kind() {
+                EquationKind::Redex => Equation::redex(
+                    translation.translate_cell(eqn.get_redex_ctr()),
+                    translation.translate_cell(eqn.get_redex_fun()),
+                ),
+                EquationKind::Bind => Equation::bind(
+                    translation.translate_var(eqn.get_bind_var()),
+                    translation.translate_cell(eqn.get_bind_cell()),
+                ),
+                EquationKind::Connect => Equation::connect(
+                    translation.translate_var(eqn.get_connect_left()),
+                    translation.translate_var(eqn.get_connect_right()),
+                ),
+            };
+        }
+        // the names of freed vars are dropped, also when their slot was
+        // reused: the generation moves with the var
+        let vars = &self.heap.vars;
+        self.var_names.retain_mut(|(_, named)| match translation.vars.get(named.ptr.get_index()) {
+            Some(new_var_ptr) if vars.versioned(new_var_ptr).generation == named.generation => {
+                named.ptr = new_var_ptr;
+                true
+            }
+            _ => false,
+        });
+    }
+
+    // Comparison -------------------------
+
+    /// Checks that both nets have the same structure up to a renaming of
+    /// their vars, starting from the head vars and the pending equations.
+    pub fn alpha_equivalent(&self, other: &Net, symbols: &SymbolBook) -> bool {
+        if self.head.len() != other.head.len() || self.body.len() != other.body.len() {
+            return false;
+        }
+
+        let mut pending: Vec<(TermPtr, TermPtr)> = self
+            .head
+            .iter()
+            .zip(other.head.iter())
+            .map(|(left, right)| (TermPtr::new_var(*left), TermPtr::new_var(*right)))
+            .collect();
+        for (left, right) in self.body.iter().zip(other.body.iter()) {
+            match (left.get_kind(), right.get_kind()) {
+                (EquationKind::Redex, EquationKind::Redex) => {
+                    pending.push((left.get_redex_ctr().into(), right.get_redex_ctr().into()));
+                    pending.push((left.get_redex_fun().into(), right.get_redex_fun().into()));
+                }
+                (EquationKind::Bind, EquationKind::Bind) => {
+                    pending.push((
+                        TermPtr::new_var(left.get_bind_var()),
+                        TermPtr::new_var(right.get_bind_var()),
+                    ));
+                    pending.push((left.get_bind_cell().into(), right.get_bind_cell().into()));
+                }
+                (EquationKind::Connect, EquationKind::Connect) => {
+                    pending.push((
+                        TermPtr::new_var(left.get_connect_left()),
+                        TermPtr::new_var(right.get_connect_left()),
+                    ));
+                    pending.push((
+                        TermPtr::new_var(left.get_connect_right()),
+                        TermPtr::new_var(right.get_connect_right()),
+                    ));
+                }
+                _ => return false,
+            }
+        }
+
+        // var indices of self mapped to var indices of other, and back
+        let mut renaming = HashMap::new();
+        let mut inverse = HashMap::new();
+
+        while let Some((left, right)) = pending.pop() {
+            match (left.get_kind(), right.get_kind()) {
+                (TermKind::Cell, TermKind::Cell) => {
+                    let left_cell = self.heap.copy_cell(left.get_cell_ptr());
+                    let right_cell = other.heap.copy_cell(right.get_cell_ptr());
+                    if left_cell.symbol_name(symbols) != right_cell.symbol_name(symbols)
+                        || left_cell.get_arity() != right_cell.get_arity()
+                    {
+                        return false;
+                    }
+                    for port_num in 0..left_cell.get_arity().port_count() {
+                        let port_num = PortNum::from(port_num);
+                        pending
+                            .push((left_cell.get_port(port_num), right_cell.get_port(port_num)));
+                    }
+                }
+                (TermKind::Var, TermKind::Var) => {
+                    let left_var = left.get_var_ptr();
+                    let right_var = right.get_var_ptr();
+                    if left_var.get_polarity() != right_var.get_polarity() {
+                        return false;
+                    }
+                    let left_index = left_var.get_fvar_ptr().get_index();
+                    let right_index = right_var.get_fvar_ptr().get_index();
+                    match (renaming.get(&left_index), inverse.get(&right_index)) {
+                        (Some(mapped), _) if *mapped == right_index => continue,
+                        (None, None) => {
+                            renaming.insert(left_index, right_index);
+                            inverse.insert(right_index, left_index);
+                        }
+                        _ => return false,
+                    }
+                    match (self.get_var_cell(left_var), other.get_var_cell(right_var)) {
+                        (None, None) => (),
+                        (Some(left_cell), Some(right_cell)) => {
+                            pending.push((left_cell.into(), right_cell.into()))
+                        }
+                        _ => return false,
+                    }
+                }
+                // a var holding a cell stands for the cell itself
+                (TermKind::Var, TermKind::Cell) => match self.get_var_cell(left.get_var_ptr()) {
+                    Some(left_cell) => pending.push((left_cell.into(), right)),
+                    None => return false,
+                },
+                (TermKind::Cell, TermKind::Var) => match other.get_var_cell(right.get_var_ptr()) {
+                    Some(right_cell) => pending.push((left, right_cell.into())),
+                    None => return false,
+                },
+            }
+        }
+        true
+    }
+
+    // Diffing ----------------------------
+
+    /// Compares this net with `other` index by index, so both must come from
+    /// the same heap, e.g. a net and its state after a rewrite.
+    pub fn diff(&self, other: &Net, symbols: &SymbolBook) -> NetDiff {
+        let mut diff = NetDiff::default();
+
+        let cells = |net: &Net| -> HashMap<usize, CellPtr> {
+            net.heap.cells().map(|cell_ptr| (cell_ptr.get_index(), cell_ptr)).collect()
+        };
+        let (old_cells, new_cells) = (cells(self), cells(other));
+        let same_cell = |old_ptr: CellPtr, new_ptr: CellPtr| {
+            let (old, new) = (self.heap.copy_cell(old_ptr), other.heap.copy_cell(new_ptr));
+            old.get_symbol_ptr() == new.get_symbol_ptr()
+                && old.get_arity() == new.get_arity()
+                && (0..old.get_arity().port_count()).map(PortNum::from).all(|port_num| {
+                    old.get_port(port_num).get_ptr() == new.get_port(port_num).get_ptr()
+                })
+        };
+        let mut old_indexes: Vec<&usize> = old_cells.keys().collect();
+        old_indexes.sort();
+        for index in old_indexes {
+            let old_ptr = old_cells[index];
+            match new_cells.get(index) {
+                Some(new_ptr) if same_cell(old_ptr, *new_ptr) => (),
+                _ => {
+                    diff.lines
+                        .push(format!("- {}", self.heap.display_cell(symbols, old_ptr)));
+                    diff.removed_cells.push(old_ptr);
+                }
+            }
+        }
+        let mut new_indexes: Vec<&usize> = new_cells.keys().collect();
+        new_indexes.sort();
+        for index in new_indexes {
+            let new_ptr = new_cells[index];
+            match old_cells.get(index) {
+                Some(old_ptr) if same_cell(*old_ptr, new_ptr) => (),
+                _ => {
+                    diff.lines
+                        .push(format!("+ {}", other.heap.display_cell(symbols, new_ptr)));
+                    diff.added_cells.push(new_ptr);
+                }
+            }
+        }
+
+        // equations are compared as multisets
+        let mut new_body: Vec<EquationBits> = other.body.iter().map(|eqn| eqn.0).collect();
+        for eqn in self.body.iter() {
+            match new_body.iter().position(|new_eqn| *new_eqn == eqn.0) {
+                Some(position) => {
+                    new_body.swap_remove(position);
+                }
+                None => {
+                    diff.lines
+                        .push(format!("- {}", eqn.to_display_string(symbols, &self.heap)));
+                    diff.removed_equations.push(*eqn);
+                }
+            }
+        }
+        let mut old_body: Vec<EquationBits> = self.body.iter().map(|eqn| eqn.0).collect();
+        for eqn in other.body.iter() {
+            match old_body.iter().position(|old_eqn| *old_eqn == eqn.0) {
+                Some(position) => {
+                    old_body.swap_remove(position);
+                }
+                None => {
+                    diff.lines
+                        .push(format!("+ {}", eqn.to_display_string(symbols, &other.heap)));
+                    diff.added_equations.push(*eqn);
+                }
+            }
+        }
+
+        let vars = |net: &Net| -> HashMap<usize, (VarPtr, Option<CellPtr>)> {
+            net.heap
+                .vars()
+                .map(|var_ptr| {
+                    let value = net.heap.get_var(PVarPtr::wire(var_ptr).0).get_store();
+                    (var_ptr.get_index(), (var_ptr, value.get_cell_ptr()))
+                })
+                .collect()
+        };
+        let (old_vars, new_vars) = (vars(self), vars(other));
+        let mut indexes: Vec<&usize> = old_vars.keys().chain(new_vars.keys()).collect();
+        indexes.sort();
+        indexes.dedup();
+        let display = |net: &Net, value: Option<CellPtr>| match value {
+            Some(cell_ptr) => net.heap.display_cell(symbols, cell_ptr).to_string(),
+            None => "_".to_string(),
+        };
+        for index in indexes {
+            let (old, new) = (old_vars.get(index), new_vars.get(index));
+            let (old_value, new_value) = (old.and_then(|var| var.1), new.and_then(|var| var.1));
+            if old_value == new_value {
+                continue;
+            }
+            diff.lines.push(format!("- x.{} = {}", index, display(self, old_value)));
+            diff.lines.push(format!("+ x.{} = {}", index, display(other, new_value)));
+            let var_ptr = new.or(old).unwrap().0;
+            diff.changed_vars.push((var_ptr, old_value, new_value));
+        }
+        diff
+    }
+
+    // Diagrams ---------------------------
+
+    /// Lists the wires of the net, one per line, for reading a net in a
+    /// terminal. A redex is `(S#0 ⋈ add#1)` and a var is drawn between the two
+    /// ends it joins, e.g. `(S#0:0 --x.2-- add#1:1)` when port 0 of cell 0
+    /// and port 1 of cell 1 hold the ends of `x.2`. An end is a cell port, a
+    /// cell held by the var, another var it is connected to, or `head`, and a
+    /// missing end is `?`. Cells nested in a port are joined with `---`.
+    pub fn to_interaction_diagram(&self, symbols: &SymbolBook) -> String {
+        let cell_name = |cell_ptr: CellPtr| {
+            let name = self.heap.get_cell(cell_ptr).symbol_name(symbols);
+            let name = name.map_or("?", |name| name.0);
+            format!("{}#{}", name, cell_ptr.get_index())
+        };
+        let var_name = |var_ptr: PVarPtr| match self.heap.get_var(var_ptr) {
+            Var::Bound(_) => format!("x.{}", var_ptr.get_fvar_ptr().get_index()),
+            Var::Free(_) => format!("_.{}", var_ptr.get_fvar_ptr().get_index()),
+        };
+
+        let mut lines = Vec::new();
+        // the ends of each var by var index, with the var to name it by
+        let mut wires: HashMap<usize, (PVarPtr, Vec<String>)> = HashMap::new();
+        let mut add_end = |var_ptr: PVarPtr, end: String| {
+            let index = var_ptr.get_fvar_ptr().get_index();
+            let (_, ends) = wires.entry(index).or_insert_with(|| (var_ptr, Vec::new()));
+            if !ends.contains(&end) {
+                ends.push(end);
+            }
+        };
+
+        for eqn in self.body.iter() {
+            match eqn.get_kind() {
+                EquationKind::Redex => {
+                    let (ctr, fun) = (eqn.get_redex_ctr(), eqn.get_redex_fun());
+                    lines.push(format!("({} ⋈ {})", cell_name(ctr), cell_name(fun)));
+                }
+                EquationKind::Bind => add_end(eqn.get_bind_var(), cell_name(eqn.get_bind_cell())),
+                EquationKind::Connect => {
+                    let (left, right) = (eqn.get_connect_left(), eqn.get_connect_right());
+                    add_end(left, var_name(right));
+                    add_end(right, var_name(left));
+                }
+            }
+        }
+        for var_ptr in self.head.iter().copied() {
+            add_end(var_ptr, "head".to_string());
+        }
+        let mut cell_ptrs: Vec<CellPtr> = self.heap.cells().collect();
+        cell_ptrs.sort_by_key(|cell_ptr| cell_ptr.get_index());
+        for cell_ptr in cell_ptrs {
+            let cell = self.heap.copy_cell(cell_ptr);
+            for port_num in (0..cell.get_arity().port_count()).map(PortNum::from) {
+                let port = cell.get_port(port_num);
+                let end = format!("{}:{}", cell_name(cell_ptr), port_num as usize);
+                match port.get_kind() {
+                    TermKind::Var => add_end(port.get_var_ptr(), end),
+                    TermKind::Cell => {
+                        lines.push(format!("({} --- {})", end, cell_name(port.get_cell_ptr())))
+                    }
+                }
+            }
+        }
+        for var_ptr in self.heap.vars() {
+            let var_ptr = PVarPtr::wire(var_ptr).0;
+            match self.heap.get_var(var_ptr).get_store().get_value() {
+                NetValue::Empty => (),
+                NetValue::Cell(cell_ptr) => add_end(var_ptr, cell_name(cell_ptr)),
+                NetValue::Link(linked_ptr) => add_end(var_ptr, var_name(linked_ptr)),
+            }
+        }
+
+        let mut wires: Vec<_> = wires.into_iter().collect();
+        wires.sort_by_key(|(index, _)| *index);
+        for (_, (var_ptr, ends)) in wires {
+            let end = |nth: usize| ends.get(nth).map_or("?", |end| end.as_str());
+            let extra: String = ends.iter().skip(2).map(|end| format!(" {}", end)).collect();
+            lines.push(format!("({} --{}-- {}{})", end(0), var_name(var_ptr), end(1), extra));
+        }
+        lines.iter().map(|line| format!("{}\n", line)).collect()
+    }
+
+    // Garbage collection ---------------
+
+    /// Checks everything reachable from the head vars and the pending equations:
+    /// referenced cells and vars must be allocated, polarities must match, and
+    /// head vars must be free. Returns every problem found.
+    pub fn validate(&self) -> Result<(), Vec<NetError>> {
+        let mut errors = Vec::new();
+        let mut visited_cells = HashSet::new();
+        let mut visited_vars = HashSet::new();
+        let mut pending = Vec::new();
+
+        for var_ptr in self.head.iter().copied() {
+            if self.heap.contains_var(var_ptr) && self.heap.get_var(var_ptr).is_bound() {
+                errors.push(NetError::BoundHeadVar(var_ptr));
+            }
+            pending.push(TermPtr::new_var(var_ptr));
+        }
+        for eqn in self.body.iter() {
+            match eqn.get_kind() {
+                EquationKind::Redex => {
+                    let (ctr_ptr, fun_ptr) = (eqn.get_redex_ctr(), eqn.get_redex_fun());
+                    if ctr_ptr.get_polarity() != Polarity::Pos
+                        || fun_ptr.get_polarity() != Polarity::Neg
+                    {
+                        errors.push(NetError::RedexPolarity(ctr_ptr, fun_ptr));
+                    }
+                    pending.push(TermPtr::new_cell(ctr_ptr));
+                    pending.push(TermPtr::new_cell(fun_ptr));
+                }
+                EquationKind::Bind => {
+                    pending.push(TermPtr::new_var(eqn.get_bind_var()));
+                    pending.push(TermPtr::new_cell(eqn.get_bind_cell()));
+                }
+                EquationKind::Connect => {
+                    let (left, right) = (eqn.get_connect_left(), eqn.get_connect_right());
+                    if left.get_polarity() == right.get_polarity() {
+                        errors.push(NetError::ShortCircuit(left, right));
+                    }
+                    pending.push(TermPtr::new_var(left));
+                    pending.push(TermPtr::new_var(right));
+                }
+            }
+        }
+
+        while let Some(term_ptr) = pending.pop() {
+            match term_ptr.get_kind() {
+                TermKind::Cell => {
+                    let cell_ptr = term_ptr.get_cell_ptr();
+                    if !visited_cells.insert(cell_ptr.get_index()) {
+                        continue;
+                    }
+                    if !self.heap.contains_cell(cell_ptr) {
+                        errors.push(NetError::DanglingCell(cell_ptr));
+                        continue;
+                    }
+                    let cell = self.heap.copy_cell(cell_ptr);
+                    let symbol = self.symbols.get(cell.get_symbol_ptr());
+                    for port_num in (0..cell.get_arity().port_count()).map(PortNum::from) {
+                        let port = cell.get_port(port_num);
+                        if port.get_polarity() == symbol.get_port_polarity(port_num) {
+                            errors.push(NetError::PortPolarity(cell_ptr, port_num));
+                        }
+                        pending.push(port);
+                    }
+                }
+                TermKind::Var => {
+                    let var_ptr = term_ptr.get_var_ptr();
+                    if !visited_vars.insert(var_ptr.get_fvar_ptr().get_index()) {
+                        continue;
+                    }
+                    if !self.heap.contains_var(var_ptr) {
+                        errors.push(NetError::DanglingVar(var_ptr));
+                        continue;
+                    }
+                    match self.heap.get_var(var_ptr).get_store().get_value() {
+                        NetValue::Empty => (),
+                        NetValue::Cell(cell_ptr) => pending.push(TermPtr::new_cell(cell_ptr)),
+                        NetValue::Link(linked_ptr) => pending.push(TermPtr::new_var(linked_ptr)),
+                    }
+                }
+            }
+        }
+
+        match errors.is_empty() {
+            true => Ok(()),
+            false => Err(errors),
+        }
+    }
+
+    /// Frees every cell and var that cannot be reached from the head vars or
+    /// the pending equations. Only safe between evaluations.
+    pub fn gc(&mut self) -> GcStats {
+        let mut marked_cells = HashSet::new();
+        let mut marked_vars = HashSet::new();
+        let mut pending = Vec::new();
+
+        // roots
+        pending.extend(self.head.iter().map(|var_ptr| TermPtr::new_var(*var_ptr)));
+        for eqn in self.body.iter() {
+            match eqn.get_kind() {
+                EquationKind::Redex => {
+                    pending.push(TermPtr::new_cell(eqn.get_redex_ctr()));
+                    pending.push(TermPtr::new_cell(eqn.get_redex_fun()));
+                }
+                EquationKind::Bind => {
+                    pending.push(TermPtr::new_var(eqn.get_bind_var()));
+                    pending.push(TermPtr::new_cell(eqn.get_bind_cell()));
+                }
+                EquationKind::Connect => {
+                    pending.push(TermPtr::new_var(eqn.get_connect_left()));
+                    pending.push(TermPtr::new_var(eqn.get_connect_right()));
+                }
+            }
+        }
+
+        // mark
+        while let Some(term_ptr) = pending.pop() {
+            match term_ptr.get_kind() {
+                TermKind::Cell => {
+                    let cell_ptr = term_ptr.get_cell_ptr();
+                    if !marked_cells.insert(cell_ptr.get_index()) {
+                        continue;
+                    }
+                    let cell = self.heap.copy_cell(cell_ptr);
+                    pending.extend(
+                        (0..cell.get_arity().port_count())
+                            .map(|port_num| cell.get_port(PortNum::from(port_num))),
+                    );
+                }
+                TermKind::Var => {
+                    let var_ptr = term_ptr.get_var_ptr();
+                    if !marked_vars.insert(var_ptr.get_fvar_ptr().get_index()) {
+                        continue;
+                    }
+                    match self.heap.get_var(var_ptr).get_store().get_value() {
+                        NetValue::Empty => (),
+                        NetValue::Cell(cell_ptr) => pending.push(TermPtr::new_cell(cell_ptr)),
+                        NetValue::Link(linked_ptr) => pending.push(TermPtr::new_var(linked_ptr)),
+                    }
+                }
+            }
+        }
+
+        // sweep
+        let unreachable_cells: Vec<CellPtr> = self
+            .heap
+            .cells()
+            .filter(|cell_ptr| !marked_cells.contains(&cell_ptr.get_index()))
+            .collect();
+        let unreachable_vars: Vec<VarPtr> = self
+            .heap
+            .vars()
+            .filter(|var_ptr| !marked_vars.contains(&var_ptr.get_index()))
+            .collect();
+
+        let mut stats = GcStats::default();
+        for cell_ptr in unreachable_cells {
+            // n-ary cells free their entry in the heap's n-ary ports as well
+            if let SymbolArity::N(port_count) = self.heap.free_cell(cell_ptr).get_arity() {
+                stats.bytes_freed += std::mem::size_of::<NaryPorts>()
+                    + port_count as usize * std::mem::size_of::<TermPtr>();
+            }
+            stats.cells_freed += 1;
+            stats.bytes_freed += std::mem::size_of::<Cell<NetF>>();
+        }
+        for var_ptr in unreachable_vars {
+            self.heap.free_var(PVarPtr::wire(var_ptr).0);
+            stats.vars_freed += 1;
+            stats.bytes_freed += std::mem::size_of::<Var<NetF>>();
+        }
+        debug!("GC: {:?}", stats);
+        stats
+    }
+
+    // Fuzzing ----------------------------
+
+    /// Builds a net of `size` redexes between cells picked uniformly from the
+    /// constructors and functions of `symbols`. Ports are wired to each other
+    /// through random vars, one of which is the single head var, and any var
+    /// end left over is connected to another of opposite polarity when one
+    /// exists. The same seed always gives the same net. Generated nets are not
+    /// guaranteed to be valid, filter them with `validate`.
+    pub fn generate_random(symbols: &'a SymbolBook, seed: u64, size: usize) -> Self {
+        let (ctrs, funs): (Vec<SymbolPtr>, Vec<SymbolPtr>) = symbols
+            .to_sorted_by_index_vec()
+            .into_iter()
+            .map(|(_, symbol_ptr)| symbol_ptr)
+            .partition(|symbol_ptr| symbol_ptr.get_polarity() == Polarity::Pos);
+        assert!(
+            !ctrs.is_empty() && !funs.is_empty(),
+            "Random nets need at least one constructor and one function"
+        );
+
+        let mut net = Net::new(symbols);
+        let mut rng = SplitMix64(seed);
+        // var ends waiting for a port, by polarity
+        let mut open: [Vec<PVarPtr>; 2] = [Vec::new(), Vec::new()];
+        let (neg_pvar, pos_pvar) = net.heap.alloc_fvar_pair(NetVar::default());
+        net.head.push(pos_pvar);
+        open[Polarity::Neg as usize].push(neg_pvar);
+
+        let mut random_cell = |net: &mut Net, rng: &mut SplitMix64, symbol_ptr: SymbolPtr| {
+            let symbol = symbols.get(symbol_ptr);
+            let ports: Vec<TermPtr> = (0..symbol.get_arity().port_count())
+                .map(|port_num| {
+                    let polarity = symbol.get_port_polarity(PortNum::from(port_num)).flip();
+                    let pending = &mut open[polarity as usize];
+                    let var_ptr = match rng.below(2) == 0 && !pending.is_empty() {
+                        true => pending.swap_remove(rng.below(pending.len())),
+                        false => {
+                            let (neg_pvar, pos_pvar) = net.heap.alloc_bvar_pair(NetVar::default());
+                            let (var_ptr, other) = match polarity {
+                                Polarity::Neg => (neg_pvar, pos_pvar),
+                                Polarity::Pos => (pos_pvar, neg_pvar),
+                            };
+                            open[polarity.flip() as usize].push(other);
+                            var_ptr
+                        }
+                    };
+                    TermPtr::new_var(var_ptr)
+                })
+                .collect();
+            match symbol.get_arity() {
+                SymbolArity::Zero => net.heap.cell0(symbol_ptr),
+                SymbolArity::One => net.heap.cell1(symbol_ptr, ports[0]),
+                SymbolArity::Two => net.heap.cell2(symbol_ptr, ports[0], ports[1]),
+                SymbolArity::N(_) => net.heap.cellN(symbol_ptr, &ports),
+            }
+        };
+        for _ in 0..size {
+            let ctr_ptr = ctrs[rng.below(ctrs.len())];
+            let fun_ptr = funs[rng.below(funs.len())];
+            let ctr = random_cell(&mut net, &mut rng, ctr_ptr);
+            let fun = random_cell(&mut net, &mut rng, fun_ptr);
+            net.body.push_back(Equation::redex(ctr, fun));
+        }
+
+        let [mut pos_open, mut neg_open] = open;
+        while let (Some(left), Some(right)) = (neg_open.pop(), pos_open.pop()) {
+            net.body.push_back(Equation::connect(left, right));
+        }
+        net
     }
 
     pub fn display_head(&'a self) -> HeadDisplay {
@@ -156,6 +1246,30 @@ impl<'a> Net<'a> {
             heap: &self.heap,
         }
     }
+
+    /// The head values as nested terms, e.g. `(S (S Z))`, following vars to
+    /// the cells they hold. Meant for nets in normal form.
+    pub fn display_with_values<'s>(&'a self, symbols: &'s SymbolBook) -> NormalFormDisplay<'a, 's> {
+        NormalFormDisplay { net: self, symbols }
+    }
+}
+
+/// A small seeded generator for `Net::generate_random`, see
+/// <https://prng.di.unimi.it/splitmix64.c>.
+struct SplitMix64(u64);
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    // slightly biased for large bounds, fine for picking symbols and ports
+    fn below(&mut self, bound: usize) -> usize {
+        (self.next() % bound as u64) as usize
+    }
 }
 
 impl<'a> Display for Net<'a> {
@@ -171,6 +1285,85 @@ impl<'a> Display for Net<'a> {
     }
 }
 
+/// A chaining alternative to `Net::equations`. Since every method returns the
+/// builder, new cells and vars are handed back through `Option` out-params:
+///
+/// ```ignore
+/// let (mut out, mut z) = (None, None);
+/// let mut builder = net.builder();
+/// builder
+///     .output(&mut out)
+///     .cell0(&Z, &mut z)
+///     .bind(out.unwrap(), z.unwrap());
+/// builder.finish()?;
+/// ```
+pub struct NetBuilder<'b> {
+    builder: EquationBuilder<'b, NetF>,
+}
+impl<'b> NetBuilder<'b> {
+    pub fn cell0(&mut self, name: &SymbolName, cell: &mut Option<CellPtr>) -> &mut Self {
+        *cell = Some(self.builder.cell0(name));
+        self
+    }
+
+    pub fn cell1(
+        &mut self,
+        name: &SymbolName,
+        left_port: TermPtr,
+        cell: &mut Option<CellPtr>,
+    ) -> &mut Self {
+        *cell = Some(self.builder.cell1(name, left_port));
+        self
+    }
+
+    pub fn cell2(
+        &mut self,
+        name: &SymbolName,
+        left_port: TermPtr,
+        right_port: TermPtr,
+        cell: &mut Option<CellPtr>,
+    ) -> &mut Self {
+        *cell = Some(self.builder.cell2(name, left_port, right_port));
+        self
+    }
+
+    pub fn output(&mut self, var: &mut Option<PVarPtr>) -> &mut Self {
+        *var = Some(self.builder.output());
+        self
+    }
+
+    pub fn input(&mut self, var: &mut Option<PVarPtr>) -> &mut Self {
+        *var = Some(self.builder.input());
+        self
+    }
+
+    pub fn var(&mut self, vars: &mut Option<(PVarPtr, PVarPtr)>) -> &mut Self {
+        *vars = Some(self.builder.var());
+        self
+    }
+
+    pub fn redex(&mut self, ctr_ptr: CellPtr, fun_ptr: CellPtr) -> &mut Self {
+        self.builder.redex(ctr_ptr, fun_ptr);
+        self
+    }
+
+    pub fn bind(&mut self, var_ptr: PVarPtr, cell_ptr: CellPtr) -> &mut Self {
+        self.builder.bind(var_ptr, cell_ptr);
+        self
+    }
+
+    pub fn connect(&mut self, left_ptr: PVarPtr, right_ptr: PVarPtr) -> &mut Self {
+        self.builder.connect(left_ptr, right_ptr);
+        self
+    }
+
+    /// Ends the builder and checks what it added in debug builds, like
+    /// `Net::equations` does when its closure returns.
+    pub fn finish(self) -> Result<(), UsageError> {
+        self.builder.build().map(|_| ())
+    }
+}
+
 pub struct HeadDisplay<'a> {
     net: &'a Net<'a>,
 }
@@ -186,7 +1379,7 @@ impl<'a> Display for HeadDisplay<'a> {
                     assert!(fvar.is_free());
                     match fvar {
                         Var::Bound(_) => unreachable!(),
-                        Var::Free(store) => match store.get_cell_ptr() {
+                        Var::Free(_) => match self.net.get_var_cell(fvar_ptr) {
                             Some(cell_ptr) => write!(
                                 f,
                                 " _.{}={}",
@@ -200,3 +1393,859 @@ impl<'a> Display for HeadDisplay<'a> {
             })
     }
 }
+
+/// The head values of a net separated by spaces, `_.N` for an empty head
+/// var and `x.N` for an empty var inside a value. A cell reached again
+/// through its own ports is `…`.
+pub struct NormalFormDisplay<'a, 's> {
+    net: &'a Net<'a>,
+    symbols: &'s SymbolBook,
+}
+impl<'a, 's> NormalFormDisplay<'a, 's> {
+    fn fmt_term(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+        term: TermPtr,
+        path: &mut Vec<CellPtr>,
+    ) -> std::fmt::Result {
+        let cell_ptr = match term.get_kind() {
+            TermKind::Cell => term.get_cell_ptr(),
+            TermKind::Var => match self.net.get_var_cell(term.get_var_ptr()) {
+                Some(cell_ptr) => cell_ptr,
+                None => return write!(f, "{}", self.var_name(term.get_var_ptr())),
+            },
+        };
+        if path.contains(&cell_ptr) {
+            return write!(f, "…");
+        }
+        let cell = self.net.heap.copy_cell(cell_ptr);
+        let name = cell.symbol_name(self.symbols).map_or("?", |name| name.0);
+        if cell.get_arity().port_count() == 0 {
+            return write!(f, "{}", name);
+        }
+        path.push(cell_ptr);
+        write!(f, "({}", name)?;
+        for port_num in (0..cell.get_arity().port_count()).map(PortNum::from) {
+            write!(f, " ")?;
+            self.fmt_term(f, cell.get_port(port_num), path)?;
+        }
+        path.pop();
+        write!(f, ")")
+    }
+
+    fn var_name(&self, var_ptr: PVarPtr) -> String {
+        match self.net.heap.get_var(var_ptr) {
+            Var::Bound(_) => format!("x.{}", var_ptr.get_fvar_ptr().get_index()),
+            Var::Free(_) => format!("_.{}", var_ptr.get_fvar_ptr().get_index()),
+        }
+    }
+}
+impl<'a, 's> Display for NormalFormDisplay<'a, 's> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (nth, var_ptr) in self.net.head.iter().copied().enumerate() {
+            if nth > 0 {
+                write!(f, " ")?;
+            }
+            self.fmt_term(f, var_ptr.into(), &mut Vec::new())?;
+        }
+        Ok(())
+    }
+}
+
+/// A pending equation and, for a redex, the rule that would rewrite it,
+/// displayed as e.g. `(add x Z) = (S Z)  [rule: S ⋈ add]`.
+pub struct EquationWithRule<'s> {
+    pub equation: Equation<NetF>,
+    pub rule: Option<&'s Rule>,
+    symbols: &'s SymbolBook,
+    heap: &'s Heap<NetF>,
+}
+impl<'s> Display for EquationWithRule<'s> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.equation.display_equation(self.symbols, self.heap))?;
+        let name = |symbol_ptr| self.symbols.get_name(symbol_ptr).map_or("?", |name| name.0);
+        match self.rule {
+            Some(rule) => write!(f, "  [rule: {} ⋈ {}]", name(rule.ctr_ptr), name(rule.fun_ptr)),
+            None if self.equation.get_kind() == EquationKind::Redex => write!(f, "  [no rule]"),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inet::{rule::RuleSet, runtime::Runtime};
+
+    #[test]
+    fn test_net_head_values() {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+        symbols.declare_combinator_symbols();
+
+        let mut rules = RuleSet::new(&symbols);
+        rules.define_combinator_rules();
+
+        let mut net = Net::new(&symbols);
+        net.equations(|b| {
+            let dup1 = b.output();
+            let dup2 = b.output();
+            let zero = b.zero();
+            b.duplicate(zero.into(), dup1.into(), dup2.into());
+        });
+        assert!(net.head_values().iter().all(|(_, value)| value.is_none()));
+
+        let runtime = Runtime::new(&rules, false);
+        let net = runtime.eval(net).unwrap();
+
+        let values = net.head_values();
+        assert_eq!(values.len(), 2);
+        for (fvar_ptr, value) in values {
+            assert!(
+                net.head
+                    .iter()
+                    .any(|head| head.get_fvar_ptr().get_index()
+                        == fvar_ptr.get_fvar_ptr().get_index())
+            );
+            let cell = net.heap.get_cell(value.unwrap());
+            assert_eq!(symbols.get_name(cell.get_symbol_ptr()).unwrap(), "Z".into());
+        }
+    }
+
+    #[test]
+    fn test_net_reduce_all_binds() {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+        symbols.declare_arith_symbols();
+        let mut rules = RuleSet::new(&symbols);
+        rules.arith_rules();
+
+        let mut net = Net::new(&symbols);
+        let mut zero = None;
+        net.equations(|b| {
+            let result = b.output();
+            let (x_in, x_out) = b.var();
+            zero = Some(b.zero());
+            b.bind(x_in, zero.unwrap());
+            let one = b.one();
+            let adder = b.adder(result.into(), one.into());
+            b.bind(x_out, adder);
+        });
+        // evaluate the first bind by hand, as if a runtime stopped after it
+        let first = net.body.pop_front().unwrap();
+        let store = net.heap.get_var(first.get_bind_var()).get_store();
+        assert!(matches!(store.set_or_get(zero.unwrap()), NetValue::Empty));
+        assert!(net.is_normal());
+
+        assert_eq!(net.reduce_all_binds(), 1);
+        assert!(!net.is_normal());
+        assert_eq!(net.reduce_all_binds(), 0);
+        let net = Runtime::new(&rules, false).eval(net).unwrap();
+        assert_eq!(net.read_nat(net.single_output().unwrap()), Some(1));
+    }
+
+    #[test]
+    fn test_net_head_named_iter() {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+        symbols.declare_arith_symbols();
+        let mut rules = RuleSet::new(&symbols);
+        rules.arith_rules();
+
+        let net = Net::from_str("< r x | (S Z) ⋈ (add r x) >", &symbols).unwrap();
+        let names: Vec<_> = net.head_named_iter(&symbols).map(|(name, ..)| name).collect();
+        assert_eq!(names, [None, None]);
+
+        let net = Runtime::new(&rules, false).eval(net).unwrap();
+        let head: Vec<_> = net.head_named_iter(&symbols).collect();
+        assert_eq!(head[0].0, Some(&SymbolName("S")));
+        assert_eq!(head[0].2, net.head_values()[0].1);
+        assert_eq!(head[1].0, None);
+    }
+
+    #[test]
+    fn test_net_to_interaction_diagram() {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+        symbols.declare_arith_symbols();
+        let mut rules = RuleSet::new(&symbols);
+        rules.arith_rules();
+
+        let net = Net::from_str("< r | (S x) ⋈ (add r Z), x ← Z >", &symbols).unwrap();
+        assert_eq!(
+            net.to_interaction_diagram(&symbols),
+            "(S#0 ⋈ add#2)\n\
+             (add#2:1 --- Z#1)\n\
+             (head --_.0-- add#2:0)\n\
+             (Z#3 --x.1-- S#0:0)\n"
+        );
+        let net = Runtime::new(&rules, false).eval(net).unwrap();
+        assert_eq!(
+            net.to_interaction_diagram(&symbols),
+            "(head --_.0-- S#0)\n(S#0:0 --x.2-- Z#1)\n"
+        );
+    }
+
+    #[test]
+    fn test_net_display_with_values() {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+        symbols.declare_arith_symbols();
+        let mut rules = RuleSet::new(&symbols);
+        rules.arith_rules();
+
+        let input = "< r s | (S (S Z)) ⋈ (add r (S Z)), s ← (S x), y ← Z, x ↔ y >";
+        let net = Net::from_str(input, &symbols).unwrap();
+        // binds reach the head vars when evaluated
+        assert_eq!(net.display_with_values(&symbols).to_string(), "_.0 _.1");
+        let net = Runtime::new(&rules, false).eval(net).unwrap();
+        assert_eq!(net.display_with_values(&symbols).to_string(), "(S (S (S Z))) (S Z)");
+    }
+
+    #[test]
+    fn test_heap_merge_heap() {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+        symbols.declare_arith_symbols();
+        let mut rules = RuleSet::new(&symbols);
+        rules.arith_rules();
+        let runtime = Runtime::new(&rules, false);
+
+        let mut net = Net::from_str("< r | (S Z) ⋈ (add r Z) >", &symbols).unwrap();
+        // evaluated first, so its head var holds a cell to translate
+        let evaluated = Net::from_str("< s | (S (S Z)) ⋈ (add s Z) >", &symbols).unwrap();
+        let evaluated = runtime.eval(evaluated).unwrap();
+        let pending = Net::from_str("< t | (S x) ⋈ (add t Z), x ← Z >", &symbols).unwrap();
+
+        for other in [evaluated, pending] {
+            let translation = net.heap.merge_heap(other.heap);
+            for var_ptr in other.head {
+                net.head.push(translation.translate_var(var_ptr));
+            }
+            for eqn in other.body {
+                net.body.push_back(match eqn.get_kind() {
+                    EquationKind::Redex => Equation::redex(
+                        translation.translate_cell(eqn.get_redex_ctr()),
+                        translation.translate_cell(eqn.get_redex_fun()),
+                    ),
+                    EquationKind::Bind => Equation::bind(
+                        translation.translate_var(eqn.get_bind_var()),
+                        translation.translate_cell(eqn.get_bind_cell()),
+                    ),
+                    EquationKind::Connect => unreachable!(),
+                });
+            }
+        }
+        assert_eq!(net.validate(), Ok(()));
+
+        let net = runtime.eval(net).unwrap();
+        let values: Vec<_> = net
+            .head_values()
+            .into_iter()
+            .map(|(_, value)| net.read_nat(value.unwrap()))
+            .collect();
+        assert_eq!(values, [Some(1), Some(2), Some(1)]);
+    }
+
+    #[test]
+    fn test_heap_compact() {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+        symbols.declare_arith_symbols();
+        let mut rules = RuleSet::new(&symbols);
+        rules.arith_rules();
+        let runtime = Runtime::new(&rules, false);
+
+        let mut net = Net::new(&symbols);
+        // freed below, leaving holes at the front of both arenas
+        let zero = net.heap.cell0(symbols.get_by_name(&"Z".into()).unwrap());
+        let var = net.heap.fvar(NetVar::default());
+        net.equations(|b| {
+            let result = b.output();
+            let (x_in, x_out) = b.var_named("x");
+            let one = b.one();
+            b.bind(x_in, one);
+            let adder = b.adder(result.into(), x_out.into());
+            let two = b.n(2);
+            b.add(two, adder);
+        });
+        net.heap.free_cell(zero);
+        net.heap.free_var(PVarPtr::wire(var).0);
+        assert!(runtime.step(&mut net));
+        let (cells, vars) = (net.heap.cells.len(), net.heap.vars.len());
+
+        let translation = net.heap.compact();
+        net.apply_translations(&translation);
+        assert_eq!((translation.cells.len(), translation.vars.len()), (cells, vars));
+        assert!(translation.cells.iter().all(|(old, new)| old > new.get_index()));
+        let indices = |ptrs: Vec<usize>| ptrs == (0..ptrs.len()).collect::<Vec<_>>();
+        assert!(indices(net.heap.cells().map(|ptr| ptr.get_index()).collect()));
+        assert!(indices(net.heap.vars().map(|ptr| ptr.get_index()).collect()));
+        assert_eq!(net.validate(), Ok(()));
+        assert!(net.var_names.iter().all(|(_, named)| net.heap.vars.contains(named.ptr)));
+
+        let net = runtime.eval(net).unwrap();
+        assert_eq!(net.read_nat(net.single_output().unwrap()), Some(3));
+    }
+
+    #[test]
+    fn test_net_equations_with_rule() {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+        symbols.declare_arith_symbols();
+        let mut rules = RuleSet::new(&symbols);
+        rules.arith_add_rules();
+
+        let text = "< r s y | (S Z) ⋈ (add r Z), Z ⋈ (sub s Z), x ← Z, x ↔ y >";
+        let net = Net::from_str(text, &symbols).unwrap();
+        let equations = net.equations_with_rule(&rules, &symbols);
+        let displayed: Vec<String> = equations.iter().map(|eqn| eqn.to_string()).collect();
+        assert_eq!(
+            displayed,
+            [
+                "(add _.0 Z) = (S Z)  [rule: S ⋈ add]",
+                "(sub _.1 Z) = Z  [no rule]",
+                "x.3 ← Z",
+                "x.3 ↔ _.2",
+            ]
+        );
+        let add_rule = rules.get_by_symbols(
+            symbols.get_by_name(&"S".into()).unwrap(),
+            symbols.get_by_name(&"add".into()).unwrap(),
+        );
+        assert!(std::ptr::eq(equations[0].rule.unwrap(), rules.get_rule(add_rule.unwrap())));
+        assert!(equations[1..].iter().all(|eqn| eqn.rule.is_none()));
+    }
+
+    #[test]
+    fn test_net_var_name() {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+        symbols.declare_arith_symbols();
+        let mut rules = RuleSet::new(&symbols);
+        rules.arith_rules();
+
+        // 1 + 1 through a named var
+        let mut net = Net::new(&symbols);
+        let mut sum = None;
+        net.equations(|b| {
+            let (sum_input, sum_output) = b.var_named("sum");
+            let one = b.one();
+            let adder = b.adder(sum_input.into(), one.into());
+            let one = b.one();
+            b.add(one, adder);
+            let out = b.output();
+            b.connect(out, sum_output);
+            sum = Some(sum_input.get_fvar_ptr());
+        });
+        let sum = sum.unwrap();
+        assert_eq!(net.var_name(sum), Some("sum"));
+        assert_eq!(net.var_name(net.head[0].get_fvar_ptr()), None);
+
+        let net = Runtime::new(&rules, false).eval(net).unwrap();
+        assert_eq!(net.var_name(sum), Some("sum"));
+        assert_eq!(net.read_nat(net.single_output().unwrap()), Some(2));
+
+        // a var reusing the slot of a freed named var has no name
+        let mut net = Net::new(&symbols);
+        let mut x = None;
+        net.equations(|b| {
+            let (x_input, x_output) = b.var_named("x");
+            let out = b.output();
+            b.connect(out, x_output);
+            let zero = b.zero();
+            b.bind(x_input, zero);
+            x = Some(x_input);
+        });
+        let x = x.unwrap();
+        net.heap.free_var(x);
+        let x = x.get_fvar_ptr();
+        let reused = net.heap.fvar(NetVar::default());
+        assert_eq!(reused.get_index(), x.get_index());
+        assert_eq!(net.var_name(reused), None);
+    }
+
+    #[test]
+    fn test_net_with_capacity_hint() {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+        symbols.declare_arith_symbols();
+        let mut rules = RuleSet::new(&symbols);
+        rules.arith_rules();
+
+        let net = Net::with_capacity_hint(&symbols, &rules, 10);
+        assert_eq!(net.body.capacity(), 10);
+        assert_eq!(net.heap.cells.capacity(), MIN_CAPACITY_HINT);
+        assert_eq!(net.heap.vars.capacity(), MIN_CAPACITY_HINT);
+
+        // 7 equations over 6 rules round up to 2 per rule
+        let net = Net::with_capacity_hint(&symbols, &rules, 1000);
+        assert_eq!(net.heap.cells.capacity(), 6000);
+        assert!(net.heap.cells.capacity() < DEFAULT_CAPACITY);
+    }
+
+    #[test]
+    fn test_net_with_capacity_hint_eval_past_hint() {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+        symbols.declare_arith_symbols();
+        symbols.declare_combinator_symbols();
+        symbols.declare_fib_symbols();
+        let mut rules = RuleSet::new(&symbols);
+        rules.arith_rules();
+        rules.define_combinator_rules();
+        rules.fib_rules();
+
+        // the result alone needs more cells than the hint allocates
+        let mut net = Net::with_capacity_hint(&symbols, &rules, 1);
+        assert_eq!(net.heap.cells.capacity(), MIN_CAPACITY_HINT);
+        net.fib(17);
+        let net = Runtime::new(&rules, false).eval(net).unwrap();
+        assert_eq!(net.read_nat(net.single_output().unwrap()), Some(1597));
+        assert_eq!(net.heap.cells.capacity(), DEFAULT_CAPACITY);
+        assert_eq!(net.heap.vars.capacity(), DEFAULT_CAPACITY);
+    }
+
+    #[test]
+    fn test_net_builder() {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+
+        let mut net = Net::new(&symbols);
+        let (mut out, mut z, mut one) = (None, None, None);
+        let mut builder = net.builder();
+        builder
+            .output(&mut out)
+            .cell0(&"Z".into(), &mut z)
+            .cell1(&"S".into(), z.unwrap().into(), &mut one)
+            .bind(out.unwrap(), one.unwrap());
+        assert_eq!(builder.finish(), Ok(()));
+
+        assert_eq!(net.body.len(), 1);
+        assert_eq!(net.head.len(), 1);
+        assert_eq!(net.heap.cells.len(), 2);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    fn test_net_builder_finish_checks_vars() {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+
+        let mut net = Net::new(&symbols);
+        let (mut out, mut z) = (None, None);
+        let mut builder = net.builder();
+        builder.output(&mut out).cell0(&"Z".into(), &mut z);
+        let index = out.unwrap().get_fvar_ptr().get_index();
+        assert_eq!(builder.finish(), Err(UsageError::UnusedHeadVar(index)));
+
+        // Net::equations logs the error instead
+        net.equations(|b| {
+            b.output();
+        });
+    }
+
+    #[test]
+    fn test_net_copy_with_fresh_vars() {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+        symbols.declare_arith_symbols();
+
+        let mut rules = RuleSet::new(&symbols);
+        rules.arith_rules();
+
+        // 1 + 1
+        let mut net = Net::new(&symbols);
+        net.equations(|b| {
+            let result = b.output();
+            let one = b.one();
+            let adder = b.adder(result.into(), one.into());
+            let one = b.one();
+            b.add(one, adder);
+        });
+
+        let (head, body) = net.copy_with_fresh_vars(&net.heap);
+        assert_eq!(head.len(), 1);
+        assert_eq!(body.len(), net.body.len());
+        assert_ne!(
+            head[0].get_fvar_ptr().get_index(),
+            net.head[0].get_fvar_ptr().get_index()
+        );
+        net.head.extend(head);
+        net.body.extend(body);
+
+        let runtime = Runtime::new(&rules, false);
+        let net = runtime.eval(net).unwrap();
+
+        let values = net.head_values();
+        assert_eq!(values.len(), 2);
+        for (_, value) in values {
+            assert_eq!(net.read_nat(value.unwrap()), Some(2));
+        }
+    }
+
+    #[test]
+    fn test_net_copy_with_fresh_vars_deep() {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+        let zero = symbols.get_by_name(&"Z".into()).unwrap();
+        let succ = symbols.get_by_name(&"S".into()).unwrap();
+
+        // frees a block of cells first so the chain is allocated top down, and
+        // copying the outermost S first has to walk the whole chain
+        let depth = 100_000;
+        let mut net = Net::new(&symbols);
+        let placeholders: Vec<CellPtr> = (0..=depth).map(|_| net.heap.cell0(zero)).collect();
+        for cell_ptr in placeholders {
+            net.heap.free_cell(cell_ptr);
+        }
+        let mut num = net.heap.cell0(zero);
+        for _ in 0..depth {
+            num = net.heap.cell1(succ, num.into());
+        }
+        assert_eq!(num.get_index(), 0);
+        let (neg_pvar, pos_pvar) = net.heap.alloc_fvar_pair(NetVar::default());
+        net.head.push(pos_pvar);
+        net.body.push_back(Equation::bind(neg_pvar, num));
+
+        let target_heap = Heap::new();
+        let (_, body) = net.copy_with_fresh_vars(&target_heap);
+        assert_eq!(target_heap.cell_count_exact(), depth + 1);
+        let mut cell_ptr = body[0].get_bind_cell();
+        for _ in 0..depth {
+            cell_ptr = target_heap.get_cell(cell_ptr).get_left_port().get_cell_ptr();
+        }
+        assert_eq!(target_heap.get_cell(cell_ptr).get_symbol_ptr(), zero);
+    }
+
+    #[test]
+    fn test_net_alpha_equivalent() {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+        symbols.declare_arith_symbols();
+
+        let mut rules = RuleSet::new(&symbols);
+        rules.arith_rules();
+        let runtime = Runtime::new(&rules, false);
+
+        let nat = |n: usize| {
+            let mut net = Net::new(&symbols);
+            net.equations(|b| {
+                let result = b.output();
+                let num = b.n(n);
+                b.bind(result, num);
+            });
+            runtime.eval(net).unwrap()
+        };
+
+        // 1 + 1
+        let mut sum = Net::new(&symbols);
+        sum.equations(|b| {
+            let result = b.output();
+            let one = b.one();
+            let adder = b.adder(result.into(), one.into());
+            let one = b.one();
+            b.add(one, adder);
+        });
+        let sum = runtime.eval(sum).unwrap();
+
+        assert!(sum.alpha_equivalent(&sum, &symbols));
+        assert!(sum.alpha_equivalent(&nat(2), &symbols));
+        assert!(nat(2).alpha_equivalent(&sum, &symbols));
+        assert!(!sum.alpha_equivalent(&nat(3), &symbols));
+        assert!(!sum.alpha_equivalent(&Net::new(&symbols), &symbols));
+    }
+
+    #[test]
+    fn test_net_equations_of_kind() {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+        symbols.declare_arith_symbols();
+
+        let mut net = Net::new(&symbols);
+        net.equations(|b| {
+            let result = b.output();
+            let one = b.one();
+            let adder = b.adder(result.into(), one.into());
+            let one = b.one();
+            b.add(one, adder);
+            let (x_in, x_out) = b.var();
+            let zero = b.zero();
+            b.bind(x_in, zero);
+            let (y_in, y_out) = b.var();
+            b.connect(x_out, y_in);
+            let zero = b.zero();
+            b.bind(y_out, zero);
+        });
+
+        assert_eq!(net.redexes().count(), 1);
+        assert_eq!(net.binds().count(), 2);
+        assert_eq!(net.connects().count(), 1);
+        assert!(net
+            .equations_of_kind(EquationKind::Bind)
+            .all(|eqn| eqn.get_kind() == EquationKind::Bind));
+    }
+
+    #[test]
+    fn test_net_drain_equations_of_kind() {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+        symbols.declare_arith_symbols();
+
+        let mut net = Net::new(&symbols);
+        net.equations(|b| {
+            let (x_in, x_out) = b.var();
+            let zero = b.zero();
+            b.bind(x_in, zero);
+            let result = b.output();
+            let adder = b.adder(result.into(), x_out.into());
+            let one = b.one();
+            b.add(one, adder);
+            let (y_in, y_out) = b.var();
+            let one = b.one();
+            b.bind(y_out, one);
+            let (z_in, z_out) = b.var();
+            b.connect(y_in, z_out);
+            let zero = b.zero();
+            b.bind(z_in, zero);
+        });
+
+        let binds: Vec<_> = net.drain_equations_of_kind(EquationKind::Bind).collect();
+        assert_eq!(binds.len(), 3);
+        assert!(binds.iter().all(|eqn| eqn.get_kind() == EquationKind::Bind));
+        let kinds: Vec<_> = net.body.iter().map(|eqn| eqn.get_kind()).collect();
+        assert_eq!(kinds, [EquationKind::Redex, EquationKind::Connect]);
+        assert_eq!(net.drain_equations_of_kind(EquationKind::Bind).count(), 0);
+    }
+
+    #[test]
+    fn test_net_sort_equations() {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+        symbols.declare_arith_symbols();
+
+        let mut net = Net::new(&symbols);
+        net.equations(|b| {
+            let (y_in, y_out) = b.var();
+            let zero = b.zero();
+            b.bind(y_out, zero);
+            let (x_in, x_out) = b.var();
+            b.connect(x_out, y_in);
+            let zero = b.zero();
+            b.bind(x_in, zero);
+            let result = b.output();
+            let one = b.one();
+            let adder = b.adder(result.into(), one.into());
+            let one = b.one();
+            b.add(one, adder);
+        });
+        net.sort_equations();
+
+        let kinds: Vec<EquationKind> = net.body.iter().map(|eqn| eqn.get_kind()).collect();
+        assert_eq!(
+            kinds,
+            [
+                EquationKind::Redex,
+                EquationKind::Bind,
+                EquationKind::Bind,
+                EquationKind::Connect
+            ]
+        );
+        // binds are ordered by var: y was created before x
+        let bind_vars: Vec<usize> = net
+            .binds()
+            .map(|eqn| eqn.get_bind_var().get_fvar_ptr().get_index())
+            .collect();
+        assert!(bind_vars[0] < bind_vars[1]);
+    }
+
+    #[test]
+    fn test_net_gc() {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+        symbols.declare_arith_symbols();
+        symbols.declare_combinator_symbols();
+        symbols.declare_fib_symbols();
+
+        let mut rules = RuleSet::new(&symbols);
+        rules.arith_rules();
+        rules.define_combinator_rules();
+        rules.fib_rules();
+
+        // fib(8) is computed into a var nobody reads
+        let mut net = Net::new(&symbols);
+        net.equations(|b| {
+            let result = b.output();
+            let zero = b.zero();
+            b.bind(result, zero);
+
+            let unused_input = b.output();
+            let num = b.n(8);
+            b.fibonacci(num.into(), unused_input.into());
+        });
+        // a dangling var would fail the builder's check, drop the head var instead
+        net.head.pop();
+
+        let runtime = Runtime::new(&rules, false);
+        let mut net = runtime.eval(net).unwrap();
+
+        let cells_len = net.heap.cells.len();
+        let vars_len = net.heap.vars.len();
+        let stats = net.gc();
+        assert!(stats.cells_freed >= 22); // S⁲¹ Z
+        assert_eq!(net.heap.cells.len(), cells_len - stats.cells_freed);
+        assert_eq!(net.heap.vars.len(), vars_len - stats.vars_freed);
+        assert_eq!(net.read_nat(net.single_output().unwrap()), Some(0));
+
+        // nothing left to collect
+        assert_eq!(net.gc(), GcStats::default());
+    }
+
+    #[test]
+    fn test_net_gc_nary_ports() {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+        let triple = symbols.declareN(&"Triple".into(), Polarity::Pos, &[Polarity::Neg; 3]);
+
+        let mut net = Net::new(&symbols);
+        let zero = symbols.get_by_name(&"Z".into()).unwrap();
+        let ports: Vec<TermPtr> = (0..3).map(|_| net.heap.cell0(zero).into()).collect();
+        net.heap.cellN(triple, &ports);
+
+        let stats = net.gc();
+        assert_eq!(stats.cells_freed, 4);
+        let nary_bytes = std::mem::size_of::<NaryPorts>() + 3 * std::mem::size_of::<TermPtr>();
+        assert_eq!(stats.bytes_freed, 4 * std::mem::size_of::<Cell<NetF>>() + nary_bytes);
+        assert!(net.heap.nary_ports.get().unwrap().is_empty());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_net_single_output_with_many_outputs() {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+        symbols.declare_combinator_symbols();
+
+        let mut net = Net::new(&symbols);
+        net.equations(|b| {
+            let dup1 = b.output();
+            let dup2 = b.output();
+            let zero = b.zero();
+            b.duplicate(zero.into(), dup1.into(), dup2.into());
+        });
+        net.single_output();
+    }
+
+    #[test]
+    fn test_net_diff() {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+        symbols.declare_arith_symbols();
+        let mut rules = RuleSet::new(&symbols);
+        rules.arith_rules();
+
+        // 1 + 1
+        let one_plus_one = || {
+            let mut net = Net::new(&symbols);
+            net.equations(|b| {
+                let result = b.output();
+                let one = b.one();
+                let adder = b.adder(result.into(), one.into());
+                let one = b.one();
+                b.add(one, adder);
+            });
+            net
+        };
+        let before = one_plus_one();
+        assert!(before.diff(&one_plus_one(), &symbols).is_empty());
+
+        let after = Runtime::new(&rules, false).eval(one_plus_one()).unwrap();
+        let diff = before.diff(&after, &symbols);
+        assert_eq!(diff.removed_equations.len(), 1);
+        assert!(diff.added_equations.is_empty());
+        // the output var now holds 2
+        let output = before.head[0].get_fvar_ptr().get_index();
+        assert!(diff
+            .changed_vars
+            .iter()
+            .any(|(var_ptr, old, new)| var_ptr.get_index() == output
+                && old.is_none()
+                && new.is_some()));
+        let output = diff.to_string();
+        assert!(output.lines().all(|line| line.starts_with("- ") || line.starts_with("+ ")));
+    }
+
+    #[test]
+    fn test_net_complexity_metric() {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+        symbols.declare_arith_symbols();
+        let mut rules = RuleSet::new(&symbols);
+        rules.arith_rules();
+
+        let mut net = Net::new(&symbols);
+        assert_eq!(net.complexity_metric(&rules), 0);
+
+        // 1 + 1
+        net.equations(|b| {
+            let result = b.output();
+            let one = b.one();
+            let adder = b.adder(result.into(), one.into());
+            let one = b.one();
+            b.add(one, adder);
+        });
+        let redex = net.redexes().next().unwrap();
+        let ctr = net.heap.get_cell(redex.get_redex_ctr()).get_symbol_ptr();
+        let fun = net.heap.get_cell(redex.get_redex_fun()).get_symbol_ptr();
+        let rule = rules.get_rule(rules.get_by_symbols(ctr, fun).unwrap());
+        assert_eq!(net.complexity_metric(&rules), 1 + rule.body.len() as u64);
+
+        net.equations(|b| {
+            let (left, right) = (b.input(), b.output());
+            b.connect(left, right);
+        });
+        assert_eq!(
+            net.complexity_metric(&rules),
+            1 + rule.body.len() as u64 + Net::CONNECT_PENALTY
+        );
+    }
+    #[test]
+    fn test_net_var_compare_and_bind() {
+        let var = NetVar::default();
+        let (first, second) = (CellPtr::from(1), CellPtr::from(2));
+        assert!(var.compare_and_bind(NetVar::NULL, first).is_ok());
+        match var.compare_and_bind(NetVar::NULL, second) {
+            Err(NetValue::Cell(cell_ptr)) => assert!(cell_ptr == first),
+            other => panic!("Expected the first cell, got {:?}", other),
+        }
+        assert!(var.get_cell_ptr() == Some(first));
+
+        let var = NetVar::default();
+        var.link(PVarPtr::from(3));
+        assert!(matches!(var.compare_and_bind(NetVar::NULL, first), Err(NetValue::Link(_))));
+    }
+
+    #[test]
+    fn test_net_generate_random() {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+        symbols.declare_arith_symbols();
+        symbols.fun0(&"era".into());
+        let rules = RuleSet::generate_complete(&symbols, |b| b.connect_ports());
+
+        let net = Net::generate_random(&symbols, 42, 20);
+        assert_eq!(net.redexes().count(), 20);
+        assert_eq!(net.head.len(), 1);
+        let again = Net::generate_random(&symbols, 42, 20);
+        assert_eq!(net.to_string(), again.to_string());
+
+        for seed in 0..100 {
+            let net = Net::generate_random(&symbols, seed, 10);
+            if net.validate().is_ok() {
+                let net = Runtime::new(&rules, false).eval(net).unwrap();
+                assert!(net.is_normal(), "seed {}: {}", seed, net);
+            }
+        }
+    }
+}
+
@@ -0,0 +1,895 @@
+//! Text format for nets, as written by `Net`'s `Display`:
+//!
+//! ```text
+//! < x | (S (S Z)) ⋈ (add x Z) >
+//! ```
+//!
+//! Head vars come before the `|`, a head var may carry its value as in
+//! `_.0=(S Z)`. Redexes are written `term = term` or `term ⋈ term`, binds
+//! `var ← term` and connects `var ↔ var`. Any name that is not a symbol is a
+//! var: head vars are used once in the body and other vars exactly twice.
+//! The cell and var counts `Display` appends after the `>` are ignored. Bound
+//! vars holding a cell are written `x.2=...` and cannot be read back.
+//!
+//! Rules are written as `RuleSet` displays them, a redex with a var on every
+//! port, then `⟶` and the body:
+//!
+//! ```text
+//! (add x y) ⋈ (S z)  ⟶  x ← (S w), z ← (add w y)
+//! ```
+//!
+//! Port vars are used once in the body. Commas are optional.
+
+use std::{
+    collections::HashMap,
+    fmt::{Display, Formatter},
+};
+
+use super::{
+    cell::CellPtr,
+    equation::EquationBuilder,
+    net::{Net, NetF},
+    rule::{PortNum, RuleBuilder, RuleError, RulePort, RulePtr, RuleSet},
+    symbol::{Symbol, SymbolArity, SymbolBook, SymbolName},
+    term::TermPtr,
+    var::PVarPtr,
+    Polarity,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseError {
+    UnexpectedEnd,
+    /// The byte offset and text of a token that does not fit.
+    UnexpectedToken(usize, String),
+    /// A cell with the wrong number of ports.
+    InvalidArity(String, usize),
+    /// A redex needs a constructor and a function cell.
+    InvalidRedex(usize),
+    /// A var used the wrong number of times.
+    InvalidVarUse(String, usize),
+    /// A var whose ends cannot have opposite polarities, or a cell in a port
+    /// of the same polarity.
+    InvalidPolarity(String),
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::UnexpectedEnd => write!(f, "unexpected end of input"),
+            ParseError::UnexpectedToken(offset, token) => {
+                write!(f, "unexpected '{}' at {}", token, offset)
+            }
+            ParseError::InvalidArity(name, ports) => {
+                write!(f, "symbol {} does not take {} ports", name, ports)
+            }
+            ParseError::InvalidRedex(offset) => write!(f, "invalid redex at {}", offset),
+            ParseError::InvalidVarUse(name, uses) => {
+                write!(f, "var {} is used {} times", name, uses)
+            }
+            ParseError::InvalidPolarity(name) => write!(f, "{} has no valid polarity", name),
+        }
+    }
+}
+
+impl<'a> Net<'a> {
+    /// Parses a net written in the text format of its `Display`.
+    pub fn from_str(input: &str, symbols: &'a SymbolBook) -> Result<Net<'a>, ParseError> {
+        let ast = Parser::new(input, symbols).parse_net()?;
+        let polarities = ast.infer_polarities(&[])?;
+
+        let mut net = Net::new(symbols);
+        net.equations(|b| {
+            // the net uses the other end of the head var
+            ast.build(b, &polarities, |b, _, polarity| match polarity {
+                Polarity::Neg => b.output(),
+                Polarity::Pos => b.input(),
+            })
+        });
+        Ok(net)
+    }
+}
+
+/// A rule read from the text format, ready to be added to a `RuleSet` of the
+/// same symbols.
+pub struct ParsedRule<'i, 's> {
+    ast: RuleAst<'i, 's>,
+    polarities: Vec<Polarity>,
+}
+
+impl<'i, 's> ParsedRule<'i, 's> {
+    pub fn parse(input: &'i str, symbols: &'s SymbolBook) -> Result<Self, ParseError> {
+        let ast = Parser::new(input, symbols).parse_rule()?;
+        let fixed: Vec<(&str, Polarity)> = ast
+            .ports
+            .iter()
+            .map(|(name, _, polarity)| (*name, *polarity))
+            .collect();
+        let polarities = ast.body.infer_polarities(&fixed)?;
+        Ok(Self { ast, polarities })
+    }
+
+    pub fn get_ctr_name(&self) -> &'s SymbolName {
+        self.ast.ctr
+    }
+
+    pub fn get_fun_name(&self) -> &'s SymbolName {
+        self.ast.fun
+    }
+
+    fn build(&self, b: &mut RuleBuilder) {
+        let ports = &self.ast.ports;
+        self.ast.body.build(b, &self.polarities, |b, index, _| match ports[index].1 {
+            RulePort::Ctr(port_num) => b.ctr_port(port_num),
+            RulePort::Fun(port_num) => b.fun_port(port_num),
+        })
+    }
+}
+
+impl<'a> RuleSet<'a> {
+    /// Parses a rule written in the text format of its `Display` and adds it.
+    pub fn parse_rule(&mut self, input: &str) -> Result<RulePtr, ParseError> {
+        let parsed = ParsedRule::parse(input, self.symbols)?;
+        Ok(self.rule(parsed.get_ctr_name(), parsed.get_fun_name(), |b| parsed.build(b)))
+    }
+
+    /// A rule set with every parsed rule. A rule for a (ctr, fun) pair that
+    /// already has one is left out, and all of them are reported together.
+    pub fn from_rules_iter<'i, I>(symbols: &'a SymbolBook, iter: I) -> Result<Self, RuleError>
+    where
+        I: IntoIterator<Item = ParsedRule<'i, 'a>>,
+    {
+        let mut rules = RuleSet::new(symbols);
+        let mut duplicates = Vec::new();
+        for (index, parsed) in iter.into_iter().enumerate() {
+            let (ctr_name, fun_name) = (parsed.get_ctr_name(), parsed.get_fun_name());
+            let ctr_ptr = symbols.get_by_name(ctr_name).unwrap();
+            let fun_ptr = symbols.get_by_name(fun_name).unwrap();
+            if rules.get_by_symbols(ctr_ptr, fun_ptr).is_some() {
+                duplicates.push((index, ctr_name.clone(), fun_name.clone()));
+                continue;
+            }
+            rules.rule(ctr_name, fun_name, |b| parsed.build(b));
+        }
+        match duplicates.is_empty() {
+            true => Ok(rules),
+            false => Err(RuleError { duplicates }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Token<'i> {
+    Open,
+    Close,
+    Lt,
+    Gt,
+    Bar,
+    Redex,
+    Bind,
+    Connect,
+    Arrow,
+    Name(&'i str),
+}
+
+fn tokenize(input: &str) -> Vec<(usize, Token)> {
+    let mut tokens = Vec::new();
+    let mut name_start = None;
+    let mut in_uses = false;
+    for (offset, c) in input.char_indices() {
+        // rule displays follow bound vars with their uses, e.g. `?0(2)`
+        if in_uses {
+            in_uses = c != ')';
+            continue;
+        }
+        let bvar_start = name_start.filter(|start| input[*start..].starts_with('?'));
+        if let (Some(start), '(') = (bvar_start, c) {
+            tokens.push((start, Token::Name(&input[start..offset])));
+            name_start = None;
+            in_uses = true;
+            continue;
+        }
+        let token = match c {
+            '(' => Some(Token::Open),
+            ')' => Some(Token::Close),
+            '<' => Some(Token::Lt),
+            '>' => Some(Token::Gt),
+            '|' => Some(Token::Bar),
+            '=' | '⋈' => Some(Token::Redex),
+            '←' => Some(Token::Bind),
+            '↔' => Some(Token::Connect),
+            '⟶' => Some(Token::Arrow),
+            ',' => None,
+            c if c.is_whitespace() => None,
+            _ => {
+                name_start.get_or_insert(offset);
+                continue;
+            }
+        };
+        if let Some(start) = name_start.take() {
+            tokens.push((start, Token::Name(&input[start..offset])));
+        }
+        if let Some(token) = token {
+            tokens.push((offset, token));
+        }
+    }
+    if let Some(start) = name_start {
+        tokens.push((start, Token::Name(&input[start..])));
+    }
+    tokens
+}
+
+// every var occurrence gets an id, its polarity is inferred from the context
+enum Term<'s> {
+    Cell(&'s SymbolName, Symbol, Vec<Term<'s>>),
+    Var(usize),
+}
+
+enum Eqn<'s> {
+    Redex(Term<'s>, Term<'s>),
+    Bind(usize, Term<'s>),
+    Connect(usize, usize),
+}
+
+struct NetAst<'i, 's> {
+    head: Vec<&'i str>,
+    body: Vec<Eqn<'s>>,
+    // the var name of each occurrence id
+    occurrences: Vec<&'i str>,
+}
+
+struct RuleAst<'i, 's> {
+    ctr: &'s SymbolName,
+    fun: &'s SymbolName,
+    // the var on each port of the redex and the polarity of its body end
+    ports: Vec<(&'i str, RulePort, Polarity)>,
+    // the port vars are the head
+    body: NetAst<'i, 's>,
+}
+
+struct Parser<'i, 's> {
+    tokens: Vec<(usize, Token<'i>)>,
+    position: usize,
+    symbols: HashMap<&'s str, &'s SymbolName>,
+    book: &'s SymbolBook,
+    occurrences: Vec<&'i str>,
+}
+
+impl<'i, 's> Parser<'i, 's> {
+    fn new(input: &'i str, book: &'s SymbolBook) -> Self {
+        Self {
+            tokens: tokenize(input),
+            position: 0,
+            symbols: book
+                .to_sorted_vec()
+                .into_iter()
+                .map(|(name, _)| (name.0, name))
+                .collect(),
+            book,
+            occurrences: Vec::new(),
+        }
+    }
+
+    fn peek(&self) -> Option<Token<'i>> {
+        self.tokens.get(self.position).map(|(_, token)| *token)
+    }
+
+    fn offset(&self) -> usize {
+        self.tokens.get(self.position).map_or(0, |(offset, _)| *offset)
+    }
+
+    fn next(&mut self) -> Result<Token<'i>, ParseError> {
+        let token = self.peek().ok_or(ParseError::UnexpectedEnd)?;
+        self.position += 1;
+        Ok(token)
+    }
+
+    fn unexpected(&self) -> ParseError {
+        match self.tokens.get(self.position) {
+            Some((offset, token)) => ParseError::UnexpectedToken(*offset, format!("{:?}", token)),
+            None => ParseError::UnexpectedEnd,
+        }
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), ParseError> {
+        match self.peek() {
+            Some(token) if token == expected => {
+                self.position += 1;
+                Ok(())
+            }
+            _ => Err(self.unexpected()),
+        }
+    }
+
+    fn var(&mut self, name: &'i str) -> usize {
+        self.occurrences.push(name);
+        self.occurrences.len() - 1
+    }
+
+    fn parse_net(mut self) -> Result<NetAst<'i, 's>, ParseError> {
+        let mut head = Vec::new();
+        let mut body = Vec::new();
+
+        self.expect(Token::Lt)?;
+        while let Some(Token::Name(name)) = self.peek() {
+            if self.symbols.contains_key(name) {
+                return Err(self.unexpected());
+            }
+            self.position += 1;
+            head.push(name);
+            // a head var with its value, written by Display after evaluating
+            if self.peek() == Some(Token::Redex) {
+                self.position += 1;
+                let var = self.var(name);
+                body.push(Eqn::Bind(var, self.parse_cell()?));
+            }
+        }
+        self.expect(Token::Bar)?;
+        while self.peek() != Some(Token::Gt) {
+            body.push(self.parse_equation()?);
+        }
+        self.expect(Token::Gt)?;
+
+        // skip the cell and var counts
+        if self.peek() == Some(Token::Open) {
+            while self.next()? != Token::Close {}
+        }
+        match self.peek() {
+            None => Ok(NetAst {
+                head,
+                body,
+                occurrences: self.occurrences,
+            }),
+            Some(_) => Err(self.unexpected()),
+        }
+    }
+
+    fn parse_rule(mut self) -> Result<RuleAst<'i, 's>, ParseError> {
+        let offset = self.offset();
+        let left = self.parse_cell()?;
+        self.expect(Token::Redex)?;
+        let right = self.parse_cell()?;
+        // the ports are not occurrences of the body vars
+        let names = std::mem::take(&mut self.occurrences);
+
+        let ((ctr, ctr_ports), (fun, fun_ports)) = match (left, right) {
+            (Term::Cell(left, left_symbol, left_ports), Term::Cell(right, _, right_ports)) => {
+                match left_symbol.get_polarity() {
+                    Polarity::Pos => ((left, left_ports), (right, right_ports)),
+                    Polarity::Neg => ((right, right_ports), (left, left_ports)),
+                }
+            }
+            _ => unreachable!(),
+        };
+        let (ctr_symbol, fun_symbol) = (
+            self.book.get(self.book.get_by_name(ctr).unwrap()),
+            self.book.get(self.book.get_by_name(fun).unwrap()),
+        );
+        if ctr_symbol.get_polarity() != Polarity::Pos || fun_symbol.get_polarity() != Polarity::Neg
+        {
+            return Err(ParseError::InvalidRedex(offset));
+        }
+
+        let mut ports = Vec::new();
+        let sides = [
+            (ctr_ports, ctr_symbol, RulePort::Ctr as fn(PortNum) -> RulePort),
+            (fun_ports, fun_symbol, RulePort::Fun),
+        ];
+        for (terms, symbol, rule_port) in sides {
+            for (port_num, term) in terms.into_iter().enumerate() {
+                let port_num = PortNum::from(port_num);
+                let name = match term {
+                    Term::Var(occurrence) => names[occurrence],
+                    Term::Cell(..) => return Err(ParseError::InvalidRedex(offset)),
+                };
+                if ports.iter().any(|(other, _, _)| *other == name) {
+                    return Err(ParseError::InvalidVarUse(name.to_string(), 2));
+                }
+                // the body uses the end opposite to the port
+                let polarity = symbol.get_port_polarity(port_num).flip();
+                ports.push((name, rule_port(port_num), polarity));
+            }
+        }
+
+        self.expect(Token::Arrow)?;
+        let mut body = Vec::new();
+        while self.peek().is_some() {
+            body.push(self.parse_equation()?);
+        }
+        Ok(RuleAst {
+            ctr,
+            fun,
+            body: NetAst {
+                head: ports.iter().map(|(name, _, _)| *name).collect(),
+                body,
+                occurrences: self.occurrences,
+            },
+            ports,
+        })
+    }
+
+    fn parse_equation(&mut self) -> Result<Eqn<'s>, ParseError> {
+        let offset = self.offset();
+        let left = self.parse_term()?;
+        match (self.next()?, left) {
+            (Token::Redex, left) => {
+                let right = self.parse_term()?;
+                // a positive ctr and a negative fun, in either order
+                match (&left, &right) {
+                    (Term::Cell(_, left_symbol, _), Term::Cell(_, right_symbol, _))
+                        if left_symbol.get_polarity() != right_symbol.get_polarity() =>
+                    {
+                        Ok(Eqn::Redex(left, right))
+                    }
+                    _ => Err(ParseError::InvalidRedex(offset)),
+                }
+            }
+            (Token::Bind, Term::Var(var)) => Ok(Eqn::Bind(var, self.parse_cell()?)),
+            (Token::Connect, Term::Var(left)) => match self.parse_term()? {
+                Term::Var(right) => Ok(Eqn::Connect(left, right)),
+                Term::Cell(..) => Err(ParseError::UnexpectedToken(offset, "↔".to_string())),
+            },
+            _ => {
+                self.position -= 1;
+                Err(self.unexpected())
+            }
+        }
+    }
+
+    fn parse_cell(&mut self) -> Result<Term<'s>, ParseError> {
+        let position = self.position;
+        match self.parse_term()? {
+            Term::Var(_) => {
+                self.position = position;
+                Err(self.unexpected())
+            }
+            cell => Ok(cell),
+        }
+    }
+
+    fn parse_term(&mut self) -> Result<Term<'s>, ParseError> {
+        match self.next()? {
+            Token::Name(name) => match self.symbols.get(name) {
+                Some(symbol_name) => self.cell(symbol_name, Vec::new()),
+                None => Ok(Term::Var(self.var(name))),
+            },
+            Token::Open => {
+                let symbol_name = match self.next()? {
+                    Token::Name(name) => match self.symbols.get(name) {
+                        Some(symbol_name) => *symbol_name,
+                        None => {
+                            self.position -= 1;
+                            return Err(self.unexpected());
+                        }
+                    },
+                    _ => {
+                        self.position -= 1;
+                        return Err(self.unexpected());
+                    }
+                };
+                let mut ports = Vec::new();
+                while self.peek() != Some(Token::Close) {
+                    ports.push(self.parse_term()?);
+                }
+                self.expect(Token::Close)?;
+                self.cell(symbol_name, ports)
+            }
+            _ => {
+                self.position -= 1;
+                Err(self.unexpected())
+            }
+        }
+    }
+
+    fn cell(&self, name: &'s SymbolName, ports: Vec<Term<'s>>) -> Result<Term<'s>, ParseError> {
+        let symbol = self.book.get(self.book.get_by_name(name).unwrap());
+        if symbol.get_arity().port_count() != ports.len() {
+            return Err(ParseError::InvalidArity(name.to_string(), ports.len()));
+        }
+        // a nested cell must have the polarity opposite to its port
+        for (port_num, port) in ports.iter().enumerate() {
+            if let Term::Cell(port_name, port_symbol, _) = port {
+                if port_symbol.get_polarity() == symbol.get_port_polarity(PortNum::from(port_num)) {
+                    return Err(ParseError::InvalidPolarity(port_name.to_string()));
+                }
+            }
+        }
+        Ok(Term::Cell(name, symbol, ports))
+    }
+}
+
+impl<'i, 's> NetAst<'i, 's> {
+    /// The polarity of each var occurrence: ports, binds and `fixed` head vars
+    /// fix it, the two ends of a var and of a connect are opposite.
+    fn infer_polarities(&self, fixed: &[(&str, Polarity)]) -> Result<Vec<Polarity>, ParseError> {
+        let mut ends: HashMap<&str, Vec<usize>> = HashMap::new();
+        for (occurrence, name) in self.occurrences.iter().enumerate() {
+            ends.entry(*name).or_default().push(occurrence);
+        }
+        for (name, occurrences) in ends.iter() {
+            let expected = if self.head.contains(name) { 1 } else { 2 };
+            if occurrences.len() != expected {
+                return Err(ParseError::InvalidVarUse(name.to_string(), occurrences.len()));
+            }
+        }
+        if let Some(name) = self.head.iter().find(|name| !ends.contains_key(*name)) {
+            return Err(ParseError::InvalidVarUse(name.to_string(), 0));
+        }
+
+        let mut polarities = vec![None; self.occurrences.len()];
+        let mut connects = Vec::new();
+        for eqn in self.body.iter() {
+            match eqn {
+                Eqn::Redex(left, right) => {
+                    fix_term_polarities(left, &mut polarities);
+                    fix_term_polarities(right, &mut polarities);
+                }
+                Eqn::Bind(var, term) => {
+                    if let Term::Cell(_, symbol, _) = term {
+                        polarities[*var] = Some(symbol.get_polarity().flip());
+                    }
+                    fix_term_polarities(term, &mut polarities);
+                }
+                Eqn::Connect(left, right) => connects.push((*left, *right)),
+            }
+        }
+        for (name, polarity) in fixed.iter() {
+            let occurrence = ends[name][0];
+            match polarities[occurrence] {
+                Some(other) if other != *polarity => {
+                    return Err(ParseError::InvalidPolarity(name.to_string()))
+                }
+                _ => polarities[occurrence] = Some(*polarity),
+            }
+        }
+        // both ends of a bound var and of a connect are opposite
+        let pairs: Vec<(usize, usize)> = ends
+            .values()
+            .filter(|occurrences| occurrences.len() == 2)
+            .map(|occurrences| (occurrences[0], occurrences[1]))
+            .chain(connects.iter().copied())
+            .collect();
+        loop {
+            let mut changed = false;
+            for (left, right) in pairs.iter().copied() {
+                match (polarities[left], polarities[right]) {
+                    (Some(left_polarity), Some(right_polarity)) => {
+                        if left_polarity == right_polarity {
+                            let name = self.occurrences[left];
+                            return Err(ParseError::InvalidPolarity(name.to_string()));
+                        }
+                    }
+                    (Some(polarity), None) => {
+                        polarities[right] = Some(polarity.flip());
+                        changed = true;
+                    }
+                    (None, Some(polarity)) => {
+                        polarities[left] = Some(polarity.flip());
+                        changed = true;
+                    }
+                    (None, None) => (),
+                }
+            }
+            if changed {
+                continue;
+            }
+            // only connected vars are left, their direction is arbitrary
+            match pairs.iter().find(|(left, _)| polarities[*left].is_none()) {
+                Some((left, _)) => polarities[*left] = Some(Polarity::Neg),
+                None => break,
+            }
+        }
+        Ok(polarities.into_iter().map(|polarity| polarity.unwrap()).collect())
+    }
+
+    /// Builds the body, `head_var` gives the end of each head var used in the
+    /// body from its index and polarity.
+    fn build<B: AstBuilder>(
+        &self,
+        b: &mut B,
+        polarities: &[Polarity],
+        mut head_var: impl FnMut(&mut B, usize, Polarity) -> PVarPtr,
+    ) {
+        let mut vars: HashMap<&str, (PVarPtr, PVarPtr)> = HashMap::new();
+        for (index, name) in self.head.iter().enumerate() {
+            let occurrence = self.occurrences.iter().position(|other| other == name).unwrap();
+            let pvar_ptr = head_var(b, index, polarities[occurrence]);
+            vars.insert(*name, (pvar_ptr, pvar_ptr));
+        }
+        let mut pvar = |b: &mut B, occurrence: usize| {
+            let name = self.occurrences[occurrence];
+            let (neg, pos) = *vars.entry(name).or_insert_with(|| b.var());
+            match polarities[occurrence] {
+                Polarity::Neg => neg,
+                Polarity::Pos => pos,
+            }
+        };
+
+        for eqn in self.body.iter() {
+            match eqn {
+                Eqn::Redex(left, right) => {
+                    let left = build_term(b, left, &mut pvar).get_cell_ptr();
+                    let right = build_term(b, right, &mut pvar).get_cell_ptr();
+                    match left.get_polarity() {
+                        Polarity::Pos => b.redex(left, right),
+                        Polarity::Neg => b.redex(right, left),
+                    }
+                }
+                Eqn::Bind(var, term) => {
+                    let var_ptr = pvar(b, *var);
+                    let cell_ptr = build_term(b, term, &mut pvar).get_cell_ptr();
+                    b.bind(var_ptr, cell_ptr);
+                }
+                Eqn::Connect(left, right) => {
+                    let (left, right) = (pvar(b, *left), pvar(b, *right));
+                    b.connect(left, right);
+                }
+            }
+        }
+    }
+}
+
+/// What `NetAst::build` needs from the net and the rule builders.
+trait AstBuilder {
+    fn var(&mut self) -> (PVarPtr, PVarPtr);
+    fn cell(&mut self, name: &SymbolName, arity: SymbolArity, ports: &[TermPtr]) -> CellPtr;
+    fn redex(&mut self, ctr_ptr: CellPtr, fun_ptr: CellPtr);
+    fn bind(&mut self, var_ptr: PVarPtr, cell_ptr: CellPtr);
+    fn connect(&mut self, left_ptr: PVarPtr, right_ptr: PVarPtr);
+}
+
+impl<'a> AstBuilder for EquationBuilder<'a, NetF> {
+    fn var(&mut self) -> (PVarPtr, PVarPtr) {
+        EquationBuilder::var(self)
+    }
+
+    fn cell(&mut self, name: &SymbolName, arity: SymbolArity, ports: &[TermPtr]) -> CellPtr {
+        match (arity, ports) {
+            (SymbolArity::Zero, []) => self.cell0(name),
+            (SymbolArity::One, [port]) => self.cell1(name, *port),
+            (SymbolArity::Two, [left, right]) => self.cell2(name, *left, *right),
+            (_, ports) => self.cellN(name, ports),
+        }
+    }
+
+    fn redex(&mut self, ctr_ptr: CellPtr, fun_ptr: CellPtr) {
+        EquationBuilder::redex(self, ctr_ptr, fun_ptr)
+    }
+
+    fn bind(&mut self, var_ptr: PVarPtr, cell_ptr: CellPtr) {
+        EquationBuilder::bind(self, var_ptr, cell_ptr)
+    }
+
+    fn connect(&mut self, left_ptr: PVarPtr, right_ptr: PVarPtr) {
+        EquationBuilder::connect(self, left_ptr, right_ptr)
+    }
+}
+
+impl<'a, 'b> AstBuilder for RuleBuilder<'a, 'b> {
+    fn var(&mut self) -> (PVarPtr, PVarPtr) {
+        RuleBuilder::var(self)
+    }
+
+    fn cell(&mut self, name: &SymbolName, arity: SymbolArity, ports: &[TermPtr]) -> CellPtr {
+        match (arity, ports) {
+            (SymbolArity::Zero, []) => self.cell0(name),
+            (SymbolArity::One, [port]) => self.cell1(name, *port),
+            (SymbolArity::Two, [left, right]) => self.cell2(name, *left, *right),
+            (_, ports) => self.cellN(name, ports),
+        }
+    }
+
+    fn redex(&mut self, ctr_ptr: CellPtr, fun_ptr: CellPtr) {
+        RuleBuilder::redex(self, ctr_ptr, fun_ptr);
+    }
+
+    fn bind(&mut self, var_ptr: PVarPtr, cell_ptr: CellPtr) {
+        RuleBuilder::bind(self, var_ptr, cell_ptr);
+    }
+
+    fn connect(&mut self, left_ptr: PVarPtr, right_ptr: PVarPtr) {
+        RuleBuilder::connect(self, left_ptr, right_ptr);
+    }
+}
+
+fn fix_term_polarities(term: &Term, polarities: &mut [Option<Polarity>]) {
+    if let Term::Cell(_, symbol, ports) = term {
+        for (port_num, port) in ports.iter().enumerate() {
+            match port {
+                Term::Var(var) => {
+                    let port_polarity = symbol.get_port_polarity(PortNum::from(port_num));
+                    polarities[*var] = Some(port_polarity.flip());
+                }
+                cell => fix_term_polarities(cell, polarities),
+            }
+        }
+    }
+}
+
+fn build_term<B: AstBuilder>(
+    b: &mut B,
+    term: &Term,
+    pvar: &mut impl FnMut(&mut B, usize) -> PVarPtr,
+) -> TermPtr {
+    match term {
+        Term::Var(var) => pvar(b, *var).into(),
+        Term::Cell(name, symbol, ports) => {
+            let ports: Vec<TermPtr> = ports.iter().map(|port| build_term(b, port, pvar)).collect();
+            b.cell(name, symbol.get_arity(), &ports).into()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inet::{rule::RuleSet, runtime::Runtime};
+
+    #[test]
+    fn test_parse_net() {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+        symbols.declare_arith_symbols();
+        let mut rules = RuleSet::new(&symbols);
+        rules.arith_rules();
+
+        let net = Net::from_str("< x | (S (S Z)) ⋈ (add x Z) >", &symbols).unwrap();
+        assert_eq!(net.head.len(), 1);
+        assert_eq!(net.redexes().count(), 1);
+
+        let net = Runtime::new(&rules, false).eval(net).unwrap();
+        assert_eq!(net.read_nat(net.single_output().unwrap()), Some(2));
+    }
+
+    #[test]
+    fn test_parse_net_display_round_trip() {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+        symbols.declare_arith_symbols();
+        let mut rules = RuleSet::new(&symbols);
+        rules.arith_rules();
+
+        // 1 + 1
+        let mut net = Net::new(&symbols);
+        net.equations(|b| {
+            let result = b.output();
+            let (x_in, x_out) = b.var();
+            b.connect(result, x_out);
+            let one = b.one();
+            let adder = b.adder(x_in.into(), one.into());
+            let one = b.one();
+            b.add(one, adder);
+        });
+        let parsed = Net::from_str(&net.to_string(), &symbols).unwrap();
+        assert_eq!(parsed.to_string(), net.to_string());
+        assert!(parsed.alpha_equivalent(&net, &symbols));
+
+        // head values are read back as binds
+        let parsed = Net::from_str("< _.0=(S (S Z)) |  > (3 cells, 1 vars)", &symbols).unwrap();
+        let parsed = Runtime::new(&rules, false).eval(parsed).unwrap();
+        assert_eq!(parsed.read_nat(parsed.single_output().unwrap()), Some(2));
+    }
+
+    #[test]
+    fn test_parse_net_errors() {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+        symbols.declare_arith_symbols();
+
+        let parse = |input| Net::from_str(input, &symbols).err();
+        assert_eq!(parse("< x | (S Z) ⋈ (add x Z)"), Some(ParseError::UnexpectedEnd));
+        assert_eq!(
+            parse("< x | (S Z Z) ⋈ (add x Z) >"),
+            Some(ParseError::InvalidArity("S".to_string(), 2))
+        );
+        assert_eq!(
+            parse("< x | (S y) ⋈ (add x Z) >"),
+            Some(ParseError::InvalidVarUse("y".to_string(), 1))
+        );
+        assert_eq!(parse("< x | x ⋈ (add x Z) >"), Some(ParseError::InvalidRedex(6)));
+        assert_eq!(parse("< x | (S x) ⋈ (S Z) >"), Some(ParseError::InvalidRedex(6)));
+        assert_eq!(
+            parse("< x | (add x Z) ⋈ (add Z Z) >"),
+            Some(ParseError::InvalidPolarity("Z".to_string()))
+        );
+        assert_eq!(parse("< x | (add x Z) ⋈ (add y y) >"), Some(ParseError::InvalidRedex(6)));
+        assert_eq!(
+            parse("< x | x ← (add Z Z) >"),
+            Some(ParseError::InvalidPolarity("Z".to_string()))
+        );
+        assert_eq!(
+            parse("< x | (S Z) ⋈ (add (S x) Z) >"),
+            Some(ParseError::InvalidPolarity("S".to_string()))
+        );
+        assert_eq!(
+            parse("< x | (S Z) ⋈ (add x Z) > )"),
+            Some(ParseError::UnexpectedToken(28, "Close".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_parse_rule() {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+        symbols.declare_arith_symbols();
+        let mut rules = RuleSet::new(&symbols);
+        rules.parse_rule("(add x y) ⋈ Z  ⟶  x ↔ y").unwrap();
+        rules.parse_rule("(S z) ⋈ (add x y)  ⟶  x ← (S w), z ← (add w y)").unwrap();
+        assert_eq!(rules.validate_all_rules(), Ok(()));
+
+        let net = Net::from_str("< r | (S (S Z)) ⋈ (add r (S Z)) >", &symbols).unwrap();
+        let net = Runtime::new(&rules, false).eval(net).unwrap();
+        assert_eq!(net.read_nat(net.single_output().unwrap()), Some(3));
+
+        // the rules read back as displayed
+        let mut all_rules = RuleSet::new(&symbols);
+        all_rules.arith_rules();
+        let mut parsed = RuleSet::new(&symbols);
+        for rule_ptr in all_rules.rules() {
+            let rule = all_rules.display_rule(rule_ptr).to_string();
+            let parsed_ptr = parsed.parse_rule(&rule).unwrap();
+            assert_eq!(parsed.display_rule(parsed_ptr).to_string(), rule);
+        }
+    }
+
+    #[test]
+    fn test_rule_set_from_rules_iter() {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+        symbols.declare_arith_symbols();
+        let parse = |input| ParsedRule::parse(input, &symbols).unwrap();
+
+        let rules = RuleSet::from_rules_iter(
+            &symbols,
+            [
+                parse("(add x y) ⋈ Z  ⟶  x ↔ y"),
+                parse("(S z) ⋈ (add x y)  ⟶  x ← (S w), z ← (add w y)"),
+            ],
+        )
+        .unwrap();
+        let net = Net::from_str("< r | (S Z) ⋈ (add r (S Z)) >", &symbols).unwrap();
+        let net = Runtime::new(&rules, false).eval(net).unwrap();
+        assert_eq!(net.read_nat(net.single_output().unwrap()), Some(2));
+
+        let error = RuleSet::from_rules_iter(
+            &symbols,
+            [
+                parse("(add x y) ⋈ Z  ⟶  x ↔ y"),
+                parse("Z ⋈ (add x y)  ⟶  y ↔ x"),
+                parse("(add x y) ⋈ Z  ⟶  x ↔ y"),
+            ],
+        )
+        .err()
+        .unwrap();
+        let (z, add) = (SymbolName("Z"), SymbolName("add"));
+        assert_eq!(error.duplicates, [(1, z.clone(), add.clone()), (2, z, add)]);
+    }
+
+    #[test]
+    fn test_parse_rule_errors() {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+        symbols.declare_arith_symbols();
+        let mut rules = RuleSet::new(&symbols);
+
+        assert_eq!(
+            rules.parse_rule("(add x y) ⋈ (S Z)  ⟶  x ↔ y").err(),
+            Some(ParseError::InvalidRedex(0))
+        );
+        assert_eq!(
+            rules.parse_rule("(add x y) ⋈ (S z)  ⟶  x ↔ y").err(),
+            Some(ParseError::InvalidVarUse("z".to_string(), 0))
+        );
+        assert_eq!(
+            rules.parse_rule("(add x y) ⋈ Z  ⟶  x ← Z, y ← Z").err(),
+            Some(ParseError::InvalidPolarity("y".to_string()))
+        );
+        assert_eq!(
+            rules.parse_rule("(S z) ⋈ (add x y)  ⟶  x ← (S w), z ← (add (S Z) y)").err(),
+            Some(ParseError::InvalidPolarity("S".to_string()))
+        );
+        assert_eq!(
+            rules.parse_rule("(S z) ⋈ (add x y)  ⟶  (S x) ⋈ (S w), z ← (add w y)").err(),
+            Some(ParseError::InvalidRedex(26))
+        );
+    }
+}
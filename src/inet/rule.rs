@@ -1,16 +1,20 @@
 use std::{
     collections::HashMap,
-    fmt::{Debug, Display, Formatter},
+    fmt::{Debug, Display, Formatter, Write},
+    panic::{catch_unwind, AssertUnwindSafe},
 };
 
-use raw_arena::{Ptr, ArenaValue, arenaraw::RawArena};
+use raw_arena::{Ptr, ArenaValue, arenaraw::{ArenaPtrIter, RawArena}};
 
 use super::{
     cell::CellPtr,
-    equation::{Equation, EquationDisplay, EquationPtr, Equations},
+    equation::{Equation, EquationDisplay, EquationKind, EquationPriority, EquationPtr, Equations},
     heap::{CellDisplay, Heap, VarDisplay},
+    net::Net,
+    parse::ParseError,
+    runtime::Runtime,
     symbol::{Symbol, SymbolArity, SymbolBook, SymbolName, SymbolPtr},
-    term::{TermFamily, TermPtr},
+    term::{TermFamily, TermKind, TermPtr},
     var::{PVarPtr, Var, VarPtr},
     BitSet16, Polarity,
 };
@@ -18,7 +22,7 @@ use super::{
 #[derive(Debug, Clone)]
 pub struct RuleF {}
 impl TermFamily for RuleF {
-    type BoundStore = u8;
+    type BoundStore = RuleBVar;
     type FreeStore = RulePort;
 
     fn display_store(
@@ -29,41 +33,101 @@ impl TermFamily for RuleF {
         index: usize,
     ) -> std::fmt::Result {
         match var {
-            Var::Bound(store) => {
-                write!(f, "?{}", store)
+            // a linear bvar has both ends used once
+            Var::Bound(store) => match store.get_uses() {
+                1 => write!(f, "?{}(1!)", store.get_id()),
+                uses => write!(f, "?{}({})", store.get_id(), uses),
+            },
+            Var::Free(RulePort::Ctr(port_num)) => {
+                write!(f, "C{}", port_num)
             }
-            Var::Free(RulePort::Ctr(PortNum::Zero)) => {
-                write!(f, "C₀")
-            }
-            Var::Free(RulePort::Ctr(PortNum::One)) => {
-                write!(f, "C₁")
-            }
-            Var::Free(RulePort::Fun(PortNum::Zero)) => {
-                write!(f, "F₀")
-            }
-            Var::Free(RulePort::Fun(PortNum::One)) => {
-                write!(f, "F₁")
+            Var::Free(RulePort::Fun(port_num)) => {
+                write!(f, "F{}", port_num)
             }
         }
     }
 }
 
+/// A bound var of a rule and the number of times its ends are used.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct RuleBVar {
+    id: u16,
+    uses: u8,
+}
+
+impl RuleBVar {
+    pub fn new(id: u16) -> Self {
+        Self { id, uses: 0 }
+    }
+
+    pub fn get_id(&self) -> u16 {
+        self.id
+    }
+
+    pub fn get_uses(&self) -> u8 {
+        self.uses
+    }
+
+    fn add_use(&mut self) {
+        self.uses = self.uses.saturating_add(1);
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
 pub enum PortNum {
     Zero = 0,
     One = 1,
+    Two = 2,
+    Three = 3,
+    Four = 4,
+    Five = 5,
+    Six = 6,
+    Seven = 7,
 }
 
 impl PortNum {
     pub fn is_valid_port(&self, arity: SymbolArity) -> bool {
-        match arity {
-            SymbolArity::Zero => false,
-            SymbolArity::One => *self == PortNum::Zero,
-            SymbolArity::Two => true,
+        (*self as usize) < Self::count(arity)
+    }
+
+    /// Like `PortNum::from`, but `None` past the last port number.
+    pub fn from_index(index: usize) -> Option<Self> {
+        match index {
+            0..=7 => Some(PortNum::from(index)),
+            _ => None,
+        }
+    }
+
+    /// The number of ports of a cell with this arity, so the ports of a cell
+    /// are `(0..PortNum::count(arity)).map(PortNum::from)`.
+    pub fn count(arity: SymbolArity) -> usize {
+        arity.port_count()
+    }
+}
+
+impl From<usize> for PortNum {
+    fn from(value: usize) -> Self {
+        match value {
+            0 => PortNum::Zero,
+            1 => PortNum::One,
+            2 => PortNum::Two,
+            3 => PortNum::Three,
+            4 => PortNum::Four,
+            5 => PortNum::Five,
+            6 => PortNum::Six,
+            7 => PortNum::Seven,
+            _ => panic!("Invalid port number: {}", value),
         }
     }
 }
-#[derive(Debug, Clone, Copy)]
+
+impl Display for PortNum {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        const SUBSCRIPTS: [&str; 8] = ["₀", "₁", "₂", "₃", "₄", "₅", "₆", "₇"];
+        f.write_str(SUBSCRIPTS[*self as usize])
+    }
+}
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum RulePort {
     Ctr(PortNum),
     Fun(PortNum),
@@ -121,13 +185,64 @@ impl Debug for RulePtr {
 
 type RuleKey = (usize, usize);
 
+/// Why a rule body is not linear. Bound vars are identified by their index in
+/// the rule.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LinearityError {
+    UnusedBvar(u16),
+    DuplicatedBvar(u16),
+    UnusedFvar(RulePort),
+    DuplicatedFvar(RulePort),
+}
+
+/// A test case of `RuleSet::verify_rule` that failed, with its index.
+#[derive(Debug, Clone, PartialEq)]
+pub enum VerifyError {
+    /// The port terms do not make a valid net with the rule's symbols.
+    InvalidTestCase(usize, ParseError),
+    /// Evaluation panicked, e.g. on a missing rule.
+    EvalFailed(usize),
+    /// Evaluation left redexes or an invalid net.
+    NotNormal(usize),
+}
+
+impl Display for VerifyError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VerifyError::InvalidTestCase(index, error) => {
+                write!(f, "test case {} is invalid: {}", index, error)
+            }
+            VerifyError::EvalFailed(index) => write!(f, "test case {} failed to evaluate", index),
+            VerifyError::NotNormal(index) => write!(f, "test case {} is not normal", index),
+        }
+    }
+}
+
+/// The rules `RuleSet::from_rules_iter` left out: their index in its input
+/// and the (ctr, fun) pair that already had a rule.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuleError {
+    pub duplicates: Vec<(usize, SymbolName, SymbolName)>,
+}
+
+impl Display for RuleError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (index, ctr_name, fun_name) in self.duplicates.iter() {
+            writeln!(f, "rule {}: {} ⋈ {} is already defined", index, ctr_name, fun_name)?;
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug)]
 pub struct Rule {
     pub(crate) ctr_ptr: SymbolPtr,
     pub(crate) fun_ptr: SymbolPtr,
     fvar_ptrs: Vec<PVarPtr>,
-    bvar_count: u8,
+    bvar_count: u16,
     pub body: Vec<EquationPtr>,
+    // `body` in the order the runtime instantiates it
+    by_priority: Vec<EquationPtr>,
 }
 impl Rule {
     pub fn new(ctr_ptr: SymbolPtr, fun_ptr: SymbolPtr) -> Self {
@@ -137,14 +252,15 @@ impl Rule {
             fvar_ptrs: Vec::new(),
             bvar_count: 0,
             body: Vec::new(),
+            by_priority: Vec::new(),
         }
     }
 
     pub fn get_key(&self) -> RuleKey {
-        (self.ctr_ptr.get_index(), self.fun_ptr.get_index())
+        RuleSet::to_key(self.ctr_ptr, self.fun_ptr)
     }
 
-    pub fn get_bvar_count(&self) -> u8 {
+    pub fn get_bvar_count(&self) -> u16 {
         self.bvar_count
     }
 
@@ -155,6 +271,187 @@ impl Rule {
     pub fn body(&self) -> std::slice::Iter<EquationPtr> {
         self.body.iter()
     }
+
+    /// The body ordered by `EquationPriority`, sorted once when the rule is
+    /// added to its rule set.
+    pub fn body_by_priority(&self) -> std::slice::Iter<EquationPtr> {
+        self.by_priority.iter()
+    }
+
+    fn sort_by_priority(&mut self) {
+        self.by_priority = self.body.clone();
+        // stable, so equations of the same priority keep the body order
+        self.by_priority
+            .sort_by_key(|eqn_ptr| EquationPriority::from(eqn_ptr.get_kind()));
+    }
+
+    /// Copies this rule of `src_rules` into `dst_rules` with fresh cells, vars
+    /// and equations, replacing any rule `dst_rules` has for the same pair.
+    /// Symbols are matched by name, so `dst_rules` must declare every symbol
+    /// the rule uses.
+    pub fn clone_into(&self, src_rules: &RuleSet, dst_rules: &mut RuleSet) -> RulePtr {
+        let symbol = |symbol_ptr: SymbolPtr| {
+            let name = src_rules.symbols.get_name_ref(symbol_ptr).unwrap();
+            dst_rules
+                .symbols
+                .get_by_name(name)
+                .unwrap_or_else(|| panic!("Symbol {} is not declared", name))
+        };
+        let mut rule = Rule::new(symbol(self.ctr_ptr), symbol(self.fun_ptr));
+        rule.bvar_count = self.bvar_count;
+
+        // src var indices to dst vars, free vars first to keep their order
+        let mut vars: HashMap<usize, VarPtr> = HashMap::new();
+        let mut copy_var = |var_ptr: PVarPtr| {
+            let dst_var_ptr = *vars.entry(var_ptr.get_fvar_ptr().get_index()).or_insert_with(|| {
+                match src_rules.heap.get_var(var_ptr) {
+                    Var::Bound(store) => dst_rules.heap.bvar(*store),
+                    Var::Free(store) => dst_rules.heap.fvar(*store),
+                }
+            });
+            let (neg_pvar, pos_pvar) = PVarPtr::wire(dst_var_ptr);
+            match var_ptr.get_polarity() {
+                Polarity::Neg => neg_pvar,
+                Polarity::Pos => pos_pvar,
+            }
+        };
+        rule.fvar_ptrs = self.fvar_ptrs.iter().map(|var_ptr| copy_var(*var_ptr)).collect();
+
+        // ports are copied before their cell, which is created with them
+        fn copy_cell(
+            src_rules: &RuleSet,
+            dst_heap: &Heap<RuleF>,
+            symbol: &dyn Fn(SymbolPtr) -> SymbolPtr,
+            copy_var: &mut dyn FnMut(PVarPtr) -> PVarPtr,
+            cell_ptr: CellPtr,
+        ) -> CellPtr {
+            let cell = src_rules.heap.copy_cell(cell_ptr);
+            let ports: Vec<TermPtr> = (0..cell.get_arity().port_count())
+                .map(|port_num| {
+                    let port = cell.get_port(PortNum::from(port_num));
+                    match port.get_kind() {
+                        TermKind::Cell => {
+                            let port_cell = port.get_cell_ptr();
+                            copy_cell(src_rules, dst_heap, symbol, copy_var, port_cell).into()
+                        }
+                        TermKind::Var => copy_var(port.get_var_ptr()).into(),
+                    }
+                })
+                .collect();
+            let symbol_ptr = symbol(cell.get_symbol_ptr());
+            match cell.get_arity() {
+                SymbolArity::Zero => dst_heap.cell0(symbol_ptr),
+                SymbolArity::One => dst_heap.cell1(symbol_ptr, ports[0]),
+                SymbolArity::Two => dst_heap.cell2(symbol_ptr, ports[0], ports[1]),
+                SymbolArity::N(_) => dst_heap.cellN(symbol_ptr, &ports),
+            }
+        }
+
+        for eqn_ptr in self.body.iter() {
+            let eqn = src_rules.get_equation(*eqn_ptr);
+            let cell = |copy_var: &mut dyn FnMut(PVarPtr) -> PVarPtr, cell_ptr| {
+                copy_cell(src_rules, &dst_rules.heap, &symbol, copy_var, cell_ptr)
+            };
+            let eqn = match eqn.get_kind() {
+                EquationKind::Redex => {
+                    let ctr = cell(&mut copy_var, eqn.get_redex_ctr());
+                    Equation::redex(ctr, cell(&mut copy_var, eqn.get_redex_fun()))
+                }
+                EquationKind::Bind => {
+                    let var = copy_var(eqn.get_bind_var());
+                    Equation::bind(var, cell(&mut copy_var, eqn.get_bind_cell()))
+                }
+                EquationKind::Connect => Equation::connect(
+                    copy_var(eqn.get_connect_left()),
+                    copy_var(eqn.get_connect_right()),
+                ),
+            };
+            rule.body.push(dst_rules.body.alloc(eqn));
+        }
+        rule.sort_by_priority();
+
+        let rule_key = rule.get_key();
+        let rule_ptr = dst_rules.rules.alloc(rule);
+        dst_rules.rule_by_symbols.insert(rule_key, rule_ptr.get_index());
+        rule_ptr
+    }
+
+    /// Checks that every port of the interacting cells is used exactly once
+    /// and that both ends of every bound var are used exactly once.
+    pub fn validate_linearity(&self, rules: &RuleSet) -> Result<(), LinearityError> {
+        let ctr_arity = rules.symbols.get(self.ctr_ptr).get_arity();
+        let fun_arity = rules.symbols.get(self.fun_ptr).get_arity();
+        let mut ctr_uses = vec![0; ctr_arity.port_count()];
+        let mut fun_uses = vec![0; fun_arity.port_count()];
+        // uses of the (neg, pos) ends of each bvar
+        let mut bvar_uses = vec![(0, 0); self.bvar_count as usize];
+
+        let mut use_var = |var_ptr: PVarPtr| match rules.heap.get_var(var_ptr) {
+            Var::Bound(bvar) => {
+                let uses = &mut bvar_uses[bvar.get_id() as usize];
+                match var_ptr.get_polarity() {
+                    Polarity::Neg => uses.0 += 1,
+                    Polarity::Pos => uses.1 += 1,
+                }
+            }
+            Var::Free(RulePort::Ctr(port_num)) => ctr_uses[*port_num as usize] += 1,
+            Var::Free(RulePort::Fun(port_num)) => fun_uses[*port_num as usize] += 1,
+        };
+
+        let mut cells = Vec::new();
+        for eqn_ptr in self.body() {
+            let eqn = rules.get_equation(*eqn_ptr);
+            match eqn.get_kind() {
+                EquationKind::Redex => {
+                    cells.push(eqn.get_redex_ctr());
+                    cells.push(eqn.get_redex_fun());
+                }
+                EquationKind::Bind => {
+                    use_var(eqn.get_bind_var());
+                    cells.push(eqn.get_bind_cell());
+                }
+                EquationKind::Connect => {
+                    use_var(eqn.get_connect_left());
+                    use_var(eqn.get_connect_right());
+                }
+            }
+        }
+        while let Some(cell_ptr) = cells.pop() {
+            let cell = rules.heap.copy_cell(cell_ptr);
+            for port_num in 0..cell.get_arity().port_count() {
+                let port = cell.get_port(PortNum::from(port_num));
+                match port.get_kind() {
+                    TermKind::Cell => cells.push(port.get_cell_ptr()),
+                    TermKind::Var => use_var(port.get_var_ptr()),
+                }
+            }
+        }
+
+        for (port_num, uses) in ctr_uses.into_iter().enumerate() {
+            Self::check_fvar_uses(RulePort::Ctr(PortNum::from(port_num)), uses)?;
+        }
+        for (port_num, uses) in fun_uses.into_iter().enumerate() {
+            Self::check_fvar_uses(RulePort::Fun(PortNum::from(port_num)), uses)?;
+        }
+        for (bvar, uses) in bvar_uses.into_iter().enumerate() {
+            match uses {
+                (1, 1) => (),
+                (neg, pos) if neg > 1 || pos > 1 => {
+                    return Err(LinearityError::DuplicatedBvar(bvar as u16))
+                }
+                _ => return Err(LinearityError::UnusedBvar(bvar as u16)),
+            }
+        }
+        Ok(())
+    }
+
+    fn check_fvar_uses(port: RulePort, uses: usize) -> Result<(), LinearityError> {
+        match uses {
+            0 => Err(LinearityError::UnusedFvar(port)),
+            1 => Ok(()),
+            _ => Err(LinearityError::DuplicatedFvar(port)),
+        }
+    }
 }
 
 impl ArenaValue<RulePtr> for Rule {
@@ -184,7 +481,34 @@ impl<'a, 'b> RuleBuilder<'a, 'b> {
         }
     }
 
-    fn build(self) -> RulePtr {
+    /// The symbols the rule set was created with.
+    pub fn symbols(&self) -> &SymbolBook {
+        self.rules.symbols
+    }
+
+    /// Whether the rule's constructor has the port.
+    pub fn has_ctr_port(&self, port_num: PortNum) -> bool {
+        port_num.is_valid_port(self.ctr_symbol.get_arity())
+    }
+
+    /// Whether the rule's function has the port.
+    pub fn has_fun_port(&self, port_num: PortNum) -> bool {
+        port_num.is_valid_port(self.fun_symbol.get_arity())
+    }
+
+    fn build(mut self) -> RulePtr {
+        #[cfg(debug_assertions)]
+        if let Err(error) = self.rule.validate_linearity(self.rules) {
+            panic!(
+                "Rule {} ⋈ {} is not linear: {:?}: {}",
+                self.rules.symbols.display_symbol(self.rule.ctr_ptr),
+                self.rules.symbols.display_symbol(self.rule.fun_ptr),
+                error,
+                self.rules.display_body(&self.rule.body[..])
+            );
+        }
+
+        self.rule.sort_by_priority();
         let rule_key = self.rule.get_key();
 
         let rule_ptr = self.rules.rules.alloc(self.rule);
@@ -203,6 +527,7 @@ impl<'a, 'b> RuleBuilder<'a, 'b> {
     }
 
     pub fn bind(&mut self, var: PVarPtr, cell: CellPtr) -> EquationPtr {
+        self.use_var(var);
         let eqn_ptr = self.rules.body.alloc(Equation::bind(var, cell));
         self.rule.body.push(eqn_ptr);
         eqn_ptr
@@ -213,11 +538,30 @@ impl<'a, 'b> RuleBuilder<'a, 'b> {
             left.get_polarity() == right.get_polarity().flip(),
             "Short-circuit!"
         );
+        self.use_var(left);
+        self.use_var(right);
         let eqn_ptr = self.rules.body.alloc(Equation::connect(left, right));
         self.rule.body.push(eqn_ptr);
         eqn_ptr
     }
 
+    // Chaining variants for rules that do not need the equation ptrs
+
+    pub fn then_redex(&mut self, ctr: CellPtr, fun: CellPtr) -> &mut Self {
+        self.redex(ctr, fun);
+        self
+    }
+
+    pub fn then_bind(&mut self, var: PVarPtr, cell: CellPtr) -> &mut Self {
+        self.bind(var, cell);
+        self
+    }
+
+    pub fn then_connect(&mut self, left: PVarPtr, right: PVarPtr) -> &mut Self {
+        self.connect(left, right);
+        self
+    }
+
     /// ------------------------------------------------
 
     pub fn cell0(&mut self, name: &SymbolName) -> CellPtr {
@@ -234,6 +578,7 @@ impl<'a, 'b> RuleBuilder<'a, 'b> {
             "Short-circuit connecting port for {:?}",
             symbol_ptr
         );
+        self.use_port(port);
         self.rules.heap.cell1(symbol_ptr, port)
     }
 
@@ -255,38 +600,87 @@ impl<'a, 'b> RuleBuilder<'a, 'b> {
             "Short-circuit connecting right port for {}",
             self.rules.symbols.display_symbol(symbol_ptr)
         );
+        self.use_port(left_port);
+        self.use_port(right_port);
         self.rules.heap.cell2(symbol_ptr, left_port, right_port)
     }
 
+    #[allow(non_snake_case)]
+    pub fn cellN(&mut self, name: &SymbolName, ports: &[TermPtr]) -> CellPtr {
+        let symbol_ptr = self.rules.symbols.get_by_name(name).unwrap();
+        let symbol = self.rules.symbols.get(symbol_ptr);
+        assert!(
+            symbol.get_arity().port_count() == ports.len(),
+            "Wrong number of ports for {}",
+            self.rules.symbols.display_symbol(symbol_ptr)
+        );
+        // check port polarities
+        for (port_num, port) in ports.iter().enumerate() {
+            assert!(
+                port.get_polarity()
+                    .is_opposite(symbol.get_port_polarity(PortNum::from(port_num))),
+                "Short-circuit connecting port {} for {}",
+                port_num,
+                self.rules.symbols.display_symbol(symbol_ptr)
+            );
+        }
+        ports.iter().for_each(|port| self.use_port(*port));
+        self.rules.heap.cellN(symbol_ptr, ports)
+    }
+
+    // Cells whose ports are placeholder vars, wired to the given var ends
+    // by a connect each, so the cell can be allocated before what its
+    // ports connect to is built.
+
+    pub fn fresh_cell0(&mut self, name: &SymbolName) -> CellPtr {
+        self.cell0(name)
+    }
+
+    pub fn fresh_cell1(&mut self, name: &SymbolName, port: PVarPtr) -> CellPtr {
+        let port = self.placeholder(port);
+        self.cell1(name, port.into())
+    }
+
+    pub fn fresh_cell2(
+        &mut self,
+        name: &SymbolName,
+        left_port: PVarPtr,
+        right_port: PVarPtr,
+    ) -> CellPtr {
+        let left_port = self.placeholder(left_port);
+        let right_port = self.placeholder(right_port);
+        self.cell2(name, left_port.into(), right_port.into())
+    }
+
+    /// A new var end with the polarity of `port`, its other end connected
+    /// to `port`.
+    fn placeholder(&mut self, port: PVarPtr) -> PVarPtr {
+        let (neg, pos) = self.var();
+        let (placeholder, wire) = match port.get_polarity() {
+            Polarity::Neg => (neg, pos),
+            Polarity::Pos => (pos, neg),
+        };
+        self.connect(wire, port);
+        placeholder
+    }
+
     /// ------------------------------------------------
     fn get_port_polarity(&self, port: RulePort) -> Polarity {
         let (symbol_ptr, symbol, port_num) = match port {
             RulePort::Ctr(port_num) => (self.rule.ctr_ptr, self.ctr_symbol, port_num),
             RulePort::Fun(port_num) => (self.rule.fun_ptr, self.fun_symbol, port_num),
         };
-        match port_num {
-            PortNum::Zero => {
-                assert!(
-                    symbol.get_arity() >= SymbolArity::One,
-                    "Symbol {} has no ports",
-                    self.rules.symbols.display_symbol(symbol_ptr)
-                );
-                return symbol.get_left_polarity();
-            }
-            PortNum::One => {
-                assert!(
-                    symbol.get_arity() == SymbolArity::Two,
-                    "Symbol {} does not have two ports",
-                    self.rules.symbols.display_symbol(symbol_ptr)
-                );
-                return symbol.get_right_polarity();
-            }
-        }
+        assert!(
+            port_num.is_valid_port(symbol.get_arity()),
+            "Symbol {} does not have port {}",
+            self.rules.symbols.display_symbol(symbol_ptr),
+            port_num as usize
+        );
+        symbol.get_port_polarity(port_num)
     }
 
     fn port_var(&mut self, port: RulePort) -> PVarPtr {
-        let var_ptr = self.rules.heap.fvar(port);
-        let (neg_pvar, pos_pvar) = PVarPtr::wire(var_ptr);
+        let (neg_pvar, pos_pvar) = self.rules.heap.alloc_fvar_pair(port);
         match self.get_port_polarity(port) {
             crate::inet::Polarity::Pos => {
                 self.rule.fvar_ptrs.push(pos_pvar);
@@ -315,16 +709,129 @@ impl<'a, 'b> RuleBuilder<'a, 'b> {
         self.port_var(RulePort::Fun(PortNum::One))
     }
 
+    pub fn ctr_port(&mut self, port_num: PortNum) -> PVarPtr {
+        self.port_var(RulePort::Ctr(port_num))
+    }
+
+    pub fn fun_port(&mut self, port_num: PortNum) -> PVarPtr {
+        self.port_var(RulePort::Fun(port_num))
+    }
+
+    /// A default rule body: connects each ctr port to the first unused fun port
+    /// of opposite polarity and binds the ports left over to a nullary symbol,
+    /// which erases them. Panics if no nullary symbol of the needed polarity
+    /// is declared.
+    pub fn connect_ports(&mut self) {
+        let ctr_ports = self.ctr_symbol.get_arity().port_count();
+        let fun_ports = self.fun_symbol.get_arity().port_count();
+        let mut fun_used = vec![false; fun_ports];
+        let mut unconnected = Vec::new();
+        for ctr_port in 0..ctr_ports {
+            let ctr_port = RulePort::Ctr(PortNum::from(ctr_port));
+            let polarity = self.get_port_polarity(ctr_port);
+            let fun_port = (0..fun_ports).find(|fun_port| {
+                !fun_used[*fun_port]
+                    && self.get_port_polarity(RulePort::Fun(PortNum::from(*fun_port)))
+                        == polarity.flip()
+            });
+            match fun_port {
+                Some(fun_port) => {
+                    fun_used[fun_port] = true;
+                    let left = self.port_var(ctr_port);
+                    let right = self.port_var(RulePort::Fun(PortNum::from(fun_port)));
+                    self.connect(left, right);
+                }
+                None => unconnected.push(ctr_port),
+            }
+        }
+        let fun_unused = fun_used.into_iter().enumerate().filter(|(_, used)| !used);
+        unconnected.extend(fun_unused.map(|(fun_port, _)| RulePort::Fun(PortNum::from(fun_port))));
+
+        for port in unconnected {
+            let polarity = self.get_port_polarity(port);
+            let eraser = self
+                .rules
+                .symbols
+                .to_sorted_by_index_vec()
+                .into_iter()
+                .map(|(_, symbol_ptr)| symbol_ptr)
+                .find(|symbol_ptr| {
+                    let symbol = self.rules.symbols.get(*symbol_ptr);
+                    symbol.get_arity() == SymbolArity::Zero && symbol.get_polarity() == polarity
+                })
+                .unwrap_or_else(|| panic!("No nullary symbol with polarity {} to erase", polarity));
+            let var = self.port_var(port);
+            let cell = self.rules.heap.cell0(eraser);
+            self.bind(var, cell);
+        }
+    }
+
+    /// An identity rule body: connects each ctr port to the fun port with the
+    /// same number. Both symbols must have the same arity.
+    pub fn connect_all_fun_ports_to_ctr_ports(&mut self) {
+        assert!(
+            self.ctr_symbol.get_arity() == self.fun_symbol.get_arity(),
+            "Rule {} ⋈ {} has symbols of different arities",
+            self.rules.symbols.display_symbol(self.rule.ctr_ptr),
+            self.rules.symbols.display_symbol(self.rule.fun_ptr)
+        );
+        for port_num in (0..self.ctr_symbol.get_arity().port_count()).map(PortNum::from) {
+            let left = self.ctr_port(port_num);
+            let right = self.fun_port(port_num);
+            self.connect(left, right);
+        }
+    }
+
+    /// An eraser rule body: binds every fun port to a new copy of the ctr,
+    /// which must be nullary, e.g. `Z ⋈ (dup r₀ r₁)  ⟶  r₀ ← Z, r₁ ← Z`.
+    pub fn erase_fun_ports(&mut self) {
+        assert!(
+            self.ctr_symbol.get_arity() == SymbolArity::Zero,
+            "Eraser {} must be nullary",
+            self.rules.symbols.display_symbol(self.rule.ctr_ptr)
+        );
+        for port_num in (0..self.fun_symbol.get_arity().port_count()).map(PortNum::from) {
+            assert!(
+                self.get_port_polarity(RulePort::Fun(port_num)) == self.ctr_symbol.get_polarity(),
+                "Eraser {} cannot be bound to port {} of {}",
+                self.rules.symbols.display_symbol(self.rule.ctr_ptr),
+                port_num as usize,
+                self.rules.symbols.display_symbol(self.rule.fun_ptr)
+            );
+            let var = self.fun_port(port_num);
+            let cell = self.rules.heap.cell0(self.rule.ctr_ptr);
+            self.bind(var, cell);
+        }
+    }
+
     pub fn var(&mut self) -> (PVarPtr, PVarPtr) {
+        assert!(
+            self.rule.bvar_count < u16::MAX,
+            "Max number of bound vars reached for rule {} ⋈ {}",
+            self.rules.symbols.display_symbol(self.rule.ctr_ptr),
+            self.rules.symbols.display_symbol(self.rule.fun_ptr)
+        );
         self.rule.bvar_count += 1;
-        let bvar = self.rules.heap.bvar(self.rule.bvar_count - 1);
-        PVarPtr::wire(bvar)
+        self.rules.heap.alloc_bvar_pair(RuleBVar::new(self.rule.bvar_count - 1))
+    }
+
+    /// Counts a use of a bvar end, rule displays show the count.
+    fn use_var(&mut self, var: PVarPtr) {
+        if let Var::Bound(store) = self.rules.heap.get_var_mut(var) {
+            store.add_use();
+        }
+    }
+
+    fn use_port(&mut self, port: TermPtr) {
+        if port.is_var() {
+            self.use_var(port.get_var_ptr());
+        }
     }
 }
 
 #[derive(Debug)]
 pub struct RuleSet<'a> {
-    symbols: &'a SymbolBook,
+    pub(crate) symbols: &'a SymbolBook,
     rules: Rules,
     rule_by_symbols: HashMap<RuleKey, usize>,
     pub(crate) body: Equations<RuleF>,
@@ -362,6 +869,48 @@ impl<'a> RuleSet<'a> {
         builder.build()
     }
 
+    /// A rule set with a rule for every (ctr, fun) pair of `symbols`, each
+    /// built by `default_body`, e.g. `|b| b.connect_ports()`.
+    pub fn generate_complete<F>(symbols: &'a SymbolBook, default_body: F) -> Self
+    where
+        F: Fn(&mut RuleBuilder),
+    {
+        let mut rules = Self::new(symbols);
+        rules.complete(default_body);
+        rules
+    }
+
+    /// Adds a rule built by `default_body` for every (ctr, fun) pair that has
+    /// none yet, so tests only define the rules they care about.
+    pub fn complete<F>(&mut self, default_body: F)
+    where
+        F: Fn(&mut RuleBuilder),
+    {
+        while let Err((ctr_ptr, fun_ptr)) = self.check_completeness() {
+            let mut builder = RuleBuilder::new(ctr_ptr, fun_ptr, self);
+            default_body(&mut builder);
+            builder.build();
+        }
+    }
+
+    /// Checks that every (ctr, fun) pair of symbols has a rule, returning the
+    /// first pair without one, in symbol index order.
+    pub fn check_completeness(&self) -> Result<(), (SymbolPtr, SymbolPtr)> {
+        let symbol_ptrs = self.symbols.to_sorted_by_index_vec();
+        let (ctrs, funs): (Vec<SymbolPtr>, Vec<SymbolPtr>) = symbol_ptrs
+            .into_iter()
+            .map(|(_, symbol_ptr)| symbol_ptr)
+            .partition(|symbol_ptr| symbol_ptr.get_polarity() == Polarity::Pos);
+        for ctr_ptr in ctrs.iter() {
+            for fun_ptr in funs.iter() {
+                if self.get_by_symbols(*ctr_ptr, *fun_ptr).is_none() {
+                    return Err((*ctr_ptr, *fun_ptr));
+                }
+            }
+        }
+        Ok(())
+    }
+
     pub fn get_by_symbols(&self, ctr: SymbolPtr, fun: SymbolPtr) -> Option<RulePtr> {
         let key = RuleSet::to_key(ctr, fun);
         match self.rule_by_symbols.get(&key) {
@@ -370,6 +919,87 @@ impl<'a> RuleSet<'a> {
         }
     }
 
+    /// The rules where `symbol_ptr` is the ctr or the fun, in definition
+    /// order.
+    pub fn rules_for_symbol(&self, symbol_ptr: SymbolPtr) -> Vec<RulePtr> {
+        let index = symbol_ptr.get_index();
+        let mut rule_ptrs: Vec<RulePtr> = self
+            .rule_by_symbols
+            .iter()
+            .filter(|(key, _)| key.0 == index || key.1 == index)
+            .map(|(_, rule_index)| RulePtr::new(*rule_index))
+            .collect();
+        rule_ptrs.sort_by_key(|rule_ptr| rule_ptr.get_index());
+        rule_ptrs
+    }
+
+    /// Validates the linearity of every rule, stopping at the first failure.
+    pub fn validate_all_rules(&self) -> Result<(), (RulePtr, LinearityError)> {
+        self.rules.iter().try_for_each(|rule_ptr| {
+            self.rules
+                .get(rule_ptr)
+                .unwrap()
+                .validate_linearity(self)
+                .map_err(|error| (rule_ptr, error))
+        })
+    }
+
+    /// Evaluates the redex of a rule once per test case, each giving the ctr
+    /// and fun ports as terms in the net text format, e.g. `(vec!["Z"],
+    /// vec!["r", "(S Z)"])` for `Z ⋈ (add r (S Z))`. Names that are not
+    /// symbols and occur once are the head vars of the net. Every case must
+    /// evaluate sequentially, without panicking, to a valid normal net.
+    pub fn verify_rule(
+        &self,
+        rule_ptr: RulePtr,
+        test_cases: &[(Vec<&str>, Vec<&str>)],
+    ) -> Result<(), VerifyError> {
+        let rule = self.get_rule(rule_ptr);
+        let symbol_names = self.symbols.to_sorted_vec();
+        let cell = |symbol_ptr: SymbolPtr, ports: &[&str]| {
+            let name = self.symbols.get_name_ref(symbol_ptr).unwrap();
+            match ports {
+                [] => name.to_string(),
+                ports => format!("({} {})", name, ports.join(" ")),
+            }
+        };
+        for (index, (ctr_ports, fun_ports)) in test_cases.iter().enumerate() {
+            let words: Vec<&str> = ctr_ports
+                .iter()
+                .chain(fun_ports.iter())
+                .flat_map(|term| term.split(|c: char| c.is_whitespace() || c == '(' || c == ')'))
+                .filter(|word| !word.is_empty())
+                .filter(|word| !symbol_names.iter().any(|(name, _)| name.0 == *word))
+                .collect();
+            let head: Vec<&str> = words
+                .iter()
+                .copied()
+                .filter(|word| words.iter().filter(|other| *other == word).count() == 1)
+                .collect();
+            let input = format!(
+                "< {} | {} ⋈ {} >",
+                head.join(" "),
+                cell(rule.ctr_ptr, ctr_ports),
+                cell(rule.fun_ptr, fun_ports)
+            );
+            let net = Net::from_str(&input, self.symbols)
+                .map_err(|error| VerifyError::InvalidTestCase(index, error))?;
+
+            let runtime = Runtime::new(self, false);
+            let net = catch_unwind(AssertUnwindSafe(|| runtime.eval_seq(net)))
+                .map_err(|_| VerifyError::EvalFailed(index))?;
+            if !net.is_normal() || net.validate().is_err() {
+                return Err(VerifyError::NotNormal(index));
+            }
+        }
+        Ok(())
+    }
+
+    /// Rules in the order they were defined.
+    pub fn rules(&self) -> ArenaPtrIter<Rule, RulePtr> {
+        self.rules.iter()
+    }
+
     pub fn get_rule(&'a self, rule_ptr: RulePtr) -> &'a Rule {
         self.rules.get(rule_ptr).unwrap()
     }
@@ -378,6 +1008,111 @@ impl<'a> RuleSet<'a> {
         self.body.get(ptr).unwrap()
     }
 
+    /// The equations of a rule body, in order.
+    pub fn iter_rule_bodies(&self, rule_ptr: RulePtr) -> impl Iterator<Item = &Equation<RuleF>> {
+        self.rules
+            .get(rule_ptr)
+            .unwrap()
+            .body()
+            .map(|eqn_ptr| self.body.get(*eqn_ptr).unwrap())
+    }
+
+    /// The equations of every rule body, tagged with their rule.
+    pub fn iter_all_rule_equations(&self) -> impl Iterator<Item = (RulePtr, &Equation<RuleF>)> {
+        self.rules.iter().flat_map(move |rule_ptr| {
+            self.iter_rule_bodies(rule_ptr)
+                .map(move |eqn| (rule_ptr, eqn))
+        })
+    }
+
+    /// The symbols of every cell a rule body creates, nested ones included.
+    /// A symbol appears once per cell.
+    pub fn body_symbols(&self, rule_ptr: RulePtr) -> Vec<SymbolPtr> {
+        let mut cells = Vec::new();
+        for eqn in self.iter_rule_bodies(rule_ptr) {
+            match eqn.get_kind() {
+                EquationKind::Redex => {
+                    cells.push(eqn.get_redex_ctr());
+                    cells.push(eqn.get_redex_fun());
+                }
+                EquationKind::Bind => cells.push(eqn.get_bind_cell()),
+                EquationKind::Connect => (),
+            }
+        }
+        let mut symbol_ptrs = Vec::new();
+        while let Some(cell_ptr) = cells.pop() {
+            let cell = self.heap.copy_cell(cell_ptr);
+            symbol_ptrs.push(cell.get_symbol_ptr());
+            for port_num in (0..cell.get_arity().port_count()).map(PortNum::from) {
+                let port = cell.get_port(port_num);
+                if port.is_cell() {
+                    cells.push(port.get_cell_ptr());
+                }
+            }
+        }
+        symbol_ptrs
+    }
+
+    /// Rules that can never fire in a net built from `entry_symbols`: a rule
+    /// fires once both its symbols are reachable, and its body then makes the
+    /// symbols it creates reachable too. Returned in definition order.
+    pub fn dead_rules(&self, entry_symbols: &[SymbolPtr]) -> Vec<RulePtr> {
+        let mut reached = vec![false; self.symbols.len()];
+        for symbol_ptr in entry_symbols {
+            reached[symbol_ptr.get_index()] = true;
+        }
+        let mut fired: Vec<RulePtr> = Vec::new();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for rule_ptr in self.rules() {
+                let rule = self.get_rule(rule_ptr);
+                if fired.contains(&rule_ptr)
+                    || !reached[rule.ctr_ptr.get_index()]
+                    || !reached[rule.fun_ptr.get_index()]
+                {
+                    continue;
+                }
+                fired.push(rule_ptr);
+                for symbol_ptr in self.body_symbols(rule_ptr) {
+                    reached[symbol_ptr.get_index()] = true;
+                }
+                changed = true;
+            }
+        }
+        self.rules()
+            .filter(|rule_ptr| !fired.contains(rule_ptr))
+            .collect()
+    }
+
+    /// A Graphviz graph with a node per symbol and an edge from each symbol of
+    /// a rule to every symbol its body creates, labelled with the rule.
+    pub fn to_dot(&self, symbols: &SymbolBook) -> String {
+        let name = |symbol_ptr: SymbolPtr| {
+            let name = symbols.get_name_ref(symbol_ptr).map_or("?", |name| name.0);
+            name.replace('\\', "\\\\").replace('"', "\\\"")
+        };
+        let mut dot = String::from("digraph rules {\n");
+        for (_, symbol_ptr) in symbols.to_sorted_by_index_vec() {
+            writeln!(dot, "    \"{}\";", name(symbol_ptr)).unwrap();
+        }
+        for rule_ptr in self.rules() {
+            let rule = self.get_rule(rule_ptr);
+            let (ctr, fun) = (name(rule.ctr_ptr), name(rule.fun_ptr));
+            let mut body_symbols = self.body_symbols(rule_ptr);
+            body_symbols.sort_by_key(|symbol_ptr| symbol_ptr.get_index());
+            body_symbols.dedup();
+            for trigger in [&ctr, &fun] {
+                for symbol_ptr in body_symbols.iter() {
+                    let edge = format!("\"{}\" -> \"{}\"", trigger, name(*symbol_ptr));
+                    writeln!(dot, "    {} [label=\"{} ⋈ {}\"];", edge, ctr, fun).unwrap();
+                }
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
     pub fn display_rule(&'a self, rule_ptr: RulePtr) -> RuleDisplay {
         RuleDisplay {
             rule_ptr,
@@ -414,6 +1149,59 @@ impl<'a> Display for RuleSet<'a> {
     }
 }
 
+/// A rule set that owns its symbol book, for when both are built together
+/// and stored side by side. The book cannot change once rules refer to it.
+pub struct RuleSetWithSymbols {
+    // declared first so it is dropped before the book it borrows
+    rules: RuleSet<'static>,
+    symbols: Box<SymbolBook>,
+}
+
+impl RuleSetWithSymbols {
+    pub fn new(symbols: SymbolBook) -> Self {
+        let symbols = Box::new(symbols);
+        // SAFETY: the book is boxed, so it does not move with `self`, and it is
+        // never mutated or dropped while `rules` borrows it. The 'static
+        // lifetime is only handed out shortened to a borrow of `self`.
+        let book: &'static SymbolBook = unsafe { &*(symbols.as_ref() as *const SymbolBook) };
+        Self {
+            rules: RuleSet::new(book),
+            symbols,
+        }
+    }
+
+    /// Owns `symbols` and the rule set `rules_fn` builds against them, or
+    /// returns its error.
+    pub fn try_new<E>(
+        symbols: SymbolBook,
+        rules_fn: impl for<'b> FnOnce(&'b SymbolBook) -> Result<RuleSet<'b>, E>,
+    ) -> Result<Self, E> {
+        let symbols = Box::new(symbols);
+        // SAFETY: as in `new`, and `rules_fn` cannot keep the borrow since it
+        // must accept any lifetime
+        let book: &'static SymbolBook = unsafe { &*(symbols.as_ref() as *const SymbolBook) };
+        Ok(Self {
+            rules: rules_fn(book)?,
+            symbols,
+        })
+    }
+
+    pub fn symbols(&self) -> &SymbolBook {
+        &self.symbols
+    }
+
+    pub fn rules(&self) -> &RuleSet<'_> {
+        &self.rules
+    }
+
+    pub fn rule<F>(&mut self, ctr_name: &SymbolName, fun_name: &SymbolName, body: F) -> RulePtr
+    where
+        F: FnOnce(&mut RuleBuilder),
+    {
+        self.rules.rule(ctr_name, fun_name, body)
+    }
+}
+
 pub struct RuleDisplay<'a> {
     rule_ptr: RulePtr,
     rules: &'a RuleSet<'a>,
@@ -425,16 +1213,29 @@ impl<'a> Display for RuleDisplay<'a> {
         let ctr_name = self.rules.symbols.get_name(rule.ctr_ptr).unwrap();
         let fun_name = self.rules.symbols.get_name(rule.fun_ptr).unwrap();
 
-        match rule.fun_ptr.get_arity() {
+        let fun_arity = self.rules.symbols.get(rule.fun_ptr).get_arity();
+        let ctr_arity = self.rules.symbols.get(rule.ctr_ptr).get_arity();
+
+        match fun_arity {
             SymbolArity::Zero => write!(f, "{}", fun_name),
             SymbolArity::One => write!(f, "({} F₀)", fun_name),
             SymbolArity::Two => write!(f, "({} F₀ F₁)", fun_name),
+            SymbolArity::N(n) => (0..n as usize)
+                .fold(write!(f, "({}", fun_name), |result, port| {
+                    result.and_then(|_| write!(f, " F{}", PortNum::from(port)))
+                })
+                .and_then(|_| write!(f, ")")),
         }
         .and_then(|_| write!(f, " ⋈ "))
-        .and_then(|_| match rule.ctr_ptr.get_arity() {
+        .and_then(|_| match ctr_arity {
             SymbolArity::Zero => write!(f, "{}", ctr_name),
             SymbolArity::One => write!(f, "({} C₀)", ctr_name),
             SymbolArity::Two => write!(f, "({} C₀ C₁)", ctr_name),
+            SymbolArity::N(n) => (0..n as usize)
+                .fold(write!(f, "({}", ctr_name), |result, port| {
+                    result.and_then(|_| write!(f, " C{}", PortNum::from(port)))
+                })
+                .and_then(|_| write!(f, ")")),
         })
         .and_then(|_| write!(f, "  ⟶  "))
         .and_then(|_| self.rules.display_body(&rule.body[..]).fmt(f))
@@ -447,24 +1248,96 @@ pub struct RuleBodyDisplay<'a> {
 }
 impl<'a> Display for RuleBodyDisplay<'a> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        self.body
-            .iter()
-            .fold(Ok(()), |result, eqn_ptr| {
-                result.and_then(|_| {
-                    let equation = self.rules.body.get(*eqn_ptr).unwrap();
-                    write!(f, ", {}", self.rules.display_equation(equation))
-                })
+        self.body.iter().enumerate().fold(Ok(()), |result, (index, eqn_ptr)| {
+            result.and_then(|_| {
+                let equation = self.rules.body.get(*eqn_ptr).unwrap();
+                let separator = if index == 0 { "" } else { ", " };
+                write!(f, "{}{}", separator, self.rules.display_equation(equation))
             })
-            .and_then(|_| write!(f, ""))
+        })
     }
 }
 
+#[cfg(test)]
 mod tests {
 
-    use crate::inet::Polarity;
+    use crate::inet::{net::Net, runtime::Runtime, Polarity};
 
     use super::*;
 
+    #[test]
+    fn test_rule_display() {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+        symbols.declare_arith_symbols();
+        let mut rules = RuleSet::new(&symbols);
+        rules.arith_add_rules();
+
+        let add = symbols.get_by_name(&"add".into()).unwrap();
+        let s = symbols.get_by_name(&"S".into()).unwrap();
+        let rule_ptr = rules.get_by_symbols(s, add).unwrap();
+        let display = rules.display_rule(rule_ptr).to_string();
+        assert_eq!(display, "(add F₀ F₁) ⋈ (S C₀)  ⟶  F₀ ← (S ?0(2)), C₀ ← (add ?0(2) F₁)");
+
+        let mut parsed = RuleSet::new(&symbols);
+        let parsed_ptr = parsed.parse_rule(&display).unwrap();
+        assert_eq!(parsed.display_rule(parsed_ptr).to_string(), display);
+    }
+
+    #[test]
+    fn test_rule_set_to_dot() {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+        symbols.declare_arith_symbols();
+        let mut rules = RuleSet::new(&symbols);
+        rules.arith_add_rules();
+
+        let dot = rules.to_dot(&symbols);
+        assert!(dot.starts_with("digraph rules {\n    \"Z\";\n    \"S\";\n"));
+        assert!(dot.ends_with("}\n"));
+        assert!(dot.contains("    \"S\" -> \"add\" [label=\"S ⋈ add\"];\n"));
+        assert!(dot.contains("    \"add\" -> \"S\" [label=\"S ⋈ add\"];\n"));
+        // Z ⋈ add only connects ports, so it adds no edge
+        assert!(!dot.contains("label=\"Z ⋈ add\""));
+        assert_eq!(dot.matches(" -> ").count(), 4);
+    }
+
+    #[test]
+    fn test_rule_set_with_symbols() {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+        symbols.declare_arith_symbols();
+        let mut owned = RuleSetWithSymbols::new(symbols);
+        owned.rule(&"Z".into(), &"add".into(), |b| {
+            let add0 = b.fun_port_0();
+            let add1 = b.fun_port_1();
+            b.connect(add0, add1);
+        });
+        // moving it keeps the book the rules point to
+        let owned = Box::new(owned);
+
+        let net = Net::from_str("< r | Z ⋈ (add r (S Z)) >", owned.symbols()).unwrap();
+        let net = Runtime::new(owned.rules(), false).eval(net).unwrap();
+        assert_eq!(net.read_nat(net.single_output().unwrap()), Some(1));
+    }
+
+    #[test]
+    fn test_port_num_from_index_and_count() {
+        assert_eq!(PortNum::from_index(0), Some(PortNum::Zero));
+        assert_eq!(PortNum::from_index(1), Some(PortNum::One));
+        assert_eq!(PortNum::from_index(7), Some(PortNum::Seven));
+        assert_eq!(PortNum::from_index(8), None);
+
+        assert_eq!(PortNum::count(SymbolArity::Zero), 0);
+        assert_eq!(PortNum::count(SymbolArity::One), 1);
+        assert_eq!(PortNum::count(SymbolArity::Two), 2);
+        assert_eq!(PortNum::count(SymbolArity::N(5)), 5);
+        let ports: Vec<PortNum> = (0..PortNum::count(SymbolArity::Two))
+            .map_while(PortNum::from_index)
+            .collect();
+        assert_eq!(ports, [PortNum::Zero, PortNum::One]);
+    }
+
     #[test]
     fn test_rule_ptrs() {
         let mut ptr = RulePtr::new(42);
@@ -473,6 +1346,341 @@ mod tests {
         assert_eq!(ptr.get_index(), 43);
     }
 
+    #[test]
+    fn test_rule_set_validate_all_rules() {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+        symbols.declare_arith_symbols();
+        symbols.declare_combinator_symbols();
+        symbols.declare_fib_symbols();
+
+        let mut rules = RuleSet::new(&symbols);
+        rules.arith_rules();
+        rules.define_combinator_rules();
+        rules.fib_rules();
+
+        assert_eq!(rules.validate_all_rules(), Ok(()));
+    }
+
+    #[test]
+    fn test_rule_set_iter_rule_bodies() {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+        symbols.declare_arith_symbols();
+        let mut rules = RuleSet::new(&symbols);
+        rules.arith_add_rules();
+
+        let rule_ptrs: Vec<RulePtr> = rules.rules().collect();
+        // Z ⋈ add connects, (S n) ⋈ add binds twice
+        let kinds: Vec<EquationKind> = rules
+            .iter_rule_bodies(rule_ptrs[1])
+            .map(|eqn| eqn.get_kind())
+            .collect();
+        assert_eq!(kinds, [EquationKind::Bind, EquationKind::Bind]);
+
+        let all: Vec<(RulePtr, EquationKind)> = rules
+            .iter_all_rule_equations()
+            .map(|(rule_ptr, eqn)| (rule_ptr, eqn.get_kind()))
+            .collect();
+        assert_eq!(
+            all,
+            [
+                (rule_ptrs[0], EquationKind::Connect),
+                (rule_ptrs[1], EquationKind::Bind),
+                (rule_ptrs[1], EquationKind::Bind)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rule_body_by_priority() {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+        symbols.declare_arith_symbols();
+        let mut rules = RuleSet::new(&symbols);
+
+        // (S r0) ⋈ (add l0 l1)  ⟶  l0 = x, x ← (S y), r0 ← (add y l1)
+        let rule_ptr = rules.rule(&"S".into(), &"add".into(), |b| {
+            let (y_neg, y_pos) = b.var();
+            let (x_neg, x_pos) = b.var();
+            let l0 = b.fun_port_0();
+            b.connect(l0, x_pos);
+            let s_y = b.cell1(&"S".into(), y_pos.into());
+            b.bind(x_neg, s_y);
+            let l1 = b.fun_port_1();
+            let add = b.cell2(&"add".into(), y_neg.into(), l1.into());
+            let r0 = b.ctr_port_0();
+            b.bind(r0, add);
+        });
+
+        // the same equations, the connect after the binds
+        let rule = rules.get_rule(rule_ptr);
+        let kinds = |eqn_ptrs: std::slice::Iter<EquationPtr>| {
+            eqn_ptrs.map(|eqn_ptr| eqn_ptr.get_kind()).collect::<Vec<_>>()
+        };
+        let (bind, connect) = (EquationKind::Bind, EquationKind::Connect);
+        assert_eq!(kinds(rule.body()), [connect, bind, bind]);
+        assert_eq!(kinds(rule.body_by_priority()), [bind, bind, connect]);
+        assert_eq!(rule.body_by_priority().nth(2), rule.body().next());
+    }
+
+    #[test]
+    #[should_panic(expected = "UnusedFvar(Fun(One))")]
+    fn test_rule_with_unused_port() {
+        let mut symbols = SymbolBook::new();
+        symbols.ctr0(&"Z".into());
+        symbols.fun2(&"dup".into(), Polarity::Pos, Polarity::Pos);
+        let mut rules = RuleSet::new(&symbols);
+        rules.rule(&"Z".into(), &"dup".into(), |b| {
+            let r0 = b.fun_port_0();
+            let z0 = b.cell0(&"Z".into());
+            b.bind(r0, z0);
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "UnusedBvar(0)")]
+    fn test_rule_with_unused_bvar() {
+        let mut symbols = SymbolBook::new();
+        symbols.ctr0(&"Z".into());
+        symbols.fun1(&"id".into(), Polarity::Pos);
+        let mut rules = RuleSet::new(&symbols);
+        rules.rule(&"Z".into(), &"id".into(), |b| {
+            let (_, x_out) = b.var();
+            let r0 = b.fun_port_0();
+            b.connect(r0, x_out);
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "UnusedBvar(0): F₀ ↔ ?0(1!)")]
+    fn test_rule_display_unused_bvar() {
+        let mut symbols = SymbolBook::new();
+        symbols.ctr0(&"Z".into());
+        symbols.fun1(&"id".into(), Polarity::Pos);
+        let mut rules = RuleSet::new(&symbols);
+        rules.rule(&"Z".into(), &"id".into(), |b| {
+            let (_, x_out) = b.var();
+            let r0 = b.fun_port_0();
+            b.connect(r0, x_out);
+        });
+    }
+
+    #[test]
+    fn test_rule_builder_fresh_cells() {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+        symbols.declare_arith_symbols();
+        let mut rules = RuleSet::new(&symbols);
+        rules.rule(&"Z".into(), &"add".into(), |b| {
+            let (f0, f1) = (b.fun_port_0(), b.fun_port_1());
+            b.connect(f0, f1);
+        });
+        let rule_ptr = rules.rule(&"S".into(), &"add".into(), |b| {
+            let (x_in, x_out) = b.var();
+            let s = b.fresh_cell1(&"S".into(), x_out);
+            let (f0, f1) = (b.fun_port_0(), b.fun_port_1());
+            let add = b.fresh_cell2(&"add".into(), x_in, f1);
+            b.bind(f0, s);
+            let c0 = b.ctr_port_0();
+            b.bind(c0, add);
+        });
+        assert_eq!(
+            rules.display_rule(rule_ptr).to_string(),
+            "(add F₀ F₁) ⋈ (S C₀)  ⟶  ?1(2) ↔ ?0(2), ?2(2) ↔ ?0(2), ?3(2) ↔ F₁, \
+             F₀ ← (S ?1(2)), C₀ ← (add ?2(2) ?3(2))"
+        );
+
+        let net = Net::from_str("< r | (S (S Z)) ⋈ (add r (S Z)) >", &symbols).unwrap();
+        let net = Runtime::new(&rules, false).eval(net).unwrap();
+        assert_eq!(net.read_nat(net.single_output().unwrap()), Some(3));
+    }
+
+    #[test]
+    fn test_rule_builder_then() {
+        let mut symbols = SymbolBook::new();
+        symbols.ctr0(&"Z".into());
+        symbols.fun2(&"dup".into(), Polarity::Pos, Polarity::Pos);
+        let mut rules = RuleSet::new(&symbols);
+        let rule_ptr = rules.rule(&"Z".into(), &"dup".into(), |b| {
+            let r0 = b.fun_port_0();
+            let r1 = b.fun_port_1();
+            let z0 = b.cell0(&"Z".into());
+            let z1 = b.cell0(&"Z".into());
+            b.then_bind(r0, z0).then_bind(r1, z1);
+        });
+        assert_eq!(rules.get_rule(rule_ptr).body().len(), 2);
+    }
+
+    #[test]
+    fn test_rule_builder_connect_all_and_erase() {
+        let mut symbols = SymbolBook::new();
+        symbols.ctr0(&"Z".into());
+        symbols.ctr2(&"pair".into(), Polarity::Pos, Polarity::Pos);
+        symbols.fun2(&"id".into(), Polarity::Neg, Polarity::Neg);
+        symbols.fun2(&"dup".into(), Polarity::Pos, Polarity::Pos);
+        let mut rules = RuleSet::new(&symbols);
+        let erase = rules.rule(&"Z".into(), &"dup".into(), |b| b.erase_fun_ports());
+        let id = rules.rule(&"pair".into(), &"id".into(), |b| {
+            b.connect_all_fun_ports_to_ctr_ports()
+        });
+        assert_eq!(rules.validate_all_rules(), Ok(()));
+        assert_eq!(
+            rules.display_rule(erase).to_string(),
+            "(dup F₀ F₁) ⋈ Z  ⟶  F₀ ← Z, F₁ ← Z"
+        );
+        assert_eq!(
+            rules.display_rule(id).to_string(),
+            "(id F₀ F₁) ⋈ (pair C₀ C₁)  ⟶  C₀ ↔ F₀, C₁ ↔ F₁"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "different arities")]
+    fn test_rule_builder_connect_all_different_arities() {
+        let mut symbols = SymbolBook::new();
+        symbols.ctr0(&"Z".into());
+        symbols.fun2(&"id".into(), Polarity::Neg, Polarity::Neg);
+        let mut rules = RuleSet::new(&symbols);
+        rules.rule(&"Z".into(), &"id".into(), |b| b.connect_all_fun_ports_to_ctr_ports());
+    }
+
+    #[test]
+    fn test_rule_set_generate_complete() {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+        symbols.declare_arith_symbols();
+        symbols.fun0(&"era".into());
+
+        let mut rules = RuleSet::new(&symbols);
+        rules.arith_add_rules();
+        let (ctr_ptr, fun_ptr) = rules.check_completeness().unwrap_err();
+        assert_eq!(symbols.get_name(ctr_ptr), Some("Z".into()));
+        assert_eq!(symbols.get_name(fun_ptr), Some("sub₀".into()));
+
+        // 2 ctrs times 4 funs, the add rules are kept
+        let add_rules: Vec<RulePtr> = rules.rules().collect();
+        rules.complete(|b| b.connect_ports());
+        assert_eq!(rules.check_completeness(), Ok(()));
+        assert_eq!(rules.validate_all_rules(), Ok(()));
+        assert_eq!(rules.rules().count(), 8);
+        assert_eq!(rules.rules().take(2).collect::<Vec<RulePtr>>(), add_rules);
+
+        let complete = RuleSet::generate_complete(&symbols, |b| b.connect_ports());
+        assert_eq!(complete.check_completeness(), Ok(()));
+        assert_eq!(complete.rules().count(), 8);
+
+        // (S x) ⋈ era  ⟶  x ← era
+        let mut net = Net::new(&symbols);
+        net.equations(|b| {
+            let z = b.cell0(&"Z".into());
+            let s = b.cell1(&"S".into(), z.into());
+            let s = b.cell1(&"S".into(), s.into());
+            let era = b.cell0(&"era".into());
+            b.redex(s, era);
+        });
+        let net = Runtime::new(&rules, false).eval(net).unwrap();
+        assert!(net.is_normal());
+        assert_eq!(net.binds().count() + net.connects().count(), 0);
+    }
+
+    #[test]
+    fn test_rule_set_dead_rules() {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+        symbols.declare_arith_symbols();
+        let mut rules = RuleSet::new(&symbols);
+        rules.arith_rules();
+
+        let ptr = |name: &'static str| symbols.get_by_name(&name.into()).unwrap();
+        let rule_ptrs: Vec<RulePtr> = rules.rules().collect();
+        // Z ⋈ add, S ⋈ add, then the sub rules
+        assert_eq!(rules.dead_rules(&[ptr("Z"), ptr("add")]), rule_ptrs[1..]);
+        // S ⋈ add only creates S and add, Z ⋈ add fires because Z is an entry
+        assert_eq!(rules.dead_rules(&[ptr("Z"), ptr("S"), ptr("add")]), rule_ptrs[2..]);
+        assert_eq!(rules.dead_rules(&[]), rule_ptrs);
+        // S ⋈ sub creates sub₀, but nothing creates Z
+        let dead = [rule_ptrs[0], rule_ptrs[1], rule_ptrs[2], rule_ptrs[4]];
+        assert_eq!(rules.dead_rules(&[ptr("S"), ptr("sub")]), dead);
+    }
+
+    #[test]
+    fn test_rule_set_rules_for_symbol() {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+        symbols.declare_arith_symbols();
+        let mut rules = RuleSet::new(&symbols);
+        rules.arith_rules();
+
+        let ptr = |name: &'static str| symbols.get_by_name(&name.into()).unwrap();
+        let rule_ptrs: Vec<RulePtr> = rules.rules().collect();
+        // Z ⋈ add, S ⋈ add
+        assert_eq!(rules.rules_for_symbol(ptr("add")), rule_ptrs[..2]);
+        let for_z = rules.rules_for_symbol(ptr("Z"));
+        assert!(for_z.contains(&rule_ptrs[0]) && !for_z.contains(&rule_ptrs[1]));
+        assert!(for_z.iter().all(|rule_ptr| {
+            let rule = rules.get_rule(*rule_ptr);
+            rule.ctr_ptr == ptr("Z") || rule.fun_ptr == ptr("Z")
+        }));
+    }
+
+    #[test]
+    fn test_rule_set_verify_rule() {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+        symbols.declare_arith_symbols();
+        let mut rules = RuleSet::new(&symbols);
+        // (add x y) ⋈ (S n)  ⟶  (add x y) ⋈ n, without a Z ⋈ add rule
+        let rule_ptr = rules.rule(&"S".into(), &"add".into(), |b| {
+            let x = b.fun_port_0();
+            let y = b.fun_port_1();
+            let add = b.cell2(&"add".into(), x.into(), y.into());
+            let n = b.ctr_port_0();
+            b.bind(n, add);
+        });
+
+        // the second case ends in Z ⋈ add
+        let cases = [(vec!["n"], vec!["r", "Z"]), (vec!["Z"], vec!["r", "Z"])];
+        assert_eq!(rules.verify_rule(rule_ptr, &cases), Err(VerifyError::EvalFailed(1)));
+        let error = rules.verify_rule(rule_ptr, &[(vec!["(T Z)"], vec!["r", "Z"])]);
+        assert!(matches!(error, Err(VerifyError::InvalidTestCase(0, _))));
+    }
+
+    #[test]
+    fn test_rule_clone_into() {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+        symbols.declare_arith_symbols();
+        let mut rules = RuleSet::new(&symbols);
+        rules.arith_rules();
+
+        // other symbol indices, and a rule the clones have to go around
+        let mut dst_symbols = SymbolBook::new();
+        dst_symbols.declare_arith_symbols();
+        dst_symbols.declare_nat_symbols();
+        let mut dst_rules = RuleSet::new(&dst_symbols);
+        dst_rules.rule(&"Z".into(), &"sub".into(), |b| {
+            let l0 = b.fun_port_0();
+            let l1 = b.fun_port_1();
+            b.connect(l0, l1);
+        });
+
+        for rule_ptr in rules.rules() {
+            let dst_rule_ptr = rules.get_rule(rule_ptr).clone_into(&rules, &mut dst_rules);
+            assert_eq!(
+                dst_rules.display_rule(dst_rule_ptr).to_string(),
+                rules.display_rule(rule_ptr).to_string()
+            );
+        }
+        // Z ⋈ sub was replaced
+        assert_eq!(dst_rules.rule_by_symbols.len(), 6);
+        assert_eq!(dst_rules.validate_all_rules(), Ok(()));
+
+        let net = Net::from_str("< r | (S (S Z)) ⋈ (add r (S Z)) >", &dst_symbols).unwrap();
+        let net = Runtime::new(&dst_rules, false).eval(net).unwrap();
+        assert_eq!(net.read_nat(net.single_output().unwrap()), Some(3));
+    }
+
     #[test]
     fn test_rule_add_and_get() {
         let mut rules = Rules::new();
@@ -506,6 +1714,58 @@ mod tests {
         // assert_eq!(all_rules.get(ptr2).unwrap(), &rule2);
     }
 
+    #[test]
+    fn test_rule_with_many_bvars() {
+        const BVARS: u16 = 300;
+
+        let mut symbols = SymbolBook::new();
+        symbols.ctr0(&"Z".into());
+        symbols.ctr1(&"S".into(), Polarity::Neg);
+        symbols.fun1(&"f".into(), Polarity::Pos);
+
+        // Z ⋈ (f r₀)  ⟶  r₀ ← (S x₀), x₀ ← (S x₁), ..., x₂₉₉ ← Z
+        let mut rules = RuleSet::new(&symbols);
+        let rule_ptr = rules.rule(&"Z".into(), &"f".into(), |b| {
+            let mut port = b.fun_port_0();
+            for _ in 0..BVARS {
+                let (neg_pvar, pos_pvar) = b.var();
+                let s = b.cell1(&"S".into(), pos_pvar.into());
+                b.bind(port, s);
+                port = neg_pvar;
+            }
+            let z = b.cell0(&"Z".into());
+            b.bind(port, z);
+        });
+        assert_eq!(rules.get_rule(rule_ptr).get_bvar_count(), BVARS);
+
+        let mut net = Net::new(&symbols);
+        net.equations(|b| {
+            let result = b.output();
+            let f = b.cell1(&"f".into(), result.into());
+            let z = b.cell0(&"Z".into());
+            b.redex(z, f);
+        });
+
+        let runtime = Runtime::new(&rules, false);
+        let net = runtime.eval(net).unwrap();
+
+        // walk the result: it should be 300 S cells followed by Z
+        let mut cell_ptr = net.single_output().unwrap();
+        let mut count = 0;
+        loop {
+            let cell = net.heap.get_cell(cell_ptr);
+            let name = symbols.get_name(cell.get_symbol_ptr()).unwrap();
+            if name == "Z".into() {
+                break;
+            }
+            assert_eq!(name, "S".into());
+            let var = net.heap.get_var(cell.get_left_port().get_var_ptr());
+            cell_ptr = var.get_store().get_cell_ptr().unwrap();
+            count += 1;
+        }
+        assert_eq!(count, BVARS);
+    }
+
     // #[test]
     // fn test_rule_item_display() {
     //     let mut rules = Rules::new();
@@ -1,48 +1,257 @@
-use std::{sync::atomic::AtomicUsize, time::Instant};
+use std::sync::{atomic::AtomicUsize, Arc, Mutex};
+#[cfg(not(feature = "wasm"))]
+use std::time::Instant;
 
-use crate::inet::var::{PVarPtrBuffer, Var};
+use crate::inet::var::PVarPtrBuffer;
 
+#[cfg(not(feature = "wasm"))]
 use rayon::Scope;
-use tracing::{debug, info};
+#[cfg(feature = "wasm")]
+use wasm::Scope;
+use tracing::{debug, Level};
 
 use super::{
-    cell::{Cell, CellPtr},
-    equation::{Equation, EquationKind},
+    cell::{CellCopy, CellPtr},
+    equation::{Equation, EquationKind, InstantiateError, Instantiation},
     heap::Heap,
-    net::{Net, NetF, NetVar},
-    rule::{RuleF, RulePort, RuleSet},
-    symbol::{SymbolArity, SymbolBook},
-    term::{TermKind, TermPtr},
+    net::{Net, NetError, NetF, NetValue, NetVar},
+    rule::{RuleF, RulePtr, RuleSet},
+    symbol::SymbolBook,
     var::PVarPtr,
     Polarity,
 };
 
+// Logs an equation with the id of the current worker thread. The equation is
+// only formatted when the level is enabled.
+macro_rules! event_equation {
+    ($level:expr, $message:literal, $eqn:expr, $symbols:expr, $heap:expr) => {
+        tracing::event!(
+            $level,
+            "[{}] {} {}: {}",
+            Runtime::current_thread_id(),
+            $message,
+            $eqn.get_kind(),
+            $eqn.to_display_string($symbols, $heap)
+        )
+    };
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CheckedEvalError {
+    PreCondition(Vec<NetError>),
+    PostCondition(Vec<NetError>),
+    /// The runtime's `max_rewrites` was reached, the partial net is dropped.
+    MaxRewritesExceeded(usize),
+}
+
+/// Returned by `Runtime::eval` when the net needed more rewrites than the
+/// runtime's `max_rewrites`. The partial net keeps the redexes left.
+#[derive(Debug)]
+pub struct MaxRewritesExceeded<'a> {
+    pub partial_net: Box<Net<'a>>,
+    pub rewrites: usize,
+}
+
+// the rewrites left in one eval call and the redexes skipped once there were
+// none, shared with the queued runtimes of that call only
+#[derive(Debug)]
+struct RewriteBound {
+    max_rewrites: usize,
+    remaining: AtomicUsize,
+    skipped: Mutex<Vec<(CellPtr, CellPtr)>>,
+}
+
+/// How much a `Runtime` checks while evaluating. `Basic` traces connects,
+/// `Full` also evaluates one redex at a time and validates the whole net after
+/// each rewrite, panicking on the first invalid state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DebugLevel {
+    None,
+    Basic,
+    Full,
+}
+
+impl Default for DebugLevel {
+    fn default() -> Self {
+        #[cfg(debug_assertions)]
+        return DebugLevel::Basic;
+        #[cfg(not(debug_assertions))]
+        return DebugLevel::None;
+    }
+}
+
 #[derive(Debug)]
 pub struct Runtime<'a> {
-    debug: bool,
+    debug_level: DebugLevel,
+    checked: bool,
     rules: &'a RuleSet<'a>,
     rewrites: AtomicUsize,
     cell_instantiations: AtomicUsize,
     cell_reuses: AtomicUsize,
     var_instatiations: AtomicUsize,
+    var_links: AtomicUsize,
+    // the global rayon pool is used when not set
+    #[cfg(not(feature = "wasm"))]
+    pool: Option<Arc<rayon::ThreadPool>>,
+    // fired rules, only while recording a RewriteLog
+    log: Option<Mutex<Vec<(RulePtr, RedexTag)>>>,
+    // redexes waiting for their turn, only while replaying a RewriteLog
+    pending: Option<Mutex<Vec<(RedexTag, CellPtr, CellPtr)>>>,
+    max_rewrites: Option<usize>,
+    // only set on the runtime an eval with max_rewrites runs on
+    bound: Option<Arc<RewriteBound>>,
+    // cells and vars the heap of an evaluated net is grown to
+    heap_capacity: Option<(usize, usize)>,
+}
+
+/// Identifies a redex across runs by the rewrite step that created it and the
+/// order it was created in. Redexes of the initial net have no step.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct RedexTag {
+    step: Option<usize>,
+    ordinal: usize,
+}
+
+thread_local! {
+    // the tag of the next redex created on this thread
+    static NEXT_TAG: std::cell::Cell<RedexTag> = Default::default();
+}
+
+impl RedexTag {
+    fn next() -> Self {
+        NEXT_TAG.with(|next| {
+            let tag = next.get();
+            next.set(RedexTag {
+                ordinal: tag.ordinal + 1,
+                ..tag
+            });
+            tag
+        })
+    }
+
+    /// Tags the redexes created from now on as created by `step`, returning
+    /// the tags to restore afterwards.
+    fn enter(step: Option<usize>) -> Self {
+        NEXT_TAG.with(|next| next.replace(RedexTag { step, ordinal: 0 }))
+    }
+
+    fn restore(self) {
+        NEXT_TAG.with(|next| next.set(self))
+    }
+}
+
+/// The rules fired by an evaluation, in firing order. A parallel run can be
+/// recorded once and replayed sequentially with `Runtime::eval_replay`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RewriteLog {
+    rewrites: Vec<(RulePtr, RedexTag)>,
+}
+
+impl RewriteLog {
+    /// Evaluates `net`, recording every rule fired.
+    pub fn record<'a>(net: Net<'a>, rules: &'a RuleSet<'a>) -> (Net<'a>, RewriteLog) {
+        let mut runtime = Runtime {
+            log: Some(Mutex::new(Vec::new())),
+            ..Runtime::new(rules, false)
+        };
+        let net = runtime.eval_unbounded(net);
+        let rewrites = runtime.log.take().unwrap().into_inner().unwrap();
+        (net, RewriteLog { rewrites })
+    }
+
+    pub fn len(&self) -> usize {
+        self.rewrites.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rewrites.is_empty()
+    }
+
+    /// The fired rules, in order.
+    pub fn iter(&self) -> impl Iterator<Item = RulePtr> + '_ {
+        self.rewrites.iter().map(|(rule_ptr, _)| *rule_ptr)
+    }
 }
 
 impl<'a> Runtime<'a> {
+    /// A runtime at `DebugLevel::Basic` when `debug` is set, `None` otherwise.
     pub fn new(rules: &'a RuleSet, debug: bool) -> Self {
         Self {
             rules,
-            debug,
+            debug_level: match debug {
+                true => DebugLevel::Basic,
+                false => DebugLevel::None,
+            },
+            checked: cfg!(debug_assertions),
             rewrites: Default::default(),
             cell_reuses: Default::default(),
             cell_instantiations: Default::default(),
             var_instatiations: Default::default(),
+            var_links: Default::default(),
+            #[cfg(not(feature = "wasm"))]
+            pool: None,
+            log: None,
+            pending: None,
+            max_rewrites: None,
+            bound: None,
+            heap_capacity: None,
         }
     }
 
+    /// Like `new`, growing the heap of each net it evaluates to at least
+    /// `cells_capacity` cells and `vars_capacity` vars first. Heaps do not
+    /// grow during evaluation, so this fits nets whose rewrites allocate
+    /// more than the heap was created for.
+    pub fn new_with_heap_capacity(
+        rules: &'a RuleSet,
+        cells_capacity: usize,
+        vars_capacity: usize,
+        debug: bool,
+    ) -> Self {
+        let mut runtime = Self::new(rules, debug);
+        runtime.heap_capacity = Some((cells_capacity, vars_capacity));
+        runtime
+    }
+
+    // grows the heap to the capacity of the runtime and of the net itself,
+    // see `Net::eval_capacity`
+    fn reserve_heap(&self, net: &mut Net<'a>) {
+        let (cells_capacity, vars_capacity) = self.heap_capacity.unwrap_or((0, 0));
+        let eval_capacity = net.eval_capacity.unwrap_or(0);
+        let (cells_capacity, vars_capacity) = (
+            cells_capacity.max(eval_capacity),
+            vars_capacity.max(eval_capacity),
+        );
+        if net.heap.cells.capacity() < cells_capacity {
+            net.heap.resize_cells(cells_capacity).unwrap();
+        }
+        if net.heap.vars.capacity() < vars_capacity {
+            net.heap.resize_vars(vars_capacity).unwrap();
+        }
+    }
+
+    pub fn builder(rules: &'a RuleSet) -> RuntimeBuilder<'a> {
+        RuntimeBuilder {
+            rules,
+            debug_level: DebugLevel::default(),
+            checked: cfg!(debug_assertions),
+            num_threads: None,
+            stack_size: None,
+            thread_name_prefix: None,
+            max_rewrites: None,
+        }
+    }
+
+    #[cfg(not(feature = "wasm"))]
     fn current_thread_id() -> usize {
         rayon::current_thread_index().unwrap()
     }
 
+    #[cfg(feature = "wasm")]
+    fn current_thread_id() -> usize {
+        0
+    }
+
     pub fn get_rewrites(&self) -> usize {
         self.rewrites.load(std::sync::atomic::Ordering::SeqCst)
     }
@@ -81,26 +290,357 @@ impl<'a> Runtime<'a> {
             .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
     }
 
+    /// Connects of two vars that were both still empty, which link the vars
+    /// until a cell arrives. Evaluating binds and connects before redexes
+    /// keeps this low, see `EquationPriority`.
+    pub fn get_var_links(&self) -> usize {
+        self.var_links.load(std::sync::atomic::Ordering::SeqCst)
+    }
+
+    pub fn inc_var_link(&self) {
+        self.var_links
+            .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+    }
+
+    fn add_instantiation_stats<I: Iterator<Item = CellPtr>>(&self, stats: &Instantiation<I>) {
+        let ordering = std::sync::atomic::Ordering::SeqCst;
+        self.cell_reuses.fetch_add(stats.cell_reuses, ordering);
+        self.cell_instantiations
+            .fetch_add(stats.cell_instantiations, ordering);
+        self.var_instatiations
+            .fetch_add(stats.var_instantiations, ordering);
+    }
+
     pub fn display_stats(&self) {
         tracing::info!("REWRITES: {}", self.get_rewrites());
         tracing::info!("CELL REUSES: {}", self.get_cell_reuses());
         tracing::info!("CELL INSTANTIATIONS: {}", self.get_cell_instantiations());
         tracing::info!("VAR INSTANTIATIONS: {}", self.get_var_instantiations());
+        tracing::info!("VAR LINKS: {}", self.get_var_links());
     }
 
-    pub fn eval(&self, mut net: Net<'a>) -> Net<'a> {
+    /// Evaluates like `eval`, validating the net before and after. Malformed
+    /// input fails the pre-condition, a heap corrupted while instantiating rules
+    /// fails the post-condition. Validation is skipped when the runtime is not
+    /// checked, the default for release builds.
+    pub fn eval_checked(&self, net: Net<'a>) -> Result<Net<'a>, CheckedEvalError> {
+        let exceeded = |exceeded: MaxRewritesExceeded| {
+            CheckedEvalError::MaxRewritesExceeded(exceeded.rewrites)
+        };
+        if !self.checked {
+            return self.eval(net).map_err(exceeded);
+        }
+        net.validate().map_err(CheckedEvalError::PreCondition)?;
+        let net = self.eval(net).map_err(exceeded)?;
+        net.validate().map_err(CheckedEvalError::PostCondition)?;
+        Ok(net)
+    }
+
+    /// Evaluates like `eval`, reporting rewrites, cell reuses, the eval time and
+    /// the live heap cells through the `metrics` crate. Without the `metrics`
+    /// feature this is just `eval`.
+    pub fn eval_with_metrics(&self, net: Net<'a>) -> Result<Net<'a>, MaxRewritesExceeded<'a>> {
+        #[cfg(feature = "metrics")]
+        let (rewrites, cell_reuses) = (self.get_rewrites(), self.get_cell_reuses());
+        #[cfg(all(feature = "metrics", not(feature = "wasm")))]
         let now = Instant::now();
 
-        rayon::scope(|scope| {
+        let result = self.eval(net);
+
+        #[cfg(feature = "metrics")]
+        {
+            let net = match &result {
+                Ok(net) => net,
+                Err(exceeded) => &exceeded.partial_net,
+            };
+            metrics::counter!("inet.rewrites", (self.get_rewrites() - rewrites) as u64);
+            metrics::counter!("inet.cell_reuses", (self.get_cell_reuses() - cell_reuses) as u64);
+            metrics::gauge!("inet.heap_cells_live", net.heap.cell_count_approx() as f64);
+        }
+        // there is no clock on wasm32
+        #[cfg(all(feature = "metrics", not(feature = "wasm")))]
+        metrics::histogram!("inet.eval_duration_ms", now.elapsed().as_secs_f64() * 1000.0);
+        result
+    }
+
+    /// Evaluates `net` sequentially, firing rules in the order of `log`. Each
+    /// step rewrites the redex created where the recorded one was, or else the
+    /// oldest pending redex for the logged rule, and panics if there is none.
+    pub fn eval_replay(&self, mut net: Net<'a>, log: &RewriteLog) -> Net<'a> {
+        self.reserve_heap(&mut net);
+        let replay = self.queued();
+        #[cfg(not(feature = "wasm"))]
+        rayon::scope(|scope| replay.replay_body(scope, &mut net, log));
+        #[cfg(feature = "wasm")]
+        wasm::scope(|scope| replay.replay_body(scope, &mut net, log));
+        self.add_stats(&replay);
+        net
+    }
+
+    /// A runtime on the same rules that queues redexes instead of spawning
+    /// them. Its stats are added back with `add_stats`.
+    fn queued(&self) -> Runtime<'a> {
+        Runtime {
+            debug_level: self.debug_level,
+            pending: Some(Mutex::new(Vec::new())),
+            bound: self.bound.clone(),
+            ..Runtime::new(self.rules, false)
+        }
+    }
+
+    /// A runtime on the same rules and pool with its own bound of
+    /// `max_rewrites`, so evals running at the same time do not share one.
+    /// Its stats are added back with `add_stats`.
+    fn bounded(&self, max_rewrites: usize) -> Runtime<'a> {
+        Runtime {
+            debug_level: self.debug_level,
+            #[cfg(not(feature = "wasm"))]
+            pool: self.pool.clone(),
+            bound: Some(Arc::new(RewriteBound {
+                max_rewrites,
+                remaining: AtomicUsize::new(max_rewrites),
+                skipped: Mutex::new(Vec::new()),
+            })),
+            ..Runtime::new(self.rules, false)
+        }
+    }
+
+    fn add_stats(&self, other: &Runtime) {
+        let ordering = std::sync::atomic::Ordering::SeqCst;
+        self.rewrites.fetch_add(other.get_rewrites(), ordering);
+        self.cell_reuses.fetch_add(other.get_cell_reuses(), ordering);
+        self.cell_instantiations
+            .fetch_add(other.get_cell_instantiations(), ordering);
+        self.var_instatiations
+            .fetch_add(other.get_var_instantiations(), ordering);
+        self.var_links.fetch_add(other.get_var_links(), ordering);
+    }
+
+    // redexes are queued instead of spawned, so the scope never runs a job
+    fn replay_body<'scope>(
+        &'scope self,
+        scope: &Scope<'scope>,
+        net: &'scope mut Net<'a>,
+        log: &RewriteLog,
+    ) {
+        let parent = RedexTag::enter(None);
+        net.body.make_contiguous().sort_by_key(|eqn| eqn.get_priority());
+        net.body
+            .drain(..)
+            .for_each(|eqn| self.eval_equation(scope, net.symbols, &net.heap, eqn));
+
+        let pending = self.pending.as_ref().unwrap();
+        for (step, (rule_ptr, tag)) in log.rewrites.iter().enumerate() {
+            let (ctr_ptr, fun_ptr) = {
+                let mut pending = pending.lock().unwrap();
+                let fires = |(_, ctr_ptr, fun_ptr): &(RedexTag, CellPtr, CellPtr)| {
+                    self.rules.get_by_symbols(
+                        net.heap.get_cell(*ctr_ptr).get_symbol_ptr(),
+                        net.heap.get_cell(*fun_ptr).get_symbol_ptr(),
+                    ) == Some(*rule_ptr)
+                };
+                // redexes created by a bind depend on which side arrived last
+                let position = pending
+                    .iter()
+                    .position(|redex| redex.0 == *tag && fires(redex))
+                    .or_else(|| pending.iter().position(fires))
+                    .unwrap_or_else(|| panic!("Replay step {}: no redex for {:?}", step, rule_ptr));
+                let (_, ctr_ptr, fun_ptr) = pending.remove(position);
+                (ctr_ptr, fun_ptr)
+            };
+            RedexTag::enter(Some(step));
+            self.eval_redex(scope, net.symbols, &net.heap, ctr_ptr, fun_ptr, None);
+        }
+        parent.restore();
+        let pending = pending.lock().unwrap();
+        assert!(pending.is_empty(), "{} redexes left after replay", pending.len());
+    }
+
+    /// Rewrites the oldest redex of `net` after evaluating its binds and
+    /// connects. The redexes created are left in the body for the next step,
+    /// returns false when there was no redex to rewrite.
+    pub fn step(&self, net: &mut Net<'a>) -> bool {
+        self.reserve_heap(net);
+        let step = self.queued();
+        #[cfg(not(feature = "wasm"))]
+        let rewritten = rayon::scope(|scope| step.step_body(scope, net));
+        #[cfg(feature = "wasm")]
+        let rewritten = wasm::scope(|scope| step.step_body(scope, net));
+        self.add_stats(&step);
+        rewritten
+    }
+
+    fn step_body<'scope>(&'scope self, scope: &Scope<'scope>, net: &'scope mut Net<'a>) -> bool {
+        net.body.make_contiguous().sort_by_key(|eqn| eqn.get_priority());
+        net.body
+            .drain(..)
+            .for_each(|eqn| self.eval_equation(scope, net.symbols, &net.heap, eqn));
+
+        let pending = self.pending.as_ref().unwrap();
+        let redex = {
+            let mut pending = pending.lock().unwrap();
+            (!pending.is_empty()).then(|| pending.remove(0))
+        };
+        if let Some((_, ctr_ptr, fun_ptr)) = redex {
+            self.eval_redex(scope, net.symbols, &net.heap, ctr_ptr, fun_ptr, None);
+        }
+        net.body.extend(
+            pending
+                .lock()
+                .unwrap()
+                .drain(..)
+                .map(|(_, ctr_ptr, fun_ptr)| Equation::redex(ctr_ptr, fun_ptr)),
+        );
+        redex.is_some()
+    }
+
+    /// Evaluates `net` to normal form, failing with the partial net when the
+    /// limit set by `RuntimeBuilder::max_rewrites` is reached. Each call has a
+    /// limit of its own. Redexes over the limit are not rewritten, so no
+    /// further work is spawned once it is reached.
+    pub fn eval(&self, mut net: Net<'a>) -> Result<Net<'a>, MaxRewritesExceeded<'a>> {
+        self.reserve_heap(&mut net);
+        let Some(max_rewrites) = self.max_rewrites else {
+            return Ok(self.eval_unbounded(net));
+        };
+        let bounded = self.bounded(max_rewrites);
+        let mut net = bounded.eval_unbounded(net);
+        self.add_stats(&bounded);
+        let bound = bounded.bound.as_ref().unwrap();
+        let skipped = std::mem::take(&mut *bound.skipped.lock().unwrap());
+        if skipped.is_empty() {
+            return Ok(net);
+        }
+        net.body.extend(
+            skipped
+                .into_iter()
+                .map(|(ctr_ptr, fun_ptr)| Equation::redex(ctr_ptr, fun_ptr)),
+        );
+        Err(MaxRewritesExceeded {
+            partial_net: Box::new(net),
+            rewrites: bound.max_rewrites,
+        })
+    }
+
+    /// Evaluates like `eval` inside `pool`, for applications that manage their
+    /// own rayon pools. A pool set by `RuntimeBuilder` is still used for the
+    /// parallel rewrites, so build the runtime without one.
+    #[cfg(not(feature = "wasm"))]
+    pub fn eval_on_pool(
+        &self,
+        net: Net<'a>,
+        pool: &rayon::ThreadPool,
+    ) -> Result<Net<'a>, MaxRewritesExceeded<'a>> {
+        pool.install(|| self.eval(net))
+    }
+
+    #[cfg(feature = "wasm")]
+    fn eval_unbounded(&self, net: Net<'a>) -> Net<'a> {
+        if self.debug_level == DebugLevel::Full {
+            return self.eval_validated(net);
+        }
+        self.eval_seq(net)
+    }
+
+    /// Evaluates one redex at a time, validating the net after each rewrite,
+    /// see `DebugLevel::Full`.
+    fn eval_validated(&self, mut net: Net<'a>) -> Net<'a> {
+        let mut rewrites = 0;
+        while self.step(&mut net) {
+            rewrites += 1;
+            if let Err(errors) = net.validate() {
+                panic!("Invalid net after {} rewrites: {:?}", rewrites, errors);
+            }
+        }
+        net
+    }
+
+    /// Evaluates on the current thread, spawned redexes are queued and run
+    /// in order. There is no clock on wasm32 so the run is not timed.
+    #[cfg(feature = "wasm")]
+    pub fn eval_seq(&self, mut net: Net<'a>) -> Net<'a> {
+        self.reserve_heap(&mut net);
+        if self.log.is_some() {
+            RedexTag::enter(None);
+        }
+        net.body.make_contiguous().sort_by_key(|eqn| eqn.get_priority());
+        wasm::scope(|scope| {
             net.body
                 .drain(..)
-                .for_each(|eqn| self.eval_equation(scope, &net.symbols, &net.heap, eqn));
+                .for_each(|eqn| self.eval_equation(scope, net.symbols, &net.heap, eqn));
         });
+        net
+    }
 
-        info!("Net evaluated in {}", now.elapsed().as_millis());
+    /// Nets estimated below this by `Net::complexity_metric` are evaluated by
+    /// `eval_seq`, unless the runtime has its own pool.
+    pub const SEQUENTIAL_THRESHOLD: u64 = 16;
+
+    #[cfg(not(feature = "wasm"))]
+    fn eval_unbounded(&self, mut net: Net<'a>) -> Net<'a> {
+        if self.debug_level == DebugLevel::Full {
+            return self.eval_validated(net);
+        }
+        if self.pool.is_none()
+            && self.log.is_none()
+            && net.complexity_metric(self.rules) < Self::SEQUENTIAL_THRESHOLD
+        {
+            return self.eval_seq(net);
+        }
+        let now = Instant::now();
+
+        match &self.pool {
+            Some(pool) => pool.scope(|scope| self.eval_body(scope, &mut net)),
+            None => rayon::scope(|scope| self.eval_body(scope, &mut net)),
+        }
+
+        tracing::info!("Net evaluated in {}", now.elapsed().as_millis());
+        net.heap.debug_print(net.symbols);
+        net
+    }
+
+    /// Evaluates on a single worker, redexes are queued and rewritten one at a
+    /// time, which saves spawning for small nets.
+    #[cfg(not(feature = "wasm"))]
+    pub fn eval_seq(&self, mut net: Net<'a>) -> Net<'a> {
+        self.reserve_heap(&mut net);
+        let seq = self.queued();
+        rayon::scope(|scope| seq.seq_body(scope, &mut net));
+        self.add_stats(&seq);
         net
     }
 
+    #[cfg(not(feature = "wasm"))]
+    fn seq_body<'scope>(&'scope self, scope: &Scope<'scope>, net: &'scope mut Net<'a>) {
+        net.body.make_contiguous().sort_by_key(|eqn| eqn.get_priority());
+        net.body
+            .drain(..)
+            .for_each(|eqn| self.eval_equation(scope, net.symbols, &net.heap, eqn));
+
+        let pending = self.pending.as_ref().unwrap();
+        loop {
+            let redex = pending.lock().unwrap().pop();
+            match redex {
+                Some((_, ctr_ptr, fun_ptr)) => {
+                    self.eval_redex(scope, net.symbols, &net.heap, ctr_ptr, fun_ptr, None)
+                }
+                None => break,
+            }
+        }
+    }
+
+    #[cfg(not(feature = "wasm"))]
+    fn eval_body<'scope>(&'scope self, scope: &Scope<'scope>, net: &'scope mut Net<'a>) {
+        if self.log.is_some() {
+            RedexTag::enter(None);
+        }
+        // binds and connects before redexes, the sort is stable
+        net.body.make_contiguous().sort_by_key(|eqn| eqn.get_priority());
+        net.body
+            .drain(..)
+            .for_each(|eqn| self.eval_equation(scope, net.symbols, &net.heap, eqn));
+    }
+
     fn eval_equation<'scope>(
         &'scope self,
         scope: &Scope<'scope>,
@@ -108,13 +648,10 @@ impl<'a> Runtime<'a> {
         heap: &'scope Heap<NetF>,
         eqn: Equation<NetF>,
     ) {
-        debug!(
-            "[{}] Evaluating {}: {}",
-            Self::current_thread_id(),
-            eqn.get_kind(),
-            eqn.display_equation(symbols, heap)
-        );
+        event_equation!(Level::DEBUG, "Evaluating", eqn, symbols, heap);
 
+        // one span per kind so profilers can time redexes, binds and connects apart
+        let _span = tracing::trace_span!("equation_kind", kind = %eqn.get_kind()).entered();
         match eqn.get_kind() {
             EquationKind::Redex => self.rewrite_redex(
                 scope,
@@ -149,7 +686,20 @@ impl<'a> Runtime<'a> {
         ctr_ptr: CellPtr,
         fun_ptr: CellPtr,
     ) {
-        scope.spawn(move |scope| self.eval_redex(scope, symbols, heap, ctr_ptr, fun_ptr));
+        match (&self.pending, &self.log) {
+            (Some(pending), _) => {
+                pending.lock().unwrap().push((RedexTag::next(), ctr_ptr, fun_ptr))
+            }
+            (None, Some(_)) => {
+                let tag = Some(RedexTag::next());
+                scope.spawn(move |scope| {
+                    self.eval_redex(scope, symbols, heap, ctr_ptr, fun_ptr, tag)
+                })
+            }
+            (None, None) => scope.spawn(move |scope| {
+                self.eval_redex(scope, symbols, heap, ctr_ptr, fun_ptr, None)
+            }),
+        }
     }
 
     fn eval_redex<'scope>(
@@ -159,11 +709,31 @@ impl<'a> Runtime<'a> {
         heap: &'scope Heap<NetF>,
         ctr_ptr: CellPtr,
         fun_ptr: CellPtr,
+        tag: Option<RedexTag>,
     ) {
-        let mut reuse_cell_iter = std::iter::once(ctr_ptr).chain(std::iter::once(fun_ptr));
-
-        let ctr = heap.get_cell(ctr_ptr).clone(); // TODO move clone() to arenaraw?
-        let fun = heap.get_cell(fun_ptr).clone(); // TODO move clone() to arenaraw?
+        if let Some(bound) = &self.bound {
+            let ordering = std::sync::atomic::Ordering::SeqCst;
+            let taken = bound
+                .remaining
+                .fetch_update(ordering, ordering, |remaining| remaining.checked_sub(1));
+            if taken.is_err() {
+                bound.skipped.lock().unwrap().push((ctr_ptr, fun_ptr));
+                return;
+            }
+        }
+        let reuse_cell_iter = std::iter::once(ctr_ptr).chain(std::iter::once(fun_ptr));
+        let mut instantiation = Instantiation::new(reuse_cell_iter);
+
+        // copies, the rule may reuse the redex cells before reading their ports
+        let ctr = heap.copy_cell(ctr_ptr);
+        let fun = heap.copy_cell(fun_ptr);
+        // the fields are only formatted when the span is enabled
+        let _span = tracing::trace_span!(
+            "eval_redex",
+            ctr = %ctr.symbol_name(symbols).unwrap(),
+            fun = %fun.symbol_name(symbols).unwrap()
+        )
+        .entered();
 
         // let ctr = heap.free_cell(ctr_ptr);
         // let fun = heap.free_cell(fun_ptr);
@@ -175,12 +745,21 @@ impl<'a> Runtime<'a> {
             .or_else(|| {
                 panic!(
                     "Rule not found for: {} ⋈ {}",
-                    symbols.get_name(ctr.get_symbol_ptr()).unwrap(),
-                    symbols.get_name(fun.get_symbol_ptr()).unwrap()
+                    ctr.symbol_name(symbols).unwrap(),
+                    fun.symbol_name(symbols).unwrap()
                 )
             })
             .unwrap();
         let rule = self.rules.get_rule(rule_ptr);
+        // redexes created while instantiating the rule are tagged with this step
+        let parent = match (&self.log, tag) {
+            (Some(log), Some(tag)) => {
+                let mut log = log.lock().unwrap();
+                log.push((rule_ptr, tag));
+                Some(RedexTag::enter(Some(log.len() - 1)))
+            }
+            _ => None,
+        };
 
         // track the number of rewrites (binds and connects are considered house keeping)
         self.inc_rewrite();
@@ -188,27 +767,29 @@ impl<'a> Runtime<'a> {
         // info!("Rule: {}", rule.display(symbols, heap));
         // preallocate bound vars (TODO can we allocate in consecutive indexes to simplify rewrite?)
         // let bvars = net.alloc_bvars(rule.get_bvar_count());
-        let mut bvars = self.new_bvar_buffer(heap, rule.get_bvar_count());
+        let bvars = self.new_bvar_buffer(heap, rule.get_bvar_count());
 
-        // interpret rule
-        for rule_eqn_ptr in rule.body() {
+        // interpret rule, binds and connects before redexes
+        for rule_eqn_ptr in rule.body_by_priority() {
             let rule_eqn = self.rules.get_equation(*rule_eqn_ptr);
-            //
             self.instantiate_equation(
                 scope,
                 symbols,
                 heap,
-                &mut bvars,
-                ctr,
-                fun,
-                &mut reuse_cell_iter,
-                rule_eqn.clone(),
+                &bvars,
+                (&ctr, &fun),
+                &mut instantiation,
+                rule_eqn,
             );
         }
 
-        for reuse_cell in reuse_cell_iter {
+        self.add_instantiation_stats(&instantiation);
+        for reuse_cell in instantiation.into_unused() {
             heap.free_cell(reuse_cell);
         }
+        if let Some(parent) = parent {
+            parent.restore();
+        }
     }
 
     fn eval_bind<'scope>(
@@ -219,9 +800,15 @@ impl<'a> Runtime<'a> {
         var_ptr: PVarPtr,
         cell_ptr: CellPtr,
     ) {
+        let _span = tracing::trace_span!(
+            "eval_bind",
+            var = var_ptr.get_fvar_ptr().get_index(),
+            cell = %heap.get_cell(cell_ptr).symbol_name(symbols).unwrap()
+        )
+        .entered();
         let var = heap.get_var(var_ptr.clone());
-        match var.get_store().set_or_get(cell_ptr) {
-            (cell_ptr, Some(other_cell_ptr)) => {
+        match var.get_store().compare_and_bind(NetVar::NULL, cell_ptr) {
+            Err(NetValue::Cell(other_cell_ptr)) => {
                 if var.is_bound() {
                     // cell communicated, free the bound var
                     heap.free_var(var_ptr);
@@ -231,7 +818,15 @@ impl<'a> Runtime<'a> {
 
                 self.rewrite_redex(scope, symbols, heap, ctr_ptr, fun_ptr);
             }
-            (_, None) => {
+            Err(NetValue::Link(linked_var_ptr)) => {
+                if var.is_bound() {
+                    // cell forwarded, free the bound var
+                    heap.free_var(var_ptr);
+                }
+                self.eval_bind(scope, symbols, heap, linked_var_ptr, cell_ptr)
+            }
+            Err(NetValue::Empty) => unreachable!("An empty var always takes the cell"),
+            Ok(()) => {
                 // value set
             }
         }
@@ -245,7 +840,13 @@ impl<'a> Runtime<'a> {
         left_var_ptr: PVarPtr,
         right_var_ptr: PVarPtr,
     ) {
-        if self.debug {
+        let _span = tracing::trace_span!(
+            "eval_connect",
+            left = left_var_ptr.get_fvar_ptr().get_index(),
+            right = right_var_ptr.get_fvar_ptr().get_index()
+        )
+        .entered();
+        if self.debug_level >= DebugLevel::Basic {
             debug!(
                 "[{:?}] Evaluating CONNECT: {} ↔ {}",
                 Self::current_thread_id(),
@@ -258,11 +859,11 @@ impl<'a> Runtime<'a> {
         let right_var = heap.get_var(right_var_ptr);
 
         match (
-            left_var.get_store().get_cell_ptr(),
-            right_var.get_store().get_cell_ptr(),
+            left_var.get_store().get_value(),
+            right_var.get_store().get_value(),
         ) {
             // both vars are set
-            (Some(left_cell_ptr), Some(right_cell_ptr)) => {
+            (NetValue::Cell(left_cell_ptr), NetValue::Cell(right_cell_ptr)) => {
                 let (left_cell_ptr, right_cell_ptr) =
                     self.order_ctr_fun(symbols, heap, left_cell_ptr, right_cell_ptr);
 
@@ -278,8 +879,21 @@ impl<'a> Runtime<'a> {
                     heap.free_var(right_var_ptr);
                 }
             }
+            // follow links to the connected vars
+            (NetValue::Link(linked_var_ptr), _) => {
+                if left_var.is_bound() {
+                    heap.free_var(left_var_ptr);
+                }
+                self.eval_connect(scope, symbols, heap, linked_var_ptr, right_var_ptr)
+            }
+            (_, NetValue::Link(linked_var_ptr)) => {
+                if right_var.is_bound() {
+                    heap.free_var(right_var_ptr);
+                }
+                self.eval_connect(scope, symbols, heap, left_var_ptr, linked_var_ptr)
+            }
             // one var is set
-            (None, Some(cell_ptr)) => {
+            (NetValue::Empty, NetValue::Cell(cell_ptr)) => {
                 if right_var.is_bound() {
                     // cell communicated, free the bound var
                     heap.free_var(right_var_ptr);
@@ -288,7 +902,7 @@ impl<'a> Runtime<'a> {
                 self.eval_bind(scope, symbols, heap, left_var_ptr, cell_ptr)
             }
             // one var is set
-            (Some(cell_ptr), None) => {
+            (NetValue::Cell(cell_ptr), NetValue::Empty) => {
                 // free vars
                 if left_var.is_bound() {
                     // cell communicated, free the bound var
@@ -297,14 +911,14 @@ impl<'a> Runtime<'a> {
 
                 self.eval_bind(scope, symbols, heap, right_var_ptr, cell_ptr);
             }
-            // none are set
-            (None, None) => {
-                tracing::warn!(
-                    "[{:?}] TODO: got nothing: wait for vars {} and {}",
-                    Self::current_thread_id(),
-                    heap.display_var(symbols, left_var_ptr.get_fvar_ptr()),
-                    heap.display_var(symbols, right_var_ptr.get_fvar_ptr())
-                );
+            // none are set: link left to right so a cell arriving at either
+            // var ends up in the right one
+            (NetValue::Empty, NetValue::Empty) => {
+                match left_var.get_store().link(right_var_ptr) {
+                    NetValue::Empty => self.inc_var_link(),
+                    // a cell or link arrived meanwhile, start over
+                    _ => self.eval_connect(scope, symbols, heap, left_var_ptr, right_var_ptr),
+                }
             }
         }
     }
@@ -314,375 +928,47 @@ impl<'a> Runtime<'a> {
         scope: &Scope<'scope>,
         symbols: &'scope SymbolBook,
         heap: &'scope Heap<NetF>,
-        bvars: &mut PVarPtrBuffer,
-        ctr: Cell<NetF>,
-        fun: Cell<NetF>,
-        reuse: &mut impl Iterator<Item = CellPtr>,
-        rule_eqn: Equation<RuleF>,
+        bvars: &PVarPtrBuffer,
+        (ctr, fun): (&CellCopy<NetF>, &CellCopy<NetF>),
+        instantiation: &mut Instantiation<impl Iterator<Item = CellPtr>>,
+        rule_eqn: &Equation<RuleF>,
     ) {
-        match rule_eqn.get_kind() {
-            EquationKind::Redex => self.instantiate_redex(
-                scope,
-                symbols,
-                heap,
-                bvars,
-                ctr,
-                fun,
-                rule_eqn.get_redex_ctr(),
-                rule_eqn.get_redex_fun(),
-                reuse,
-            ),
-            EquationKind::Bind => self.instantiate_bind(
-                scope,
-                symbols,
-                heap,
-                bvars,
-                ctr,
-                fun,
-                rule_eqn.get_bind_var(),
-                rule_eqn.get_bind_cell(),
-                reuse,
-            ),
-            EquationKind::Connect => self.instantiate_connect(
+        let eqn = rule_eqn
+            .instantiate_into(self.rules, heap, bvars, ctr, fun, instantiation)
+            .unwrap_or_else(|InstantiateError::ShortCircuit(left_ptr, right_ptr)| {
+                panic!(
+                    "Short-circuit ({:?} x {:?}): {} x {} ({:?} x {:?})",
+                    left_ptr.get_polarity(),
+                    right_ptr.get_polarity(),
+                    heap.display_cell(symbols, left_ptr),
+                    heap.display_cell(symbols, right_ptr),
+                    left_ptr,
+                    right_ptr
+                )
+            });
+        debug!(
+            "[{:?}] Instantiate {} from rule {}",
+            Self::current_thread_id(),
+            eqn.get_kind(),
+            rule_eqn.get_kind()
+        );
+        match eqn.get_kind() {
+            EquationKind::Redex => {
+                self.rewrite_redex(scope, symbols, heap, eqn.get_redex_ctr(), eqn.get_redex_fun())
+            }
+            EquationKind::Bind => {
+                self.eval_bind(scope, symbols, heap, eqn.get_bind_var(), eqn.get_bind_cell())
+            }
+            EquationKind::Connect => self.eval_connect(
                 scope,
                 symbols,
                 heap,
-                bvars,
-                ctr,
-                fun,
-                rule_eqn.get_connect_left(),
-                rule_eqn.get_connect_right(),
+                eqn.get_connect_left(),
+                eqn.get_connect_right(),
             ),
         }
     }
 
-    fn instantiate_redex<'scope>(
-        &'scope self,
-        scope: &Scope<'scope>,
-        symbols: &'scope SymbolBook,
-        heap: &'scope Heap<NetF>,
-        bvars: &mut PVarPtrBuffer,
-        ctr: Cell<NetF>,
-        fun: Cell<NetF>,
-        rule_ctr_ptr: CellPtr,
-        rule_fun_ptr: CellPtr,
-        reuse: &mut impl Iterator<Item = CellPtr>,
-    ) {
-        let ctr_ptr = self.instantiate_cell(symbols, heap, bvars, ctr, fun, rule_ctr_ptr, reuse);
-        let fun_ptr = self.instantiate_cell(symbols, heap, bvars, ctr, fun, rule_fun_ptr, reuse);
-
-        self.rewrite_redex(scope, symbols, heap, ctr_ptr, fun_ptr);
-    }
-
-    fn instantiate_bind<'scope>(
-        &'scope self,
-        scope: &Scope<'scope>,
-        symbols: &'scope SymbolBook,
-        heap: &'scope Heap<NetF>,
-        bvars: &mut PVarPtrBuffer,
-        ctr: Cell<NetF>,
-        fun: Cell<NetF>,
-        rule_var_ptr: PVarPtr,
-        rule_cell_ptr: CellPtr,
-        reuse: &mut impl Iterator<Item = CellPtr>,
-    ) {
-        let cell_ptr = self.instantiate_cell(symbols, heap, bvars, ctr, fun, rule_cell_ptr, reuse);
-        let term_ptr = self.instantiate_var(bvars, ctr, fun, rule_var_ptr);
-
-        match term_ptr.get_kind() {
-            TermKind::Cell => {
-                let (ctr_ptr, fun_ptr) =
-                    self.order_ctr_fun(symbols, heap, cell_ptr, term_ptr.get_cell_ptr());
-
-                debug!(
-                    "[{:?}] Instantiate REDEX from rule bind: {} ← {}  ⟶    {} = {}",
-                    Self::current_thread_id(),
-                    self.rules.display_var(rule_var_ptr.get_fvar_ptr()),
-                    self.rules.display_cell(rule_cell_ptr),
-                    heap.display_cell(symbols, ctr_ptr),
-                    heap.display_cell(symbols, fun_ptr),
-                );
-
-                self.rewrite_redex(scope, symbols, heap, ctr_ptr, fun_ptr);
-            }
-            TermKind::Var => {
-                let pvar_ptr = term_ptr.get_var_ptr();
-                let var = heap.get_var(pvar_ptr);
-                match var.get_store().set_or_get(cell_ptr) {
-                    (cell_ptr, Some(other_cell_ptr)) => {
-                        let (ctr_ptr, fun_ptr) =
-                            self.order_ctr_fun(symbols, heap, cell_ptr, other_cell_ptr);
-
-                        debug!(
-                            "[{:?}] Instantiate REDEX from rule bind: {} ← {}  ⟶    {} = {}",
-                            Self::current_thread_id(),
-                            self.rules.display_var(rule_var_ptr.get_fvar_ptr()),
-                            self.rules.display_cell(rule_cell_ptr),
-                            heap.display_cell(symbols, ctr_ptr),
-                            heap.display_cell(symbols, fun_ptr),
-                        );
-
-                        self.rewrite_redex(scope, symbols, heap, ctr_ptr, fun_ptr);
-
-                        // free var
-                        if var.is_bound() {
-                            // cell communicated, free the bound var
-                            heap.free_var(pvar_ptr);
-                        }
-                    }
-                    (cell_ptr, None) => {
-                        debug!(
-                            "[{:?}] Instantiate BIND from rule bind: {} ← {}  ⟶    {} ← {}",
-                            Self::current_thread_id(),
-                            self.rules.display_var(rule_var_ptr.get_fvar_ptr()),
-                            self.rules.display_cell(rule_cell_ptr),
-                            heap.display_var(symbols, term_ptr.get_var_ptr().get_fvar_ptr()),
-                            heap.display_cell(symbols, cell_ptr),
-                        );
-                        // value set, do nothing
-                    }
-                }
-            }
-        }
-    }
-
-    fn instantiate_connect<'scope>(
-        &'scope self,
-        scope: &Scope<'scope>,
-        symbols: &'scope SymbolBook,
-        heap: &'scope Heap<NetF>,
-        bvars: &mut PVarPtrBuffer,
-        ctr: Cell<NetF>,
-        fun: Cell<NetF>,
-        rule_left_var: PVarPtr,
-        rule_right_var: PVarPtr,
-    ) {
-        let left_port_ptr = self.instantiate_var(bvars, ctr, fun, rule_left_var);
-        let right_port_ptr = self.instantiate_var(bvars, ctr, fun, rule_right_var);
-
-        match (left_port_ptr.get_kind(), right_port_ptr.get_kind()) {
-            (TermKind::Cell, TermKind::Cell) => {
-                let (ctr_ptr, fun_ptr) = self.order_ctr_fun(
-                    symbols,
-                    heap,
-                    left_port_ptr.get_cell_ptr(),
-                    right_port_ptr.get_cell_ptr(),
-                );
-
-                debug!(
-                    "[{:?}] Instantiate REDEX from rule connect: {} ← {}  ⟶    {} = {}",
-                    Self::current_thread_id(),
-                    self.rules.display_var(rule_left_var.get_fvar_ptr()),
-                    self.rules.display_var(rule_right_var.get_fvar_ptr()),
-                    heap.display_cell(symbols, fun_ptr),
-                    heap.display_cell(symbols, ctr_ptr)
-                );
-
-                self.rewrite_redex(scope, symbols, heap, ctr_ptr, fun_ptr);
-            }
-            (TermKind::Cell, TermKind::Var) => {
-                debug!(
-                    "[{:?}] Instantiate BIND from rule connect: {} ← {}  ⟶    {} ← {}",
-                    Self::current_thread_id(),
-                    self.rules.display_var(rule_left_var.get_fvar_ptr()),
-                    self.rules.display_var(rule_right_var.get_fvar_ptr()),
-                    heap.display_var(symbols, right_port_ptr.get_var_ptr().get_fvar_ptr()),
-                    heap.display_cell(symbols, left_port_ptr.get_cell_ptr())
-                );
-
-                self.eval_bind(
-                    scope,
-                    symbols,
-                    heap,
-                    right_port_ptr.get_var_ptr().into(),
-                    left_port_ptr.get_cell_ptr(),
-                )
-            }
-            (TermKind::Var, TermKind::Cell) => {
-                debug!(
-                    "[{:?}] Instantiate BIND from rule connect: {} ← {}  ⟶    {} ← {}",
-                    Self::current_thread_id(),
-                    self.rules.display_var(rule_left_var.get_fvar_ptr()),
-                    self.rules.display_var(rule_right_var.get_fvar_ptr()),
-                    heap.display_var(symbols, right_port_ptr.get_var_ptr().get_fvar_ptr()),
-                    heap.display_cell(symbols, left_port_ptr.get_cell_ptr())
-                );
-
-                self.eval_bind(
-                    scope,
-                    symbols,
-                    heap,
-                    left_port_ptr.get_var_ptr().into(),
-                    right_port_ptr.get_cell_ptr(),
-                )
-            }
-            (TermKind::Var, TermKind::Var) => {
-                debug!(
-                    "[{:?}] Instantiate CONNECT from rule connect: {} ← {}  ⟶    {} ↔ {}",
-                    Self::current_thread_id(),
-                    self.rules.display_var(rule_left_var.get_fvar_ptr()),
-                    self.rules.display_var(rule_right_var.get_fvar_ptr()),
-                    heap.display_var(symbols, left_port_ptr.get_var_ptr().get_fvar_ptr()),
-                    heap.display_var(symbols, right_port_ptr.get_var_ptr().get_fvar_ptr())
-                );
-
-                self.eval_connect(
-                    scope,
-                    symbols,
-                    heap,
-                    left_port_ptr.get_var_ptr(),
-                    right_port_ptr.get_var_ptr(),
-                )
-            }
-        }
-    }
-
-    pub fn instantiate_cell(
-        &self,
-        symbols: &SymbolBook,
-        heap: &Heap<NetF>,
-        bvars: &mut PVarPtrBuffer,
-        ctr: Cell<NetF>,
-        fun: Cell<NetF>,
-        rule_cell_ptr: CellPtr,
-        reuse: &mut impl Iterator<Item = CellPtr>,
-    ) -> CellPtr {
-        let rule_cell = self.rules.heap.get_cell(rule_cell_ptr);
-        let cell_ptr = match rule_cell.get_symbol_ptr().get_arity() {
-            SymbolArity::Zero => {
-                // try to use a cell from the reuse buffer
-                match reuse.next() {
-                    Some(cell_ptr) => {
-                        self.inc_cell_reuse();
-                        heap.reuse_cell0(rule_cell.get_symbol_ptr(), cell_ptr)
-                    }
-                    None => {
-                        self.inc_cell_instantiation();
-                        heap.cell0(rule_cell.get_symbol_ptr())
-                    }
-                }
-            }
-            SymbolArity::One => {
-                let term_ptr = self.instantiate_port(
-                    symbols,
-                    heap,
-                    bvars,
-                    ctr,
-                    fun,
-                    rule_cell.get_left_port(),
-                    reuse,
-                );
-
-                // try to use a cell from the reuse buffer
-                match reuse.next() {
-                    Some(cell_ptr) => {
-                        self.inc_cell_reuse();
-                        heap.reuse_cell1(rule_cell.get_symbol_ptr(), term_ptr, cell_ptr)
-                    }
-                    None => {
-                        self.inc_cell_instantiation();
-                        heap.cell1(rule_cell.get_symbol_ptr(), term_ptr)
-                    }
-                }
-            }
-            SymbolArity::Two => {
-                let left_port_ptr = self.instantiate_port(
-                    symbols,
-                    heap,
-                    bvars,
-                    ctr,
-                    fun,
-                    rule_cell.get_left_port(),
-                    reuse,
-                );
-                let right_port_ptr = self.instantiate_port(
-                    symbols,
-                    heap,
-                    bvars,
-                    ctr,
-                    fun,
-                    rule_cell.get_right_port(),
-                    reuse,
-                );
-
-                // try to use a cell from the reuse buffer
-                match reuse.next() {
-                    Some(cell_ptr) => {
-                        self.inc_cell_reuse();
-                        heap.reuse_cell2(
-                            rule_cell.get_symbol_ptr(),
-                            left_port_ptr,
-                            right_port_ptr,
-                            cell_ptr,
-                        )
-                    }
-                    None => {
-                        self.inc_cell_instantiation();
-                        heap.cell2(rule_cell.get_symbol_ptr(), left_port_ptr, right_port_ptr)
-                    }
-                }
-            }
-        };
-        // debug!(
-        //     "[{}] Instantiate CELL: {:?}",
-        //     Self::current_thread_id(),
-        //     cell_ptr
-        // );
-
-        cell_ptr
-    }
-
-    fn instantiate_port(
-        &self,
-        symbols: &SymbolBook,
-        heap: &Heap<NetF>,
-        bvars: &mut PVarPtrBuffer,
-        ctr: Cell<NetF>,
-        fun: Cell<NetF>,
-        rule_port_ptr: TermPtr,
-        reuse: &mut impl Iterator<Item = CellPtr>,
-    ) -> TermPtr {
-        match rule_port_ptr.get_kind() {
-            TermKind::Cell => self
-                .instantiate_cell(
-                    symbols,
-                    heap,
-                    bvars,
-                    ctr,
-                    fun,
-                    rule_port_ptr.get_cell_ptr(),
-                    reuse,
-                )
-                .into(),
-            TermKind::Var => self.instantiate_var(bvars, ctr, fun, rule_port_ptr.get_var_ptr()),
-        }
-    }
-
-    fn instantiate_var(
-        &self,
-        bvars: &mut PVarPtrBuffer,
-        ctr: Cell<NetF>,
-        fun: Cell<NetF>,
-        rule_var_ptr: PVarPtr,
-    ) -> TermPtr {
-        self.inc_var_instantiation();
-        let rule_var = self.rules.heap.get_var(rule_var_ptr.into());
-        match rule_var {
-            Var::Bound(bvar_id) => match rule_var_ptr.get_polarity() {
-                Polarity::Pos => bvars.get_pos_var(*bvar_id).into(),
-                Polarity::Neg => bvars.get_neg_var(*bvar_id).into(),
-            },
-            Var::Free(port) => self.resolve_fvar(ctr, fun, port.clone()),
-        }
-    }
-
-    fn resolve_fvar(&self, ctr: Cell<NetF>, fun: Cell<NetF>, port: RulePort) -> TermPtr {
-        match port {
-            RulePort::Ctr(port_num) => ctr.get_port(port_num),
-            RulePort::Fun(port_num) => fun.get_port(port_num),
-        }
-    }
-
     fn order_ctr_fun(
         &self,
         symbols: &SymbolBook,
@@ -713,7 +999,7 @@ impl<'a> Runtime<'a> {
     pub(crate) fn new_bvar_buffer(
         &self,
         heap: &Heap<NetF>,
-        bvar_count: u8,
+        bvar_count: u16,
     ) -> PVarPtrBuffer {
         let mut buffer = PVarPtrBuffer::new(bvar_count);
         for i in 0..bvar_count {
@@ -723,6 +1009,71 @@ impl<'a> Runtime<'a> {
     }
 }
 
+/// Evaluation from async code. The blocking pool needs `'static` work, so the
+/// runtime is shared through an `Arc` and the rules and symbols must live for
+/// the whole program, e.g. leaked at startup.
+#[cfg(feature = "tokio")]
+impl Runtime<'static> {
+    /// Evaluates like `eval` on the tokio blocking pool, so the async worker
+    /// threads are not blocked while rayon runs. Dropping the future, e.g. when
+    /// `tokio::time::timeout` expires, stops waiting for the result but not
+    /// the evaluation itself, which is bounded by `max_rewrites` if set.
+    pub fn eval_async(
+        self: &Arc<Self>,
+        net: Net<'static>,
+    ) -> impl std::future::Future<Output = Result<Net<'static>, MaxRewritesExceeded<'static>>> {
+        let runtime = self.clone();
+        let handle = tokio::task::spawn_blocking(move || runtime.eval(net));
+        async move {
+            match handle.await {
+                Ok(result) => result,
+                Err(error) if error.is_panic() => std::panic::resume_unwind(error.into_panic()),
+                Err(error) => panic!("Evaluation did not complete: {}", error),
+            }
+        }
+    }
+}
+
+/// A single threaded stand-in for `rayon::scope`. Spawned jobs are queued and
+/// run after the scope body, in the order they were spawned.
+#[cfg(feature = "wasm")]
+mod wasm {
+    use std::{cell::RefCell, collections::VecDeque};
+
+    type Job<'scope> = Box<dyn FnOnce(&Scope<'scope>) + 'scope>;
+
+    pub struct Scope<'scope> {
+        jobs: RefCell<VecDeque<Job<'scope>>>,
+    }
+
+    impl<'scope> Scope<'scope> {
+        pub fn spawn<F>(&self, job: F)
+        where
+            F: FnOnce(&Scope<'scope>) + 'scope,
+        {
+            self.jobs.borrow_mut().push_back(Box::new(job));
+        }
+    }
+
+    pub fn scope<'scope, OP, R>(op: OP) -> R
+    where
+        OP: FnOnce(&Scope<'scope>) -> R,
+    {
+        let scope = Scope {
+            jobs: RefCell::new(VecDeque::new()),
+        };
+        let result = op(&scope);
+        // jobs may spawn more jobs, so the queue is not borrowed while they run
+        loop {
+            let job = scope.jobs.borrow_mut().pop_front();
+            match job {
+                Some(job) => job(&scope),
+                None => break result,
+            }
+        }
+    }
+}
+
 // impl<'a> Runtime<'a> {
 //     pub fn display_equation(
 //         &self,
@@ -745,6 +1096,12 @@ pub struct EquationsBuffer {
     len: u8,
 }
 
+impl Default for EquationsBuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl EquationsBuffer {
     const MAX_BUFFER_LEN: usize = 10;
 
@@ -769,4 +1126,526 @@ impl EquationsBuffer {
     pub fn slice(&self) -> &[Equation<NetF>] {
         &self.buffer[0..self.len as usize]
     }
+
+    pub fn iter(&self) -> std::slice::Iter<Equation<NetF>> {
+        self.slice().iter()
+    }
+
+    /// Empties the buffer, returning the equations it held in push order.
+    pub fn drain(&mut self) -> std::iter::Copied<std::slice::Iter<Equation<NetF>>> {
+        let len = std::mem::take(&mut self.len);
+        self.buffer[0..len as usize].iter().copied()
+    }
+}
+
+/// Configures the rayon pool a `Runtime` evaluates in, so reductions can be
+/// kept apart from other work. Without any thread setting the global pool is
+/// used. The settings are ignored by the single threaded `wasm` runtime.
+pub struct RuntimeBuilder<'a> {
+    rules: &'a RuleSet<'a>,
+    debug_level: DebugLevel,
+    checked: bool,
+    num_threads: Option<usize>,
+    stack_size: Option<usize>,
+    thread_name_prefix: Option<String>,
+    max_rewrites: Option<usize>,
+}
+
+impl<'a> RuntimeBuilder<'a> {
+    /// `DebugLevel::Basic` by default in debug builds, `None` in release.
+    pub fn with_debug_level(mut self, level: DebugLevel) -> Self {
+        self.debug_level = level;
+        self
+    }
+
+    /// Whether `Runtime::eval_checked` validates, on by default in debug builds.
+    pub fn checked(mut self, checked: bool) -> Self {
+        self.checked = checked;
+        self
+    }
+
+    pub fn num_threads(mut self, num_threads: usize) -> Self {
+        self.num_threads = Some(num_threads);
+        self
+    }
+
+    pub fn stack_size(mut self, bytes: usize) -> Self {
+        self.stack_size = Some(bytes);
+        self
+    }
+
+    pub fn thread_name_prefix(mut self, prefix: &str) -> Self {
+        self.thread_name_prefix = Some(prefix.to_string());
+        self
+    }
+
+    /// The most rewrites one `eval` may do before it fails, see `Runtime::eval`.
+    pub fn max_rewrites(mut self, limit: usize) -> Self {
+        self.max_rewrites = Some(limit);
+        self
+    }
+
+    #[cfg(feature = "wasm")]
+    pub fn build(self) -> Runtime<'a> {
+        let mut runtime = Runtime::new(self.rules, false);
+        runtime.debug_level = self.debug_level;
+        runtime.checked = self.checked;
+        runtime.max_rewrites = self.max_rewrites;
+        runtime
+    }
+
+    #[cfg(not(feature = "wasm"))]
+    pub fn build(self) -> Runtime<'a> {
+        let mut runtime = Runtime::new(self.rules, false);
+        runtime.debug_level = self.debug_level;
+        runtime.checked = self.checked;
+        runtime.max_rewrites = self.max_rewrites;
+        if self.num_threads.is_none()
+            && self.stack_size.is_none()
+            && self.thread_name_prefix.is_none()
+        {
+            return runtime;
+        }
+
+        let mut pool = rayon::ThreadPoolBuilder::new();
+        if let Some(num_threads) = self.num_threads {
+            pool = pool.num_threads(num_threads);
+        }
+        if let Some(stack_size) = self.stack_size {
+            pool = pool.stack_size(stack_size);
+        }
+        if let Some(prefix) = self.thread_name_prefix {
+            pool = pool.thread_name(move |index| format!("{}{}", prefix, index));
+        }
+        let pool = pool.build().expect("Failed to build the runtime thread pool");
+        runtime.pool = Some(Arc::new(pool));
+        runtime
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io,
+        sync::{Arc, Mutex},
+    };
+
+    use tracing_subscriber::{
+        filter::filter_fn, fmt::format::FmtSpan, layer::SubscriberExt, Layer,
+    };
+
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct SharedBuf(Arc<Mutex<Vec<u8>>>);
+    impl io::Write for SharedBuf {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn fib_symbols() -> SymbolBook {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+        symbols.declare_arith_symbols();
+        symbols.declare_combinator_symbols();
+        symbols.declare_fib_symbols();
+        symbols
+    }
+
+    fn fib_rules(symbols: &SymbolBook) -> RuleSet {
+        let mut rules = RuleSet::new(symbols);
+        rules.arith_rules();
+        rules.define_combinator_rules();
+        rules.fib_rules();
+        rules
+    }
+
+    // fib(n) on the output var
+    fn fib_net(symbols: &SymbolBook, n: usize) -> Net {
+        let mut net = Net::new(symbols);
+        net.equations(|b| {
+            let result = b.output();
+            let num = b.n(n);
+            b.fibonacci(num.into(), result.into());
+        });
+        net
+    }
+
+    #[test]
+    fn test_runtime_var_links() {
+        let symbols = fib_symbols();
+        let rules = fib_rules(&symbols);
+
+        // binds and connects go first, so fib never connects two empty vars,
+        // this was also the case with rule bodies in definition order
+        let runtime = Runtime::new(&rules, false);
+        let net = runtime.eval_seq(fib_net(&symbols, 15));
+        assert_eq!(net.read_nat(net.single_output().unwrap()), Some(610));
+        assert_eq!(runtime.get_var_links(), 0);
+
+        // 3 + (2 + 1), the inner sum is still pending when the outer one
+        // reaches Z and connects its output to it
+        let mut net = Net::new(&symbols);
+        net.equations(|b| {
+            let (sum_in, sum_out) = b.var();
+            let one = b.n(1);
+            let adder = b.adder(sum_in.into(), one.into());
+            let two = b.n(2);
+            b.add(two, adder);
+
+            let out = b.output();
+            let adder = b.adder(out.into(), sum_out.into());
+            let three = b.n(3);
+            b.add(three, adder);
+        });
+        let runtime = Runtime::new(&rules, false);
+        let net = runtime.eval_seq(net);
+        assert_eq!(net.read_nat(net.single_output().unwrap()), Some(6));
+        // the link depends on the order spawned redexes run in: rayon pops the
+        // pending queue last in first out, the wasm scope runs jobs in order
+        #[cfg(not(feature = "wasm"))]
+        assert_eq!(runtime.get_var_links(), 1);
+    }
+
+    #[test]
+    fn test_runtime_equation_kind_spans() {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+        symbols.declare_arith_symbols();
+        let mut rules = RuleSet::new(&symbols);
+        rules.arith_rules();
+
+        // 1 + 1
+        let mut net = Net::new(&symbols);
+        net.equations(|b| {
+            let result = b.output();
+            let one = b.one();
+            let adder = b.adder(result.into(), one.into());
+            let one = b.one();
+            b.add(one, adder);
+        });
+
+        let buf = SharedBuf::default();
+        let writer = buf.clone();
+        // only spans, the equation events are covered elsewhere
+        let subscriber = tracing_subscriber::registry().with(
+            tracing_subscriber::fmt::layer()
+                .with_span_events(FmtSpan::ENTER)
+                .with_ansi(false)
+                .with_writer(move || writer.clone())
+                .with_filter(filter_fn(|metadata| metadata.is_span())),
+        );
+
+        // a single worker so the thread-local subscriber sees every spawned redex
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(1).build().unwrap();
+        let runtime = Runtime::new(&rules, false);
+        pool.install(|| {
+            tracing::subscriber::with_default(subscriber, || runtime.eval(net).unwrap())
+        });
+
+        let output = String::from_utf8(buf.0.lock().unwrap().clone()).unwrap();
+        assert!(output.contains("equation_kind{kind=REDEX}"));
+        // (S Z) ⋈ (add r (S Z)) rewrites to Z ⋈ (add x (S Z)), binding r to (S x)
+        assert!(output.contains("eval_redex{ctr=S fun=add}"));
+        assert!(output.contains("eval_redex{ctr=Z fun=add}"));
+        assert!(output.contains("eval_bind{var="));
+    }
+
+    #[test]
+    fn test_runtime_new_with_heap_capacity() {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+        symbols.declare_arith_symbols();
+        let mut rules = RuleSet::new(&symbols);
+        rules.arith_rules();
+
+        // 2 + 2 in a heap with room for the initial cells and vars only
+        let mut net = Net::with_capacity(&symbols, [1, 7, 1]);
+        net.equations(|b| {
+            let result = b.output();
+            let two = b.two();
+            let adder = b.adder(result.into(), two.into());
+            let two = b.two();
+            b.add(two, adder);
+        });
+
+        let runtime = Runtime::new_with_heap_capacity(&rules, 64, 32, false);
+        let net = runtime.eval(net).unwrap();
+        assert_eq!(net.heap.cells.capacity(), 64);
+        assert_eq!(net.heap.vars.capacity(), 32);
+        assert_eq!(net.read_nat(net.single_output().unwrap()), Some(4));
+
+        // heaps are only grown
+        let net = Runtime::new_with_heap_capacity(&rules, 8, 8, false).eval_seq(net);
+        assert_eq!(net.heap.cells.capacity(), 64);
+    }
+
+    #[test]
+    fn test_equations_buffer_iter_and_drain() {
+        let mut buffer = EquationsBuffer::new();
+        let (neg_pvar, pos_pvar) = PVarPtr::wire(crate::inet::var::VarPtr::from(3u32));
+        buffer.push(Equation::connect(neg_pvar, pos_pvar));
+        let ctr = CellPtr::new(1, Polarity::Pos);
+        buffer.push(Equation::redex(ctr, CellPtr::new(2, Polarity::Neg)));
+        let kinds: Vec<EquationKind> = buffer.iter().map(|eqn| eqn.get_kind()).collect();
+        assert_eq!(kinds, [EquationKind::Connect, EquationKind::Redex]);
+
+        let drained: Vec<Equation<NetF>> = buffer.drain().collect();
+        assert_eq!(drained.len(), 2);
+        assert_eq!(drained[1].get_redex_fun().get_index(), 2);
+        assert!(buffer.slice().is_empty());
+        assert_eq!(buffer.iter().count(), 0);
+    }
+
+    #[test]
+    #[cfg(feature = "large-heap")]
+    fn test_runtime_eval_large_heap() {
+        assert_eq!(std::mem::size_of::<crate::inet::term::TermPtr>(), 8);
+
+        // ptrs past the 22-bit indexes of the default heap survive the
+        // equations, vars and ports they are packed in
+        let index = CellPtr::MAX_INDEX;
+        assert!(index > 1 << 22);
+        let ctr = CellPtr::new(index, Polarity::Pos);
+        let fun = CellPtr::new(index - 1, Polarity::Neg);
+        let eqn = Equation::<NetF>::redex(ctr, fun);
+        assert_eq!(eqn.get_redex_ctr(), ctr);
+        assert_eq!(eqn.get_redex_fun(), fun);
+        let (_, pos_pvar) = PVarPtr::wire(crate::inet::var::VarPtr::new(index));
+        let var = NetVar::default();
+        var.link(pos_pvar);
+        assert!(matches!(var.get_value(), NetValue::Link(pvar_ptr) if pvar_ptr == pos_pvar));
+        let var = NetVar::default();
+        assert!(var.compare_and_bind(NetVar::NULL, ctr).is_ok());
+        assert_eq!(var.get_cell_ptr(), Some(ctr));
+
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+        symbols.declare_arith_symbols();
+        let mut rules = RuleSet::new(&symbols);
+        rules.arith_rules();
+
+        let net = Net::from_str("< r | (S (S Z)) ⋈ (add r (S Z)) >", &symbols).unwrap();
+        let net = Runtime::new(&rules, false).eval(net).unwrap();
+        assert_eq!(net.read_nat(net.single_output().unwrap()), Some(3));
+    }
+
+    #[test]
+    #[cfg(feature = "tokio")]
+    fn test_runtime_eval_async() {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+        symbols.declare_arith_symbols();
+        let symbols: &'static SymbolBook = Box::leak(Box::new(symbols));
+        let mut rules = RuleSet::new(symbols);
+        rules.arith_rules();
+        let rules: &'static RuleSet = Box::leak(Box::new(rules));
+
+        let runtime = Arc::new(Runtime::new(rules, false));
+        let net = Net::from_str("< r | (S (S Z)) ⋈ (add r (S Z)) >", symbols).unwrap();
+        let tokio = tokio::runtime::Builder::new_current_thread().enable_time().build().unwrap();
+        let net = tokio
+            .block_on(async {
+                let timeout = std::time::Duration::from_secs(60);
+                tokio::time::timeout(timeout, runtime.eval_async(net)).await
+            })
+            .unwrap()
+            .unwrap();
+        assert_eq!(net.read_nat(net.single_output().unwrap()), Some(3));
+        assert_eq!(runtime.get_rewrites(), 3);
+    }
+
+    #[test]
+    #[cfg(not(feature = "wasm"))]
+    fn test_runtime_eval_on_pool() {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+        symbols.declare_arith_symbols();
+        let mut rules = RuleSet::new(&symbols);
+        rules.arith_rules();
+
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(2)
+            .thread_name(|index| format!("app-{}", index))
+            .build()
+            .unwrap();
+        let runtime = Runtime::new(&rules, false);
+        let net = Net::from_str("< r | (S (S (S Z))) ⋈ (add r (S (S Z))) >", &symbols).unwrap();
+        let net = runtime.eval_on_pool(net, &pool).unwrap();
+        assert_eq!(net.read_nat(net.single_output().unwrap()), Some(5));
+        assert_eq!(runtime.get_rewrites(), 4);
+    }
+
+    #[test]
+    #[cfg(not(feature = "wasm"))]
+    fn test_runtime_builder_pool() {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+        symbols.declare_arith_symbols();
+        let mut rules = RuleSet::new(&symbols);
+        rules.arith_rules();
+
+        let runtime = Runtime::builder(&rules)
+            .num_threads(2)
+            .stack_size(4 * 1024 * 1024)
+            .thread_name_prefix("inet-")
+            .build();
+        assert_eq!(runtime.pool.as_ref().unwrap().current_num_threads(), 2);
+        assert!(Runtime::builder(&rules).build().pool.is_none());
+
+        // 1 + 1
+        let mut net = Net::new(&symbols);
+        net.equations(|b| {
+            let result = b.output();
+            let one = b.one();
+            let adder = b.adder(result.into(), one.into());
+            let one = b.one();
+            b.add(one, adder);
+        });
+        let net = runtime.eval(net).unwrap();
+
+        let mut two = Net::new(&symbols);
+        two.equations(|b| {
+            let result = b.output();
+            let num = b.two();
+            b.bind(result, num);
+        });
+        assert!(net.alpha_equivalent(&Runtime::new(&rules, false).eval(two).unwrap(), &symbols));
+        assert_eq!(runtime.get_rewrites(), 2);
+    }
+
+    #[test]
+    fn test_runtime_eval_checked() {
+        let symbols = fib_symbols();
+        let rules = fib_rules(&symbols);
+        let runtime = Runtime::builder(&rules).checked(true).build();
+
+        let net = runtime.eval_checked(fib_net(&symbols, 8)).unwrap();
+        assert!(net.single_output().is_some());
+
+        // the bound cell is freed behind the net's back
+        let mut net = Net::new(&symbols);
+        let mut two = None;
+        net.equations(|b| {
+            let result = b.output();
+            two = Some(b.two());
+            b.bind(result, two.unwrap());
+        });
+        net.heap.free_cell(two.unwrap());
+        assert_eq!(
+            runtime.eval_checked(net).err(),
+            Some(CheckedEvalError::PreCondition(vec![NetError::DanglingCell(two.unwrap())]))
+        );
+    }
+
+    #[test]
+    fn test_runtime_eval_replay() {
+        let symbols = fib_symbols();
+        let rules = fib_rules(&symbols);
+        let (recorded, log) = RewriteLog::record(fib_net(&symbols, 10), &rules);
+        let runtime = Runtime::new(&rules, false);
+        let replayed = runtime.eval_replay(fib_net(&symbols, 10), &log);
+        assert_eq!(runtime.get_rewrites(), log.len());
+        assert!(replayed.alpha_equivalent(&recorded, &symbols));
+    }
+
+    #[test]
+    fn test_runtime_max_rewrites() {
+        let symbols = fib_symbols();
+        let rules = fib_rules(&symbols);
+        for num_threads in [1, 4] {
+            let runtime = Runtime::builder(&rules)
+                .num_threads(num_threads)
+                .max_rewrites(10)
+                .build();
+            let exceeded = runtime.eval(fib_net(&symbols, 6)).unwrap_err();
+            assert_eq!(exceeded.rewrites, 10);
+            assert_eq!(runtime.get_rewrites(), 10);
+            assert!(!exceeded.partial_net.is_normal());
+
+            // the partial net resumes where it stopped
+            let net = Runtime::new(&rules, false).eval(*exceeded.partial_net).unwrap();
+            assert_eq!(net.read_nat(net.single_output().unwrap()), Some(8));
+        }
+
+        let runtime = Runtime::builder(&rules).max_rewrites(10_000).build();
+        let net = runtime.eval(fib_net(&symbols, 6)).unwrap();
+        assert_eq!(net.read_nat(net.single_output().unwrap()), Some(8));
+
+        // evals running at the same time each have their own limit
+        let runtime = Runtime::builder(&rules).max_rewrites(10).build();
+        std::thread::scope(|scope| {
+            let evals: Vec<_> = (0..2)
+                .map(|_| scope.spawn(|| runtime.eval(fib_net(&symbols, 6)).unwrap_err().rewrites))
+                .collect();
+            for eval in evals {
+                assert_eq!(eval.join().unwrap(), 10);
+            }
+        });
+        assert_eq!(runtime.get_rewrites(), 20);
+    }
+
+    #[test]
+    #[cfg(not(feature = "wasm"))]
+    fn test_runtime_eval_seq() {
+        let symbols = fib_symbols();
+        let rules = fib_rules(&symbols);
+        let runtime = Runtime::new(&rules, false);
+        let seq = runtime.eval_seq(fib_net(&symbols, 6));
+        let rewrites = runtime.get_rewrites();
+        let parallel = Runtime::builder(&rules).num_threads(2).build();
+        let parallel = parallel.eval(fib_net(&symbols, 6)).unwrap();
+        assert!(seq.alpha_equivalent(&parallel, &symbols));
+        assert!(rewrites > 0);
+    }
+
+    #[test]
+    fn test_runtime_debug_level_full() {
+        let symbols = fib_symbols();
+        let rules = fib_rules(&symbols);
+        assert_eq!(Runtime::builder(&rules).build().debug_level, DebugLevel::default());
+        let full = Runtime::builder(&rules)
+            .with_debug_level(DebugLevel::Full)
+            .build();
+        let validated = full.eval(fib_net(&symbols, 6)).unwrap();
+        let runtime = Runtime::new(&rules, false);
+        let net = runtime.eval(fib_net(&symbols, 6)).unwrap();
+        assert!(validated.alpha_equivalent(&net, &symbols));
+        assert_eq!(full.get_rewrites(), runtime.get_rewrites());
+    }
+
+    #[test]
+    fn test_runtime_step() {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+        symbols.declare_arith_symbols();
+        let mut rules = RuleSet::new(&symbols);
+        rules.arith_rules();
+
+        // 2 + 1
+        let mut net = Net::new(&symbols);
+        net.equations(|b| {
+            let result = b.output();
+            let one = b.one();
+            let adder = b.adder(result.into(), one.into());
+            let two = b.n(2);
+            b.add(two, adder);
+        });
+        let runtime = Runtime::new(&rules, false);
+        assert!(!net.is_normal());
+        let mut steps = 0;
+        while runtime.step(&mut net) {
+            steps += 1;
+            assert_eq!(runtime.get_rewrites(), steps);
+        }
+        assert_eq!(steps, 3);
+        assert!(net.body.is_empty() && net.is_normal());
+        assert_eq!(net.read_nat(net.single_output().unwrap()), Some(3));
+    }
 }
+
@@ -1,12 +1,36 @@
 use std::{
-    collections::HashMap,
+    collections::{BTreeSet, HashMap},
     fmt::{Binary, Debug, Display, Formatter},
+    sync::Mutex,
 };
 
-use super::{BitSet16, BitSet8, Polarity};
+use super::{
+    rule::{PortNum, RuleSet},
+    BitSet16, BitSet32, Polarity,
+};
 
 #[derive(Debug, Hash, PartialEq, Eq, Clone)]
 pub struct SymbolName(pub &'static str);
+
+// names only known at run time, see `SymbolName::intern`
+static INTERNED_NAMES: Mutex<BTreeSet<&'static str>> = Mutex::new(BTreeSet::new());
+
+impl SymbolName {
+    /// A name only known at run time, e.g. read from bytecode or passed in
+    /// through the C API. Names are `&'static str`, so each distinct name is
+    /// allocated once for the life of the process and then reused.
+    pub fn intern(name: &str) -> SymbolName {
+        let mut names = INTERNED_NAMES.lock().unwrap_or_else(|error| error.into_inner());
+        match names.get(name) {
+            Some(name) => SymbolName(name),
+            None => {
+                let name: &'static str = Box::leak(name.to_owned().into_boxed_str());
+                names.insert(name);
+                SymbolName(name)
+            }
+        }
+    }
+}
 // impl SymbolName {
 //     pub fn new(name: &str) -> Self {
 //         Self(name)
@@ -29,69 +53,90 @@ impl From<&'static str> for SymbolName {
 //     }
 // }
 
-#[derive(Debug, PartialEq, PartialOrd)]
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
 pub enum SymbolArity {
-    Zero = 0,
-    One = 1,
-    Two = 2,
+    Zero,
+    One,
+    Two,
+    N(u8),
 }
 
-impl From<u64> for SymbolArity {
-    fn from(value: u64) -> Self {
+impl SymbolArity {
+    pub const MAX: u8 = 8;
+
+    pub fn port_count(&self) -> usize {
+        match self {
+            SymbolArity::Zero => 0,
+            SymbolArity::One => 1,
+            SymbolArity::Two => 2,
+            SymbolArity::N(n) => *n as usize,
+        }
+    }
+}
+
+impl From<usize> for SymbolArity {
+    fn from(value: usize) -> Self {
         match value {
             0 => SymbolArity::Zero,
             1 => SymbolArity::One,
             2 => SymbolArity::Two,
-            _ => panic!(),
+            n if n <= SymbolArity::MAX as usize => SymbolArity::N(n as u8),
+            _ => panic!("Symbol arity {} is greater than {}", value, SymbolArity::MAX),
         }
     }
 }
 
+impl From<u64> for SymbolArity {
+    fn from(value: u64) -> Self {
+        SymbolArity::from(value as usize)
+    }
+}
+
 impl From<u16> for SymbolArity {
     fn from(value: u16) -> Self {
-        match value {
-            0 => SymbolArity::Zero,
-            1 => SymbolArity::One,
-            2 => SymbolArity::Two,
-            _ => panic!(),
-        }
+        SymbolArity::from(value as usize)
     }
 }
 
 impl From<u8> for SymbolArity {
     fn from(value: u8) -> Self {
-        match value {
-            0 => SymbolArity::Zero,
-            1 => SymbolArity::One,
-            2 => SymbolArity::Two,
-            _ => panic!(),
-        }
+        SymbolArity::from(value as usize)
     }
 }
 
 #[derive(Clone, Copy, PartialEq)]
-pub struct SymbolPtr(u16);
+pub struct SymbolPtr(u32);
 impl SymbolPtr {
-    const INDEX: BitSet16<11> = BitSet16 {
-        mask: 0b00000111_11111111,
+    const INDEX: BitSet32<13> = BitSet32 {
+        mask: 0b00011111_11111111,
         offset: 0,
     };
-    const POLARITY: BitSet16<1> = BitSet16 {
-        mask: 0b00001,
-        offset: 11,
+    const POLARITY: BitSet32<1> = BitSet32 {
+        mask: 0b1,
+        offset: 13,
     };
-    const ARITY: BitSet16<2> = BitSet16 {
-        mask: 0b0011,
-        offset: 12,
+    // the exact port count, n-ary symbols included
+    const ARITY: BitSet32<4> = BitSet32 {
+        mask: 0b1111,
+        offset: 14,
     };
 
-    const PTR: BitSet16<14> = BitSet16 {
+    const PTR: BitSet32<18> = BitSet32 {
+        mask: 0b00000011_11111111_11111111,
+        offset: 0,
+    };
+    // what cells pack apart from the arity
+    const INDEX_AND_POLARITY: BitSet32<14> = BitSet32 {
         mask: 0b00111111_11111111,
         offset: 0,
     };
 
+    /// Symbol indexes are 13 bits wide, index 0 is reserved.
+    pub const MAX_INDEX: usize = (1 << 13) - 1;
+
     pub fn new(index: usize, arity: SymbolArity, polarity: Polarity) -> Self {
         assert!(index != 0, "Invalid symbol index '0'");
+        assert!(index <= Self::MAX_INDEX, "Symbol index {} out of range", index);
         let mut new = Self(0);
         new.set_index(index);
         new.set_polarity(polarity);
@@ -106,7 +151,7 @@ impl SymbolPtr {
 
     #[inline]
     fn set_polarity(&mut self, polarity: Polarity) {
-        self.0 = Self::POLARITY.set(self.0, polarity as u16)
+        self.0 = Self::POLARITY.set(self.0, polarity as u32)
     }
 
     #[inline]
@@ -116,33 +161,62 @@ impl SymbolPtr {
 
     #[inline]
     fn set_index(&mut self, index: usize) {
-        self.0 = Self::INDEX.set(self.0, index as u16)
+        self.0 = Self::INDEX.set(self.0, index as u32)
     }
 
+    #[inline]
     pub fn get_arity(&self) -> SymbolArity {
-        SymbolArity::from(Self::ARITY.get(self.0))
+        SymbolArity::from(Self::ARITY.get(self.0) as usize)
+    }
+
+    /// The port count of an n-ary symbol, `None` for arities up to two.
+    #[inline]
+    pub fn get_nary_arity(&self) -> Option<u8> {
+        match self.get_arity() {
+            SymbolArity::N(n) => Some(n),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    pub fn is_nary(&self) -> bool {
+        self.get_nary_arity().is_some()
     }
 
     fn set_arity(&mut self, arity: SymbolArity) {
-        self.0 |= Self::ARITY.set(self.0, arity as u16);
+        self.0 = Self::ARITY.set(self.0, arity.port_count() as u32);
     }
 
     #[inline]
-    pub fn get_ptr(&self) -> u16 {
+    pub fn get_ptr(&self) -> u32 {
         Self::PTR.get(self.0)
     }
 
-    pub fn get_raw(&self) -> u16 {
+    pub fn get_raw(&self) -> u32 {
         self.0
     }
+
+    /// The index and polarity bits, which `Cell` packs apart from the arity.
+    #[inline]
+    pub(crate) fn get_index_and_polarity(&self) -> u32 {
+        Self::INDEX_AND_POLARITY.get(self.0)
+    }
+
+    /// The ptr that `get_index_and_polarity` and `get_arity` were read from.
+    #[inline]
+    pub(crate) fn from_index_and_polarity(index_and_polarity: u32, arity: SymbolArity) -> Self {
+        let mut new = Self(Self::INDEX_AND_POLARITY.set(0, index_and_polarity));
+        new.set_arity(arity);
+        new
+    }
 }
 
 impl Binary for SymbolPtr {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "{:02b}_{:01b}_{:013b}",
-            self.get_arity() as u8,
+            "{:04b}_{:01b}_{:013b}",
+            Self::ARITY.get(self.0),
             self.get_polarity() as u8,
             self.get_index()
         )
@@ -151,7 +225,7 @@ impl Binary for SymbolPtr {
 
 impl From<u64> for SymbolPtr {
     fn from(value: u64) -> Self {
-        SymbolPtr(value as u16)
+        SymbolPtr(value as u32)
     }
 }
 
@@ -163,7 +237,7 @@ impl Into<u64> for SymbolPtr {
 
 impl Debug for SymbolPtr {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        let name = format!("SymbolPtr({:016b})", self.0);
+        let name = format!("SymbolPtr({:018b})", self.0);
         let mut b = f.debug_struct(&name);
         b.field("polarity", &self.get_polarity());
         b.field("index", &self.get_index());
@@ -171,24 +245,20 @@ impl Debug for SymbolPtr {
     }
 }
 
-#[derive(Clone, Copy)]
-pub struct Symbol(u8);
+#[derive(Clone, Copy, PartialEq)]
+pub struct Symbol(u16);
 impl Symbol {
-    //                                              0bAAPLR???
-    const ARITY: BitSet8<2> = BitSet8 {
-        mask: 0b11,
-        offset: 6,
-    };
-    const POLARITY: BitSet8<1> = BitSet8 {
-        mask: 0b001,
-        offset: 5,
+    //                                              0bPPPPPPPP_AAAAP___
+    const PORTS: BitSet16<8> = BitSet16 {
+        mask: 0b11111111,
+        offset: 8,
     };
-    const LEFT_POLARITY: BitSet8<1> = BitSet8 {
-        mask: 0b0001,
+    const ARITY: BitSet16<4> = BitSet16 {
+        mask: 0b1111,
         offset: 4,
     };
-    const RIGHT_POLARITY: BitSet8<1> = BitSet8 {
-        mask: 0b00001,
+    const POLARITY: BitSet16<1> = BitSet16 {
+        mask: 0b1,
         offset: 3,
     };
 
@@ -216,6 +286,17 @@ impl Symbol {
         sym
     }
 
+    #[allow(non_snake_case)]
+    pub fn newN(polarity: Polarity, port_polarities: &[Polarity]) -> Self {
+        let mut sym = Self(0);
+        sym.set_arity(SymbolArity::from(port_polarities.len()));
+        sym.set_polarity(polarity);
+        for (port, port_polarity) in port_polarities.iter().enumerate() {
+            sym.set_port_polarity(PortNum::from(port), *port_polarity);
+        }
+        sym
+    }
+
     #[inline]
     pub fn get_arity(&self) -> SymbolArity {
         SymbolArity::from(Self::ARITY.get(self.0))
@@ -223,7 +304,7 @@ impl Symbol {
 
     #[inline]
     fn set_arity(&mut self, arity: SymbolArity) {
-        self.0 = Self::ARITY.set(self.0, arity as u8)
+        self.0 = Self::ARITY.set(self.0, arity.port_count() as u16)
     }
 
     #[inline]
@@ -233,31 +314,43 @@ impl Symbol {
 
     #[inline]
     fn set_polarity(&mut self, polarity: Polarity) {
-        self.0 = Self::POLARITY.set(self.0, polarity as u8)
+        self.0 = Self::POLARITY.set(self.0, polarity as u16)
     }
 
     #[inline]
     pub fn get_left_polarity(&self) -> Polarity {
-        assert!(self.get_arity() == SymbolArity::One || self.get_arity() == SymbolArity::Two);
-        Polarity::from(Self::LEFT_POLARITY.get(self.0))
+        assert!(self.get_arity() >= SymbolArity::One);
+        self.get_port_polarity(PortNum::Zero)
     }
 
     #[inline]
     fn set_left_polarity(&mut self, polarity: Polarity) {
-        assert!(self.get_arity() == SymbolArity::One || self.get_arity() == SymbolArity::Two);
-        self.0 = Self::LEFT_POLARITY.set(self.0, polarity as u8)
+        assert!(self.get_arity() >= SymbolArity::One);
+        self.set_port_polarity(PortNum::Zero, polarity)
     }
 
     #[inline]
     pub fn get_right_polarity(&self) -> Polarity {
-        assert!(self.get_arity() == SymbolArity::Two);
-        Polarity::from(Self::RIGHT_POLARITY.get(self.0))
+        assert!(self.get_arity() >= SymbolArity::Two);
+        self.get_port_polarity(PortNum::One)
     }
 
     #[inline]
     fn set_right_polarity(&mut self, polarity: Polarity) {
-        assert!(self.get_arity() == SymbolArity::Two);
-        self.0 = Self::RIGHT_POLARITY.set(self.0, polarity as u8)
+        assert!(self.get_arity() >= SymbolArity::Two);
+        self.set_port_polarity(PortNum::One, polarity)
+    }
+
+    #[inline]
+    pub fn get_port_polarity(&self, port_num: PortNum) -> Polarity {
+        assert!(port_num.is_valid_port(self.get_arity()));
+        Polarity::from((Self::PORTS.get(self.0) >> port_num as u16) & 0b1)
+    }
+
+    #[inline]
+    fn set_port_polarity(&mut self, port_num: PortNum, polarity: Polarity) {
+        assert!(port_num.is_valid_port(self.get_arity()));
+        self.0 = Self::PORTS.set(self.0, (polarity as u16) << port_num as u16)
     }
 
     #[inline]
@@ -268,7 +361,7 @@ impl Symbol {
 
 impl Debug for Symbol {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        let name = format!("Symbol({:08b})", self.0);
+        let name = format!("Symbol({:016b})", self.0);
         let mut b = f.debug_struct(&name);
         b.field("arity", &self.get_arity());
         b.field("polarity", &self.get_polarity());
@@ -281,6 +374,12 @@ impl Debug for Symbol {
                 b.field("left", &self.get_left_polarity());
                 b.field("right", &self.get_right_polarity());
             }
+            SymbolArity::N(n) => {
+                let ports: Vec<Polarity> = (0..n)
+                    .map(|port| self.get_port_polarity(PortNum::from(port as usize)))
+                    .collect();
+                b.field("ports", &ports);
+            }
         };
         b.finish()
     }
@@ -310,10 +409,27 @@ impl<'a> Display for SymbolItem<'a> {
                     symbol.get_right_polarity()
                 )
             }
+            SymbolArity::N(n) => {
+                write!(f, "({}", name)?;
+                for port in 0..n {
+                    write!(f, " {}", symbol.get_port_polarity(PortNum::from(port as usize)))?;
+                }
+                write!(f, ")")
+            }
         }
     }
 }
 
+/// A symbol name declared more than once.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DuplicateSymbolError(pub SymbolName);
+
+impl Display for DuplicateSymbolError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "symbol {} is already declared", self.0)
+    }
+}
+
 #[derive(Debug)]
 pub struct SymbolBook {
     symbols: Vec<Symbol>,
@@ -339,10 +455,36 @@ impl SymbolBook {
         book
     }
 
+    /// Declares each `(name, polarity, port_polarities)` in order, failing on
+    /// the first name seen twice.
+    pub fn from_symbols_iter<I>(iter: I) -> Result<SymbolBook, DuplicateSymbolError>
+    where
+        I: IntoIterator<Item = (SymbolName, Polarity, Vec<Polarity>)>,
+    {
+        let mut book = SymbolBook::new();
+        for (name, polarity, port_polarities) in iter {
+            if book.symbol_by_name.contains_key(&name) {
+                return Err(DuplicateSymbolError(name));
+            }
+            book.intern(&name, polarity, &port_polarities);
+        }
+        Ok(book)
+    }
+
+    pub fn builder() -> SymbolBookBuilder {
+        SymbolBookBuilder {
+            book: SymbolBook::new(),
+        }
+    }
+
     pub fn len(&self) -> usize {
         self.symbols.len()
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+
     pub fn ctr0(&mut self, name: &SymbolName) -> SymbolPtr {
         self.declare0(name, Polarity::Pos)
     }
@@ -378,10 +520,7 @@ impl SymbolBook {
     }
 
     fn declare0(&mut self, name: &SymbolName, polarity: Polarity) -> SymbolPtr {
-        let ptr = self.add_symbol(Symbol::new0(polarity));
-        self.symbol_by_name.insert(name.clone(), ptr.get_index());
-        self.name_by_symbol.insert(ptr.get_index(), name.clone());
-        ptr
+        self.intern(name, polarity, &[])
     }
 
     fn declare1(
@@ -390,10 +529,7 @@ impl SymbolBook {
         polarity: Polarity,
         left_port_polarity: Polarity,
     ) -> SymbolPtr {
-        let ptr = self.add_symbol(Symbol::new1(polarity, left_port_polarity));
-        self.symbol_by_name.insert(name.clone(), ptr.get_index());
-        self.name_by_symbol.insert(ptr.get_index(), name.clone());
-        ptr
+        self.intern(name, polarity, &[left_port_polarity])
     }
 
     fn declare2(
@@ -403,16 +539,95 @@ impl SymbolBook {
         left_port_polarity: Polarity,
         right_port_polarity: Polarity,
     ) -> SymbolPtr {
-        let ptr = self.add_symbol(Symbol::new2(
-            polarity,
-            left_port_polarity,
-            right_port_polarity,
-        ));
+        self.intern(name, polarity, &[left_port_polarity, right_port_polarity])
+    }
+
+    #[allow(non_snake_case)]
+    pub fn declareN(
+        &mut self,
+        name: &SymbolName,
+        polarity: Polarity,
+        port_polarities: &[Polarity],
+    ) -> SymbolPtr {
+        self.intern(name, polarity, port_polarities)
+    }
+
+    /// Returns the symbol already declared under `name`, or declares it. Every
+    /// declaration goes through here, so independent modules declaring the same
+    /// symbol share one `SymbolPtr`. Redeclaring a name with another polarity
+    /// or other ports panics.
+    pub fn intern(
+        &mut self,
+        name: &SymbolName,
+        polarity: Polarity,
+        port_polarities: &[Polarity],
+    ) -> SymbolPtr {
+        let symbol = match port_polarities {
+            [] => Symbol::new0(polarity),
+            [port] => Symbol::new1(polarity, *port),
+            [left, right] => Symbol::new2(polarity, *left, *right),
+            ports => Symbol::newN(polarity, ports),
+        };
+        if let Some(index) = self.symbol_by_name.get(name) {
+            assert!(
+                self.symbols[*index] == symbol,
+                "Symbol {} redeclared as {:?}, was {:?}",
+                name,
+                symbol,
+                self.symbols[*index]
+            );
+            return symbol.to_ptr(*index);
+        }
+        let ptr = self.add_symbol(symbol);
         self.symbol_by_name.insert(name.clone(), ptr.get_index());
         self.name_by_symbol.insert(ptr.get_index(), name.clone());
         ptr
     }
 
+    /// Panics unless the name and index maps agree with each other and with
+    /// the declared symbols. It walks the whole book, so call it once after
+    /// declaring a batch of symbols rather than after each one.
+    pub fn assert_valid(&self) {
+        let mut name_by_index: HashMap<usize, &SymbolName> = HashMap::new();
+        for (name, index) in self.symbol_by_name.iter() {
+            assert!(
+                *index < self.symbols.len(),
+                "Symbol {} has index {} but only {} symbols are declared",
+                name,
+                index,
+                self.symbols.len()
+            );
+            if let Some(other) = name_by_index.insert(*index, name) {
+                panic!("Symbols {} and {} share index {}", other, name, index);
+            }
+        }
+        for (name, index) in self.symbol_by_name.iter() {
+            assert!(
+                self.name_by_symbol.get(index) == Some(name),
+                "Symbol {} has index {} but that index is named {:?}",
+                name,
+                index,
+                self.name_by_symbol.get(index)
+            );
+        }
+        for (index, name) in self.name_by_symbol.iter() {
+            assert!(
+                *index < self.symbols.len(),
+                "Index {} is named {} but only {} symbols are declared",
+                index,
+                name,
+                self.symbols.len()
+            );
+            assert!(
+                self.symbol_by_name.get(name) == Some(index),
+                "Index {} is named {} but that name has index {:?}",
+                index,
+                name,
+                self.symbol_by_name.get(name)
+            );
+        }
+    }
+
     pub fn get(&self, symbol_ptr: SymbolPtr) -> Symbol {
         self.symbols[symbol_ptr.get_index()]
     }
@@ -438,6 +653,52 @@ impl SymbolBook {
         self.name_by_symbol.get(&symbol.get_index()).cloned()
     }
 
+    pub fn get_name_ref(&self, symbol: SymbolPtr) -> Option<&SymbolName> {
+        self.name_by_symbol.get(&symbol.get_index())
+    }
+
+    /// Symbols sorted by name, so output does not depend on hashing order.
+    pub fn to_sorted_vec(&self) -> Vec<(&SymbolName, SymbolPtr)> {
+        let mut symbols = self.to_named_ptrs();
+        symbols.sort_by(|(left, _), (right, _)| left.0.cmp(right.0));
+        symbols
+    }
+
+    /// Symbols sorted by index, the canonical order for serialisation.
+    pub fn to_sorted_by_index_vec(&self) -> Vec<(&SymbolName, SymbolPtr)> {
+        let mut symbols = self.to_named_ptrs();
+        symbols.sort_by_key(|(_, symbol_ptr)| symbol_ptr.get_index());
+        symbols
+    }
+
+    /// Symbols with the given arity and polarity, e.g. every binary
+    /// constructor, in index order.
+    pub fn find_by_arity_and_polarity(
+        &self,
+        arity: SymbolArity,
+        polarity: Polarity,
+    ) -> Vec<SymbolPtr> {
+        // skips the reserved symbol at index 0
+        self.symbols
+            .iter()
+            .enumerate()
+            .skip(1)
+            .filter(|(_, symbol)| {
+                symbol.get_arity() == arity && symbol.get_polarity() == polarity
+            })
+            .map(|(index, symbol)| symbol.to_ptr(index))
+            .collect()
+    }
+
+    // the reserved symbol at index 0 has no pointer and is left out
+    fn to_named_ptrs(&self) -> Vec<(&SymbolName, SymbolPtr)> {
+        self.symbol_by_name
+            .iter()
+            .filter(|(_, index)| **index != 0)
+            .map(|(name, index)| (name, self.symbols[*index].to_ptr(*index)))
+            .collect()
+    }
+
     pub fn iter(&self) -> impl Iterator<Item = NamedSymbol> {
         self.symbols.iter().enumerate().map(|(index, symbol)| {
             let name = self.name_by_symbol.get(&index).cloned().unwrap();
@@ -455,6 +716,162 @@ impl SymbolBook {
             symbols: self,
         }
     }
+
+    /// Links both symbols of every rule to the symbols of the cells its body
+    /// creates, which are the only way a symbol enters a net under evaluation.
+    pub fn dependency_graph(&self, rules: &RuleSet) -> SymbolDepGraph {
+        let mut graph = SymbolDepGraph {
+            symbol_ptrs: self
+                .to_sorted_by_index_vec()
+                .into_iter()
+                .map(|(_, symbol_ptr)| symbol_ptr)
+                .collect(),
+            edges: vec![Vec::new(); self.len()],
+        };
+        for rule_ptr in rules.rules() {
+            let rule = rules.get_rule(rule_ptr);
+            for symbol_ptr in rules.body_symbols(rule_ptr) {
+                for trigger_ptr in [rule.ctr_ptr, rule.fun_ptr] {
+                    let edges = &mut graph.edges[trigger_ptr.get_index()];
+                    if !edges.contains(&symbol_ptr) {
+                        edges.push(symbol_ptr);
+                    }
+                }
+            }
+        }
+        for edges in graph.edges.iter_mut() {
+            edges.sort_by_key(|symbol_ptr| symbol_ptr.get_index());
+        }
+        graph
+    }
+
+    /// Compares symbols by name with `other`. A symbol is modified when its
+    /// arity, polarity or port polarities differ, its index does not count.
+    /// Each list is in the symbol index order of the book it comes from.
+    pub fn diff(&self, other: &SymbolBook) -> SymbolBookDiff {
+        let mut diff = SymbolBookDiff::default();
+        for (name, symbol_ptr) in self.to_sorted_by_index_vec() {
+            match other.get_by_name(name) {
+                None => diff.removed.push(symbol_ptr),
+                Some(other_ptr) if self.get(symbol_ptr) != other.get(other_ptr) => {
+                    diff.modified.push((symbol_ptr, other_ptr))
+                }
+                Some(_) => (),
+            }
+        }
+        for (name, other_ptr) in other.to_sorted_by_index_vec() {
+            if self.get_by_name(name).is_none() {
+                diff.added.push(other_ptr);
+            }
+        }
+        diff
+    }
+}
+
+/// The symbols that differ between two books, see `SymbolBook::diff`.
+#[derive(Debug, Default, PartialEq)]
+pub struct SymbolBookDiff {
+    /// In the other book only.
+    pub added: Vec<SymbolPtr>,
+    /// In this book only.
+    pub removed: Vec<SymbolPtr>,
+    /// The same name in both books, this book's symbol first.
+    pub modified: Vec<(SymbolPtr, SymbolPtr)>,
+}
+impl SymbolBookDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.modified.is_empty()
+    }
+}
+
+/// Which symbols the rules of a `RuleSet` can produce from each symbol, see
+/// `SymbolBook::dependency_graph`.
+#[derive(Debug)]
+pub struct SymbolDepGraph {
+    symbol_ptrs: Vec<SymbolPtr>,
+    // indexed by symbol index
+    edges: Vec<Vec<SymbolPtr>>,
+}
+impl SymbolDepGraph {
+    /// The symbols created by the rules `symbol_ptr` takes part in, in symbol
+    /// index order.
+    pub fn dependencies(&self, symbol_ptr: SymbolPtr) -> &[SymbolPtr] {
+        &self.edges[symbol_ptr.get_index()]
+    }
+
+    /// Symbols that no rule body can produce starting from `root`, in symbol
+    /// index order. `root` itself is always reachable.
+    pub fn unreachable_from(&self, root: SymbolPtr) -> Vec<SymbolPtr> {
+        let mut reached = vec![false; self.edges.len()];
+        let mut pending = vec![root];
+        while let Some(symbol_ptr) = pending.pop() {
+            if std::mem::replace(&mut reached[symbol_ptr.get_index()], true) {
+                continue;
+            }
+            pending.extend(self.dependencies(symbol_ptr));
+        }
+        self.symbol_ptrs
+            .iter()
+            .copied()
+            .filter(|symbol_ptr| !reached[symbol_ptr.get_index()])
+            .collect()
+    }
+}
+
+impl Default for SymbolBook {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Declares symbols by value so a whole book can be built in one expression.
+pub struct SymbolBookBuilder {
+    book: SymbolBook,
+}
+impl SymbolBookBuilder {
+    pub fn with_ctr0(mut self, name: &'static str) -> Self {
+        self.book.ctr0(&name.into());
+        self
+    }
+
+    pub fn with_ctr1(mut self, name: &'static str, port_polarity: Polarity) -> Self {
+        self.book.ctr1(&name.into(), port_polarity);
+        self
+    }
+
+    pub fn with_ctr2(
+        mut self,
+        name: &'static str,
+        left_polarity: Polarity,
+        right_polarity: Polarity,
+    ) -> Self {
+        self.book.ctr2(&name.into(), left_polarity, right_polarity);
+        self
+    }
+
+    pub fn with_fun0(mut self, name: &'static str) -> Self {
+        self.book.fun0(&name.into());
+        self
+    }
+
+    pub fn with_fun1(mut self, name: &'static str, port_polarity: Polarity) -> Self {
+        self.book.fun1(&name.into(), port_polarity);
+        self
+    }
+
+    pub fn with_fun2(
+        mut self,
+        name: &'static str,
+        left_polarity: Polarity,
+        right_polarity: Polarity,
+    ) -> Self {
+        self.book.fun2(&name.into(), left_polarity, right_polarity);
+        self
+    }
+
+    pub fn build(self) -> SymbolBook {
+        self.book
+    }
 }
 
 pub struct NamedSymbol<'a> {
@@ -490,13 +907,35 @@ impl<'a> Display for NamedSymbol<'a> {
                 self.symbol.get_left_polarity(),
                 self.symbol.get_right_polarity()
             ),
+            SymbolArity::N(n) => {
+                write!(
+                    f,
+                    "Symbol[{}]: {}({}",
+                    self.index,
+                    self.symbol.get_polarity(),
+                    self.name
+                )?;
+                for port in 0..n {
+                    write!(
+                        f,
+                        " {}",
+                        self.symbol.get_port_polarity(PortNum::from(port as usize))
+                    )?;
+                }
+                write!(f, ")")
+            }
         }
     }
 }
 
 impl Display for SymbolBook {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
-        for named_symbol in self.iter() {
+        for (name, symbol_ptr) in self.to_sorted_vec() {
+            let named_symbol = NamedSymbol {
+                index: symbol_ptr.get_index(),
+                name: name.clone(),
+                symbol: &self.symbols[symbol_ptr.get_index()],
+            };
             match writeln!(f, "{}", named_symbol) {
                 Ok(_) => (),
                 Err(_) => panic!(),
@@ -508,10 +947,116 @@ impl Display for SymbolBook {
 
 #[cfg(test)]
 mod tests {
+    use proptest::prelude::*;
     use tracing::debug;
 
     use super::*;
 
+    #[test]
+    fn test_symbol_book_from_symbols_iter() {
+        let symbols = SymbolBook::from_symbols_iter([
+            ("Z".into(), Polarity::Pos, vec![]),
+            ("S".into(), Polarity::Pos, vec![Polarity::Neg]),
+            ("add".into(), Polarity::Neg, vec![Polarity::Pos, Polarity::Neg]),
+        ])
+        .unwrap();
+        let add = symbols.get_by_name(&"add".into()).unwrap();
+        assert_eq!(add.get_index(), 3);
+        assert_eq!(add.get_polarity(), Polarity::Neg);
+        assert_eq!(add.get_arity(), SymbolArity::Two);
+
+        let duplicate = SymbolBook::from_symbols_iter([
+            ("Z".into(), Polarity::Pos, vec![]),
+            ("Z".into(), Polarity::Pos, vec![]),
+        ]);
+        assert_eq!(duplicate.unwrap_err(), DuplicateSymbolError("Z".into()));
+    }
+
+    #[test]
+    fn test_symbol_book_assert_valid() {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+        symbols.declare_arith_symbols();
+        symbols.assert_valid();
+    }
+
+    #[test]
+    #[should_panic(expected = "share index")]
+    fn test_symbol_book_assert_valid_shared_index() {
+        let mut symbols = SymbolBook::new();
+        let z = symbols.ctr0(&"Z".into());
+        symbols.symbol_by_name.insert("S".into(), z.get_index());
+        symbols.assert_valid();
+    }
+
+    #[test]
+    #[should_panic(expected = "only 2 symbols are declared")]
+    fn test_symbol_book_assert_valid_missing_symbol() {
+        let mut symbols = SymbolBook::new();
+        symbols.ctr0(&"Z".into());
+        symbols.name_by_symbol.insert(2, "S".into());
+        symbols.assert_valid();
+    }
+
+    #[test]
+    fn test_symbol_book_find_by_arity_and_polarity() {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+        symbols.declare_arith_symbols();
+        let triple = symbols.declareN(&"Triple".into(), Polarity::Pos, &[Polarity::Neg; 3]);
+
+        let ptr = |name: &'static str| symbols.get_by_name(&name.into()).unwrap();
+        let funs = symbols.find_by_arity_and_polarity(SymbolArity::Two, Polarity::Neg);
+        assert_eq!(funs, [ptr("add"), ptr("sub₀"), ptr("sub")]);
+        let triples = symbols.find_by_arity_and_polarity(SymbolArity::N(3), Polarity::Pos);
+        assert_eq!(triples, [triple]);
+        let ctrs = symbols.find_by_arity_and_polarity(SymbolArity::Zero, Polarity::Pos);
+        assert_eq!(ctrs, [ptr("Z")]);
+        assert!(symbols
+            .find_by_arity_and_polarity(SymbolArity::Two, Polarity::Pos)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_symbol_book_diff() {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+        symbols.declare_arith_symbols();
+        assert!(symbols.diff(&symbols).is_empty());
+
+        let other = SymbolBook::builder()
+            .with_ctr0("Z")
+            .with_ctr1("S", Polarity::Neg)
+            .with_fun2("add", Polarity::Neg, Polarity::Neg)
+            .with_fun0("era")
+            .build();
+        let ptr = |book: &SymbolBook, name: &'static str| book.get_by_name(&name.into()).unwrap();
+        let diff = symbols.diff(&other);
+        assert_eq!(diff.added, [ptr(&other, "era")]);
+        assert_eq!(diff.removed, [ptr(&symbols, "sub₀"), ptr(&symbols, "sub")]);
+        assert_eq!(diff.modified, [(ptr(&symbols, "add"), ptr(&other, "add"))]);
+        assert!(!diff.is_empty());
+    }
+
+    #[test]
+    fn test_symbol_book_dependency_graph() {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+        symbols.declare_arith_symbols();
+        let mut rules = RuleSet::new(&symbols);
+        rules.arith_add_rules();
+
+        let ptr = |name: &'static str| symbols.get_by_name(&name.into()).unwrap();
+        let graph = symbols.dependency_graph(&rules);
+        // (S n) ⋈ (add x₁ x₂) creates an S and an add, Z ⋈ add creates nothing
+        assert_eq!(graph.dependencies(ptr("S")), [ptr("S"), ptr("add")]);
+        assert_eq!(graph.dependencies(ptr("add")), [ptr("S"), ptr("add")]);
+        assert!(graph.dependencies(ptr("Z")).is_empty());
+
+        assert_eq!(graph.unreachable_from(ptr("add")), [ptr("Z"), ptr("sub₀"), ptr("sub")]);
+        assert_eq!(graph.unreachable_from(ptr("Z")).len(), 4);
+    }
+
     #[test]
     fn test_symbol_ptr_new0_neg() {
         let ptr = SymbolPtr::new(1, SymbolArity::One, Polarity::Neg);
@@ -528,6 +1073,36 @@ mod tests {
         assert_eq!(ptr.get_arity(), SymbolArity::Two);
     }
 
+    #[test]
+    fn test_symbol_ptr_max_index() {
+        let ptr = SymbolPtr::new(SymbolPtr::MAX_INDEX, SymbolArity::N(3), Polarity::Neg);
+        assert_eq!(ptr.get_index(), 8191);
+        assert_eq!(ptr.get_polarity(), Polarity::Neg);
+        assert!(ptr.is_nary());
+        assert_eq!(ptr.get_arity(), SymbolArity::N(3));
+        assert_eq!(ptr.get_nary_arity(), Some(3));
+        assert_eq!(SymbolPtr::from(Into::<u64>::into(ptr)).get_index(), 8191);
+    }
+
+    proptest! {
+        #[test]
+        fn test_symbol_ptr_bit_packing(
+            index in 1..=SymbolPtr::MAX_INDEX,
+            arity in 0..=SymbolArity::MAX as usize,
+            polarity in 0..2u16,
+        ) {
+            let arity = SymbolArity::from(arity);
+            let ptr = SymbolPtr::new(index, arity, Polarity::from(polarity));
+            let unpacked = SymbolPtr::from(Into::<u64>::into(ptr));
+            for ptr in [ptr, unpacked] {
+                prop_assert_eq!(ptr.get_index(), index);
+                prop_assert_eq!(ptr.get_polarity(), Polarity::from(polarity));
+                prop_assert_eq!(ptr.get_arity(), arity);
+                prop_assert_eq!(ptr.is_nary(), matches!(arity, SymbolArity::N(_)));
+            }
+        }
+    }
+
     #[test]
     fn test_symbol_new0_neg() {
         let sym = Symbol::new0(Polarity::Neg);
@@ -638,6 +1213,134 @@ mod tests {
         assert_eq!(sym.get_right_polarity(), Polarity::Pos);
     }
 
+    #[test]
+    fn test_symbol_new_n() {
+        let ports = [
+            Polarity::Pos,
+            Polarity::Neg,
+            Polarity::Neg,
+            Polarity::Pos,
+            Polarity::Neg,
+        ];
+        let sym = Symbol::newN(Polarity::Neg, &ports);
+        assert_eq!(sym.get_arity(), SymbolArity::N(5));
+        assert_eq!(sym.get_polarity(), Polarity::Neg);
+        assert_eq!(sym.get_left_polarity(), Polarity::Pos);
+        assert_eq!(sym.get_right_polarity(), Polarity::Neg);
+        for (port_num, polarity) in ports.iter().enumerate() {
+            assert_eq!(sym.get_port_polarity(PortNum::from(port_num)), *polarity);
+        }
+    }
+
+    #[test]
+    fn test_symbol_new_n_max_arity() {
+        let ports = [Polarity::Neg; SymbolArity::MAX as usize];
+        let sym = Symbol::newN(Polarity::Pos, &ports);
+        assert_eq!(sym.get_arity(), SymbolArity::N(SymbolArity::MAX));
+        assert_eq!(sym.get_port_polarity(PortNum::Seven), Polarity::Neg);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_symbol_new_n_too_many_ports() {
+        Symbol::newN(Polarity::Pos, &[Polarity::Neg; SymbolArity::MAX as usize + 1]);
+    }
+
+    #[test]
+    fn test_symbol_book_builder() {
+        let symbols = SymbolBook::builder()
+            .with_ctr0("Z")
+            .with_ctr1("S", Polarity::Neg)
+            .with_fun2("add", Polarity::Pos, Polarity::Neg)
+            .build();
+        assert_eq!(symbols.len(), 4); // includes the reserved symbol
+
+        let z = symbols.get_by_name(&"Z".into()).unwrap();
+        assert_eq!(z.get_polarity(), Polarity::Pos);
+        assert_eq!(z.get_arity(), SymbolArity::Zero);
+
+        let s = symbols.get(symbols.get_by_name(&"S".into()).unwrap());
+        assert_eq!(s.get_left_polarity(), Polarity::Neg);
+
+        let add = symbols.get_by_name(&"add".into()).unwrap();
+        assert_eq!(add.get_polarity(), Polarity::Neg);
+        assert_eq!(symbols.get(add).get_right_polarity(), Polarity::Neg);
+    }
+
+    #[test]
+    fn test_symbol_book_intern() {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+        let len = symbols.len();
+
+        // declared again by another module
+        let s_ptr = symbols.ctr1(&"S".into(), Polarity::Neg);
+        assert_eq!(symbols.len(), len);
+        assert_eq!(Some(s_ptr), symbols.get_by_name(&"S".into()));
+        assert_eq!(s_ptr, symbols.intern(&"S".into(), Polarity::Pos, &[Polarity::Neg]));
+
+        let dup_ptr = symbols.intern(&"dup".into(), Polarity::Neg, &[Polarity::Pos; 2]);
+        assert_eq!(symbols.len(), len + 1);
+        assert_eq!(dup_ptr, symbols.fun2(&"dup".into(), Polarity::Pos, Polarity::Pos));
+    }
+
+    #[test]
+    #[should_panic(expected = "Symbol S redeclared")]
+    fn test_symbol_book_intern_conflict() {
+        let mut symbols = SymbolBook::new();
+        symbols.declare_nat_symbols();
+        symbols.fun1(&"S".into(), Polarity::Neg);
+    }
+
+    #[test]
+    fn test_symbol_book_to_sorted_vec() {
+        let symbols = SymbolBook::builder()
+            .with_ctr1("S", Polarity::Neg)
+            .with_fun2("add", Polarity::Pos, Polarity::Neg)
+            .with_ctr0("Z")
+            .build();
+
+        let names: Vec<&str> = symbols
+            .to_sorted_vec()
+            .iter()
+            .map(|(name, _)| name.0)
+            .collect();
+        assert_eq!(names, vec!["S", "Z", "add"]);
+
+        let names: Vec<&str> = symbols
+            .to_sorted_by_index_vec()
+            .iter()
+            .map(|(name, _)| name.0)
+            .collect();
+        assert_eq!(names, vec!["S", "add", "Z"]);
+
+        let display = symbols.to_string();
+        let lines: Vec<&str> = display.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].ends_with("S -)"));
+        assert!(lines[2].contains("add"));
+    }
+
+    #[test]
+    fn test_symbol_book_declare_n() {
+        let mut symbols = SymbolBook::new();
+        let ptr = symbols.declareN(
+            &"Tuple3".into(),
+            Polarity::Pos,
+            &[Polarity::Neg, Polarity::Neg, Polarity::Pos],
+        );
+        assert!(ptr.is_nary());
+        assert_eq!(ptr.get_polarity(), Polarity::Pos);
+        assert_eq!(symbols.get_by_name(&"Tuple3".into()), Some(ptr));
+        let sym = symbols.get(ptr);
+        assert_eq!(sym.get_arity(), SymbolArity::N(3));
+        assert_eq!(sym.get_port_polarity(PortNum::Two), Polarity::Pos);
+        assert_eq!(
+            format!("{}", symbols.display_symbol(ptr)),
+            "(Tuple3 - - +)"
+        );
+    }
+
     #[test]
     #[should_panic]
     fn test_symbol_from_left_panic() {
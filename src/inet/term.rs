@@ -1,11 +1,11 @@
 use std::fmt::{Binary, Debug, Formatter};
 
 use super::{
-    cell::CellPtr,
-    heap::Heap,
+    cell::{CellPtr32, CellPtr64},
+    heap::{Heap, HeapTranslation},
     symbol::SymbolBook,
-    var::{PVarPtr, Var},
-    BitSet32, Polarity,
+    var::{PVarPtr32, PVarPtr64, Var},
+    BitSet32, BitSet64, Polarity,
 };
 
 pub trait TermFamily: Clone + Debug {
@@ -19,6 +19,10 @@ pub trait TermFamily: Clone + Debug {
         store: &Var<Self>,
         index: usize,
     ) -> std::fmt::Result;
+
+    /// Rewrites the ptrs a var holds after `Heap::merge_heap` or
+    /// `Heap::compact` moved them, for stores that hold any.
+    fn translate_store(_var: &mut Var<Self>, _translation: &HeapTranslation) {}
 }
 
 #[derive(Debug, PartialEq)]
@@ -47,36 +51,49 @@ impl From<u32> for TermKind {
     }
 }
 
+/// The term pointer used throughout the heap, `TermPtr64` with the
+/// `large-heap` feature for heaps past 2²² cells or vars.
+#[cfg(not(feature = "large-heap"))]
+pub type TermPtr = TermPtr32;
+#[cfg(feature = "large-heap")]
+pub type TermPtr = TermPtr64;
+
+/// A 24-bit term: 23 bits for the cell or var ptr and 1 bit for the kind, which
+/// limits heaps to 2²² cells and 2²² vars. Cells pack two of them next to
+/// their symbol.
 #[derive(Clone, Copy)]
-pub struct TermPtr(u32);
-impl TermPtr {
+pub struct TermPtr32(u32);
+impl TermPtr32 {
     // term can be a Var or a Cell
-    const TERM: BitSet32<24> = BitSet32 {
-        mask: 0b00000000_11111111_11111111_11111111,
+    const TERM: BitSet32<23> = BitSet32 {
+        mask: 0b00000000_01111111_11111111_11111111,
         offset: 0,
     };
     const KIND: BitSet32<1> = BitSet32 {
         mask: 0b00000001,
-        offset: 24,
+        offset: 23,
     };
-    const _UNUSED: BitSet32<7> = BitSet32 {
-        mask: 0b11111110,
-        offset: 25,
+    const _UNUSED: BitSet32<8> = BitSet32 {
+        mask: 0b11111111,
+        offset: 24,
     };
 
-    const PTR: BitSet32<25> = BitSet32 {
-        mask: 0b00000001_11111111_11111111_11111111,
+    const PTR: BitSet32<24> = BitSet32 {
+        mask: 0b00000000_11111111_11111111_11111111,
         offset: 0,
     };
 
-    pub fn new_var(var_ptr: PVarPtr) -> Self {
+    /// What unused ports hold, a cell ptr to index 0.
+    pub const NULL: TermPtr32 = TermPtr32(0);
+
+    pub fn new_var(var_ptr: PVarPtr32) -> Self {
         let mut this = Self(0);
         this.set_kind(TermKind::Var);
         this.set_term(var_ptr.get_ptr());
         this
     }
 
-    pub fn new_cell(cell_ptr: CellPtr) -> Self {
+    pub fn new_cell(cell_ptr: CellPtr32) -> Self {
         let mut this = Self(0);
         this.set_kind(TermKind::Cell);
         this.set_term(cell_ptr.get_ptr());
@@ -91,13 +108,21 @@ impl TermPtr {
         TermKind::from(Self::KIND.get(self.0))
     }
 
-    pub fn get_var_ptr(&self) -> PVarPtr {
-        assert!(self.get_kind() == TermKind::Var);
+    pub fn is_cell(&self) -> bool {
+        self.get_kind() == TermKind::Cell
+    }
+
+    pub fn is_var(&self) -> bool {
+        self.get_kind() == TermKind::Var
+    }
+
+    pub fn get_var_ptr(&self) -> PVarPtr32 {
+        assert!(self.is_var());
         self.get_term().into()
     }
 
-    pub fn get_cell_ptr(&self) -> CellPtr {
-        assert!(self.get_kind() == TermKind::Cell);
+    pub fn get_cell_ptr(&self) -> CellPtr32 {
+        assert!(self.is_cell());
         self.get_term().into()
     }
 
@@ -121,11 +146,11 @@ impl TermPtr {
     }
 }
 
-impl Binary for TermPtr {
+impl Binary for TermPtr32 {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "{:07b}_{:01b}_{:024b}",
+            "{:08b}_{:01b}_{:023b}",
             Self::_UNUSED.get(self.0),
             self.get_kind() as u8,
             self.get_term()
@@ -133,23 +158,23 @@ impl Binary for TermPtr {
     }
 }
 
-impl From<u64> for TermPtr {
+impl From<u64> for TermPtr32 {
     fn from(value: u64) -> Self {
-        assert!(value <= 1 << 25);
+        assert!(value < 1 << 24);
         Self(value as u32)
     }
 }
 
-impl From<u32> for TermPtr {
+impl From<u32> for TermPtr32 {
     fn from(value: u32) -> Self {
-        assert!(value <= 1 << 25);
+        assert!(value < 1 << 24);
         Self(value)
     }
 }
 
-impl From<PVarPtr> for TermPtr {
-    fn from(value: PVarPtr) -> Self {
-        TermPtr::new_var(value)
+impl From<PVarPtr32> for TermPtr32 {
+    fn from(value: PVarPtr32) -> Self {
+        TermPtr32::new_var(value)
     }
 }
 
@@ -173,7 +198,128 @@ impl From<PVarPtr> for TermPtr {
 //     }
 // }
 
-impl Debug for TermPtr {
+/// A term with 32 bits for the cell or var ptr, 1 bit for the kind and 31 spare
+/// bits for metadata, for heaps of up to 2³¹ cells and 2³¹ vars.
+#[derive(Clone, Copy, PartialEq)]
+pub struct TermPtr64(u64);
+impl TermPtr64 {
+    const PTR: BitSet64<33> = BitSet64 {
+        mask: 0b1_11111111_11111111_11111111_11111111,
+        offset: 0,
+    };
+
+    const TERM: BitSet64<32> = BitSet64 {
+        mask: 0b11111111_11111111_11111111_11111111,
+        offset: 0,
+    };
+    const KIND: BitSet64<1> = BitSet64 {
+        mask: 0b1,
+        offset: 32,
+    };
+    const _UNUSED: BitSet64<31> = BitSet64 {
+        mask: 0b01111111_11111111_11111111_11111111,
+        offset: 33,
+    };
+
+    /// What unused ports hold, a cell ptr to index 0.
+    pub const NULL: TermPtr64 = TermPtr64(0);
+
+    pub fn new_var(var_ptr: PVarPtr64) -> Self {
+        let bits = Self::KIND.set(0, TermKind::Var as u64);
+        Self(Self::TERM.set(bits, var_ptr.get_ptr() as u64))
+    }
+
+    pub fn new_cell(cell_ptr: CellPtr64) -> Self {
+        let bits = Self::KIND.set(0, TermKind::Cell as u64);
+        Self(Self::TERM.set(bits, cell_ptr.get_ptr() as u64))
+    }
+
+    pub fn get_kind(&self) -> TermKind {
+        TermKind::from(Self::KIND.get(self.0))
+    }
+
+    pub fn is_cell(&self) -> bool {
+        self.get_kind() == TermKind::Cell
+    }
+
+    pub fn is_var(&self) -> bool {
+        self.get_kind() == TermKind::Var
+    }
+
+    pub fn get_var_ptr(&self) -> PVarPtr64 {
+        assert!(self.is_var());
+        PVarPtr64::from(self.get_term())
+    }
+
+    pub fn get_cell_ptr(&self) -> CellPtr64 {
+        assert!(self.is_cell());
+        CellPtr64::from(self.get_term())
+    }
+
+    pub fn get_polarity(&self) -> Polarity {
+        match self.get_kind() {
+            TermKind::Cell => self.get_cell_ptr().get_polarity(),
+            TermKind::Var => self.get_var_ptr().get_polarity(),
+        }
+    }
+
+    pub fn get_term(&self) -> u32 {
+        Self::TERM.get(self.0) as u32
+    }
+
+    pub fn get_ptr(&self) -> u64 {
+        Self::PTR.get(self.0)
+    }
+}
+
+impl Binary for TermPtr64 {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:031b}_{:01b}_{:032b}",
+            Self::_UNUSED.get(self.0),
+            self.get_kind() as u8,
+            self.get_term()
+        )
+    }
+}
+
+impl From<u64> for TermPtr64 {
+    fn from(value: u64) -> Self {
+        assert!(value <= Self::PTR.mask);
+        Self(value)
+    }
+}
+
+impl From<PVarPtr64> for TermPtr64 {
+    fn from(value: PVarPtr64) -> Self {
+        TermPtr64::new_var(value)
+    }
+}
+
+impl From<TermPtr32> for TermPtr64 {
+    fn from(term_ptr: TermPtr32) -> Self {
+        match term_ptr.get_kind() {
+            TermKind::Cell => TermPtr64::new_cell(term_ptr.get_cell_ptr().into()),
+            TermKind::Var => TermPtr64::new_var(term_ptr.get_var_ptr().into()),
+        }
+    }
+}
+
+impl Debug for TermPtr64 {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = format!("TermPtr64({:064b})", self.0);
+        let mut b = f.debug_struct(&name);
+        b.field("kind", &self.get_kind());
+        match self.get_kind() {
+            TermKind::Cell => b.field("cell", &self.get_cell_ptr()),
+            TermKind::Var => b.field("var", &self.get_var_ptr()),
+        };
+        b.finish()
+    }
+}
+
+impl Debug for TermPtr32 {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         let name = format!("TermPtr({:032b})", self.0);
         let mut b = f.debug_struct(&name);
@@ -185,3 +331,70 @@ impl Debug for TermPtr {
         b.finish()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use crate::inet::var::{VarPtr32, VarPtr64};
+
+    use super::*;
+
+    #[test]
+    fn test_term_ptr64() {
+        let index = (1 << 31) - 2; // beyond the 22-bit index of TermPtr32
+        let cell = TermPtr64::new_cell(CellPtr64::new(index, Polarity::Neg));
+        assert_eq!(cell.get_kind(), TermKind::Cell);
+        assert_eq!(cell.get_cell_ptr().get_index(), index);
+        assert_eq!(cell.get_polarity(), Polarity::Neg);
+
+        let (_, pos) = PVarPtr64::wire(VarPtr64::new(index));
+        let var = TermPtr64::new_var(pos);
+        assert_eq!(var.get_kind(), TermKind::Var);
+        assert_eq!(var.get_var_ptr().get_fvar_ptr().get_index(), index);
+        assert_eq!(var.get_polarity(), Polarity::Pos);
+    }
+
+    #[test]
+    fn test_term_ptr64_from_term_ptr32() {
+        let (neg, _) = PVarPtr32::wire(VarPtr32::new(42));
+        let var = TermPtr64::from(TermPtr32::new_var(neg));
+        assert_eq!(var.get_var_ptr().get_fvar_ptr().get_index(), 42);
+        assert_eq!(var.get_polarity(), Polarity::Neg);
+    }
+
+    #[test]
+    fn test_term_ptr_max_ptr() {
+        let max = (1 << 23) - 1;
+        let cell = TermPtr32::new_cell(CellPtr32::from(max));
+        assert_eq!(cell.get_kind(), TermKind::Cell);
+        assert_eq!(cell.get_cell_ptr().get_ptr(), max);
+
+        assert!(cell.is_cell() && !cell.is_var());
+
+        let var = TermPtr32::new_var(PVarPtr32::from(max));
+        assert_eq!(var.get_kind(), TermKind::Var);
+        assert!(var.is_var() && !var.is_cell());
+        assert_eq!(var.get_var_ptr().get_ptr(), max);
+    }
+
+    proptest! {
+        // raw 25-bit cell and var ptrs, index and polarity included
+        #[test]
+        fn test_term_ptr_bit_packing(ptr in 0..1u32 << 23) {
+            let cell_ptr = CellPtr32::from(ptr);
+            let cell = TermPtr32::new_cell(cell_ptr);
+            prop_assert_eq!(cell.get_kind(), TermKind::Cell);
+            prop_assert!(cell.get_cell_ptr() == cell_ptr);
+            prop_assert_eq!(cell.get_polarity(), cell_ptr.get_polarity());
+            prop_assert_eq!(TermPtr32::from(cell.get_ptr()).get_kind(), TermKind::Cell);
+
+            let pvar_ptr = PVarPtr32::from(ptr);
+            let var = TermPtr32::new_var(pvar_ptr);
+            prop_assert_eq!(var.get_kind(), TermKind::Var);
+            prop_assert!(var.get_var_ptr() == pvar_ptr);
+            prop_assert_eq!(var.get_polarity(), pvar_ptr.get_polarity());
+            prop_assert!(TermPtr32::from(var.get_ptr()).get_var_ptr() == pvar_ptr);
+        }
+    }
+}
@@ -1,38 +1,54 @@
 use std::fmt::{Binary, Debug, Formatter};
 
-use raw_arena::{Ptr, ArenaValue, arenaraw::RawArena};
+use raw_arena::{Ptr, ArenaValue, arenaraw::{RawArena, DEFAULT_CAPACITY}};
 
 use super::{
     term::TermFamily,
-    BitSet32, Polarity,
+    util::SmallVector,
+    BitSet32, BitSet64, Polarity,
 };
 
-#[derive(Clone,Copy)]
-pub struct PVarPtr(u32);
-impl PVarPtr {
+/// The var pointers used throughout the heap, `VarPtr64` and `PVarPtr64`
+/// with the `large-heap` feature.
+#[cfg(not(feature = "large-heap"))]
+pub type VarPtr = VarPtr32;
+#[cfg(not(feature = "large-heap"))]
+pub type PVarPtr = PVarPtr32;
+#[cfg(feature = "large-heap")]
+pub type VarPtr = VarPtr64;
+#[cfg(feature = "large-heap")]
+pub type PVarPtr = PVarPtr64;
+
+// a default arena must be addressable to its last var, checked at compile time
+#[allow(clippy::assertions_on_constants)]
+const _: () = assert!(VarPtr::MAX_INDEX >= DEFAULT_CAPACITY - 1);
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct PVarPtr32(u32);
+impl PVarPtr32 {
     const POLARITY: BitSet32<1> = BitSet32 {
-        mask: 0b00000000_1,
-        offset: 23,
+        mask: 0b1,
+        offset: 22,
     };
 
-    const VAR_PTR: BitSet32<23> = BitSet32 {
-        mask: 0b00000000_01111111_11111111_11111111,
+    const VAR_PTR: BitSet32<22> = BitSet32 {
+        mask: 0b00000000_00111111_11111111_11111111,
         offset: 0,
     };
 
-    const PTR: BitSet32<24> = BitSet32 {
-        mask: 0b00000000_11111111_11111111_11111111,
+    const PTR: BitSet32<23> = BitSet32 {
+        mask: 0b00000000_01111111_11111111_11111111,
         offset: 0,
     };
 
-    fn new(var_ptr: VarPtr, polarity: Polarity) -> PVarPtr {
+    fn new(var_ptr: VarPtr32, polarity: Polarity) -> PVarPtr32 {
         let mut pvar_ptr = Self(var_ptr.get_ptr());
         pvar_ptr.set_polarity(polarity);
         pvar_ptr
     }
 
     #[inline]
-    pub fn wire(var_ptr: VarPtr) -> (PVarPtr, PVarPtr) {
+    pub fn wire(var_ptr: VarPtr32) -> (PVarPtr32, PVarPtr32) {
         let in_ptr = Self::new(var_ptr, Polarity::Neg);
         let out_ptr = Self::new(var_ptr, Polarity::Pos);
         (in_ptr, out_ptr)
@@ -43,14 +59,20 @@ impl PVarPtr {
         Polarity::from(Self::POLARITY.get(self.0))
     }
 
+    /// The other end of this var's wire.
+    #[inline]
+    pub fn flip(&self) -> PVarPtr32 {
+        Self::new(self.get_fvar_ptr(), self.get_polarity().flip())
+    }
+
     #[inline]
     fn set_polarity(&mut self, polarity: Polarity) {
         self.0 = Self::POLARITY.set(self.0, polarity as u32)
     }
 
     #[inline]
-    pub fn get_fvar_ptr(&self) -> VarPtr {
-        VarPtr(Self::VAR_PTR.get(self.0))
+    pub fn get_fvar_ptr(&self) -> VarPtr32 {
+        VarPtr32(Self::VAR_PTR.get(self.0))
     }
 
     #[inline]
@@ -59,7 +81,7 @@ impl PVarPtr {
     }
 }
 
-impl Debug for PVarPtr {
+impl Debug for PVarPtr32 {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         let name = format!("PVarPtr({:0b})", self.0);
         let mut b = f.debug_struct(&name);
@@ -68,21 +90,21 @@ impl Debug for PVarPtr {
         b.finish()
     }
 }
-impl Into<VarPtr> for PVarPtr {
-    fn into(self) -> VarPtr {
+impl Into<VarPtr32> for PVarPtr32 {
+    fn into(self) -> VarPtr32 {
         self.get_fvar_ptr()
     }
 }
 
-impl Into<VarPtr> for &PVarPtr {
-    fn into(self) -> VarPtr {
+impl Into<VarPtr32> for &PVarPtr32 {
+    fn into(self) -> VarPtr32 {
         self.get_fvar_ptr()
     }
 }
 
-impl From<u32> for PVarPtr {
+impl From<u32> for PVarPtr32 {
     fn from(value: u32) -> Self {
-        PVarPtr(value)
+        PVarPtr32(value)
     }
 }
 
@@ -90,48 +112,45 @@ impl From<u32> for PVarPtr {
 ///
 
 pub struct PVarPtrBuffer {
-    buffer: [VarPtr; Self::MAX_BUFFER_LEN],
-    len: u8,
+    buffer: SmallVector<VarPtr, { PVarPtrBuffer::MAX_INLINE_LEN }>,
 }
 
 impl PVarPtrBuffer {
-    const MAX_BUFFER_LEN: usize = 10;
+    // rules with more bvars than this spill over to the heap
+    const MAX_INLINE_LEN: usize = 10;
 
     #[inline]
-    pub fn new(len: u8) -> Self {
-        assert!(Self::MAX_BUFFER_LEN > len as usize);
+    pub fn new(len: u16) -> Self {
         Self {
-            len,
-            buffer: [
-                VarPtr(0),
-                VarPtr(0),
-                VarPtr(0),
-                VarPtr(0),
-                VarPtr(0),
-                VarPtr(0),
-                VarPtr(0),
-                VarPtr(0),
-                VarPtr(0),
-                VarPtr(0),
-            ],
+            buffer: SmallVector::new(VarPtr::new(0), len as usize),
         }
     }
 
     #[inline]
-    pub fn set(&mut self, index: u8, var_ptr: VarPtr) {
-        assert!(index < self.len);
+    pub fn len(&self) -> u16 {
+        self.buffer.len() as u16
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    #[inline]
+    pub fn set(&mut self, index: u16, var_ptr: VarPtr) {
+        assert!(index < self.len());
         self.buffer[index as usize] = var_ptr;
     }
 
     #[inline]
-    pub fn get_neg_var(&self, index: u8) -> PVarPtr {
-        assert!(index < self.len);
+    pub fn get_neg_var(&self, index: u16) -> PVarPtr {
+        assert!(index < self.len());
         PVarPtr::new(self.buffer[index as usize], Polarity::Neg)
     }
 
     #[inline]
-    pub fn get_pos_var(&self, index: u8) -> PVarPtr {
-        assert!(index < self.len);
+    pub fn get_pos_var(&self, index: u16) -> PVarPtr {
+        assert!(index < self.len());
         PVarPtr::new(self.buffer[index as usize], Polarity::Pos)
     }
 }
@@ -139,32 +158,35 @@ impl PVarPtrBuffer {
 /// # VarPtr
 
 #[derive(Clone, Copy)]
-pub struct VarPtr(u32);
-impl VarPtr {
-    const INDEX: BitSet32<23> = BitSet32 {
-        mask: 0b00000000_01111111_11111111_11111111,
+pub struct VarPtr32(u32);
+impl VarPtr32 {
+    const INDEX: BitSet32<22> = BitSet32 {
+        mask: 0b00000000_00111111_11111111_11111111,
         offset: 0,
     };
-    const _UNUSED: BitSet32<1> = BitSet32 {
-        mask: 0b11111111_1,
-        offset: 23,
+    const _UNUSED: BitSet32<10> = BitSet32 {
+        mask: 0b1111111111,
+        offset: 22,
     };
 
-    const PTR: BitSet32<23> = BitSet32 {
-        mask: 0b00000000_01111111_11111111_11111111,
+    const PTR: BitSet32<22> = BitSet32 {
+        mask: 0b00000000_00111111_11111111_11111111,
         offset: 0,
     };
 
+    /// Var indexes are 22 bits wide, enough for a `DEFAULT_CAPACITY` arena.
+    pub const MAX_INDEX: usize = (1 << 22) - 1;
+
     pub fn new(index: usize) -> Self {
         assert!(index < (u32::MAX - 1) as usize);
         let mut var = Self(0);
-        var.set_index(index); // 23-bits
+        var.set_index(index); // 22-bits
         var
     }
 
     #[inline]
     pub fn get_ptr(&self) -> u32 {
-        Self::PTR.get(self.0) // all 24-bits
+        Self::PTR.get(self.0) // all 22-bits
     }
 
     #[inline]
@@ -178,14 +200,14 @@ impl VarPtr {
     }
 }
 
-impl Ptr for VarPtr {
+impl Ptr for VarPtr32 {
     #[inline]
     fn get_index(&self) -> usize {
         self.get_index()
     }
 }
 
-impl Debug for VarPtr {
+impl Debug for VarPtr32 {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         let name = format!("VarPtr({:0b})", self.0);
         let mut b = f.debug_struct(&name);
@@ -194,18 +216,164 @@ impl Debug for VarPtr {
     }
 }
 
-impl Binary for VarPtr {
+impl Binary for VarPtr32 {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         Binary::fmt(&self.0, f)
     }
 }
 
-impl From<u32> for VarPtr {
+impl From<u32> for VarPtr32 {
     fn from(raw: u32) -> Self {
         Self(raw)
     }
 }
 
+/// A `VarPtr` with a 31-bit index.
+#[derive(Clone, Copy, PartialEq)]
+pub struct VarPtr64(u64);
+impl VarPtr64 {
+    const INDEX: BitSet64<31> = BitSet64 {
+        mask: 0b01111111_11111111_11111111_11111111,
+        offset: 0,
+    };
+
+    /// Var indexes are 31 bits wide.
+    pub const MAX_INDEX: usize = (1 << 31) - 1;
+
+    pub fn new(index: usize) -> Self {
+        Self(Self::INDEX.set(0, index as u64))
+    }
+
+    #[inline]
+    pub fn get_ptr(&self) -> u32 {
+        Self::INDEX.get(self.0) as u32
+    }
+
+    #[inline]
+    pub fn get_index(&self) -> usize {
+        Self::INDEX.get(self.0) as usize
+    }
+}
+
+impl Ptr for VarPtr64 {
+    #[inline]
+    fn get_index(&self) -> usize {
+        self.get_index()
+    }
+}
+
+impl From<VarPtr32> for VarPtr64 {
+    fn from(var_ptr: VarPtr32) -> Self {
+        VarPtr64::new(var_ptr.get_index())
+    }
+}
+
+impl From<u32> for VarPtr64 {
+    fn from(raw: u32) -> Self {
+        Self(raw as u64)
+    }
+}
+
+impl Binary for VarPtr64 {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Binary::fmt(&self.0, f)
+    }
+}
+
+impl Debug for VarPtr64 {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = format!("VarPtr64({:064b})", self.0);
+        let mut b = f.debug_struct(&name);
+        b.field("index", &self.get_index());
+        b.finish()
+    }
+}
+
+/// A `PVarPtr` over a `VarPtr64`, the var half of a `TermPtr64`.
+#[derive(Clone, Copy, PartialEq)]
+pub struct PVarPtr64(u64);
+impl PVarPtr64 {
+    const POLARITY: BitSet64<1> = BitSet64 {
+        mask: 0b1,
+        offset: 31,
+    };
+
+    const PTR: BitSet64<32> = BitSet64 {
+        mask: 0b11111111_11111111_11111111_11111111,
+        offset: 0,
+    };
+
+    fn new(var_ptr: VarPtr64, polarity: Polarity) -> PVarPtr64 {
+        Self(Self::POLARITY.set(var_ptr.0, polarity as u64))
+    }
+
+    #[inline]
+    pub fn wire(var_ptr: VarPtr64) -> (PVarPtr64, PVarPtr64) {
+        let neg = Self::new(var_ptr, Polarity::Neg);
+        let pos = Self::new(var_ptr, Polarity::Pos);
+        (neg, pos)
+    }
+
+    #[inline]
+    pub fn get_polarity(&self) -> Polarity {
+        Polarity::from(Self::POLARITY.get(self.0))
+    }
+
+    /// The other end of this var's wire.
+    #[inline]
+    pub fn flip(&self) -> PVarPtr64 {
+        Self::new(self.get_fvar_ptr(), self.get_polarity().flip())
+    }
+
+    #[inline]
+    pub fn get_fvar_ptr(&self) -> VarPtr64 {
+        VarPtr64(VarPtr64::INDEX.get(self.0))
+    }
+
+    #[inline]
+    pub fn get_ptr(&self) -> u32 {
+        Self::PTR.get(self.0) as u32
+    }
+}
+
+impl From<PVarPtr64> for VarPtr64 {
+    fn from(pvar_ptr: PVarPtr64) -> Self {
+        pvar_ptr.get_fvar_ptr()
+    }
+}
+
+impl From<&PVarPtr64> for VarPtr64 {
+    fn from(pvar_ptr: &PVarPtr64) -> Self {
+        pvar_ptr.get_fvar_ptr()
+    }
+}
+
+impl From<PVarPtr32> for PVarPtr64 {
+    fn from(pvar_ptr: PVarPtr32) -> Self {
+        let (neg, pos) = PVarPtr64::wire(pvar_ptr.get_fvar_ptr().into());
+        match pvar_ptr.get_polarity() {
+            Polarity::Neg => neg,
+            Polarity::Pos => pos,
+        }
+    }
+}
+
+impl From<u32> for PVarPtr64 {
+    fn from(raw: u32) -> Self {
+        Self(raw as u64)
+    }
+}
+
+impl Debug for PVarPtr64 {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = format!("PVarPtr64({:064b})", self.0);
+        let mut b = f.debug_struct(&name);
+        b.field("polarity", &self.get_polarity());
+        b.field("index", &self.get_fvar_ptr().get_index());
+        b.finish()
+    }
+}
+
 #[derive(Debug)]
 pub enum Var<T: TermFamily> {
     Bound(T::BoundStore),
@@ -239,6 +407,38 @@ impl<T: TermFamily> Var<T> {
         }
     }
 
+    #[inline]
+    pub fn bound_store(&self) -> Option<&T::BoundStore> {
+        match self {
+            Var::Bound(store) => Some(store),
+            Var::Free(_) => None,
+        }
+    }
+
+    #[inline]
+    pub fn free_store(&self) -> Option<&T::FreeStore> {
+        match self {
+            Var::Bound(_) => None,
+            Var::Free(store) => Some(store),
+        }
+    }
+
+    #[inline]
+    pub fn into_bound_store(self) -> Option<T::BoundStore> {
+        match self {
+            Var::Bound(store) => Some(store),
+            Var::Free(_) => None,
+        }
+    }
+
+    #[inline]
+    pub fn into_free_store(self) -> Option<T::FreeStore> {
+        match self {
+            Var::Bound(_) => None,
+            Var::Free(store) => Some(store),
+        }
+    }
+
     #[inline]
     pub fn to_ptr(&self, index: usize) -> VarPtr {
         VarPtr::new(index)
@@ -255,7 +455,10 @@ pub type Vars<T> = RawArena<Var<T>, VarPtr>;
 
 #[cfg(test)]
 mod tests {
+    use proptest::prelude::*;
+
     use super::*;
+    use crate::inet::rule::{PortNum, RuleBVar, RuleF, RulePort};
 
     // #[tokio::test]
     // async fn test_bvar() {
@@ -271,4 +474,47 @@ mod tests {
     //     // assert_eq!(Some(cell1), bvar.get_store().try_get());
     //     assert_eq!(Some(cell2), bvar.try_set(cell2));
     // }
+
+    #[test]
+    fn test_var_stores() {
+        let port = RulePort::Fun(PortNum::One);
+        let bvar = Var::<RuleF>::bvar(RuleBVar::new(7));
+        let fvar = Var::<RuleF>::fvar(port);
+        assert_eq!(bvar.bound_store(), Some(&RuleBVar::new(7)));
+        assert_eq!(bvar.free_store(), None);
+        assert_eq!(fvar.bound_store(), None);
+        assert_eq!(fvar.free_store(), Some(&port));
+        assert_eq!(bvar.into_bound_store(), Some(RuleBVar::new(7)));
+        assert_eq!(fvar.into_free_store(), Some(port));
+        assert_eq!(Var::<RuleF>::bvar(RuleBVar::new(7)).into_free_store(), None);
+        assert_eq!(Var::<RuleF>::fvar(port).into_bound_store(), None);
+    }
+
+    #[test]
+    fn test_var_ptr_max_index() {
+        let max = VarPtr::MAX_INDEX;
+        let (neg, pos) = PVarPtr::wire(VarPtr::new(max));
+        assert_eq!(neg.get_fvar_ptr().get_index(), max);
+        assert_eq!(pos.get_fvar_ptr().get_index(), max);
+        assert_eq!(neg.get_polarity(), Polarity::Neg);
+        assert_eq!(pos.get_polarity(), Polarity::Pos);
+    }
+
+    proptest! {
+        #[test]
+        fn test_var_ptr_bit_packing(index in 0..1usize << 22) {
+            let var_ptr = VarPtr::new(index);
+            prop_assert_eq!(var_ptr.get_index(), index);
+            prop_assert_eq!(VarPtr::from(var_ptr.get_ptr()).get_index(), index);
+
+            let (neg, pos) = PVarPtr::wire(var_ptr);
+            prop_assert_eq!(neg.get_fvar_ptr().get_index(), index);
+            prop_assert_eq!(neg.get_polarity(), Polarity::Neg);
+            prop_assert_eq!(pos.get_fvar_ptr().get_index(), index);
+            prop_assert_eq!(pos.get_polarity(), Polarity::Pos);
+            prop_assert!(PVarPtr::from(pos.get_ptr()) == pos);
+            prop_assert!(neg.flip() == pos && pos.flip() == neg);
+            prop_assert!(pos.flip().flip() == pos);
+        }
+    }
 }
@@ -0,0 +1,13 @@
+// #![feature(once_cell)]
+#![feature(generic_const_exprs)]
+#![allow(incomplete_features)]
+#![feature(const_alloc_layout)]
+#![feature(thread_local)]
+// mod net;
+pub mod inet;
+
+mod examples;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "wasm")]
+pub mod wasm;
@@ -1,19 +1,10 @@
-// #![feature(once_cell)]
-#![feature(generic_const_exprs)]
-#![feature(const_alloc_layout)]
-#![feature(thread_local)]
-// mod net;
-mod inet;
-
 fn main() {
     inet_main()
 }
 
 use tracing::info;
 
-use crate::inet::{net::Net, rule::RuleSet, runtime::Runtime, symbol::SymbolBook};
-
-mod examples;
+use rinet::inet::{net::Net, rule::RuleSet, runtime::Runtime, symbol::SymbolBook};
 
 pub fn inet_main() {
     tracing_subscriber::fmt::init();
@@ -24,6 +15,9 @@ pub fn inet_main() {
     symbols.declare_arith_symbols();
     symbols.declare_combinator_symbols();
     symbols.declare_fib_symbols();
+    symbols.declare_eraser_symbols();
+    symbols.declare_lambda_symbols();
+    symbols.declare_ski_symbols();
 
     // // Fib
     // let fib_sym = symbols.declare1("fib", Polarity::Neg, Polarity::Pos);
@@ -38,6 +32,9 @@ pub fn inet_main() {
     rules.arith_rules();
     rules.define_combinator_rules();
     rules.fib_rules();
+    rules.define_eraser_rules();
+    rules.define_lambda_rules();
+    rules.define_ski_rules();
 
     info!("{}", rules);
 
@@ -140,6 +137,41 @@ pub fn inet_main() {
         b.duplicate(two.into(), dup1.into(), dup2.into());
     });
 
+    // K 0 1 = 0
+    info!("--- K 0 1 ---");
+    net.equations(|b| {
+        let result = b.output();
+
+        let (x_in, x_out) = b.var();
+        let (y_in, y_out) = b.var();
+        let era = b.eraser();
+        b.bind(y_out, era);
+        let inner = b.lambda(x_out.into(), y_in.into());
+        let k = b.lambda(inner.into(), x_in.into());
+
+        let zero = b.church(0);
+        let k_zero = b.apply(k.into(), zero.into());
+        let one = b.church(1);
+        let numeral = b.apply(k_zero.into(), one.into());
+        b.church_to_nat(numeral.into(), result.into());
+    });
+
+    // S K K 0 = 0
+    info!("--- S K K 0 ---");
+    net.equations(|b| {
+        let result = b.output();
+
+        let s = b.ski_s();
+        let k = b.ski_k();
+        let sk = b.apply(s.into(), k.into());
+        let k = b.ski_k();
+        let skk = b.apply(sk.into(), k.into());
+
+        let zero = b.church(0);
+        let numeral = b.apply(skk.into(), zero.into());
+        b.church_to_nat(numeral.into(), result.into());
+    });
+
     info!("--- Fib(0) ---");
     net.fib(0);
     info!("--- Fib(1) ---");
@@ -165,7 +197,7 @@ pub fn inet_main() {
     let runtime = Runtime::new(&rules, false);
 
     // let net = runtime.run(net);
-    let net = runtime.eval(net);
+    let net = runtime.eval(net).expect("The runtime has no rewrite limit");
 
     info!("{}", net);
     runtime.display_stats();
@@ -0,0 +1,47 @@
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::wasm_bindgen;
+
+use crate::inet::{net::Net, rule::RuleSet, runtime::Runtime, symbol::SymbolBook};
+
+/// The allocator wasm-bindgen projects ship with. It backs every allocation
+/// on wasm32, the arenas' `Global` allocations included, and is much smaller
+/// than the default one.
+#[cfg(target_arch = "wasm32")]
+#[global_allocator]
+static ALLOC: wee_alloc::WeeAlloc = wee_alloc::WeeAlloc::INIT;
+
+/// Adds two nats with the arith rules. Exported through wasm-bindgen so it
+/// can be called from JavaScript once compiled to wasm32 (see `js/rinet.js`).
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+pub fn rinet_add(left: u32, right: u32) -> u32 {
+    let mut symbols = SymbolBook::new();
+    symbols.declare_nat_symbols();
+    symbols.declare_arith_symbols();
+
+    let mut rules = RuleSet::new(&symbols);
+    rules.arith_rules();
+
+    let mut net = Net::new(&symbols);
+    net.equations(|b| {
+        let result = b.output();
+        let right = b.n(right as usize);
+        let adder = b.adder(result.into(), right.into());
+        let left = b.n(left as usize);
+        b.add(left, adder);
+    });
+
+    let runtime = Runtime::new(&rules, false);
+    let net = runtime.eval(net).expect("The runtime has no rewrite limit");
+    net.read_nat(net.single_output().unwrap()).unwrap() as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rinet_add() {
+        assert_eq!(rinet_add(2, 3), 5);
+        assert_eq!(rinet_add(0, 0), 0);
+    }
+}
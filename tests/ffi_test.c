@@ -0,0 +1,92 @@
+/*
+ * Checks 1 + 2 = 3 through the C API only:
+ *
+ *   cargo build --features ffi
+ *   cc -Iinclude tests/ffi_test.c target/debug/librinet.a -lpthread -ldl -lm -o target/ffi_test
+ *   ./target/ffi_test
+ */
+#include <assert.h>
+#include <stdio.h>
+
+#include "rinet.h"
+
+/* (add x₁ x₂)=Z  ⟶  x₁ = x₂ */
+static void z_add(void *b, void *user_data) {
+    uint32_t add0 = inet_rule_fun_port(b, 0);
+    uint32_t add1 = inet_rule_fun_port(b, 1);
+    assert(inet_rule_fun_port(b, 2) == INET_INVALID_HANDLE);
+    assert(inet_rule_cell0(b, "nope") == INET_INVALID_HANDLE);
+    inet_rule_connect(b, add0, add1);
+}
+
+/* (add x₁ x₂)=(S n)  ⟶  x₁ = (S X), (add X x₂) ⋈ n */
+static void s_add(void *b, void *user_data) {
+    uint32_t neg, pos;
+    inet_rule_var(b, &neg, &pos);
+
+    uint32_t add0 = inet_rule_fun_port(b, 0);
+    uint32_t s = inet_rule_cell1(b, "S", pos);
+    inet_rule_bind(b, add0, s);
+
+    uint32_t add1 = inet_rule_fun_port(b, 1);
+    uint32_t add = inet_rule_cell2(b, "add", neg, add1);
+
+    uint32_t s0 = inet_rule_ctr_port(b, 0);
+    inet_rule_bind(b, s0, add);
+}
+
+/* leaves the fun port unused */
+static void unused_port(void *b, void *user_data) {}
+
+static uint32_t nat(void *b, int n) {
+    uint32_t num = inet_net_cell0(b, "Z");
+    for (int i = 0; i < n; i++) {
+        num = inet_net_cell1(b, "S", num);
+    }
+    return num;
+}
+
+/* 1 + 2 */
+static void one_plus_two(void *b, void *user_data) {
+    uint32_t result = inet_net_output(b);
+    uint32_t adder = inet_net_cell2(b, "add", result, nat(b, 2));
+    inet_net_redex(b, nat(b, 1), adder);
+}
+
+int main(void) {
+    void *book = inet_symbol_book_new();
+    assert(inet_symbol_book_declare_ctr0(book, "Z") == INET_OK);
+    assert(inet_symbol_book_declare_ctr1(book, "S", INET_NEG) == INET_OK);
+    assert(inet_symbol_book_declare_fun2(book, "add", INET_POS, INET_NEG) == INET_OK);
+
+    /* redeclaring is a no-op, unless the polarities differ */
+    assert(inet_symbol_book_declare_ctr0(book, "Z") == INET_OK);
+    assert(inet_symbol_book_declare_ctr1(book, "S", INET_POS) == INET_ERR_SYMBOL);
+    assert(inet_symbol_book_declare_ctr1(book, "T", 7) == INET_ERR_PORT);
+
+    void *rules = inet_rule_set_new(book);
+    assert(inet_rule_set_rule(rules, "Z", "add", z_add, NULL) == INET_OK);
+    assert(inet_rule_set_rule(rules, "S", "add", s_add, NULL) == INET_OK);
+    assert(inet_rule_set_rule(rules, "S", "mul", s_add, NULL) == INET_ERR_SYMBOL);
+
+    /* the panic is caught at the boundary */
+    assert(inet_symbol_book_declare_fun1(book, "id", INET_POS) == INET_OK);
+    assert(inet_rule_set_rule(rules, "Z", "id", unused_port, NULL) == INET_ERR_FAILED);
+
+    void *net = inet_net_new(book);
+    assert(inet_net_equations(net, one_plus_two, NULL) == INET_OK);
+
+    void *runtime = inet_runtime_new(rules);
+    net = inet_runtime_eval(runtime, net);
+
+    assert(inet_net_read_nat(net) == 3);
+    assert(inet_runtime_get_rewrites(runtime) == 2);
+
+    inet_net_free(net);
+    inet_runtime_free(runtime);
+    inet_rule_set_free(rules);
+    inet_symbol_book_free(book);
+
+    printf("1 + 2 = 3\n");
+    return 0;
+}